@@ -1,19 +1,31 @@
 #![feature(test)]
 extern crate test;
 
-use rand::{rngs::StdRng, SeedableRng};
+use duplicate::duplicate_item;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
 use test::{black_box, Bencher};
 use tricksterstable_rs::{
-    games::szs::{deck, Card, Game, Suit},
-    utils::shuffle_and_divide_matching_cards,
+    engine::GameEngine,
+    games::{
+        self,
+        szs::{deck, Card, Game, Suit},
+    },
+    utils::{shuffle_and_divide_matching_cards, shuffle_matching_cards_between},
 };
 
+// Random moves made from the deal before benchmarking a single position so
+// every engine is measured past its bidding/passing phase
+const MIDGAME_MOVES: usize = 12;
+
+// Safety net for engines that never reach a terminal state
+const MAX_MOVES_PER_GAME: usize = 10_000;
+
 fn szs_playthrough(no_changes: bool) {
     let mut game = Game::new();
     if no_changes {
         game.with_no_changes();
     }
-    game.round = 4;
+    game.with_final_hand();
     while game.winner.is_none() {
         let action = *game.get_moves().first().unwrap();
         game.apply_move(action);
@@ -34,6 +46,32 @@ fn bench_random_playthrough_no_changes(b: &mut Bencher) {
     })
 }
 
+fn dealers_dilemma_playthrough(no_changes: bool) {
+    let mut game = games::dealers_dilemma::Game::new();
+    if no_changes {
+        game.with_no_changes();
+    }
+    while game.winner.is_none() {
+        let action = *game.get_moves().first().unwrap();
+        game.apply_move(action);
+    }
+}
+
+#[bench]
+fn bench_dealers_dilemma_playthrough(b: &mut Bencher) {
+    b.iter(|| {
+        black_box(dealers_dilemma_playthrough(false));
+    })
+}
+
+// with the change lists compiled out (see apply_move_internal)
+#[bench]
+fn bench_dealers_dilemma_playthrough_no_changes(b: &mut Bencher) {
+    b.iter(|| {
+        black_box(dealers_dilemma_playthrough(true));
+    })
+}
+
 #[bench]
 fn test_random_shuffles(b: &mut Bencher) {
     b.iter(|| {
@@ -52,3 +90,121 @@ fn test_random_shuffles(b: &mut Bencher) {
         ));
     })
 }
+
+fn determinization_hands() -> (Vec<Card>, Vec<Card>) {
+    let mut deck = deck();
+    let second = deck.split_off(deck.len() - 12);
+    deck.truncate(12);
+    (deck, second)
+}
+
+// how the determinizations swapped cards between each pair of hands
+#[bench]
+fn bench_determinization_shuffle_cloned(b: &mut Bencher) {
+    let (mut first, mut second) = determinization_hands();
+    let mut rng = StdRng::seed_from_u64(42);
+    b.iter(|| {
+        let mut hands = vec![first.clone(), second.clone()];
+        shuffle_and_divide_matching_cards(|c: &Card| c.suit != Suit::Red, &mut hands, &mut rng);
+        first = hands[0].clone();
+        second = hands[1].clone();
+        black_box(&first);
+    })
+}
+
+#[bench]
+fn bench_determinization_shuffle_in_place(b: &mut Bencher) {
+    let (mut first, mut second) = determinization_hands();
+    let mut rng = StdRng::seed_from_u64(42);
+    b.iter(|| {
+        shuffle_matching_cards_between(
+            |c: &Card| c.suit != Suit::Red,
+            &mut first,
+            &mut second,
+            &mut rng,
+        );
+        black_box(&first);
+    })
+}
+
+/// Play up to `moves` random moves from a fresh deal
+fn random_position<G: GameEngine>(moves: usize, rng: &mut StdRng) -> G {
+    let mut game = G::new_game();
+    for _ in 0..moves {
+        if game.is_over() {
+            break;
+        }
+        let action = *game.legal_moves().choose(rng).unwrap();
+        game.play(action);
+    }
+    game
+}
+
+fn midgame<G: GameEngine>() -> G {
+    random_position(MIDGAME_MOVES, &mut StdRng::seed_from_u64(42))
+}
+
+fn bench_get_moves<G: GameEngine>(b: &mut Bencher) {
+    let game: G = midgame();
+    b.iter(|| black_box(game.legal_moves()))
+}
+
+// includes cloning the position since a move can only be applied once
+fn bench_apply_move<G: GameEngine>(b: &mut Bencher) {
+    let game: G = midgame();
+    let action = game.legal_moves()[0];
+    b.iter(|| {
+        let mut game = game.clone();
+        game.play(action);
+        black_box(game)
+    })
+}
+
+// a whole game (every round) with the change lists the UI would receive
+fn bench_random_game<G: GameEngine>(b: &mut Bencher) {
+    let mut rng = StdRng::seed_from_u64(42);
+    b.iter(|| black_box(random_position::<G>(MAX_MOVES_PER_GAME, &mut rng)))
+}
+
+fn bench_randomize_determination<G: GameEngine>(b: &mut Bencher) {
+    let game: G = midgame();
+    b.iter(|| {
+        let mut determination = game.clone();
+        determination.randomize_determination(game.current_player());
+        black_box(determination)
+    })
+}
+
+#[duplicate_item(
+    engine game_type;
+    [dealers_dilemma] [games::dealers_dilemma::Game];
+    [hotdog] [games::hotdog::HotdogGame];
+    [kaibosh] [games::kaibosh::KaiboshGame];
+    [kansascity] [games::kansascity::KansasCityGame];
+    [so8] [games::so8::SixOfVIIIGame];
+    [szs] [games::szs::Game];
+    [yokai2p] [games::yokai2p::Yokai2pGame];
+)]
+mod engine {
+    use super::*;
+
+    #[bench]
+    fn get_moves(b: &mut Bencher) {
+        bench_get_moves::<game_type>(b)
+    }
+
+    #[bench]
+    fn apply_move(b: &mut Bencher) {
+        bench_apply_move::<game_type>(b)
+    }
+
+    #[bench]
+    fn random_game(b: &mut Bencher) {
+        bench_random_game::<game_type>(b)
+    }
+
+    #[bench]
+    fn randomize_determination(b: &mut Bencher) {
+        bench_randomize_determination::<game_type>(b)
+    }
+}
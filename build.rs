@@ -0,0 +1,6 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    // the gRPC service is generated from proto/engine.proto
+    #[cfg(feature = "grpc")]
+    tonic_build::compile_protos("proto/engine.proto").expect("proto/engine.proto should compile");
+}
@@ -128,7 +128,9 @@ fn main() {
         } else {
             action = get_mcts_move(&game, 250);
         }
-        game = game.clone_and_apply_move(action);
+        game = game
+            .clone_and_apply_move(action)
+            .expect("the move should be legal");
     }
     display_game(&game);
 }
@@ -15,7 +15,7 @@ enum Engine {
 fn get_mcts_move(game: &Game, engine: Engine) -> i32 {
     let iterations = 500;
     let mut new_game = game.clone();
-    new_game.round = 6;
+    new_game.with_final_hand();
     new_game.no_changes = true;
     match engine {
         Engine::Random => {
@@ -58,7 +58,7 @@ fn main() {
     for i in 0..1000 {
         let mut game = Game::new();
         game = game.deal();
-        game.round = 6;
+        game.with_final_hand();
         let engine = engines[i % 3];
         while game.winner.is_none() {
             if game.current_player == 0 {
@@ -66,11 +66,13 @@ fn main() {
                 let start = Instant::now();
                 let action = get_mcts_move(&game, engine);
                 *duration += start.elapsed();
-                game = game.clone_and_apply_move(action);
+                game = game
+                    .clone_and_apply_move(action)
+                    .expect("the bot should pick a legal move");
             } else {
                 let mut moves = game.get_moves();
                 moves.shuffle(&mut thread_rng());
-                game = game.clone_and_apply_move(moves[0]);
+                game = game.clone_and_apply_move(moves[0]).unwrap();
             }
         }
         let max_score: i32 = *game.scores.iter().max().unwrap();
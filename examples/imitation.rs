@@ -0,0 +1,38 @@
+use std::env;
+use std::io;
+use std::path::PathBuf;
+
+use tricksterstable_rs::games::{dealers_dilemma, hotdog, kaibosh, kansascity, so8, szs, yokai2p};
+use tricksterstable_rs::training::record_imitation_dataset;
+
+// cargo run --release --example imitation -- <game> [games] [iterations] [output]
+fn main() -> io::Result<()> {
+    let args: Vec<String> = env::args().collect();
+    let game = args.get(1).map(String::as_str).unwrap_or("dealers_dilemma");
+    let games: usize = args.get(2).and_then(|a| a.parse().ok()).unwrap_or(1000);
+    let iterations: i32 = args.get(3).and_then(|a| a.parse().ok()).unwrap_or(5000);
+    let path = args
+        .get(4)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(format!("training/{}/data/imitation.jsonl", game)));
+
+    let samples = match game {
+        "dealers_dilemma" => {
+            record_imitation_dataset::<dealers_dilemma::Game>(&path, games, iterations)?
+        }
+        "hotdog" => record_imitation_dataset::<hotdog::HotdogGame>(&path, games, iterations)?,
+        "kaibosh" => record_imitation_dataset::<kaibosh::KaiboshGame>(&path, games, iterations)?,
+        "kansascity" => {
+            record_imitation_dataset::<kansascity::KansasCityGame>(&path, games, iterations)?
+        }
+        "so8" => record_imitation_dataset::<so8::SixOfVIIIGame>(&path, games, iterations)?,
+        "szs" => record_imitation_dataset::<szs::Game>(&path, games, iterations)?,
+        "yokai2p" => record_imitation_dataset::<yokai2p::Yokai2pGame>(&path, games, iterations)?,
+        _ => {
+            eprintln!("unknown game {}", game);
+            std::process::exit(1);
+        }
+    };
+    println!("wrote {} samples to {}", samples, path.display());
+    Ok(())
+}
@@ -0,0 +1,284 @@
+/*
+Game statistics for checking an engine's balance
+
+  cargo run --release -- analyze <game> [--games <n>] [--iterations <n>] [--seed <n>]
+      [--json <path>]
+
+Plays games with random moves (or ISMCTS bots with --iterations) and
+reports the distribution of final scores, game and hand lengths, tricks won
+by each seat, how often the leader wins the trick and how often each kind
+of bid is made. Trick, hand and bid statistics come from
+`GameEngine::events` so engines that don't report events only get the game
+level numbers.
+*/
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+
+use crate::engine::GameEvent;
+use crate::registry::{self, RegistryError};
+use crate::utils::render;
+
+/// Safety net for engines that never reach a terminal state
+const MAX_MOVES_PER_GAME: usize = 10_000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalysisConfig {
+    pub game: String,
+    pub games: usize,
+    /// ISMCTS iterations per move - random moves when None
+    pub iterations: Option<i32>,
+    /// Seed for the random moves
+    pub seed: u64,
+}
+
+impl Default for AnalysisConfig {
+    fn default() -> Self {
+        AnalysisConfig {
+            game: "dealers_dilemma".to_string(),
+            games: 100,
+            iterations: None,
+            seed: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Distribution {
+    pub count: usize,
+    pub min: i64,
+    pub max: i64,
+    pub mean: f64,
+    pub std_dev: f64,
+    /// Number of samples with each value
+    pub histogram: BTreeMap<i64, usize>,
+}
+
+impl Distribution {
+    pub fn from_values(values: &[i64]) -> Self {
+        if values.is_empty() {
+            return Distribution::default();
+        }
+        let count = values.len();
+        let mean = values.iter().sum::<i64>() as f64 / count as f64;
+        let variance = values
+            .iter()
+            .map(|value| (*value as f64 - mean).powi(2))
+            .sum::<f64>()
+            / count as f64;
+        let mut histogram = BTreeMap::new();
+        for value in values {
+            *histogram.entry(*value).or_insert(0) += 1;
+        }
+        Distribution {
+            count,
+            min: *values.iter().min().unwrap(),
+            max: *values.iter().max().unwrap(),
+            mean,
+            std_dev: variance.sqrt(),
+            histogram,
+        }
+    }
+}
+
+impl fmt::Display for Distribution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "mean {:.2} sd {:.2} range {} to {}",
+            self.mean, self.std_dev, self.min, self.max
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BidStatistics {
+    pub made: usize,
+    pub missed: usize,
+    pub success_rate: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalysisReport {
+    pub config: AnalysisConfig,
+    /// Final score of each seat
+    pub scores: Vec<Distribution>,
+    /// Games won by each seat (shared wins count for every winner)
+    pub wins: Vec<usize>,
+    /// Moves per game
+    pub game_lengths: Distribution,
+    pub hands_per_game: Distribution,
+    /// Moves per hand
+    pub hand_lengths: Distribution,
+    pub tricks_won: Vec<usize>,
+    /// Share of the tricks won by the seat that led them
+    pub leader_win_rate: Option<f64>,
+    /// Outcomes by kind of bid
+    pub bids: BTreeMap<String, BidStatistics>,
+}
+
+impl fmt::Display for AnalysisReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} games of {}", self.config.games, self.config.game)?;
+        let rows: Vec<Vec<String>> = self
+            .scores
+            .iter()
+            .enumerate()
+            .map(|(seat, scores)| {
+                vec![
+                    seat.to_string(),
+                    self.wins[seat].to_string(),
+                    self.tricks_won[seat].to_string(),
+                    scores.to_string(),
+                ]
+            })
+            .collect();
+        writeln!(f, "{}", render::table(&["seat", "wins", "tricks", "final score"], &rows))?;
+        writeln!(f, "moves per game: {}", self.game_lengths)?;
+        writeln!(f, "hands per game: {}", self.hands_per_game)?;
+        writeln!(f, "moves per hand: {}", self.hand_lengths)?;
+        if let Some(rate) = self.leader_win_rate {
+            writeln!(f, "tricks won by the leader: {:.3}", rate)?;
+        }
+        for (bid, statistics) in self.bids.iter() {
+            writeln!(
+                f,
+                "{} bids: {} made {} missed ({:.3})",
+                bid, statistics.made, statistics.missed, statistics.success_rate
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Play `config.games` games and collect their statistics
+pub fn analyze(config: &AnalysisConfig) -> Result<AnalysisReport, RegistryError> {
+    let player_count = registry::new_game(&config.game)?.player_count();
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let mut scores: Vec<Vec<i64>> = vec![vec![]; player_count];
+    let mut wins = vec![0; player_count];
+    let mut game_lengths = vec![];
+    let mut hands_per_game = vec![];
+    let mut hand_lengths = vec![];
+    let mut tricks_won = vec![0; player_count];
+    let mut tricks_won_by_leader = 0;
+    let mut bids: BTreeMap<String, BidStatistics> = BTreeMap::new();
+    for _ in 0..config.games {
+        let mut game = registry::new_game(&config.game)?;
+        let mut moves = 0;
+        let mut hand_moves: i64 = 0;
+        let mut hands: i64 = 0;
+        while game.winners().is_empty() && moves < MAX_MOVES_PER_GAME {
+            let action = match config.iterations {
+                Some(iterations) => game.bot_move(iterations),
+                None => *game.moves().choose(&mut rng).expect("should have a move to make"),
+            };
+            moves += 1;
+            hand_moves += 1;
+            for event in game.apply_move_with_events(action)? {
+                match event {
+                    GameEvent::TrickWon { leader, winner } => {
+                        tricks_won[winner] += 1;
+                        if leader == winner {
+                            tricks_won_by_leader += 1;
+                        }
+                    }
+                    GameEvent::HandOver => {
+                        hand_lengths.push(hand_moves);
+                        hand_moves = 0;
+                        hands += 1;
+                    }
+                    GameEvent::Bid { bid, made, .. } => {
+                        let statistics = bids.entry(bid).or_default();
+                        if made {
+                            statistics.made += 1;
+                        } else {
+                            statistics.missed += 1;
+                        }
+                    }
+                }
+            }
+        }
+        for (seat, score) in game.scores().into_iter().enumerate() {
+            scores[seat].push(score as i64);
+        }
+        for seat in game.winners() {
+            wins[seat] += 1;
+        }
+        game_lengths.push(moves as i64);
+        hands_per_game.push(hands);
+    }
+    for statistics in bids.values_mut() {
+        let total = statistics.made + statistics.missed;
+        statistics.success_rate = statistics.made as f64 / total as f64;
+    }
+    let total_tricks: usize = tricks_won.iter().sum();
+    Ok(AnalysisReport {
+        config: config.clone(),
+        scores: scores.iter().map(|scores| Distribution::from_values(scores)).collect(),
+        wins,
+        game_lengths: Distribution::from_values(&game_lengths),
+        hands_per_game: Distribution::from_values(&hands_per_game),
+        hand_lengths: Distribution::from_values(&hand_lengths),
+        tricks_won,
+        leader_win_rate: if total_tricks > 0 {
+            Some(tricks_won_by_leader as f64 / total_tricks as f64)
+        } else {
+            None
+        },
+        bids,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distribution() {
+        let distribution = Distribution::from_values(&[1, 3, 3, 5]);
+        assert_eq!(distribution.mean, 3.0);
+        assert_eq!((distribution.min, distribution.max), (1, 5));
+        assert_eq!(distribution.histogram[&3], 2);
+        assert!((distribution.std_dev - 2f64.sqrt()).abs() < 1e-9);
+        assert_eq!(Distribution::from_values(&[]).count, 0);
+    }
+
+    #[test]
+    fn test_analyze() {
+        let config = AnalysisConfig {
+            games: 3,
+            ..Default::default()
+        };
+        let report = analyze(&config).unwrap();
+        assert_eq!(report.game_lengths.count, 3);
+        assert_eq!(report.scores.len(), 3);
+        assert!(report.wins.iter().sum::<usize>() >= 3);
+        // every player's bid is scored at the end of each hand
+        let hands = report.hand_lengths.count;
+        assert!(hands >= 3);
+        let bids: usize = report.bids.values().map(|bid| bid.made + bid.missed).sum();
+        assert_eq!(bids, 3 * hands);
+        assert!(report.leader_win_rate.is_some());
+        assert!(report.to_string().starts_with("3 games of dealers_dilemma"));
+
+        // no events - only game level statistics
+        let config = AnalysisConfig {
+            game: "kaibosh".to_string(),
+            games: 1,
+            ..Default::default()
+        };
+        let report = analyze(&config).unwrap();
+        assert_eq!(report.game_lengths.count, 1);
+        assert_eq!(report.leader_win_rate, None);
+    }
+}
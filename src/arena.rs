@@ -0,0 +1,250 @@
+/*
+Head to head evaluation of two bot configurations
+
+Every deal is replayed with each seating of the two configurations so
+neither side benefits from a lucky hand or a favourable seat. For two and
+four player games the seats alternate (A B / A B A B - partners in team games
+share a configuration). Three player games are also played with the
+complement lineup (B A A) so both sides occupy the same number of seats.
+
+Win rates are reported with a Wilson score interval so promotion decisions
+can require the lower bound to clear a threshold instead of relying on a
+point estimate.
+*/
+
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+
+use crate::engine::GameEngine;
+use crate::policy::PolicyHandle;
+
+/// z value for a 95% confidence interval
+pub const Z_95: f64 = 1.96;
+
+/// Safety net for engines that never reach a terminal state
+const MAX_MOVES_PER_GAME: usize = 10_000;
+
+pub trait MoveMaker<G> {
+    /// `rng` is seeded per game so bots that need randomness are reproducible
+    fn get_move(&mut self, game: &G, rng: &mut StdRng) -> i32;
+    fn get_name(&self) -> &str;
+}
+
+/// ISMCTS bot optionally guided by a policy model
+pub struct MctsMove<G> {
+    pub name: String,
+    pub iterations: i32,
+    pub policy: Option<PolicyHandle<G>>,
+}
+
+impl<G: GameEngine> MoveMaker<G> for MctsMove<G> {
+    fn get_move(&mut self, game: &G, _rng: &mut StdRng) -> i32 {
+        game.bot_move(self.iterations, self.policy.clone())
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+}
+
+pub struct RandomMove {
+    pub name: String,
+}
+
+impl<G: GameEngine> MoveMaker<G> for RandomMove {
+    fn get_move(&mut self, game: &G, rng: &mut StdRng) -> i32 {
+        *game
+            .legal_moves()
+            .choose(rng)
+            .expect("should have a move to make")
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArenaConfig {
+    /// Number of deals - each one is played once per seating
+    pub deals: usize,
+    /// Seed of the first deal's random number generator
+    pub seed: u64,
+    pub z: f64,
+}
+
+impl Default for ArenaConfig {
+    fn default() -> Self {
+        ArenaConfig {
+            deals: 50,
+            seed: 0,
+            z: Z_95,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArenaReport {
+    pub a: String,
+    pub b: String,
+    pub games: usize,
+    // games won by A outright, shared with B, and lost to B
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+    /// A's points (draws count half) divided by games
+    pub win_rate: f64,
+    pub confidence_interval: (f64, f64),
+    // total thinking time of each configuration
+    pub move_time: [Duration; 2],
+}
+
+impl ArenaReport {
+    /// A is better than `threshold` with the configured confidence
+    pub fn beats(&self, threshold: f64) -> bool {
+        self.confidence_interval.0 > threshold
+    }
+}
+
+/// Wilson score interval for `successes` out of `trials`
+pub fn wilson_interval(successes: f64, trials: usize, z: f64) -> (f64, f64) {
+    if trials == 0 {
+        return (0.0, 1.0);
+    }
+    let n = trials as f64;
+    let p = successes / n;
+    let z2 = z * z;
+    let center = (p + z2 / (2.0 * n)) / (1.0 + z2 / n);
+    let margin = z * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt() / (1.0 + z2 / n);
+    ((center - margin).max(0.0), (center + margin).min(1.0))
+}
+
+/// Seatings used for every deal - true marks a seat played by A
+pub fn seatings(player_count: usize) -> Vec<Vec<bool>> {
+    let alternating: Vec<bool> = (0..player_count).map(|seat| seat % 2 == 0).collect();
+    let mut bases = vec![alternating.clone()];
+    if player_count % 2 == 1 {
+        bases.push(alternating.iter().map(|a| !a).collect());
+    }
+    let mut seatings: Vec<Vec<bool>> = vec![];
+    for base in bases {
+        for shift in 0..player_count {
+            let mut seating = base.clone();
+            seating.rotate_right(shift);
+            if !seatings.contains(&seating) {
+                seatings.push(seating);
+            }
+        }
+    }
+    seatings
+}
+
+/// Play `a` against `b` on fresh deals from `G::new_game`
+pub fn play_match<G: GameEngine>(
+    a: &mut dyn MoveMaker<G>,
+    b: &mut dyn MoveMaker<G>,
+    config: &ArenaConfig,
+) -> ArenaReport {
+    play_match_from(a, b, config, G::new_game)
+}
+
+/// Play `a` against `b` on deals produced by `new_game`
+pub fn play_match_from<G: GameEngine>(
+    a: &mut dyn MoveMaker<G>,
+    b: &mut dyn MoveMaker<G>,
+    config: &ArenaConfig,
+    mut new_game: impl FnMut() -> G,
+) -> ArenaReport {
+    let mut report = ArenaReport {
+        a: a.get_name().to_string(),
+        b: b.get_name().to_string(),
+        ..Default::default()
+    };
+    let seatings = seatings(G::PLAYER_COUNT);
+    for deal in 0..config.deals {
+        let start_game = new_game();
+        for seating in seatings.iter() {
+            let mut rng = StdRng::seed_from_u64(config.seed.wrapping_add(deal as u64));
+            let mut game = start_game.clone();
+            for _ in 0..MAX_MOVES_PER_GAME {
+                if game.is_over() {
+                    break;
+                }
+                let side = if seating[game.current_seat()] { 0 } else { 1 };
+                let player: &mut dyn MoveMaker<G> = if side == 0 { &mut *a } else { &mut *b };
+                let start = Instant::now();
+                let action = player.get_move(&game, &mut rng);
+                report.move_time[side] += start.elapsed();
+                game.play(action);
+            }
+            let winners = game.winners();
+            let a_won = winners.iter().any(|seat| seating[*seat]);
+            let b_won = winners.iter().any(|seat| !seating[*seat]);
+            match (a_won, b_won) {
+                (true, false) => report.wins += 1,
+                (true, true) => report.draws += 1,
+                _ => report.losses += 1,
+            }
+            report.games += 1;
+        }
+    }
+    let points = report.wins as f64 + report.draws as f64 / 2.0;
+    if report.games > 0 {
+        report.win_rate = points / report.games as f64;
+    }
+    report.confidence_interval = wilson_interval(points, report.games, config.z);
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::dealers_dilemma::Game;
+
+    #[test]
+    fn test_seatings() {
+        assert_eq!(seatings(2), vec![vec![true, false], vec![false, true]]);
+        let three = seatings(3);
+        assert_eq!(three.len(), 6);
+        for seat in 0..3 {
+            let a_seats = three.iter().filter(|seating| seating[seat]).count();
+            assert_eq!(a_seats, 3);
+        }
+        assert_eq!(seatings(4).len(), 2);
+    }
+
+    #[test]
+    fn test_wilson_interval() {
+        let (low, high) = wilson_interval(50.0, 100, Z_95);
+        assert!(low < 0.5 && high > 0.5);
+        assert!((0.5 - low - (high - 0.5)).abs() < 1e-9);
+        let (low, high) = wilson_interval(0.0, 10, Z_95);
+        assert_eq!(low, 0.0);
+        assert!(high > 0.0);
+    }
+
+    #[test]
+    fn test_play_match() {
+        let config = ArenaConfig {
+            deals: 2,
+            ..Default::default()
+        };
+        let mut a = RandomMove {
+            name: "random1".to_string(),
+        };
+        let mut b = RandomMove {
+            name: "random2".to_string(),
+        };
+        let report = play_match::<Game>(&mut a, &mut b, &config);
+        assert_eq!(report.games, 2 * seatings(3).len());
+        assert_eq!(report.wins + report.draws + report.losses, report.games);
+        assert!(report.confidence_interval.0 <= report.win_rate);
+        assert!(report.win_rate <= report.confidence_interval.1);
+    }
+}
@@ -0,0 +1,37 @@
+/*
+Writes a `.dart` file per game with its change protocol enums.
+
+  cargo run --bin dart [output directory]
+*/
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use tricksterstable_rs::games::{dealers_dilemma, hotdog, kaibosh, kansascity, so8, szs, yokai2p};
+
+fn main() -> io::Result<()> {
+    let dir = env::args()
+        .nth(1)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("dart"));
+    fs::create_dir_all(&dir)?;
+
+    let games = [
+        ("dealers_dilemma", dealers_dilemma::dart_enums()),
+        ("hotdog", hotdog::dart_enums()),
+        ("kaibosh", kaibosh::dart_enums()),
+        ("kansascity", kansascity::dart_enums()),
+        ("so8", so8::dart_enums()),
+        ("szs", szs::dart_enums()),
+        ("yokai2p", yokai2p::dart_enums()),
+    ];
+    for (name, enums) in games {
+        let path = dir.join(format!("{}.dart", name));
+        let header = "// Generated by `cargo run --bin dart` - do not edit";
+        fs::write(&path, format!("{}\n\n{}", header, enums))?;
+        println!("wrote {}", path.display());
+    }
+    Ok(())
+}
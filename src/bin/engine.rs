@@ -0,0 +1,11 @@
+/*
+Speaks the line based engine protocol from protocol.rs on stdin/stdout.
+
+  cargo run --release --bin engine
+*/
+
+use std::io;
+
+fn main() -> io::Result<()> {
+    tricksterstable_rs::protocol::run(io::stdin().lock(), io::stdout().lock())
+}
@@ -0,0 +1,24 @@
+/*
+Serves the engines over gRPC (see proto/engine.proto).
+
+  cargo run --release --features grpc --bin grpc [address]
+*/
+
+use std::env;
+
+use tonic::transport::Server;
+use tricksterstable_rs::grpc::{EngineServer, EngineService};
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let address = env::args()
+        .nth(1)
+        .unwrap_or_else(|| "[::1]:50051".to_string())
+        .parse()?;
+    println!("serving on {}", address);
+    Server::builder()
+        .add_service(EngineServer::new(EngineService::new()))
+        .serve(address)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,44 @@
+/*
+Writes a JSON Schema for every game's serialized state and Change type so
+changes to the contract with the apps show up as a diff.
+
+  cargo run --features schema --bin schema [output directory]
+*/
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use schemars::{schema::RootSchema, schema_for};
+use tricksterstable_rs::games::{dealers_dilemma, hotdog, kaibosh, kansascity, so8, szs, yokai2p};
+
+fn write_schema(dir: &Path, file_name: &str, schema: RootSchema) -> io::Result<()> {
+    let path = dir.join(file_name);
+    fs::write(&path, serde_json::to_string_pretty(&schema)? + "\n")?;
+    println!("wrote {}", path.display());
+    Ok(())
+}
+
+fn main() -> io::Result<()> {
+    let dir = env::args()
+        .nth(1)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("schema"));
+    fs::create_dir_all(&dir)?;
+
+    write_schema(&dir, "dealers_dilemma.state.json", schema_for!(dealers_dilemma::Game))?;
+    write_schema(&dir, "dealers_dilemma.change.json", schema_for!(dealers_dilemma::Change))?;
+    write_schema(&dir, "hotdog.state.json", schema_for!(hotdog::HotdogGame))?;
+    write_schema(&dir, "hotdog.change.json", schema_for!(hotdog::Change))?;
+    write_schema(&dir, "kaibosh.state.json", schema_for!(kaibosh::KaiboshGame))?;
+    write_schema(&dir, "kansascity.state.json", schema_for!(kansascity::KansasCityGame))?;
+    write_schema(&dir, "kansascity.change.json", schema_for!(kansascity::Change))?;
+    write_schema(&dir, "so8.state.json", schema_for!(so8::SixOfVIIIGame))?;
+    write_schema(&dir, "so8.change.json", schema_for!(so8::Change))?;
+    write_schema(&dir, "szs.state.json", schema_for!(szs::Game))?;
+    write_schema(&dir, "szs.change.json", schema_for!(szs::Change))?;
+    write_schema(&dir, "yokai2p.state.json", schema_for!(yokai2p::Yokai2pGame))?;
+    write_schema(&dir, "yokai2p.change.json", schema_for!(yokai2p::Change))?;
+    Ok(())
+}
@@ -0,0 +1,37 @@
+/*
+Writes a `.d.ts` file per game describing its serialized state and changes.
+
+  cargo run --features typescript --bin typescript [output directory]
+*/
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use tricksterstable_rs::games::{dealers_dilemma, hotdog, kaibosh, kansascity, so8, szs, yokai2p};
+
+fn main() -> io::Result<()> {
+    let dir = env::args()
+        .nth(1)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("bindings"));
+    fs::create_dir_all(&dir)?;
+
+    let games = [
+        ("dealers_dilemma", dealers_dilemma::typescript_declarations()),
+        ("hotdog", hotdog::typescript_declarations()),
+        ("kaibosh", kaibosh::typescript_declarations()),
+        ("kansascity", kansascity::typescript_declarations()),
+        ("so8", so8::typescript_declarations()),
+        ("szs", szs::typescript_declarations()),
+        ("yokai2p", yokai2p::typescript_declarations()),
+    ];
+    for (name, declarations) in games {
+        let path = dir.join(format!("{}.d.ts", name));
+        let header = "// Generated by `cargo run --bin typescript` - do not edit";
+        fs::write(&path, format!("{}\n\n{}", header, declarations))?;
+        println!("wrote {}", path.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,82 @@
+/*
+Dart code generation for the change protocol
+
+The Flutter client switches on the serialized names of each game's
+ChangeType, Location and State enums. Generating the Dart enums from the
+Rust definitions keeps the two in sync - the value names come from serde so
+any `rename_all` attribute is respected.
+
+  cargo run --bin dart [output directory]
+*/
+
+use enum_iterator::{all, Sequence};
+use serde::Serialize;
+
+// Identifiers that can't be used as enum values in Dart
+const RESERVED: [&str; 32] = [
+    "assert", "break", "case", "catch", "class", "const", "continue", "default", "do", "else",
+    "enum", "extends", "false", "final", "finally", "for", "if", "in", "index", "is", "new",
+    "null", "rethrow", "return", "super", "switch", "this", "throw", "true", "try", "values",
+    "while",
+];
+
+fn dart_identifier(value: &str) -> String {
+    if RESERVED.contains(&value) {
+        format!("{}_", value)
+    } else {
+        value.to_string()
+    }
+}
+
+/// Dart enum named `name` with a `fromJson` switch over the serialized
+/// values of `T`
+pub fn dart_enum<T: Sequence + Serialize>(name: &str) -> String {
+    let values: Vec<String> = all::<T>()
+        .map(|value| match serde_json::to_value(value) {
+            Ok(serde_json::Value::String(value)) => value,
+            _ => panic!("{} values must serialize to strings", name),
+        })
+        .collect();
+
+    let mut dart = format!("enum {} {{\n", name);
+    let identifiers: Vec<String> = values.iter().map(|value| dart_identifier(value)).collect();
+    dart.push_str(&format!("  {};\n\n", identifiers.join(",\n  ")));
+    dart.push_str(&format!("  static {} fromJson(String value) {{\n", name));
+    dart.push_str("    switch (value) {\n");
+    for value in values.iter() {
+        dart.push_str(&format!("      case '{}':\n", value));
+        dart.push_str(&format!("        return {}.{};\n", name, dart_identifier(value)));
+    }
+    dart.push_str("      default:\n");
+    dart.push_str(&format!("        throw ArgumentError('Unknown {}: $value');\n", name));
+    dart.push_str("    }\n  }\n\n");
+    dart.push_str("  String toJson() {\n    switch (this) {\n");
+    for value in values.iter() {
+        dart.push_str(&format!("      case {}.{}:\n", name, dart_identifier(value)));
+        dart.push_str(&format!("        return '{}';\n", value));
+    }
+    dart.push_str("    }\n  }\n}\n");
+    dart
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Clone, Copy, Sequence, Serialize, Deserialize, PartialEq, Eq)]
+    #[serde(rename_all = "camelCase")]
+    enum Example {
+        ShowPlayable,
+        Default,
+    }
+
+    #[test]
+    fn test_dart_enum() {
+        let dart = dart_enum::<Example>("Example");
+        assert!(dart.starts_with("enum Example {\n  showPlayable,\n  default_;\n"));
+        assert!(dart.contains("      case 'default':\n        return Example.default_;\n"));
+        assert!(dart.contains("      case Example.showPlayable:\n        return 'showPlayable';\n"));
+        assert!(dart.contains("throw ArgumentError('Unknown Example: $value');"));
+    }
+}
@@ -0,0 +1,197 @@
+/*
+Common interface implemented by every game engine
+
+Tooling that works across games (self-play, training, arenas, bindings)
+is written against `GameEngine` instead of each game's own API.
+*/
+
+use std::sync::Arc;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::features::FeatureExtractor;
+use crate::policy::{LeafEvaluation, LeafEvaluator, LeafHandle, PolicyHandle};
+
+/// Something a move did that the analyze command counts
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum GameEvent {
+    /// `winner` took the trick `leader` led
+    TrickWon { leader: usize, winner: usize },
+    /// A hand was scored
+    HandOver,
+    /// `seat`'s bid was made or missed when the hand was scored
+    Bid { seat: usize, bid: String, made: bool },
+}
+
+pub trait GameEngine:
+    ismcts::Game<Move = i32, MoveList = Vec<i32>>
+    + FeatureExtractor
+    + Clone
+    + Send
+    + Sync
+    + Serialize
+    + DeserializeOwned
+    + 'static
+{
+    /// Short name used to select the game from the command line and bindings
+    const NAME: &'static str;
+    /// Seats in the game returned by `new_game`
+    const PLAYER_COUNT: usize;
+    /// Range of the values returned by `ismcts::Game::result`
+    const RESULT_RANGE: (f64, f64) = (0.0, 1.0);
+
+    /// A freshly dealt game ready for the first move
+    fn new_game() -> Self;
+
+    /// Seats in this game - only differs from `PLAYER_COUNT` for games that
+    /// can be set up for more players
+    fn player_count(&self) -> usize {
+        Self::PLAYER_COUNT
+    }
+
+    /// Score for every seat (team games repeat the team score for each partner)
+    fn scores(&self) -> Vec<i32>;
+
+    /// Seats that won the game - empty while the game is in progress
+    fn winners(&self) -> Vec<usize>;
+
+    /// Move chosen by the ISMCTS bot for the player to act
+    fn bot_move(&self, iterations: i32, policy: Option<PolicyHandle<Self>>) -> i32 {
+        self.bot_move_with_leaf_evaluation(iterations, policy, None)
+    }
+
+    /// Like `bot_move` but rollouts may be truncated and scored by a leaf evaluator
+    fn bot_move_with_leaf_evaluation(
+        &self,
+        iterations: i32,
+        policy: Option<PolicyHandle<Self>>,
+        leaf_evaluation: Option<LeafEvaluation<Self>>,
+    ) -> i32;
+
+    /// Static estimate of the position for `seat` between -1.0 and 1.0 used
+    /// to score truncated rollouts - by default the seat's score relative to
+    /// the best opposing score. Searches reset the scores before they start
+    /// so trick taking engines override this to project the hand in progress.
+    fn heuristic_value(&self, seat: usize) -> f64 {
+        let scores = self.scores();
+        let own = scores.get(seat).copied().unwrap_or(0) as f64;
+        let best_opponent = scores
+            .iter()
+            .enumerate()
+            .filter(|(other, _)| *other != seat)
+            .map(|(_, score)| *score as f64)
+            .fold(f64::NEG_INFINITY, f64::max);
+        if !best_opponent.is_finite() {
+            return 0.0;
+        }
+        score_margin(own, best_opponent)
+    }
+
+    fn legal_moves(&self) -> Vec<i32> {
+        self.available_moves()
+    }
+
+    /// `legal_moves` written into `moves` (cleared first) - engines override
+    /// this to fill the buffer without allocating a new list
+    fn legal_moves_into(&self, moves: &mut Vec<i32>) {
+        moves.clear();
+        moves.extend(self.available_moves());
+    }
+
+    /// Whether `action` can be played now - engines with moves outside
+    /// `legal_moves` (e.g. a human player's undo) override this
+    fn is_legal_move(&self, action: i32) -> bool {
+        self.legal_moves().contains(&action)
+    }
+
+    fn play(&mut self, action: i32) {
+        self.make_move(&action);
+    }
+
+    fn is_over(&self) -> bool {
+        !self.winners().is_empty()
+    }
+
+    /// Short human readable description of a legal move for terminal play
+    fn describe_move(&self, action: i32) -> String {
+        action.to_string()
+    }
+
+    /// The table as seen by `seat` (hidden information stays hidden)
+    fn render(&self, _seat: usize) -> String {
+        format!("scores: {:?}", self.scores())
+    }
+
+    /// Events caused by the move that turned this position into `after` -
+    /// engines that report none only get game level statistics
+    fn events(&self, _after: &Self) -> Vec<GameEvent> {
+        vec![]
+    }
+
+    /// 1.0 for a win, -1.0 for a loss and 0.0 while the game is in progress
+    fn outcome(&self, seat: usize) -> f32 {
+        let winners = self.winners();
+        if winners.is_empty() {
+            0.0
+        } else if winners.contains(&seat) {
+            1.0
+        } else {
+            -1.0
+        }
+    }
+
+    /// Truncate rollouts after `horizon` plies and score them with `evaluator`
+    /// (the game's heuristic when None)
+    fn leaf_evaluation(
+        horizon: usize,
+        evaluator: Option<LeafHandle<Self>>,
+    ) -> LeafEvaluation<Self> {
+        LeafEvaluation {
+            horizon,
+            evaluator: evaluator.unwrap_or_else(|| Arc::new(HeuristicEvaluator)),
+            result_range: Self::RESULT_RANGE,
+        }
+    }
+}
+
+/// Squash the lead of `own` over `opponent` into the -1.0 to 1.0 range
+pub fn score_margin(own: f64, opponent: f64) -> f64 {
+    (own - opponent) / (own.abs() + opponent.abs() + 1.0)
+}
+
+/// `score_margin` of projected scores for a hand that is still being played
+/// - a lead counts for less while `reachable` points are still up for grabs
+pub fn projected_margin(own: f64, opponent: f64, reachable: f64) -> f64 {
+    (own - opponent) / (own.abs() + opponent.abs() + reachable.abs() + 1.0)
+}
+
+/// Leaf evaluator backed by `GameEngine::heuristic_value`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HeuristicEvaluator;
+
+impl<G: GameEngine> LeafEvaluator<G> for HeuristicEvaluator {
+    fn evaluate(&self, game: &G, seat: usize) -> f64 {
+        game.heuristic_value(seat)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_score_margin() {
+        assert_eq!(score_margin(0.0, 0.0), 0.0);
+        assert!(score_margin(10.0, 2.0) > 0.0);
+        assert!(score_margin(-5.0, 3.0) < 0.0);
+        assert!(score_margin(1000.0, -1000.0) < 1.0);
+    }
+
+    #[test]
+    fn test_projected_margin() {
+        assert_eq!(projected_margin(3.0, 1.0, 0.0), score_margin(3.0, 1.0));
+        assert!(projected_margin(3.0, 1.0, 10.0) < projected_margin(3.0, 1.0, 2.0));
+        assert!(projected_margin(3.0, 1.0, 10.0) > 0.0);
+    }
+}
@@ -0,0 +1,235 @@
+/*
+Multi-agent reinforcement learning environment (PettingZoo AEC style)
+
+Seats are the agents and exactly one of them acts at a time:
+
+  let mut env: Env<KaiboshGame> = Env::new();
+  env.reset();
+  while !env.done() {
+      let agent = env.agent_selection();
+      let observation = env.observe(agent);
+      env.step(policy(&observation))?;
+  }
+
+Observations are the game's FeatureExtractor features plus a mask over the
+policy head marking the legal actions of the agent to act. Rewards are only
+handed out when the game ends (1.0 for a win, -1.0 for a loss).
+*/
+
+use std::error::Error;
+use std::fmt;
+
+use crate::engine::GameEngine;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EnvError {
+    IllegalMove(i32),
+    // step called after the game was terminated or truncated
+    Done,
+}
+
+impl fmt::Display for EnvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvError::IllegalMove(action) => write!(f, "illegal move {}", action),
+            EnvError::Done => write!(f, "the episode is over - call reset"),
+        }
+    }
+}
+
+impl Error for EnvError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Observation {
+    /// `G::FEATURE_COUNT` features seen by the observing seat
+    pub features: Vec<f32>,
+    /// `G::ACTION_COUNT` slots - true for the legal actions when the
+    /// observing seat is the one to act
+    pub action_mask: Vec<bool>,
+}
+
+pub struct Env<G: GameEngine> {
+    game: G,
+    // reward of the last step and totals for the episode
+    rewards: Vec<f32>,
+    cumulative_rewards: Vec<f32>,
+    steps: usize,
+    max_steps: Option<usize>,
+}
+
+impl<G: GameEngine> Default for Env<G> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<G: GameEngine> Env<G> {
+    pub fn new() -> Self {
+        Env {
+            game: G::new_game(),
+            rewards: vec![0.0; G::PLAYER_COUNT],
+            cumulative_rewards: vec![0.0; G::PLAYER_COUNT],
+            steps: 0,
+            max_steps: None,
+        }
+    }
+
+    /// Truncate episodes after `max_steps` actions
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = Some(max_steps);
+        self
+    }
+
+    /// Deal a new game
+    pub fn reset(&mut self) {
+        self.reset_from(G::new_game());
+    }
+
+    /// Start an episode from an existing position
+    pub fn reset_from(&mut self, game: G) {
+        self.game = game;
+        self.rewards = vec![0.0; G::PLAYER_COUNT];
+        self.cumulative_rewards = vec![0.0; G::PLAYER_COUNT];
+        self.steps = 0;
+    }
+
+    pub fn agents(&self) -> std::ops::Range<usize> {
+        0..G::PLAYER_COUNT
+    }
+
+    /// The seat to act
+    pub fn agent_selection(&self) -> usize {
+        self.game.current_seat()
+    }
+
+    pub fn observe(&self, seat: usize) -> Observation {
+        Observation {
+            features: self.game.features(seat),
+            action_mask: self.action_mask(seat),
+        }
+    }
+
+    pub fn action_mask(&self, seat: usize) -> Vec<bool> {
+        let mut mask = vec![false; G::ACTION_COUNT];
+        if seat == self.agent_selection() && !self.done() {
+            for action in self.game.legal_moves() {
+                mask[self.game.action_index(action)] = true;
+            }
+        }
+        mask
+    }
+
+    pub fn legal_actions(&self) -> Vec<i32> {
+        if self.done() {
+            vec![]
+        } else {
+            self.game.legal_moves()
+        }
+    }
+
+    /// Play `action` (a game move, not a policy head slot) for the agent to act
+    pub fn step(&mut self, action: i32) -> Result<(), EnvError> {
+        if self.done() {
+            return Err(EnvError::Done);
+        }
+        if !self.game.legal_moves().contains(&action) {
+            return Err(EnvError::IllegalMove(action));
+        }
+        self.game.play(action);
+        self.steps += 1;
+        for seat in self.agents() {
+            self.rewards[seat] = if self.terminated() {
+                self.game.outcome(seat)
+            } else {
+                0.0
+            };
+            self.cumulative_rewards[seat] += self.rewards[seat];
+        }
+        Ok(())
+    }
+
+    /// Step with the policy head slot chosen by a network instead of a move
+    pub fn step_index(&mut self, index: usize) -> Result<(), EnvError> {
+        let action = self
+            .legal_actions()
+            .into_iter()
+            .find(|action| self.game.action_index(*action) == index)
+            .ok_or(EnvError::IllegalMove(index as i32))?;
+        self.step(action)
+    }
+
+    /// Rewards from the last step
+    pub fn rewards(&self) -> &[f32] {
+        &self.rewards
+    }
+
+    pub fn cumulative_rewards(&self) -> &[f32] {
+        &self.cumulative_rewards
+    }
+
+    /// The game reached a terminal state
+    pub fn terminated(&self) -> bool {
+        self.game.is_over()
+    }
+
+    /// The episode hit the step limit before the game ended
+    pub fn truncated(&self) -> bool {
+        !self.terminated() && self.max_steps.is_some_and(|max_steps| self.steps >= max_steps)
+    }
+
+    pub fn done(&self) -> bool {
+        self.terminated() || self.truncated()
+    }
+
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    pub fn game(&self) -> &G {
+        &self.game
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::yokai2p::Yokai2pGame;
+    use rand::{seq::SliceRandom, thread_rng};
+
+    #[test]
+    fn test_random_episode() {
+        let mut env: Env<Yokai2pGame> = Env::new();
+        env.reset();
+        while !env.done() {
+            let agent = env.agent_selection();
+            let observation = env.observe(agent);
+            assert_eq!(observation.features.len(), Yokai2pGame::FEATURE_COUNT);
+            let legal = env.legal_actions();
+            assert_eq!(
+                observation.action_mask.iter().filter(|legal| **legal).count(),
+                legal.len()
+            );
+            let other = (agent + 1) % Yokai2pGame::PLAYER_COUNT;
+            assert!(env.observe(other).action_mask.iter().all(|legal| !legal));
+            assert_eq!(env.step(i32::MIN), Err(EnvError::IllegalMove(i32::MIN)));
+            env.step(*legal.choose(&mut thread_rng()).unwrap()).unwrap();
+        }
+        assert!(env.terminated());
+        let total: f32 = env.cumulative_rewards().iter().map(|reward| reward.abs()).sum();
+        assert_eq!(total, Yokai2pGame::PLAYER_COUNT as f32);
+        assert_eq!(env.step(0), Err(EnvError::Done));
+    }
+
+    #[test]
+    fn test_truncation() {
+        let mut env: Env<Yokai2pGame> = Env::new().with_max_steps(2);
+        for _ in 0..2 {
+            let action = env.legal_actions()[0];
+            env.step(action).unwrap();
+        }
+        assert!(env.truncated() && env.done());
+        assert!(env.legal_actions().is_empty());
+        env.reset();
+        assert!(!env.done());
+    }
+}
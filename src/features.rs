@@ -0,0 +1,126 @@
+/*
+Numeric encodings of game positions for learned models
+
+Every game that can be driven by a policy or value network implements
+`FeatureExtractor`. Features are always built from the point of view of a
+single observer so hidden information (opponent hands, face down cards)
+never leaks into a model input.
+*/
+
+pub trait FeatureExtractor {
+    /// Length of the vector returned by `features`
+    const FEATURE_COUNT: usize;
+    /// Size of the policy head - every legal move maps to a slot below this
+    const ACTION_COUNT: usize;
+
+    /// Encode the position as seen by `observer`
+    fn features(&self, observer: usize) -> Vec<f32>;
+
+    /// Slot in the policy head for a move in the current state
+    fn action_index(&self, mov: i32) -> usize;
+
+    /// Seat of the player to act (models are evaluated for this player)
+    fn current_seat(&self) -> usize;
+}
+
+/// Helper for building fixed size feature vectors
+pub struct FeatureBuilder {
+    values: Vec<f32>,
+}
+
+impl FeatureBuilder {
+    pub fn with_capacity(capacity: usize) -> Self {
+        FeatureBuilder {
+            values: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// One slot per card in the deck set to 1.0 for every id given
+    pub fn cards(&mut self, ids: impl IntoIterator<Item = i32>, deck_size: usize) -> &mut Self {
+        let start = self.values.len();
+        self.values.resize(start + deck_size, 0.0);
+        for id in ids {
+            self.values[start + id as usize] = 1.0;
+        }
+        self
+    }
+
+    /// `size` slots with the slot at `index` set (all zero for None)
+    pub fn one_hot(&mut self, index: Option<usize>, size: usize) -> &mut Self {
+        let start = self.values.len();
+        self.values.resize(start + size, 0.0);
+        if let Some(index) = index {
+            self.values[start + index] = 1.0;
+        }
+        self
+    }
+
+    pub fn scalar(&mut self, value: f32) -> &mut Self {
+        self.values.push(value);
+        self
+    }
+
+    pub fn finish(self, expected: usize) -> Vec<f32> {
+        debug_assert_eq!(self.values.len(), expected, "feature count mismatch");
+        self.values
+    }
+}
+
+/// Seat `offset` places to the left of `observer` so every model input is
+/// laid out relative to the player it is evaluated for
+#[inline]
+pub fn relative_seat(observer: usize, offset: usize, player_count: usize) -> usize {
+    (observer + offset) % player_count
+}
+
+/// Inverse of `relative_seat`
+#[inline]
+pub fn seat_offset(observer: usize, player: usize, player_count: usize) -> usize {
+    (player + player_count - observer) % player_count
+}
+
+/// Play random moves checking that every position encodes to the declared
+/// number of features and every legal move maps into the policy head
+#[cfg(test)]
+pub(crate) fn assert_features_consistent<G>(mut game: G, player_count: usize)
+where
+    G: FeatureExtractor + ismcts::Game<Move = i32, MoveList = Vec<i32>>,
+{
+    use rand::seq::SliceRandom;
+
+    let mut rng = rand::thread_rng();
+    for _ in 0..500 {
+        for observer in 0..player_count {
+            assert_eq!(game.features(observer).len(), G::FEATURE_COUNT);
+        }
+        let moves = game.available_moves();
+        for mov in moves.iter() {
+            assert!(game.action_index(*mov) < G::ACTION_COUNT, "move {}", mov);
+        }
+        if game.result(game.current_player()).is_some() || moves.is_empty() {
+            break;
+        }
+        game.make_move(moves.choose(&mut rng).unwrap());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_feature_builder() {
+        let mut builder = FeatureBuilder::with_capacity(8);
+        builder
+            .cards(vec![0, 2], 4)
+            .one_hot(Some(1), 3)
+            .one_hot(None, 0)
+            .scalar(0.5);
+        assert_eq!(
+            builder.finish(8),
+            vec![1.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.5]
+        );
+        assert_eq!(relative_seat(2, 2, 3), 1);
+        assert_eq!(seat_offset(2, 1, 3), 2);
+    }
+}
@@ -0,0 +1,384 @@
+/*
+C ABI (enabled with the `ffi` feature)
+
+Games are opaque handles created by name through the registry and every
+structured value is returned as a JSON string that must be released with
+free_string. Panics never cross the boundary: a failing call returns NULL
+(-1 from apply_move, i32::MIN from get_bot_move) and the message is
+available from last_error.
+
+With the `protobuf` feature states and changes can also be exchanged in the
+compact encoding from wire.rs and with the `msgpack` feature as MessagePack
+snapshots - the returned buffers must be released with free_bytes.
+
+  void *game = new_game("szs");
+  char *moves = get_moves_json(game);
+  ...
+  free_string(moves);
+  free_game(game);
+*/
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+use crate::registry::{self, DynGame};
+
+pub struct FfiGame {
+    game: Box<dyn DynGame>,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Run `f` turning panics and errors into `fallback`
+fn guard<T>(fallback: T, f: impl FnOnce() -> Result<T, String>) -> T {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => value,
+        Ok(Err(message)) => {
+            set_last_error(message);
+            fallback
+        }
+        Err(panic) => {
+            let message = panic
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "panic in game engine".to_string());
+            set_last_error(message);
+            fallback
+        }
+    }
+}
+
+fn to_c_string(value: String) -> Result<*mut c_char, String> {
+    CString::new(value)
+        .map(CString::into_raw)
+        .map_err(|error| error.to_string())
+}
+
+unsafe fn read_str<'a>(value: *const c_char) -> Result<&'a str, String> {
+    if value.is_null() {
+        return Err("unexpected null string".to_string());
+    }
+    CStr::from_ptr(value)
+        .to_str()
+        .map_err(|error| error.to_string())
+}
+
+unsafe fn game_ref<'a>(game: *const FfiGame) -> Result<&'a FfiGame, String> {
+    game.as_ref().ok_or_else(|| "unexpected null game".to_string())
+}
+
+/// # Safety
+/// `name` must be a valid NUL terminated string
+#[no_mangle]
+pub unsafe extern "C" fn new_game(name: *const c_char) -> *mut FfiGame {
+    guard(ptr::null_mut(), || {
+        let game = registry::new_game(read_str(name)?).map_err(|e| e.to_string())?;
+        Ok(Box::into_raw(Box::new(FfiGame { game })))
+    })
+}
+
+/// # Safety
+/// `name` and `state` must be valid NUL terminated strings
+#[no_mangle]
+pub unsafe extern "C" fn load_game(name: *const c_char, state: *const c_char) -> *mut FfiGame {
+    guard(ptr::null_mut(), || {
+        let game =
+            registry::load_game(read_str(name)?, read_str(state)?).map_err(|e| e.to_string())?;
+        Ok(Box::into_raw(Box::new(FfiGame { game })))
+    })
+}
+
+/// # Safety
+/// `game` must come from new_game or load_game and not be used afterwards
+#[no_mangle]
+pub unsafe extern "C" fn free_game(game: *mut FfiGame) {
+    if !game.is_null() {
+        drop(Box::from_raw(game));
+    }
+}
+
+/// Returns 0 on success and -1 when the move is illegal
+///
+/// # Safety
+/// `game` must be a live handle from new_game or load_game
+#[no_mangle]
+pub unsafe extern "C" fn apply_move(game: *mut FfiGame, action: i32) -> i32 {
+    guard(-1, || {
+        let game = game.as_mut().ok_or_else(|| "unexpected null game".to_string())?;
+        game.game.apply_move(action).map_err(|e| e.to_string())?;
+        Ok(0)
+    })
+}
+
+/// # Safety
+/// `game` must be a live handle from new_game or load_game
+#[no_mangle]
+pub unsafe extern "C" fn get_state_json(game: *const FfiGame) -> *mut c_char {
+    guard(ptr::null_mut(), || to_c_string(game_ref(game)?.game.state_json()))
+}
+
+/// # Safety
+/// `game` must be a live handle from new_game or load_game
+#[no_mangle]
+pub unsafe extern "C" fn get_changes_json(game: *const FfiGame) -> *mut c_char {
+    guard(ptr::null_mut(), || to_c_string(game_ref(game)?.game.changes_json()))
+}
+
+/// # Safety
+/// `game` must be a live handle from new_game or load_game
+#[no_mangle]
+pub unsafe extern "C" fn get_moves_json(game: *const FfiGame) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let moves = game_ref(game)?.game.moves();
+        to_c_string(serde_json::to_string(&moves).map_err(|e| e.to_string())?)
+    })
+}
+
+/// Returns the bot's move or i32::MIN on failure (some games use -1 as a move)
+///
+/// # Safety
+/// `game` must be a live handle from new_game or load_game
+#[no_mangle]
+pub unsafe extern "C" fn get_bot_move(game: *const FfiGame, iterations: i32) -> i32 {
+    guard(i32::MIN, || Ok(game_ref(game)?.game.bot_move(iterations)))
+}
+
+#[cfg(any(feature = "protobuf", feature = "msgpack"))]
+fn to_bytes(bytes: Vec<u8>, len: *mut usize) -> Result<*mut u8, String> {
+    let len = unsafe { len.as_mut() }.ok_or_else(|| "unexpected null length".to_string())?;
+    *len = bytes.len();
+    Ok(Box::into_raw(bytes.into_boxed_slice()) as *mut u8)
+}
+
+#[cfg(feature = "protobuf")]
+fn encode_json(json: &str) -> Result<Vec<u8>, String> {
+    let value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    Ok(crate::wire::encode(&value))
+}
+
+/// # Safety
+/// `name` must be a valid NUL terminated string and `state` must point to
+/// `len` bytes produced by get_state_protobuf
+#[cfg(feature = "protobuf")]
+#[no_mangle]
+pub unsafe extern "C" fn load_game_protobuf(
+    name: *const c_char,
+    state: *const u8,
+    len: usize,
+) -> *mut FfiGame {
+    guard(ptr::null_mut(), || {
+        if state.is_null() {
+            return Err("unexpected null state".to_string());
+        }
+        let state = crate::wire::decode(std::slice::from_raw_parts(state, len))
+            .map_err(|e| e.to_string())?;
+        let game =
+            registry::load_game(read_str(name)?, &state.to_string()).map_err(|e| e.to_string())?;
+        Ok(Box::into_raw(Box::new(FfiGame { game })))
+    })
+}
+
+/// The protobuf encoding of get_state_json - its length is written to `len`
+///
+/// # Safety
+/// `game` must be a live handle from new_game or load_game and `len` must be
+/// a valid pointer
+#[cfg(feature = "protobuf")]
+#[no_mangle]
+pub unsafe extern "C" fn get_state_protobuf(game: *const FfiGame, len: *mut usize) -> *mut u8 {
+    guard(ptr::null_mut(), || to_bytes(encode_json(&game_ref(game)?.game.state_json())?, len))
+}
+
+/// The protobuf encoding of get_changes_json - its length is written to `len`
+///
+/// # Safety
+/// `game` must be a live handle from new_game or load_game and `len` must be
+/// a valid pointer
+#[cfg(feature = "protobuf")]
+#[no_mangle]
+pub unsafe extern "C" fn get_changes_protobuf(game: *const FfiGame, len: *mut usize) -> *mut u8 {
+    guard(ptr::null_mut(), || to_bytes(encode_json(&game_ref(game)?.game.changes_json())?, len))
+}
+
+/// # Safety
+/// `name` must be a valid NUL terminated string and `state` must point to
+/// `len` bytes produced by get_state_msgpack
+#[cfg(feature = "msgpack")]
+#[no_mangle]
+pub unsafe extern "C" fn load_game_msgpack(
+    name: *const c_char,
+    state: *const u8,
+    len: usize,
+) -> *mut FfiGame {
+    guard(ptr::null_mut(), || {
+        if state.is_null() {
+            return Err("unexpected null state".to_string());
+        }
+        let state = std::slice::from_raw_parts(state, len);
+        let game = registry::load_game_msgpack(read_str(name)?, state).map_err(|e| e.to_string())?;
+        Ok(Box::into_raw(Box::new(FfiGame { game })))
+    })
+}
+
+/// MessagePack snapshot of the state - its length is written to `len`
+///
+/// # Safety
+/// `game` must be a live handle from new_game or load_game and `len` must be
+/// a valid pointer
+#[cfg(feature = "msgpack")]
+#[no_mangle]
+pub unsafe extern "C" fn get_state_msgpack(game: *const FfiGame, len: *mut usize) -> *mut u8 {
+    guard(ptr::null_mut(), || to_bytes(game_ref(game)?.game.state_msgpack(), len))
+}
+
+/// MessagePack encoding of get_changes_json - its length is written to `len`
+///
+/// # Safety
+/// `game` must be a live handle from new_game or load_game and `len` must be
+/// a valid pointer
+#[cfg(feature = "msgpack")]
+#[no_mangle]
+pub unsafe extern "C" fn get_changes_msgpack(game: *const FfiGame, len: *mut usize) -> *mut u8 {
+    guard(ptr::null_mut(), || to_bytes(game_ref(game)?.game.changes_msgpack(), len))
+}
+
+/// # Safety
+/// `bytes` and `len` must come from one of the protobuf or msgpack
+/// functions and `bytes` must not be used afterwards
+#[cfg(any(feature = "protobuf", feature = "msgpack"))]
+#[no_mangle]
+pub unsafe extern "C" fn free_bytes(bytes: *mut u8, len: usize) {
+    if !bytes.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(bytes, len)));
+    }
+}
+
+/// Message of the last failed call on this thread (NULL when there is none)
+#[no_mangle]
+pub extern "C" fn last_error() -> *mut c_char {
+    LAST_ERROR
+        .with(|last| last.borrow_mut().take())
+        .and_then(|message| to_c_string(message).ok())
+        .unwrap_or(ptr::null_mut())
+}
+
+/// # Safety
+/// `value` must be a string returned by this library and not be used afterwards
+#[no_mangle]
+pub unsafe extern "C" fn free_string(value: *mut c_char) {
+    if !value.is_null() {
+        drop(CString::from_raw(value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ffi_round_trip() {
+        unsafe {
+            let name = CString::new("szs").unwrap();
+            let game = new_game(name.as_ptr());
+            assert!(!game.is_null());
+
+            let moves = get_moves_json(game);
+            let parsed: Vec<i32> =
+                serde_json::from_str(CStr::from_ptr(moves).to_str().unwrap()).unwrap();
+            free_string(moves);
+            assert_eq!(apply_move(game, parsed[0]), 0);
+            assert_eq!(apply_move(game, i32::MIN), -1);
+            let error = last_error();
+            assert!(!error.is_null());
+            free_string(error);
+
+            let state = get_state_json(game);
+            let restored = load_game(name.as_ptr(), state);
+            assert!(!restored.is_null());
+            free_string(state);
+            free_game(restored);
+            free_game(game);
+
+            let unknown = CString::new("chess").unwrap();
+            assert!(new_game(unknown.as_ptr()).is_null());
+            assert!(get_state_json(ptr::null()).is_null());
+        }
+    }
+
+    #[cfg(feature = "protobuf")]
+    #[test]
+    fn test_ffi_protobuf_matches_json() {
+        unsafe {
+            let name = CString::new("hotdog").unwrap();
+            let game = new_game(name.as_ptr());
+            let mut len = 0;
+
+            let changes = get_changes_protobuf(game, &mut len);
+            assert!(!changes.is_null());
+            let decoded = crate::wire::decode(std::slice::from_raw_parts(changes, len)).unwrap();
+            free_bytes(changes, len);
+            let json = get_changes_json(game);
+            let expected: serde_json::Value =
+                serde_json::from_str(CStr::from_ptr(json).to_str().unwrap()).unwrap();
+            free_string(json);
+            assert_eq!(decoded, expected);
+
+            let state = get_state_protobuf(game, &mut len);
+            let restored = load_game_protobuf(name.as_ptr(), state, len);
+            free_bytes(state, len);
+            assert!(!restored.is_null());
+            let (a, b) = (get_state_json(game), get_state_json(restored));
+            assert_eq!(CStr::from_ptr(a), CStr::from_ptr(b));
+            free_string(a);
+            free_string(b);
+            free_game(restored);
+            free_game(game);
+
+            assert!(get_state_protobuf(ptr::null(), &mut len).is_null());
+        }
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn test_ffi_msgpack_matches_json() {
+        unsafe {
+            let name = CString::new("so8").unwrap();
+            let game = new_game(name.as_ptr());
+            let mut len = 0;
+
+            let state = get_state_msgpack(game, &mut len);
+            assert!(!state.is_null());
+            let restored = load_game_msgpack(name.as_ptr(), state, len);
+            free_bytes(state, len);
+            assert!(!restored.is_null());
+            let (a, b) = (get_state_json(game), get_state_json(restored));
+            assert_eq!(CStr::from_ptr(a), CStr::from_ptr(b));
+            free_string(a);
+            free_string(b);
+            free_game(restored);
+
+            let changes = get_changes_msgpack(game, &mut len);
+            let decoded: serde_json::Value =
+                rmp_serde::from_slice(std::slice::from_raw_parts(changes, len)).unwrap();
+            free_bytes(changes, len);
+            let json = get_changes_json(game);
+            let expected: serde_json::Value =
+                serde_json::from_str(CStr::from_ptr(json).to_str().unwrap()).unwrap();
+            free_string(json);
+            assert_eq!(decoded, expected);
+            free_game(game);
+
+            let garbage = [0xc1u8];
+            assert!(load_game_msgpack(name.as_ptr(), garbage.as_ptr(), 1).is_null());
+        }
+    }
+}
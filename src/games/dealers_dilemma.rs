@@ -4,17 +4,28 @@ Designer: Shreesh Bhat
 BoardGameGeek: https://boardgamegeek.com/boardgame/378945/dealers-dilemma
 */
 
-use colored::Colorize;
+use colored::Color;
 use enum_iterator::{all, Sequence};
-use ismcts::IsmctsHandler;
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
-use rand::thread_rng;
-use serde::{Deserialize, Serialize};
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use smallvec::{smallvec, SmallVec};
 use std::cmp::{min, Ordering};
-use std::collections::{HashMap, HashSet};
-use std::mem;
-
-use crate::utils::shuffle_and_divide_matching_cards;
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::arena::MoveMaker;
+use crate::engine::{score_margin, GameEngine, GameEvent};
+use crate::features::{relative_seat, seat_offset, FeatureBuilder, FeatureExtractor};
+use crate::policy::{search_with_leaf_evaluation, LeafEvaluation, PolicyHandle, PolicyModel};
+use crate::profiling;
+use crate::quantized::LinearPolicy;
+use crate::training::Sample;
+use crate::utils::cardmask::CardMask;
+use crate::utils::render::{self, SuitStyle};
+use crate::utils::{pair_mut, reset_changes, shuffle_matching_cards_between, Hand};
 
 /// Play offsets (each possible action has a unique ID)
 // 0-35 - 36 cards 2 3 4 5 6 7 8 9 10 in 4 suits (for playing)
@@ -28,41 +39,22 @@ pub const BID_TYPE_EASY: i32 = 77;
 pub const BID_TYPE_TOP: i32 = 78;
 pub const BID_TYPE_DIFFERENCE: i32 = 79;
 pub const BID_TYPE_ZERO: i32 = 80;
+pub const CONFIRM_PLAY: i32 = 81; // only offered to human players (see confirm_plays)
 
-fn color_suit(suit: Option<Suit>, string: String) -> String {
-    if !cfg!(windows) {
-        return match suit {
-            Some(Suit::Red) => string.red().to_string(),
-            Some(Suit::Blue) => string.blue().to_string(),
-            Some(Suit::Yellow) => string.yellow().to_string(),
-            Some(Suit::Green) => string.green().to_string(),
-            _ => string,
-        };
-    } else {
-        string
-    }
-}
+/// Seats of the 3 player game and the 4 player variant
+pub const MAX_PLAYERS: usize = 4;
+/// One entry per seat in play kept inline so cloning a game doesn't allocate
+pub type Seats<T> = SmallVec<[T; MAX_PLAYERS]>;
+const DECK_SIZE: usize = 36;
+// determinizations dealt for every one kept with bid inference
+const BID_INFERENCE_SAMPLES: usize = 8;
 
 pub fn print_suit(suit: Option<Suit>) -> String {
-    let string = if let Some(suit) = suit {
-        match suit {
-            Suit::Red => "♥".to_string(),
-            Suit::Blue => "♣".to_string(),
-            Suit::Yellow => "♦".to_string(),
-            Suit::Green => "♠".to_string(),
-        }
-    } else {
-        "?".to_string()
-    };
-    color_suit(suit, string)
+    render::suit(suit.as_ref())
 }
 
 pub fn print_card(card: Card, prefix_id: bool) -> String {
-    let string = format!(
-        "{}{}",
-        color_suit(Some(card.suit), card.value.to_string()),
-        print_suit(Some(card.suit))
-    );
+    let string = render::card(card.value, &card.suit);
     if !prefix_id {
         return string;
     }
@@ -70,6 +62,8 @@ pub fn print_card(card: Card, prefix_id: bool) -> String {
 }
 
 #[derive(Debug, Clone, Copy, Default, PartialEq, Sequence, Serialize, Deserialize, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub enum State {
     #[default]
@@ -82,6 +76,8 @@ pub enum State {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub enum BidType {
     #[default]
@@ -185,12 +181,72 @@ impl BidType {
 }
 
 #[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 struct BidOption {
     id: i32,
     description: String,
 }
 
+/// A move that isn't legal in the current position
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IllegalMove {
+    pub action: i32,
+    pub state: State,
+    pub player: i32,
+}
+
+impl fmt::Display for IllegalMove {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "illegal move {} for player {} in {:?}",
+            self.action, self.player, self.state
+        )
+    }
+}
+
+impl std::error::Error for IllegalMove {}
+
+/// How one player's bid turned out at the end of a hand
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct BidResult {
+    pub round: i32,
+    pub dealer: i32,
+    pub trump_suit: Option<Suit>,
+    pub player: i32,
+    pub bid: BidType,
+    pub bid_cards: [Option<Card>; 2],
+    pub tricks_taken: i32,
+    pub points: i32,
+}
+
+impl BidResult {
+    /// Whether the bid scored (missed bids lose points)
+    pub fn made(&self) -> bool {
+        self.points > 0
+    }
+}
+
+/// One hand of the score sheet - see `Game::scoresheet`
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct ScoresheetRow {
+    pub round: i32,
+    pub dealer: i32,
+    pub trump_suit: Option<Suit>,
+    // in seat order
+    pub results: Vec<BidResult>,
+    // running totals after the hand
+    pub scores: Vec<i32>,
+}
+
 #[derive(
     Debug,
     PartialOrd,
@@ -205,6 +261,8 @@ struct BidOption {
     PartialEq,
     Eq,
 )]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub enum Suit {
     #[default]
@@ -214,7 +272,29 @@ pub enum Suit {
     Green,
 }
 
+impl SuitStyle for Suit {
+    fn symbol(&self) -> &'static str {
+        match self {
+            Suit::Red => "♥",
+            Suit::Blue => "♣",
+            Suit::Yellow => "♦",
+            Suit::Green => "♠",
+        }
+    }
+
+    fn color(&self) -> Option<Color> {
+        Some(match self {
+            Suit::Red => Color::Red,
+            Suit::Blue => Color::Blue,
+            Suit::Yellow => Color::Yellow,
+            Suit::Green => Color::Green,
+        })
+    }
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub struct Card {
     pub id: i32,
@@ -260,6 +340,11 @@ fn offset_to_bid_type(bid_id: i32) -> BidType {
     }
 }
 
+/// Ids of the cards of a suit - ids are numbered by suit (see deck)
+fn suit_mask(suit: Suit) -> CardMask {
+    CardMask(0x1ff << (suit as u32 * 9))
+}
+
 pub fn deck() -> Vec<Card> {
     let mut deck: Vec<Card> = vec![];
     let mut id = 0;
@@ -278,6 +363,8 @@ pub fn deck() -> Vec<Card> {
 }
 
 #[derive(Debug, Clone, Copy, Sequence, Default, Serialize, Deserialize, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub enum ChangeType {
     #[default]
@@ -298,9 +385,13 @@ pub enum ChangeType {
     BidDisplay,   // system sends bid string
     BidOptions,   // system sends bid options to be displayed in a dialog
     Message,      // message to display to the user
+    BidResult,    // the outcome of a player's bid at the end of the hand
+    PassDevice,   // hotseat pause while the device is passed to the next human
 }
 
 #[derive(Debug, Clone, Copy, Sequence, Default, Serialize, Deserialize, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 enum Location {
     #[default]
@@ -319,10 +410,11 @@ enum Location {
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub struct Change {
     pub change_type: ChangeType,
-    message: Option<String>,
     player: i32,
     object_id: i32,
     source_offset: i32,
@@ -335,30 +427,124 @@ pub struct Change {
     length: i32,
     cards_remaining: i32,
     pub faceup: Option<bool>,
+    #[serde(flatten)]
+    #[cfg_attr(feature = "schema", schemars(with = "Details"))]
+    #[cfg_attr(feature = "typescript", ts(as = "Details"))]
+    details: ChangeDetails,
+    round: i32,
+}
+
+/// The fields only messages, bid displays and bid options use - boxed so
+/// every other change stays small. They are flattened into the change and
+/// written out with their defaults when there are none so the JSON is the
+/// same as when they were fields of `Change`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct ChangeDetails(Option<Box<Details>>);
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[serde(default, rename_all = "camelCase")]
+struct Details {
+    message: Option<String>,
     bid_display: String,
     bid_options: Option<Vec<BidOption>>,
-    round: i32,
+    // left out of every other change
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bid_result: Option<BidResult>,
+}
+
+impl ChangeDetails {
+    fn new(details: Details) -> Self {
+        if details == Details::default() {
+            ChangeDetails(None)
+        } else {
+            ChangeDetails(Some(Box::new(details)))
+        }
+    }
+
+    fn message(message: String) -> Self {
+        ChangeDetails::new(Details {
+            message: Some(message),
+            ..Default::default()
+        })
+    }
+
+    fn bid_display(bid_display: String) -> Self {
+        ChangeDetails::new(Details {
+            bid_display,
+            ..Default::default()
+        })
+    }
+
+    fn bid_options(bid_options: Vec<BidOption>) -> Self {
+        ChangeDetails::new(Details {
+            bid_options: Some(bid_options),
+            ..Default::default()
+        })
+    }
+
+    fn bid_result(bid_result: BidResult) -> Self {
+        ChangeDetails::new(Details {
+            bid_result: Some(bid_result),
+            ..Default::default()
+        })
+    }
+}
+
+impl Serialize for ChangeDetails {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match &self.0 {
+            Some(details) => details.serialize(serializer),
+            None => Details::default().serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ChangeDetails {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Details::deserialize(deserializer).map(ChangeDetails::new)
+    }
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub struct Game {
     action_size: i32,
-    pub hands: [Vec<Card>; 3],
+    // one per player - the player count is the number of hands
+    #[cfg_attr(feature = "typescript", ts(as = "Vec<Vec<Card>>"))]
+    pub hands: Seats<Hand<Card, 12>>,
     pub changes: Vec<Vec<Change>>,
-    pub human_player: [bool; 3],
-    pub tricks_taken: [i32; 3],
+    #[cfg_attr(feature = "typescript", ts(as = "Vec<bool>"))]
+    pub human_player: Seats<bool>,
+    #[cfg_attr(feature = "typescript", ts(as = "Vec<i32>"))]
+    pub tricks_taken: Seats<i32>,
     pub trump_card: Option<Card>, // used to roll back changes
-    pub bids: [Option<BidType>; 3],
-    pub bid_cards: [[Option<Card>; 2]; 3],
-    pub current_trick: [Option<Card>; 3],
+    #[cfg_attr(feature = "typescript", ts(as = "Vec<Option<BidType>>"))]
+    pub bids: Seats<Option<BidType>>,
+    #[cfg_attr(feature = "typescript", ts(as = "Vec<[Option<Card>; 2]>"))]
+    pub bid_cards: Seats<[Option<Card>; 2]>,
+    #[cfg_attr(feature = "typescript", ts(as = "Vec<Option<Card>>"))]
+    pub current_trick: Seats<Option<Card>>,
     pub dealer_select: Vec<Card>,
     pub lead_suit: Option<Suit>,
     pub trump_suit: Option<Suit>,
     pub round: i32,
-    pub scores_this_hand: [i32; 3],
-    pub scores: [i32; 3],
-    pub voids: [HashSet<Suit>; 3],
+    // hands in a game - 0 (saved games from before this was configurable)
+    // means every player deals twice
+    #[serde(default)]
+    pub rounds: i32,
+    #[cfg_attr(feature = "typescript", ts(as = "Vec<i32>"))]
+    pub scores_this_hand: Seats<i32>,
+    #[cfg_attr(feature = "typescript", ts(as = "Vec<i32>"))]
+    pub scores: Seats<i32>,
+    // every player's bid outcome for each hand played so far
+    #[serde(default)]
+    pub round_results: Vec<BidResult>,
+    #[cfg_attr(feature = "typescript", ts(as = "Vec<HashSet<Suit>>"))]
+    pub voids: Seats<HashSet<Suit>>,
     pub current_player: i32,
     pub winner: Option<i32>,
     pub dealer: i32,
@@ -366,17 +552,69 @@ pub struct Game {
     lead_player: i32,
     #[serde(default)]
     pub no_changes: bool,
+    // Human plays wait for CONFIRM_PLAY (or undo) before the card is played
+    #[serde(default)]
+    pub confirm_plays: bool,
+    // The human player's card on the table waiting to be confirmed
+    #[serde(default)]
+    pub pending_play: Option<Card>,
+    // The human seat the device was last passed to (hotseat games)
+    #[serde(default)]
+    pub device_holder: Option<i32>,
+    // Weight the bots' determinizations by how well they fit the bids made
+    #[serde(default)]
+    pub bid_inference: bool,
+    // Bitmask of each hand - only kept while simulating (see with_no_changes)
+    #[serde(skip)]
+    #[cfg_attr(feature = "typescript", ts(skip))]
+    hand_masks: Option<[CardMask; MAX_PLAYERS]>,
+    // Score the current hand as the last one of the game - set by the MCTS
+    // entry points so the search doesn't look past the current deal
+    #[serde(skip)]
+    #[cfg_attr(feature = "typescript", ts(skip))]
+    final_hand: bool,
 }
 
 impl Game {
     /// Factory to create a default game
     pub fn new() -> Game {
+        Game::new_with_players(3)
+    }
+
+    /// A game for 3 players or the 4 player variant (9 cards each instead of
+    /// 12 with the dealer still setting aside 2 of them)
+    pub fn new_with_players(player_count: usize) -> Game {
+        Game::new_with_options(player_count, 2 * player_count as i32)
+    }
+
+    /// A game with a custom number of hands
+    pub fn new_with_options(player_count: usize, rounds: i32) -> Game {
+        Game::new_with_humans(vec![false; player_count], rounds)
+    }
+
+    /// A hotseat game - the humans share one device which is passed between
+    /// them (see `ChangeType::PassDevice`)
+    pub fn new_hotseat(human_player: Vec<bool>) -> Game {
+        let rounds = 2 * human_player.len() as i32;
+        Game::new_with_humans(human_player, rounds)
+    }
+
+    fn new_with_humans(human_player: Vec<bool>, rounds: i32) -> Game {
+        let player_count = human_player.len();
+        assert!(rounds > 0, "a game needs at least one hand");
+        assert!(
+            (3..=MAX_PLAYERS).contains(&player_count),
+            "Dealer's Dilemma is played by 3 or 4 players"
+        );
         let mut game = Game::default();
-        game.dealer = 2;
-        game.current_player = 2;
+        game.rounds = rounds;
+        game.hands = smallvec![Hand::default(); player_count];
+        game.human_player = SmallVec::from_vec(human_player);
+        game.dealer = player_count as i32 - 1;
+        game.current_player = game.dealer;
         let mut game = game.deal();
-        game.scores = [0, 0, 0];
-        game.scores_this_hand = [0, 0, 0];
+        game.scores = smallvec![0; player_count];
+        game.scores_this_hand = smallvec![0; player_count];
         if !game.no_changes {
             game.changes.push(show_playable(&game));
         }
@@ -386,209 +624,492 @@ impl Game {
     // This is used to increase the speed of simulations
     pub fn with_no_changes(self: &mut Game) {
         self.no_changes = true;
+        self.hand_masks = Some(Default::default());
+        self.update_hand_masks();
+    }
+
+    pub fn player_count(&self) -> usize {
+        self.hands.len()
+    }
+
+    /// The round after which the game is over
+    pub fn final_round(&self) -> i32 {
+        if self.rounds > 0 {
+            self.rounds
+        } else {
+            2 * self.player_count() as i32
+        }
+    }
+
+    // Treat the current hand as the last one of the game
+    pub fn with_final_hand(self: &mut Game) {
+        self.final_hand = true;
+    }
+
+    fn next_seat(&self, player: i32) -> i32 {
+        (player + 1) % self.player_count() as i32
+    }
+
+    /// Every finished hand in order - rebuilt from `round_results` so it is
+    /// available after the game is over (or restored from JSON)
+    pub fn scoresheet(&self) -> Vec<ScoresheetRow> {
+        let mut scores = vec![0; self.player_count()];
+        let mut rows: Vec<ScoresheetRow> = vec![];
+        for result in &self.round_results {
+            if rows.last().map(|row| row.round) != Some(result.round) {
+                rows.push(ScoresheetRow {
+                    round: result.round,
+                    dealer: result.dealer,
+                    trump_suit: result.trump_suit,
+                    ..Default::default()
+                });
+            }
+            let row = rows.last_mut().expect("a row was just added");
+            scores[result.player as usize] += result.points;
+            row.results.push(*result);
+            row.scores = scores.clone();
+        }
+        rows
+    }
+
+    // Put the dealer's selected card back with the other one and undo the
+    // trump it named
+    fn undo_dealer_select<const CHANGES: bool>(&mut self) {
+        let dealer = self.dealer as usize;
+        let selected = [self.dealer_select[0].id, self.dealer_select[1].id];
+        self.hands[dealer].retain(|c| !selected.contains(&c.id));
+        self.current_trick[dealer] = None;
+        self.lead_suit = None;
+        self.trump_suit = None;
+        self.trump_card = None;
+        self.state = State::DealerSelect;
+        if CHANGES {
+            // hide trump card
+            self.changes[0].push(Change {
+                change_type: ChangeType::Trump,
+                object_id: -100,
+                dest: Location::Trump,
+                ..Default::default()
+            });
+            for (offset, card) in self.dealer_select.iter().enumerate() {
+                self.changes[0].push(Change {
+                    change_type: ChangeType::DealerSelect,
+                    object_id: card.id,
+                    dest: Location::DealerSelect,
+                    dest_offset: offset as i32,
+                    player: self.dealer,
+                    hand_offset: offset as i32,
+                    length: 2,
+                    ..Default::default()
+                });
+            }
+            self.hands[dealer].sort_by(card_sorter);
+            reorder_hand_into(&mut self.changes[0], self.dealer, &self.hands[dealer]);
+        }
+    }
+
+    // Swap the cards hidden from the current player between the other hands
+    fn shuffle_hidden_cards(&mut self, rng: &mut impl Rng) {
+        let player_count = self.player_count() as i32;
+        for p1 in 0..player_count {
+            for p2 in 0..player_count {
+                if p1 == self.current_player || p2 == self.current_player || p1 == p2 {
+                    continue;
+                }
+
+                // Add hidden bid cards to player's hands so they can be swapped
+                for player in [p1 as usize, p2 as usize] {
+                    if self.bids[player] == Some(BidType::Easy) && self.bid_cards[player][1] != None
+                    {
+                        let bid_card = self.bid_cards[player][1].unwrap();
+                        self.hands[player].push(bid_card);
+                    }
+                }
+
+                let voids = [&self.voids[p1 as usize], &self.voids[p2 as usize]];
+                let (hand1, hand2) = pair_mut(&mut self.hands, p1 as usize, p2 as usize);
+
+                // allow swapping of any cards that are not in the combined void set
+                shuffle_matching_cards_between(
+                    |c: &Card| !voids.iter().any(|void| void.contains(&c.suit)),
+                    hand1,
+                    hand2,
+                    rng,
+                );
+
+                for player in [p1 as usize, p2 as usize] {
+                    if self.bids[player] == Some(BidType::Easy) && self.bid_cards[player][1] != None
+                    {
+                        // randomly take one of the cards and make it the hidden card
+                        self.bid_cards[player][1] = self.hands[player].pop();
+                    }
+                }
+            }
+        }
+    }
+
+    // Deal several determinizations and keep one with a probability that
+    // follows how well each opponent's hand fits the bid they made
+    fn sample_bid_informed(&mut self, observer: i32, rng: &mut impl Rng) {
+        let mut candidates: Vec<Game> = (0..BID_INFERENCE_SAMPLES)
+            .map(|_| {
+                let mut candidate = self.clone();
+                candidate.shuffle_hidden_cards(rng);
+                candidate
+            })
+            .collect();
+        let weights: Vec<f64> = candidates
+            .iter()
+            .map(|candidate| candidate.bid_likelihood(observer))
+            .collect();
+        let index = WeightedIndex::new(&weights)
+            .map(|weights| weights.sample(rng))
+            .unwrap_or(0);
+        *self = candidates.swap_remove(index);
+    }
+
+    /// How plausible the hands are given the bids - each opponent's estimated
+    /// tricks are compared with the tricks they still need
+    pub fn bid_likelihood(&self, observer: i32) -> f64 {
+        let mut likelihood = 1.0;
+        for player in 0..self.player_count() {
+            if player as i32 == observer || self.bid_cards[player][1].is_none() {
+                continue;
+            }
+            let bid = match self.bids[player] {
+                Some(bid) => bid,
+                None => continue,
+            };
+            let taken = self.tricks_taken[player];
+            let cards = self.bid_cards[player].map(|card| card.expect("both cards are bid"));
+            let targets = match bid {
+                BidType::Easy => vec![cards[0].value, cards[1].value],
+                BidType::Top => vec![cards[0].value],
+                BidType::Difference => vec![(cards[0].value - cards[1].value).abs()],
+                BidType::Zero => vec![0],
+            };
+            let estimate = estimated_tricks(self.hands[player].iter(), self.trump_suit);
+            // a bid that is already over tells nothing about the rest of the hand
+            let miss = targets
+                .into_iter()
+                .filter(|target| *target >= taken)
+                .map(|target| (estimate - (target - taken) as f64).abs())
+                .fold(f64::INFINITY, f64::min);
+            if miss.is_finite() {
+                likelihood *= (-miss * miss / 2.0).exp();
+            }
+        }
+        likelihood
+    }
+
+    // Rebuild the hand masks from the hands
+    fn update_hand_masks(&mut self) {
+        if let Some(masks) = self.hand_masks.as_mut() {
+            for (mask, hand) in masks.iter_mut().zip(self.hands.iter()) {
+                *mask = CardMask::from_ids(hand.iter().map(|card| card.id));
+            }
+        }
+    }
+
+    pub fn deal(mut self: Game) -> Self {
+        if self.no_changes {
+            self.deal_hand::<false>();
+        } else {
+            self.deal_hand::<true>();
+        }
+        self
     }
 
-    pub fn deal(self: Game) -> Self {
-        let mut new_game = self.clone();
-        new_game.trump_card = None;
-        new_game.state = State::DealerSelect;
-        new_game.round = self.round + 1;
-        new_game.bids = [None, None, None];
-        new_game.bid_cards = [[None, None], [None, None], [None, None]];
-        new_game.trump_suit = None;
-        new_game.lead_suit = None;
-        new_game.current_trick = [None, None, None];
-        new_game.tricks_taken = [0, 0, 0];
-        new_game.hands = [vec![], vec![], vec![]];
-        new_game.dealer = (new_game.dealer + 1) % 3;
-        new_game.current_player = new_game.dealer;
-        new_game.voids = [HashSet::new(), HashSet::new(), HashSet::new()];
+    /// Deal the next hand in place - the changes are only built when
+    /// `CHANGES` is set so simulations compile without them
+    fn deal_hand<const CHANGES: bool>(&mut self) {
+        let _timer = profiling::start("deal");
+        self.trump_card = None;
+        self.state = State::DealerSelect;
+        self.round += 1;
+        let player_count = self.player_count();
+        self.bids = smallvec![None; player_count];
+        self.bid_cards = smallvec![[None, None]; player_count];
+        self.trump_suit = None;
+        self.lead_suit = None;
+        self.current_trick = smallvec![None; player_count];
+        self.tricks_taken = smallvec![0; player_count];
+        self.dealer = self.next_seat(self.dealer);
+        self.current_player = self.dealer;
+        self.voids = smallvec![HashSet::new(); player_count];
         let mut cards = deck();
-        let deal_index: usize = new_game.changes.len();
+        let deal_index: usize = self.changes.len();
         let reorder_index = deal_index + 1;
-        if !new_game.no_changes {
-            new_game.changes.push(vec![]); // deal_index
-            new_game.changes.push(vec![]); // reorder_index
+        if CHANGES {
+            self.changes.push(vec![]); // deal_index
+            self.changes.push(vec![]); // reorder_index
         }
-        new_game.hands = [vec![], vec![], vec![]];
-        new_game.dealer_select = vec![];
+        self.hands = smallvec![Hand::default(); player_count];
+        self.dealer_select = vec![];
 
-        for y in 0..12 {
-            for player in 0..3 {
+        // the dealer's last 2 cards are set aside for the dealer to choose from
+        let hand_size = (DECK_SIZE / player_count) as i32;
+        for y in 0..hand_size {
+            for player in 0..player_count as i32 {
                 let card = cards.pop().expect("cards should be available here");
-                if player == new_game.dealer && (y == 10 || y == 11) {
-                    new_game.dealer_select.push(card);
-                    if !new_game.no_changes {
-                        new_game.changes[deal_index].push(Change {
+                if player == self.dealer && y >= hand_size - 2 {
+                    self.dealer_select.push(card);
+                    if CHANGES {
+                        self.changes[deal_index].push(Change {
                             change_type: ChangeType::DealerSelect,
                             object_id: card.id,
                             dest: Location::DealerSelect,
                             dest_offset: y,
                             player,
-                            hand_offset: y - 10, // 0 for left card 1 for right card
+                            hand_offset: y - (hand_size - 2), // 0 for left card 1 for right card
                             length: 2,
                             ..Default::default()
                         });
                     }
                 } else {
-                    if !new_game.no_changes {
-                        new_game.changes[deal_index].push(Change {
+                    if CHANGES {
+                        self.changes[deal_index].push(Change {
                             change_type: ChangeType::Deal,
                             object_id: card.id,
                             dest: Location::Hand,
                             dest_offset: player,
                             player,
                             hand_offset: y,
-                            length: if player == new_game.dealer { 10 } else { 12 },
+                            length: if player == self.dealer {
+                                hand_size - 2
+                            } else {
+                                hand_size
+                            },
                             ..Default::default()
                         });
                     }
-                    new_game.hands[player as usize].push(card);
+                    self.hands[player as usize].push(card);
                 }
             }
         }
 
-        new_game.hands[0].sort_by(card_sorter);
-        if !new_game.no_changes {
-            new_game.changes[reorder_index].append(&mut reorder_hand(0, &new_game.hands[0]));
-            new_game.changes.push(show_playable(&new_game));
+        for player in 0..player_count {
+            if self.shows_hand(player) {
+                self.hands[player].sort_by(card_sorter);
+                if CHANGES {
+                    reorder_hand_into(
+                        &mut self.changes[reorder_index],
+                        player as i32,
+                        &self.hands[player],
+                    );
+                }
+            }
         }
-        new_game
+        if CHANGES {
+            let playable = show_playable(self);
+            self.changes.push(playable);
+        }
+        self.update_hand_masks();
     }
 
-    pub fn clone_and_apply_move(self: Game, action: i32) -> Self {
-        let mut new_game: Game = self.clone();
+    /// Make a move on an owned game - an illegal move is returned as an
+    /// error instead of an unchanged game
+    pub fn clone_and_apply_move(mut self: Game, action: i32) -> Result<Self, IllegalMove> {
+        self.try_apply_move(action)?;
+        Ok(self)
+    }
 
-        // reset only after a move is made in the next round
-        new_game.scores_this_hand = [0, 0, 0];
+    /// Make a move in place - illegal moves leave the game unchanged in
+    /// release builds and panic in debug builds (use `try_apply_move` for
+    /// moves that haven't been checked)
+    pub fn apply_move(&mut self, action: i32) {
+        let result = self.try_apply_move(action);
+        debug_assert!(result.is_ok(), "dealers_dilemma: {}", result.unwrap_err());
+    }
 
-        // card from player to table or discard to draw deck
-        new_game.changes = vec![vec![]];
+    /// Make a move in place or report why it can't be made
+    pub fn try_apply_move(&mut self, action: i32) -> Result<(), IllegalMove> {
+        if !self.is_legal(action) {
+            return Err(IllegalMove {
+                action,
+                state: self.state,
+                player: self.current_player,
+            });
+        }
+        let state = self.state;
+        if self.no_changes {
+            self.apply_move_internal::<false>(action);
+        } else {
+            self.apply_move_internal::<true>(action);
+        }
+        // plays update the masks as they go - the rarer bidding moves rebuild them
+        if state != State::Play {
+            self.update_hand_masks();
+        }
+        self.pass_device();
+        Ok(())
+    }
+
+    // Hand the device to the next human player in a hotseat game
+    fn pass_device(&mut self) {
+        let player = self.current_player;
+        if self.winner.is_some()
+            || !self.human_player[player as usize]
+            || self.device_holder == Some(player)
+        {
+            return;
+        }
+        if self.device_holder.is_some() && !self.no_changes {
+            let pass = vec![Change {
+                change_type: ChangeType::PassDevice,
+                object_id: -1,
+                player,
+                dest: Location::Message,
+                ..Default::default()
+            }];
+            // before the changes showing the next player's options
+            let at = match self.changes.len() {
+                0 | 1 => self.changes.len(),
+                len => len - 1,
+            };
+            self.changes.insert(at, pass);
+        }
+        self.device_holder = Some(player);
+    }
+
+    // The seats whose hands are shown - every human seat (seat 0 when no seat
+    // is marked as human)
+    fn shows_hand(&self, player: usize) -> bool {
+        if self.human_player.contains(&true) {
+            self.human_player[player]
+        } else {
+            player == 0
+        }
+    }
 
-        let mut moves = self.get_moves();
-        moves.push(-1); // undo
+    /// A move from `get_moves` or an undo
+    pub fn is_legal(&self, action: i32) -> bool {
+        if action == -1 {
+            self.can_undo()
+        } else {
+            self.get_moves().contains(&action)
+        }
+    }
 
-        if !moves.contains(&action) {
-            return new_game;
+    /// Whether the player to act has a decision to take back
+    pub fn can_undo(&self) -> bool {
+        let player = self.current_player as usize;
+        if self.winner.is_some() {
+            return false;
+        }
+        match self.state {
+            State::BidType | State::TrumpSelect => true,
+            State::BidCard => self.bid_cards[player][0].is_some() || player == self.dealer as usize,
+            State::Play => self.pending_play.is_some(),
+            State::DealerSelect => false,
         }
+    }
+
+    /// `CHANGES` is `!self.no_changes` - as a constant the change lists are
+    /// compiled out of simulated moves instead of checked at every step
+    fn apply_move_internal<const CHANGES: bool>(&mut self, action: i32) {
+        // reset only after a move is made in the next round
+        self.scores_this_hand.fill(0);
+
+        // card from player to table or discard to draw deck
+        reset_changes(&mut self.changes);
 
-        match new_game.state {
+        match self.state {
             State::BidType => {
                 if action == -1 {
                     // Undo the bid for the human player
 
-                    let bid_cards = new_game.bid_cards[new_game.current_player as usize];
+                    let bid_cards = self.bid_cards[self.current_player as usize];
                     for bid_card in bid_cards.iter().flatten() {
-                        new_game.hands[new_game.current_player as usize].push(*bid_card);
+                        self.hands[self.current_player as usize].push(*bid_card);
                     }
-                    if !self.no_changes {
-                        new_game.hands[0].sort_by(card_sorter);
-                        new_game.changes[0].append(&mut reorder_hand(0, &new_game.hands[0]));
-                    }
-
-                    if new_game.current_player == new_game.dealer {
-                        new_game.hands[0].retain(|c| {
-                            c.id != new_game.dealer_select[0].id
-                                && c.id != new_game.dealer_select[1].id
-                        });
-                        new_game.trump_suit = None;
-                        new_game.trump_card = None;
-                        if !self.no_changes {
-                            // hide trump card
-                            new_game.changes[0].push(Change {
-                                change_type: ChangeType::Trump,
-                                object_id: -100,
-                                dest: Location::Trump,
-                                ..Default::default()
-                            });
-                            for (offset, card) in new_game.dealer_select.iter().enumerate() {
-                                new_game.changes[0].push(Change {
-                                    change_type: ChangeType::DealerSelect,
-                                    object_id: card.id,
-                                    dest: Location::DealerSelect,
-                                    dest_offset: offset as i32,
-                                    player: 0 as i32,
-                                    hand_offset: offset as i32,
-                                    length: 2,
-                                    ..Default::default()
-                                });
-                            }
-                            new_game.hands[0].sort_by(card_sorter);
-                            new_game.changes[0].append(&mut reorder_hand(0, &new_game.hands[0]));
-                        }
+                    if CHANGES {
+                        let player = self.current_player as usize;
+                        self.hands[player].sort_by(card_sorter);
+                        reorder_hand_into(&mut self.changes[0], player as i32, &self.hands[player]);
                     }
 
-                    new_game.bid_cards[new_game.current_player as usize] = [None, None];
-                    new_game.bids[new_game.current_player as usize] = None;
-                    new_game.state = State::BidCard;
+                    self.bid_cards[self.current_player as usize] = [None, None];
+                    self.bids[self.current_player as usize] = None;
+                    self.state = State::BidCard;
 
-                    if new_game.current_player == new_game.dealer {
-                        new_game.state = State::DealerSelect;
+                    if self.current_player == self.dealer {
+                        self.undo_dealer_select::<CHANGES>();
                     }
-                    if !self.no_changes {
-                        new_game.changes.push(show_playable(&new_game));
+                    if CHANGES {
+                        let playable = show_playable(self);
+                        self.changes.push(playable);
                     }
 
-                    return new_game;
+                    return;
                 }
-                new_game.bids[new_game.current_player as usize] = Some(offset_to_bid_type(action));
-                new_game.changes[0].push(Change {
-                    change_type: ChangeType::BidDisplay,
-                    object_id: -1,
-                    source_offset: new_game.current_player,
-                    dest: Location::BidDisplay,
-                    player: new_game.current_player,
-                    bid_display: new_game.bids[new_game.current_player as usize]
-                        .unwrap()
-                        .bid_display(
-                            new_game.bid_cards[new_game.current_player as usize],
-                            new_game.human_player[new_game.current_player as usize],
+                self.bids[self.current_player as usize] = Some(offset_to_bid_type(action));
+                if CHANGES {
+                    self.changes[0].push(Change {
+                        change_type: ChangeType::BidDisplay,
+                        object_id: -1,
+                        source_offset: self.current_player,
+                        dest: Location::BidDisplay,
+                        player: self.current_player,
+                        details: ChangeDetails::bid_display(
+                            self.bids[self.current_player as usize]
+                                .unwrap()
+                                .bid_display(
+                                    self.bid_cards[self.current_player as usize],
+                                    self.human_player[self.current_player as usize],
+                                ),
                         ),
-                    ..Default::default()
-                });
-                if new_game.bids[new_game.current_player as usize] != Some(BidType::Easy) {
-                    new_game.changes[0].push(Change {
+                        ..Default::default()
+                    });
+                }
+                if CHANGES && self.bids[self.current_player as usize] != Some(BidType::Easy) {
+                    self.changes[0].push(Change {
                         change_type: ChangeType::Bid,
-                        object_id: new_game.bid_cards[new_game.current_player as usize][1]
-                            .unwrap()
-                            .id,
-                        source_offset: new_game.current_player,
+                        object_id: self.bid_cards[self.current_player as usize][1].unwrap().id,
+                        source_offset: self.current_player,
                         dest: Location::Bid,
                         dest_offset: 1,
-                        player: new_game.current_player,
+                        player: self.current_player,
                         faceup: Some(true), // non-easy bid cards are all face up
                         ..Default::default()
                     });
                 }
-                new_game.state = State::BidCard;
-                new_game.current_player = (new_game.current_player + 1) % 3;
-                if new_game.bids[new_game.current_player as usize].is_some() {
+                self.state = State::BidCard;
+                self.current_player = self.next_seat(self.current_player);
+                if self.bids[self.current_player as usize].is_some() {
                     // next player has already bid - they must be the dealer and it must be the next
                     // player's lead because the dealer's lead card was already played
-                    new_game.current_player = (new_game.current_player + 1) % 3;
-                    new_game.state = State::Play;
+                    self.current_player = self.next_seat(self.current_player);
+                    self.state = State::Play;
                 }
-                if !self.no_changes {
-                    new_game.changes.push(show_playable(&new_game));
+                if CHANGES {
+                    let playable = show_playable(self);
+                    self.changes.push(playable);
                 }
-                new_game
             }
             State::TrumpSelect => {
-                new_game.state = State::BidCard;
+                if action == -1 {
+                    // the dealer can pick the other card until trump is confirmed
+                    self.undo_dealer_select::<CHANGES>();
+                    if CHANGES {
+                        let playable = show_playable(self);
+                        self.changes.push(playable);
+                    }
+                    return;
+                }
+                self.state = State::BidCard;
                 match action {
-                    NO_TRUMP => new_game,
+                    NO_TRUMP => {}
                     _ => {
-                        new_game.trump_suit = Some(new_game.trump_card.unwrap().suit);
-                        if !new_game.no_changes {
-                            new_game.changes[0].push(Change {
+                        self.trump_suit = Some(self.trump_card.unwrap().suit);
+                        if CHANGES {
+                            self.changes[0].push(Change {
                                 change_type: ChangeType::Trump,
-                                object_id: suit_to_id(new_game.trump_card.unwrap().suit),
+                                object_id: suit_to_id(self.trump_card.unwrap().suit),
                                 dest: Location::Trump,
                                 ..Default::default()
                             });
                         }
-
-                        new_game
                     }
                 }
             }
@@ -596,48 +1117,47 @@ impl Game {
                 let card_to_hand: Card;
                 let card_to_play: Card;
                 if action == DEALER_SELECT_CARD {
-                    card_to_hand = new_game.dealer_select[0];
-                    card_to_play = new_game.dealer_select[1];
+                    card_to_hand = self.dealer_select[0];
+                    card_to_play = self.dealer_select[1];
                 } else {
-                    card_to_hand = new_game.dealer_select[1];
-                    card_to_play = new_game.dealer_select[0];
+                    card_to_hand = self.dealer_select[1];
+                    card_to_play = self.dealer_select[0];
                 }
 
-                new_game.trump_card = Some(card_to_hand);
+                self.trump_card = Some(card_to_hand);
 
-                new_game.hands[new_game.current_player as usize].push(card_to_hand);
+                self.hands[self.current_player as usize].push(card_to_hand);
 
-                if !new_game.no_changes && !new_game.human_player[new_game.current_player as usize]
-                {
+                if CHANGES && !self.human_player[self.current_player as usize] {
                     // Add a label which mentions which player picked trump
-                    let player_name = match new_game.current_player {
-                        1 => "West",
-                        2 => "East",
+                    let player_name = match (self.current_player, self.player_count()) {
+                        (1, _) => "West",
+                        (2, 4) => "North",
+                        (2, _) | (3, _) => "East",
                         _ => "South",
                     };
-                    new_game.changes[0].push(Change {
+                    self.changes[0].push(Change {
                         change_type: ChangeType::Message,
-                        message: Some(format!("{} selected a card", player_name)),
+                        details: ChangeDetails::message(format!("{} selected a card", player_name)),
                         object_id: -1,
                         dest: Location::Message,
                         ..Default::default()
                     });
                     // highlight card CPU player selected to move to their hand and wait for input
-                    new_game.changes[0].push(Change {
+                    self.changes[0].push(Change {
                         change_type: ChangeType::ShowWinningCard,
                         object_id: card_to_hand.id,
                         dest: Location::Play,
                         ..Default::default()
                     });
-                    new_game.changes[0].push(Change {
+                    self.changes[0].push(Change {
                         change_type: ChangeType::OptionalPause,
                         object_id: 0,
                         dest: Location::Play,
                         ..Default::default()
                     });
                     // clear message
-                    new_game.changes[0].push(Change {
-                        message: None,
+                    self.changes[0].push(Change {
                         change_type: ChangeType::Message,
                         object_id: -1,
                         dest: Location::Message,
@@ -645,58 +1165,54 @@ impl Game {
                     });
                 }
 
-                if !new_game.no_changes && new_game.human_player[new_game.current_player as usize] {
+                if CHANGES && self.human_player[self.current_player as usize] {
                     // clear message
-                    new_game.changes[0].push(Change {
+                    self.changes[0].push(Change {
                         change_type: ChangeType::Message,
-                        message: None,
                         object_id: -1,
                         dest: Location::Message,
                         ..Default::default()
                     });
-                    new_game.hands[0].sort_by(card_sorter);
-                    new_game.changes[0].append(
-                        reorder_hand(
-                            new_game.current_player,
-                            &new_game.hands[new_game.current_player as usize],
-                        )
-                        .as_mut(),
+                    self.hands[self.current_player as usize].sort_by(card_sorter);
+                    reorder_hand_into(
+                        &mut self.changes[0],
+                        self.current_player,
+                        &self.hands[self.current_player as usize],
                     );
                 }
 
-                new_game.current_trick[new_game.current_player as usize] = Some(card_to_play);
-                new_game.lead_suit = Some(card_to_play.suit);
-                new_game.state = State::BidCard;
-                if !self.no_changes {
-                    new_game.changes[0].push(Change {
+                self.current_trick[self.current_player as usize] = Some(card_to_play);
+                self.lead_suit = Some(card_to_play.suit);
+                self.state = State::BidCard;
+                if CHANGES {
+                    self.changes[0].push(Change {
                         change_type: ChangeType::Play,
                         object_id: card_to_play.id,
-                        source_offset: new_game.current_player,
+                        source_offset: self.current_player,
                         dest: Location::Play,
-                        dest_offset: new_game.current_player,
-                        player: new_game.current_player,
+                        dest_offset: self.current_player,
+                        player: self.current_player,
                         ..Default::default()
                     });
-                    new_game.changes[0].append(
-                        reorder_hand(
-                            new_game.current_player,
-                            &new_game.hands[new_game.current_player as usize],
-                        )
-                        .as_mut(),
+                    reorder_hand_into(
+                        &mut self.changes[0],
+                        self.current_player,
+                        &self.hands[self.current_player as usize],
                     );
-                    new_game.changes.push(show_playable(&new_game));
+                    let playable = show_playable(self);
+                    self.changes.push(playable);
                 }
 
                 if card_to_hand.suit == card_to_play.suit {
                     // player can select trump or no trump
-                    new_game.state = State::TrumpSelect;
-                    if new_game.human_player[new_game.current_player as usize] {
-                        new_game.changes[0].push(Change {
+                    self.state = State::TrumpSelect;
+                    if CHANGES && self.human_player[self.current_player as usize] {
+                        self.changes[0].push(Change {
                             change_type: ChangeType::BidOptions,
                             object_id: -1, // No specific card associated with this change
-                            player: new_game.current_player,
+                            player: self.current_player,
                             dest: Location::BidOptions,
-                            bid_options: Some(vec![
+                            details: ChangeDetails::bid_options(vec![
                                 BidOption {
                                     id: NO_TRUMP,
                                     description: "No trump".to_string(),
@@ -710,99 +1226,120 @@ impl Game {
                         });
                     }
                 } else {
-                    new_game.trump_suit = Some(card_to_hand.suit);
-                    if !new_game.no_changes {
-                        new_game.changes[0].push(Change {
+                    self.trump_suit = Some(card_to_hand.suit);
+                    if CHANGES {
+                        self.changes[0].push(Change {
                             change_type: ChangeType::Trump,
-                            object_id: suit_to_id(new_game.trump_card.unwrap().suit),
+                            object_id: suit_to_id(self.trump_card.unwrap().suit),
                             dest: Location::Trump,
                             ..Default::default()
                         });
                     }
-                    new_game.state = State::BidCard;
+                    self.state = State::BidCard;
                 }
-
-                new_game
             }
             State::BidCard => {
-                let card_id = card_offset(new_game.state, action);
-                let card = &new_game.hands[new_game.current_player as usize]
+                if action == -1 {
+                    let player = self.current_player as usize;
+                    if let Some(card) = self.bid_cards[player][0].take() {
+                        // take back the primary bid card
+                        self.hands[player].push(card);
+                        if CHANGES {
+                            self.hands[player].sort_by(card_sorter);
+                            reorder_hand_into(
+                                &mut self.changes[0],
+                                self.current_player,
+                                &self.hands[player],
+                            );
+                        }
+                    } else if self.current_player == self.dealer {
+                        // nothing bid yet - go back to picking the dealer's card
+                        self.undo_dealer_select::<CHANGES>();
+                    } else {
+                        return;
+                    }
+                    if CHANGES {
+                        let playable = show_playable(self);
+                        self.changes.push(playable);
+                    }
+                    return;
+                }
+                let card_id = card_offset(self.state, action);
+                let card = &self.hands[self.current_player as usize]
                     .iter()
                     .find(|c| c.id == card_id)
                     .expect("this card has to be in the player's hand")
                     .clone();
-                new_game.hands[new_game.current_player as usize].retain(|c| c.id != card_id);
+                self.hands[self.current_player as usize].retain(|c| c.id != card_id);
                 let bid_index: usize;
-                if new_game.bid_cards[new_game.current_player as usize][0].is_none() {
+                if self.bid_cards[self.current_player as usize][0].is_none() {
                     bid_index = 0;
-                } else if new_game.bid_cards[new_game.current_player as usize][1].is_none() {
+                } else if self.bid_cards[self.current_player as usize][1].is_none() {
                     bid_index = 1;
                 } else {
                     panic!("player has already bid two cards!")
                 }
 
-                new_game.bid_cards[new_game.current_player as usize][bid_index] = Some(*card);
+                self.bid_cards[self.current_player as usize][bid_index] = Some(*card);
 
-                if !self.no_changes {
-                    let faceup = if bid_index == 1
-                        && !new_game.human_player[new_game.current_player as usize]
-                    {
-                        Some(false)
-                    } else {
-                        None
-                    };
-                    new_game.changes[0].push(Change {
+                if CHANGES {
+                    let faceup =
+                        if bid_index == 1 && !self.human_player[self.current_player as usize] {
+                            Some(false)
+                        } else {
+                            None
+                        };
+                    self.changes[0].push(Change {
                         change_type: ChangeType::Bid,
                         object_id: card.id,
-                        source_offset: new_game.current_player,
+                        source_offset: self.current_player,
                         dest: Location::Bid,
                         dest_offset: bid_index as i32,
-                        player: new_game.current_player,
+                        player: self.current_player,
                         faceup,
                         ..Default::default()
                     });
-                    new_game.changes[0].append(
-                        reorder_hand(
-                            new_game.current_player,
-                            &new_game.hands[new_game.current_player as usize],
-                        )
-                        .as_mut(),
+                    reorder_hand_into(
+                        &mut self.changes[0],
+                        self.current_player,
+                        &self.hands[self.current_player as usize],
                     );
-                    let mut new_changes = show_playable(&new_game);
-                    new_game.changes[0].append(&mut new_changes);
+                    let mut new_changes = show_playable(self);
+                    self.changes[0].append(&mut new_changes);
                 }
 
                 if bid_index == 1 {
                     // player just finished bidding
                     // Transition to BidType state only after both bid cards have been selected
-                    new_game.state = State::BidType;
+                    self.state = State::BidType;
                     // If the current player is human, add a change with bid options
-                    if new_game.human_player[new_game.current_player as usize] {
+                    if CHANGES && self.human_player[self.current_player as usize] {
                         // clear message
-                        new_game.changes[0].push(Change {
-                            message: None,
+                        self.changes[0].push(Change {
                             change_type: ChangeType::Message,
                             object_id: -1,
                             dest: Location::Message,
                             ..Default::default()
                         });
-                        let moves = new_game.get_moves();
-                        new_game.changes[0].push(Change {
+                        let moves = self.get_moves();
+                        self.changes[0].push(Change {
                             change_type: ChangeType::BidOptions,
                             object_id: -1, // No specific card associated with this change
-                            player: new_game.current_player,
+                            player: self.current_player,
                             dest: Location::BidOptions,
-                            bid_options: Some(bid_options(
-                                new_game.bid_cards[new_game.current_player as usize],
+                            details: ChangeDetails::bid_options(bid_options(
+                                self.bid_cards[self.current_player as usize],
                                 moves,
                             )),
                             ..Default::default()
                         });
                     }
                 } else {
-                    if new_game.human_player[new_game.current_player as usize] {
-                        new_game.changes.push(vec![Change {
-                            message: Some(format!("Select your secondary bid card")),
+                    if CHANGES && self.human_player[self.current_player as usize] {
+                        self.changes.push(vec![Change {
+                            details: ChangeDetails::message(format!(
+                                "Select your secondary bid card"
+                            )),
                             change_type: ChangeType::Message,
                             object_id: -1,
                             dest: Location::Message,
@@ -810,64 +1347,108 @@ impl Game {
                         }]);
                     }
                 }
-
-                new_game
             }
             State::Play => {
-                let card_id = card_offset(new_game.state, action);
-                let card = &new_game.hands[new_game.current_player as usize]
-                    .iter()
-                    .find(|c| c.id == card_id)
-                    .expect("this card has to be in the player's hand")
-                    .clone();
-                new_game.hands[new_game.current_player as usize].retain(|c| c.id != card_id);
-                if !self.no_changes {
-                    new_game.changes[0].push(Change {
-                        change_type: ChangeType::Play,
-                        object_id: card_id,
-                        source_offset: new_game.current_player,
-                        dest: Location::Play,
-                        dest_offset: new_game.current_player,
-                        player: new_game.current_player,
-                        ..Default::default()
-                    });
-                    new_game.changes[0].append(
-                        reorder_hand(
-                            new_game.current_player,
-                            &new_game.hands[new_game.current_player as usize],
-                        )
-                        .as_mut(),
-                    );
-                }
-                let last_change = new_game.changes.len() - 1;
-                let mut changes = hide_playable(&new_game);
-                new_game.changes[last_change].append(&mut changes);
-                new_game.current_trick[new_game.current_player as usize] = Some(*card);
-
-                if new_game.lead_suit.is_none() {
-                    new_game.lead_suit = Some(card.suit);
-                } else {
-                    if Some(card.suit) != new_game.lead_suit {
-                        // Player has revealed a void
-                        new_game.voids[new_game.current_player as usize].insert(card.suit);
+                let card = match self.pending_play.take() {
+                    Some(card) if action == -1 => {
+                        // take the card back off the table
+                        self.hands[self.current_player as usize].push(card);
+                        if let Some(masks) = self.hand_masks.as_mut() {
+                            masks[self.current_player as usize].insert(card.id);
+                        }
+                        if CHANGES {
+                            self.hands[self.current_player as usize].sort_by(card_sorter);
+                            reorder_hand_into(
+                                &mut self.changes[0],
+                                self.current_player,
+                                &self.hands[self.current_player as usize],
+                            );
+                            let playable = show_playable(self);
+                            self.changes.push(playable);
+                        }
+                        return;
                     }
-                }
-                new_game.current_player = (new_game.current_player + 1) % 3;
-                // end trick
-                if new_game.current_trick.iter().flatten().count() == 3 {
-                    let trick_winner = get_winner(
-                        new_game.lead_suit,
-                        new_game.trump_suit,
-                        &new_game.current_trick,
-                    );
-                    let winning_card = new_game.current_trick[trick_winner as usize]
-                        .expect("there has to be a trick_winner card");
-                    new_game.tricks_taken[trick_winner as usize] += 1;
+                    // confirmed - the card is already on the table
+                    Some(card) => card,
+                    None if action == -1 => return,
+                    None => {
+                        let card_id = card_offset(self.state, action);
+                        let card = *self.hands[self.current_player as usize]
+                            .iter()
+                            .find(|c| c.id == card_id)
+                            .expect("this card has to be in the player's hand");
+                        self.hands[self.current_player as usize].retain(|c| c.id != card_id);
+                        if let Some(masks) = self.hand_masks.as_mut() {
+                            masks[self.current_player as usize].remove(card_id);
+                        }
+                        if CHANGES {
+                            self.changes[0].push(Change {
+                                change_type: ChangeType::Play,
+                                object_id: card_id,
+                                source_offset: self.current_player,
+                                dest: Location::Play,
+                                dest_offset: self.current_player,
+                                player: self.current_player,
+                                ..Default::default()
+                            });
+                            reorder_hand_into(
+                                &mut self.changes[0],
+                                self.current_player,
+                                &self.hands[self.current_player as usize],
+                            );
+                            let last_change = self.changes.len() - 1;
+                            let mut changes = hide_playable(&self);
+                            self.changes[last_change].append(&mut changes);
+                        }
+                        if self.confirm_plays && self.human_player[self.current_player as usize] {
+                            self.pending_play = Some(card);
+                            if CHANGES {
+                                self.changes[0].push(Change {
+                                    change_type: ChangeType::BidOptions,
+                                    object_id: -1,
+                                    player: self.current_player,
+                                    dest: Location::BidOptions,
+                                    details: ChangeDetails::bid_options(vec![
+                                        BidOption {
+                                            id: CONFIRM_PLAY,
+                                            description: "Play".to_string(),
+                                        },
+                                        BidOption {
+                                            id: -1,
+                                            description: "Undo".to_string(),
+                                        },
+                                    ]),
+                                    ..Default::default()
+                                });
+                            }
+                            return;
+                        }
+                        card
+                    }
+                };
+                self.current_trick[self.current_player as usize] = Some(card);
+
+                if self.lead_suit.is_none() {
+                    self.lead_suit = Some(card.suit);
+                } else {
+                    if Some(card.suit) != self.lead_suit {
+                        // Player has revealed a void
+                        self.voids[self.current_player as usize].insert(card.suit);
+                    }
+                }
+                self.current_player = self.next_seat(self.current_player);
+                // end trick
+                if self.current_trick.iter().all(|card| card.is_some()) {
+                    let trick_winner =
+                        get_winner(self.lead_suit, self.trump_suit, &self.current_trick);
+                    let winning_card = self.current_trick[trick_winner as usize]
+                        .expect("there has to be a trick_winner card");
+                    self.tricks_taken[trick_winner as usize] += 1;
                     // winner of the trick leads
-                    new_game.current_player = trick_winner;
-                    new_game.lead_player = trick_winner;
-                    if !self.no_changes {
-                        new_game.changes.push(vec![
+                    self.current_player = trick_winner;
+                    self.lead_player = trick_winner;
+                    if CHANGES {
+                        self.changes.push(vec![
                             Change {
                                 change_type: ChangeType::ShowWinningCard,
                                 object_id: winning_card.id,
@@ -884,59 +1465,74 @@ impl Game {
                                 object_id: winning_card.id,
                                 change_type: ChangeType::HidePlayable,
                                 dest: Location::Hand,
-                                dest_offset: new_game.current_player,
+                                dest_offset: self.current_player,
                                 ..Default::default()
                             },
                         ]);
                     }
-                    new_game.changes.push(vec![]); // trick back to player
-                    let offset: usize = new_game.changes.len() - 1;
-                    for player in 0..3 {
-                        let card = new_game.current_trick[player]
-                            .expect("each player should have played a card");
-                        new_game.changes[offset].push(Change {
-                            change_type: ChangeType::TricksToWinner,
-                            object_id: card.id,
-                            source_offset: player as i32,
-                            dest: Location::TricksTaken,
-                            player: trick_winner,
-                            tricks_taken: new_game.tricks_taken[trick_winner as usize],
-                            ..Default::default()
-                        });
+                    if CHANGES {
+                        self.changes.push(vec![]); // trick back to player
+                        let offset: usize = self.changes.len() - 1;
+                        for player in 0..self.player_count() {
+                            let card = self.current_trick[player]
+                                .expect("each player should have played a card");
+                            self.changes[offset].push(Change {
+                                change_type: ChangeType::TricksToWinner,
+                                object_id: card.id,
+                                source_offset: player as i32,
+                                dest: Location::TricksTaken,
+                                player: trick_winner,
+                                tricks_taken: self.tricks_taken[trick_winner as usize],
+                                ..Default::default()
+                            });
+                        }
                     }
 
-                    if new_game.hands.iter().all(|h| h.is_empty()) {
+                    if self.hands.iter().all(|h| h.is_empty()) {
                         // hand end
-                        let reveal_bid_offset: usize = new_game.changes.len() - 1;
-                        for player in 0..3 {
-                            let score = new_game.bids[player]
-                                .expect("Must have bid here")
-                                .score_for_tricks(
-                                    new_game.bid_cards[player],
-                                    new_game.tricks_taken[player],
-                                );
-                            new_game.scores[player] += score;
-                            new_game.scores_this_hand[player] += score;
+                        let reveal_bid_offset: usize = self.changes.len() - 1;
+                        let start_scores = self.scores.clone();
+                        let first_result = self.round_results.len();
+                        for player in 0..self.player_count() {
+                            let bid = self.bids[player].expect("Must have bid here");
+                            let score = bid.score_for_tricks(
+                                self.bid_cards[player],
+                                self.tricks_taken[player],
+                            );
+                            self.scores[player] += score;
+                            self.scores_this_hand[player] += score;
+                            self.round_results.push(BidResult {
+                                round: self.round,
+                                dealer: self.dealer,
+                                trump_suit: self.trump_suit,
+                                player: player as i32,
+                                bid,
+                                bid_cards: self.bid_cards[player],
+                                tricks_taken: self.tricks_taken[player],
+                                points: score,
+                            });
                         }
-                        if !new_game.no_changes {
-                            for player in 0..3 {
+                        if CHANGES {
+                            for player in 0..self.player_count() {
                                 // reveal player's bid display (e.g. 2 or ? -> 2 or 3)
                                 // only affects players that bid easy bids
-                                new_game.changes[reveal_bid_offset].push(Change {
+                                self.changes[reveal_bid_offset].push(Change {
                                     change_type: ChangeType::BidDisplay,
                                     object_id: -1,
                                     source_offset: player as i32,
                                     dest: Location::BidDisplay,
                                     player: player as i32,
-                                    bid_display: new_game.bids[player]
-                                        .unwrap()
-                                        .bid_display(new_game.bid_cards[player], true),
+                                    details: ChangeDetails::bid_display(
+                                        self.bids[player]
+                                            .unwrap()
+                                            .bid_display(self.bid_cards[player], true),
+                                    ),
                                     ..Default::default()
                                 });
                                 // reveal bid cards (will only affect players that had a hidden easy bid card)
-                                new_game.changes[reveal_bid_offset].push(Change {
+                                self.changes[reveal_bid_offset].push(Change {
                                     change_type: ChangeType::Bid,
-                                    object_id: new_game.bid_cards[player][1].unwrap().id,
+                                    object_id: self.bid_cards[player][1].unwrap().id,
                                     source_offset: player as i32,
                                     dest: Location::Bid,
                                     dest_offset: 1,
@@ -945,78 +1541,103 @@ impl Game {
                                     ..Default::default()
                                 });
                                 // modify player's score
-                                new_game.changes.push(vec![Change {
-                                    change_type: ChangeType::Score,
-                                    object_id: player as i32,
-                                    player: player as i32,
-                                    dest: Location::Score,
-                                    start_score: self.scores[player as usize],
-                                    end_score: new_game.scores[player as usize],
-                                    ..Default::default()
-                                }]);
+                                self.changes.push(vec![
+                                    Change {
+                                        change_type: ChangeType::Score,
+                                        object_id: player as i32,
+                                        player: player as i32,
+                                        dest: Location::Score,
+                                        start_score: start_scores[player],
+                                        end_score: self.scores[player as usize],
+                                        ..Default::default()
+                                    },
+                                    Change {
+                                        change_type: ChangeType::BidResult,
+                                        object_id: -1,
+                                        player: player as i32,
+                                        dest: Location::Score,
+                                        tricks_taken: self.tricks_taken[player],
+                                        round: self.round,
+                                        details: ChangeDetails::bid_result(
+                                            self.round_results[first_result + player],
+                                        ),
+                                        ..Default::default()
+                                    },
+                                ]);
                             }
                             // let the human user see the result of the round
-                            new_game.changes.push(vec![Change {
+                            self.changes.push(vec![Change {
                                 change_type: ChangeType::OptionalPause,
                                 object_id: 0,
                                 dest: Location::Play,
                                 ..Default::default()
                             }]);
                         }
-                        if new_game.round >= 6 {
+                        if self.final_hand || self.round >= self.final_round() {
                             // game end
                             // find winners - if human player is a winner set them as the exclusive winner
-                            let max_score: i32 = *new_game.scores.iter().max().unwrap();
-                            for player in 0..3 {
-                                if new_game.scores[player] == max_score {
-                                    new_game.winner = Some(player as i32);
-                                    if new_game.human_player[player] {
+                            let max_score: i32 = *self.scores.iter().max().unwrap();
+                            for player in 0..self.player_count() {
+                                if self.scores[player] == max_score {
+                                    self.winner = Some(player as i32);
+                                    if self.human_player[player] {
                                         // if the human player is among the winners - set them as the winner
                                         break;
                                     }
                                 }
                             }
-                            if !self.no_changes {
-                                new_game.changes.push(vec![Change {
+                            if CHANGES {
+                                self.changes.push(vec![Change {
                                     change_type: ChangeType::GameOver,
                                     dest: Location::Deck,
                                     ..Default::default()
                                 }]);
                             }
-                            return new_game;
+                            return;
                         }
-                        new_game.changes.push(vec![Change {
-                            change_type: ChangeType::Shuffle,
-                            object_id: 0,
-                            source_offset: 0,
-                            dest: Location::Deck,
-                            dest_offset: 0,
-                            round: new_game.round + 1,
-                            ..Default::default()
-                        }]);
-                        return new_game.deal();
+                        if CHANGES {
+                            self.changes.push(vec![Change {
+                                change_type: ChangeType::Shuffle,
+                                object_id: 0,
+                                source_offset: 0,
+                                dest: Location::Deck,
+                                dest_offset: 0,
+                                round: self.round + 1,
+                                ..Default::default()
+                            }]);
+                        }
+                        self.deal_hand::<CHANGES>();
+                        return;
                     }
 
-                    new_game.current_player = new_game.lead_player;
-                    new_game.state = State::Play;
+                    self.current_player = self.lead_player;
+                    self.state = State::Play;
 
-                    new_game.current_trick = [None, None, None];
-                    new_game.lead_suit = None;
+                    self.current_trick.fill(None);
+                    self.lead_suit = None;
                 }
-                let change_offset = &new_game.changes.len() - 1;
-                if !self.no_changes {
-                    let mut new_changes = show_playable(&new_game);
-                    new_game.changes[change_offset].append(&mut new_changes);
+                if CHANGES {
+                    let change_offset = &self.changes.len() - 1;
+                    let mut new_changes = show_playable(self);
+                    self.changes[change_offset].append(&mut new_changes);
                 }
-                new_game
             }
         }
     }
 
     pub fn get_moves(self: &Game) -> Vec<i32> {
+        let mut moves = vec![];
+        self.get_moves_into(&mut moves);
+        moves
+    }
+
+    /// `get_moves` written into `moves` (cleared first) so callers can reuse
+    /// the buffer
+    pub fn get_moves_into(self: &Game, moves: &mut Vec<i32>) {
+        moves.clear();
         match self.state {
             State::TrumpSelect => {
-                vec![TRUMP, NO_TRUMP]
+                moves.extend([TRUMP, NO_TRUMP]);
             }
             State::BidType => {
                 if self.bid_cards[self.current_player as usize][0]
@@ -1028,39 +1649,52 @@ impl Game {
                 {
                     // difference bids with the same value (e.g. 4 - 4 = 0) is not allowed
                     // zero bids are always categorized as zero bids and are worth 6 points when made
-                    vec![BID_TYPE_EASY, BID_TYPE_TOP, BID_TYPE_ZERO]
+                    moves.extend([BID_TYPE_EASY, BID_TYPE_TOP, BID_TYPE_ZERO]);
                 } else {
-                    vec![
+                    moves.extend([
                         BID_TYPE_EASY,
                         BID_TYPE_TOP,
                         BID_TYPE_DIFFERENCE,
                         BID_TYPE_ZERO,
-                    ]
+                    ]);
                 }
             }
-            State::BidCard => self.hands[self.current_player as usize]
-                .iter()
-                .map(|c| move_offset(self.state, c))
-                .collect(),
+            State::BidCard => moves.extend(
+                self.hands[self.current_player as usize]
+                    .iter()
+                    .map(|c| move_offset(self.state, c)),
+            ),
             State::DealerSelect => {
-                vec![DEALER_SELECT_CARD, DEALER_SELECT_CARD + 1]
+                moves.extend([DEALER_SELECT_CARD, DEALER_SELECT_CARD + 1]);
             }
+            _ if self.pending_play.is_some() => moves.push(CONFIRM_PLAY),
             _ => {
-                let actions: Vec<i32>;
+                if let Some(masks) = self.hand_masks {
+                    let hand = masks[self.current_player as usize];
+                    let following = match self.lead_suit {
+                        Some(suit) => hand & suit_mask(suit),
+                        None => CardMask::EMPTY,
+                    };
+                    let playable = if following.is_empty() {
+                        hand
+                    } else {
+                        following
+                    };
+                    moves.extend(playable.ids());
+                    return;
+                }
+                let hand = &self.hands[self.current_player as usize];
                 if self.lead_suit.is_some() {
-                    actions = self.hands[self.current_player as usize]
-                        .iter()
-                        .filter(|c| Some(c.suit) == self.lead_suit)
-                        .map(|c| move_offset(self.state, c))
-                        .collect();
-                    if !actions.is_empty() {
-                        return actions;
+                    moves.extend(
+                        hand.iter()
+                            .filter(|c| Some(c.suit) == self.lead_suit)
+                            .map(|c| move_offset(self.state, c)),
+                    );
+                    if !moves.is_empty() {
+                        return;
                     }
                 }
-                self.hands[self.current_player as usize]
-                    .iter()
-                    .map(|c| move_offset(self.state, c))
-                    .collect()
+                moves.extend(hand.iter().map(|c| move_offset(self.state, c)));
             }
         }
     }
@@ -1094,22 +1728,16 @@ fn card_sorter(a: &Card, b: &Card) -> Ordering {
 pub fn get_winner(
     lead_suit: Option<Suit>,
     trump_suit: Option<Suit>,
-    trick: &[Option<Card>; 3],
+    trick: &[Option<Card>],
 ) -> i32 {
-    let mut card_id_to_player: HashMap<i32, i32> = HashMap::new();
-    for (player, card) in trick.iter().enumerate() {
-        if let Some(card) = card {
-            card_id_to_player.insert(card.id, player as i32);
-        }
-    }
-    let mut cards: Vec<Card> = trick
-        .iter() // Convert the Vec into an Iterator
-        .filter_map(|&x| x) // filter_map will only pass through the Some values
-        .collect();
-    cards.sort_by_key(|c| std::cmp::Reverse(value_for_card(lead_suit, trump_suit, c)));
-    *card_id_to_player
-        .get(&cards.first().expect("there should be a winning card").id)
-        .expect("cards_to_player missing card")
+    // min_by_key keeps the first of equal cards as sorting (stable) did
+    let (player, _) = trick
+        .iter()
+        .enumerate()
+        .filter_map(|(player, card)| card.map(|card| (player, card)))
+        .min_by_key(|(_, c)| std::cmp::Reverse(value_for_card(lead_suit, trump_suit, c)))
+        .expect("there should be a winning card");
+    player as i32
 }
 
 pub fn value_for_card(lead_suit: Option<Suit>, trump_suit: Option<Suit>, card: &Card) -> i32 {
@@ -1123,8 +1751,15 @@ pub fn value_for_card(lead_suit: Option<Suit>, trump_suit: Option<Suit>, card: &
     card.value + bonus
 }
 
-pub fn reorder_hand(player: i32, hand: &Vec<Card>) -> Vec<Change> {
-    let mut changes: Vec<Change> = vec![];
+pub fn reorder_hand(player: i32, hand: &[Card]) -> Vec<Change> {
+    let mut changes: Vec<Change> = Vec::with_capacity(hand.len());
+    reorder_hand_into(&mut changes, player, hand);
+    changes
+}
+
+/// `reorder_hand` appended to an existing change list
+pub fn reorder_hand_into(changes: &mut Vec<Change>, player: i32, hand: &[Card]) {
+    changes.reserve(hand.len());
     for (offset_in_hand, card) in hand.iter().enumerate() {
         changes.push(Change {
             object_id: card.id,
@@ -1136,19 +1771,26 @@ pub fn reorder_hand(player: i32, hand: &Vec<Card>) -> Vec<Change> {
             ..Default::default()
         });
     }
-    changes
 }
 
 fn show_playable(new_game: &Game) -> Vec<Change> {
+    let _timer = profiling::start("changes");
     if new_game.no_changes {
         return vec![];
     }
     let mut changes: Vec<Change> = vec![];
+    let current = new_game.current_player as usize;
 
-    if new_game.current_player == 0 {
-        if new_game.state == State::BidCard && new_game.bid_cards[0][0].is_none() {
+    if new_game.shows_hand(current) {
+        // the other human hands wait for their turn
+        for player in 0..new_game.player_count() {
+            if player != current && new_game.shows_hand(player) {
+                hide_hand_into(&mut changes, new_game, player);
+            }
+        }
+        if new_game.state == State::BidCard && new_game.bid_cards[current][0].is_none() {
             changes.push(Change {
-                message: Some(format!("Select your primary bid card")),
+                details: ChangeDetails::message(format!("Select your primary bid card")),
                 change_type: ChangeType::Message,
                 object_id: -1,
                 dest: Location::Message,
@@ -1163,7 +1805,7 @@ fn show_playable(new_game: &Game) -> Vec<Change> {
             };
             changes.push(Change {
                 change_type: ChangeType::Message,
-                message: Some(format!("Select a card to take{}", message)),
+                details: ChangeDetails::message(format!("Select a card to take{}", message)),
                 object_id: -1,
                 dest: Location::Message,
                 ..Default::default()
@@ -1197,20 +1839,29 @@ fn show_playable(new_game: &Game) -> Vec<Change> {
 }
 
 fn hide_playable(new_game: &Game) -> Vec<Change> {
+    let _timer = profiling::start("changes");
     if new_game.no_changes {
         return vec![];
     }
     let mut changes: Vec<Change> = vec![];
-    for card in &new_game.hands[0] {
+    for player in 0..new_game.player_count() {
+        if new_game.shows_hand(player) {
+            hide_hand_into(&mut changes, new_game, player);
+        }
+    }
+    changes
+}
+
+fn hide_hand_into(changes: &mut Vec<Change>, new_game: &Game, player: usize) {
+    for card in &new_game.hands[player] {
         changes.push(Change {
             object_id: card.id,
             change_type: ChangeType::HidePlayable,
             dest: Location::Hand,
-            dest_offset: new_game.current_player,
+            dest_offset: player as i32,
             ..Default::default()
         });
     }
-    changes
 }
 
 use duplicate::duplicate_item;
@@ -1220,52 +1871,15 @@ impl name for Game {
     type PlayerTag = i32;
     type MoveList = Vec<i32>;
 
-    fn randomize_determination(&mut self, _observer: Self::PlayerTag) {
+    fn randomize_determination(&mut self, observer: Self::PlayerTag) {
+        let _timer = profiling::start("determinization");
         let rng = &mut thread_rng();
-
-        for p1 in 0..3 {
-            for p2 in 0..3 {
-                if p1 == self.current_player() || p2 == self.current_player() || p1 == p2 {
-                    continue;
-                }
-
-                // Add hidden bid cards to player's hands so they can be swapped
-                for player in [p1 as usize, p2 as usize] {
-                    if self.bids[player] == Some(BidType::Easy) && self.bid_cards[player][1] != None
-                    {
-                        let bid_card = self.bid_cards[player][1].unwrap();
-                        self.hands[player].push(bid_card);
-                    }
-                }
-
-                let mut combined_voids: HashSet<Suit> =
-                    HashSet::from_iter(self.voids[p1 as usize].iter().cloned());
-                combined_voids.extend(self.voids[p2 as usize].iter());
-
-                let mut new_hands = vec![
-                    self.hands[p1 as usize].clone(),
-                    self.hands[p2 as usize].clone(),
-                ];
-
-                // allow swapping of any cards that are not in the combined void set
-                shuffle_and_divide_matching_cards(
-                    |c: &Card| !combined_voids.contains(&c.suit),
-                    &mut new_hands,
-                    rng,
-                );
-
-                self.hands[p1 as usize] = new_hands[0].clone();
-                self.hands[p2 as usize] = new_hands[1].clone();
-
-                for player in [p1 as usize, p2 as usize] {
-                    if self.bids[player] == Some(BidType::Easy) && self.bid_cards[player][1] != None
-                    {
-                        // randomly take one of the cards and make it the hidden card
-                        self.bid_cards[player][1] = self.hands[player].pop();
-                    }
-                }
-            }
+        if self.bid_inference && self.bids.iter().any(|bid| bid.is_some()) {
+            self.sample_bid_informed(observer, rng);
+        } else {
+            self.shuffle_hidden_cards(rng);
         }
+        self.update_hand_masks();
     }
 
     fn current_player(&self) -> Self::PlayerTag {
@@ -1273,16 +1887,17 @@ impl name for Game {
     }
 
     fn next_player(&self) -> Self::PlayerTag {
-        (self.current_player + 1) % 3
+        self.next_seat(self.current_player)
     }
 
     fn available_moves(&self) -> Self::MoveList {
+        let _timer = profiling::start("get_moves");
         self.get_moves()
     }
 
     fn make_move(&mut self, mov: &Self::Move) {
-        // FIXME - updating in place would be much faster
-        let _ = mem::replace(self, self.clone().clone_and_apply_move(*mov));
+        let _timer = profiling::start_in("apply_move", &self.state);
+        self.apply_move(*mov);
     }
 
     fn result(&self, player: Self::PlayerTag) -> Option<f64> {
@@ -1311,22 +1926,616 @@ impl name for Game {
     }
 }
 
+impl FeatureExtractor for Game {
+    // the encoding covers the 3 player game - 4 player games are never
+    // given a policy (see bot_move_with_leaf_evaluation)
+    // hand, trick, visible bid cards, bid types, trump, lead suit, state,
+    // dealer, tricks taken, scores
+    const FEATURE_COUNT: usize = 36 + 3 * 36 + 3 * 36 + 3 * 5 + 4 + 4 + 5 + 3 + 3 + 3;
+    // undo (-1) through BID_TYPE_ZERO
+    const ACTION_COUNT: usize = BID_TYPE_ZERO as usize + 2;
+
+    fn features(&self, observer: usize) -> Vec<f32> {
+        assert_eq!(self.player_count(), 3, "features cover the 3 player game");
+        let mut features = FeatureBuilder::with_capacity(Self::FEATURE_COUNT);
+        features.cards(self.hands[observer].iter().map(|c| c.id), 36);
+        for offset in 0..3 {
+            let player = relative_seat(observer, offset, 3);
+            features.cards(self.current_trick[player].iter().map(|c| c.id), 36);
+        }
+        for offset in 0..3 {
+            let player = relative_seat(observer, offset, 3);
+            // The second card of an easy bid is face down
+            let hidden = player != observer && self.bids[player] == Some(BidType::Easy);
+            features.cards(
+                self.bid_cards[player]
+                    .iter()
+                    .enumerate()
+                    .filter(|(index, _)| !(hidden && *index == 1))
+                    .filter_map(|(_, c)| c.map(|c| c.id)),
+                36,
+            );
+        }
+        for offset in 0..3 {
+            let player = relative_seat(observer, offset, 3);
+            features.one_hot(Some(self.bids[player].map_or(0, |b| b as usize + 1)), 5);
+        }
+        features
+            .one_hot(self.trump_suit.map(|s| s as usize), 4)
+            .one_hot(self.lead_suit.map(|s| s as usize), 4)
+            .one_hot(Some(self.state as usize), 5)
+            .one_hot(Some(seat_offset(observer, self.dealer as usize, 3)), 3);
+        for offset in 0..3 {
+            let player = relative_seat(observer, offset, 3);
+            features.scalar(self.tricks_taken[player] as f32 / 12.0);
+        }
+        for offset in 0..3 {
+            let player = relative_seat(observer, offset, 3);
+            features.scalar(self.scores[player] as f32 / 50.0);
+        }
+        features.finish(Self::FEATURE_COUNT)
+    }
+
+    fn action_index(&self, mov: i32) -> usize {
+        (mov + 1) as usize
+    }
+
+    fn current_seat(&self) -> usize {
+        self.current_player as usize
+    }
+}
+
+impl GameEngine for Game {
+    const NAME: &'static str = "dealers_dilemma";
+    // the 4 player variant is only available through Game::new_with_players
+    const PLAYER_COUNT: usize = 3;
+
+    fn new_game() -> Self {
+        Game::new()
+    }
+
+    fn player_count(&self) -> usize {
+        self.hands.len()
+    }
+
+    fn scores(&self) -> Vec<i32> {
+        self.scores.to_vec()
+    }
+
+    fn winners(&self) -> Vec<usize> {
+        self.winner.iter().map(|w| *w as usize).collect()
+    }
+
+    fn legal_moves_into(&self, moves: &mut Vec<i32>) {
+        self.get_moves_into(moves);
+    }
+
+    fn is_legal_move(&self, action: i32) -> bool {
+        self.is_legal(action)
+    }
+
+    fn bot_move_with_leaf_evaluation(
+        &self,
+        iterations: i32,
+        policy: Option<PolicyHandle<Self>>,
+        leaf_evaluation: Option<LeafEvaluation<Self>>,
+    ) -> i32 {
+        if is_dealer_decision(self.state) {
+            return get_dealer_select_move(self);
+        }
+        // policies are trained on the 3 player feature encoding
+        let policy = policy.filter(|_| self.player_count() == Self::PLAYER_COUNT);
+        get_mcts_move_with_leaf_evaluation(self, iterations, policy, leaf_evaluation)
+    }
+
+    /// Compares hand scores as if the hand ended with the tricks taken so far
+    /// since the search only looks at the current hand
+    fn heuristic_value(&self, seat: usize) -> f64 {
+        let mut projected = self.scores_this_hand.clone();
+        if self.state == State::Play {
+            for player in 0..self.player_count() {
+                let bid_complete = match self.bids[player] {
+                    Some(BidType::Zero) => true,
+                    Some(_) => self.bid_cards[player].iter().all(|card| card.is_some()),
+                    None => false,
+                };
+                if bid_complete {
+                    projected[player] += self.bids[player]
+                        .expect("bid is complete")
+                        .score_for_tricks(self.bid_cards[player], self.tricks_taken[player]);
+                }
+            }
+        }
+        let best_opponent = (0..self.player_count())
+            .filter(|player| *player != seat)
+            .map(|player| projected[player])
+            .max()
+            .unwrap_or(0);
+        score_margin(projected[seat] as f64, best_opponent as f64)
+    }
+
+    fn describe_move(&self, action: i32) -> String {
+        let hand = &self.hands[self.current_player as usize];
+        let card = |id: i32| {
+            hand.iter()
+                .find(|card| card.id == id)
+                .map(|card| print_card(*card, false))
+                .unwrap_or_else(|| action.to_string())
+        };
+        match self.state {
+            State::Play if action == CONFIRM_PLAY => "confirm play".to_string(),
+            State::Play => card(action),
+            State::BidCard => format!("bid {}", card(action - BID_CARD_OFFSET)),
+            State::DealerSelect => {
+                let index = card_offset(self.state, action) as usize;
+                match self.dealer_select.get(index) {
+                    Some(selected) => format!("take {}", print_card(*selected, false)),
+                    None => action.to_string(),
+                }
+            }
+            State::TrumpSelect if action == NO_TRUMP => "no trump".to_string(),
+            State::TrumpSelect => "trump".to_string(),
+            State::BidType => offset_to_bid_type(action)
+                .bid_display_detailed(self.bid_cards[self.current_player as usize]),
+        }
+    }
+
+    fn render(&self, seat: usize) -> String {
+        let rows: Vec<Vec<String>> = (0..self.player_count())
+            .map(|player| {
+                let bid = match self.bids[player] {
+                    Some(bid) if self.bid_cards[player].iter().all(|card| card.is_some()) => {
+                        bid.bid_display(self.bid_cards[player], player == seat)
+                    }
+                    _ => "-".to_string(),
+                };
+                // the second bid card is face down until the hand is scored
+                let bid_cards = self.bid_cards[player]
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(index, card)| {
+                        card.map(|card| {
+                            if index == 0 || player == seat {
+                                print_card(card, false)
+                            } else {
+                                "?".to_string()
+                            }
+                        })
+                    });
+                vec![
+                    render::seat_label(player, seat),
+                    self.scores[player].to_string(),
+                    self.tricks_taken[player].to_string(),
+                    bid,
+                    render::cards(bid_cards),
+                ]
+            })
+            .collect();
+        let cards =
+            |cards: &[Card]| render::cards(cards.iter().map(|card| print_card(*card, false)));
+        let header = ["player", "score", "tricks", "bid", "bid cards"];
+        let mut lines = vec![render::table(&header, &rows)];
+        lines.push(format!(
+            "trump: {} lead: {}",
+            print_suit(self.trump_suit),
+            print_suit(self.lead_suit)
+        ));
+        if self.state == State::DealerSelect {
+            lines.push(format!("dealer cards: {}", cards(&self.dealer_select)));
+        }
+        let played: Vec<Option<String>> = self
+            .current_trick
+            .iter()
+            .map(|card| card.map(|card| print_card(card, false)))
+            .collect();
+        let lead_player = Some(self.lead_player as usize);
+        lines.push(format!("trick: {}", render::trick(&played, lead_player)));
+        lines.push(format!("hand: {}", cards(&self.hands[seat])));
+        lines.join("\n")
+    }
+
+    fn events(&self, after: &Self) -> Vec<GameEvent> {
+        let mut events: Vec<GameEvent> = after
+            .changes
+            .iter()
+            .flatten()
+            .find(|change| change.change_type == ChangeType::TricksToWinner)
+            .map(|change| GameEvent::TrickWon {
+                leader: self.lead_player as usize,
+                winner: change.player as usize,
+            })
+            .into_iter()
+            .collect();
+        if after.round != self.round || after.winner.is_some() {
+            events.push(GameEvent::HandOver);
+            for (player, bid) in self.bids.iter().enumerate() {
+                if let Some(bid) = bid {
+                    events.push(GameEvent::Bid {
+                        seat: player,
+                        bid: format!("{:?}", bid).to_lowercase(),
+                        // every bid scores points when it is made
+                        made: after.scores[player] > self.scores[player],
+                    });
+                }
+            }
+        }
+        events
+    }
+}
+
+// Bid type selection model
+//
+// A linear policy (see quantized.rs) over features of the bidder's hand,
+// their bid cards, trump and the dealer's cards. It is trained from
+// self-play bids labelled with the score each bid went on to make in its hand.
+
+// hand, own bid cards (face up then second), trump suit, dealer select cards,
+// dealer position, bid types and face up bid cards of the other players
+pub const BID_FEATURE_COUNT: usize = 36 + 2 * 36 + 4 + 36 + 3 + 2 * 5 + 2 * 36;
+// Easy, Top, Difference, Zero
+pub const BID_ACTION_COUNT: usize = 4;
+
+fn bid_index(mov: i32) -> usize {
+    (mov - BID_TYPE_OFFSET) as usize
+}
+
+impl Game {
+    /// Features seen by the player choosing a bid type
+    pub fn bid_features(&self) -> Vec<f32> {
+        assert_eq!(self.player_count(), 3, "features cover the 3 player game");
+        let observer = self.current_player as usize;
+        let mut features = FeatureBuilder::with_capacity(BID_FEATURE_COUNT);
+        features.cards(self.hands[observer].iter().map(|c| c.id), 36);
+        for bid_card in self.bid_cards[observer] {
+            features.cards(bid_card.iter().map(|c| c.id), 36);
+        }
+        features
+            .one_hot(self.trump_suit.map(|s| s as usize), 4)
+            .cards(self.dealer_select.iter().map(|c| c.id), 36)
+            .one_hot(Some(seat_offset(observer, self.dealer as usize, 3)), 3);
+        for offset in 1..3 {
+            let player = relative_seat(observer, offset, 3);
+            features.one_hot(Some(self.bids[player].map_or(0, |b| b as usize + 1)), 5);
+        }
+        for offset in 1..3 {
+            let player = relative_seat(observer, offset, 3);
+            features.cards(self.bid_cards[player][0].iter().map(|c| c.id), 36);
+        }
+        features.finish(BID_FEATURE_COUNT)
+    }
+}
+
+/// A bid type decision seen through `bid_features` so bid models are plain
+/// linear policies
+#[derive(Debug, Clone, PartialEq)]
+pub struct BidDecision {
+    seat: usize,
+    features: Vec<f32>,
+}
+
+impl FeatureExtractor for BidDecision {
+    const FEATURE_COUNT: usize = BID_FEATURE_COUNT;
+    const ACTION_COUNT: usize = BID_ACTION_COUNT;
+
+    fn features(&self, _observer: usize) -> Vec<f32> {
+        self.features.clone()
+    }
+
+    fn action_index(&self, mov: i32) -> usize {
+        bid_index(mov)
+    }
+
+    fn current_seat(&self) -> usize {
+        self.seat
+    }
+}
+
+impl Game {
+    /// The bid type decision of the player to act
+    pub fn bid_decision(&self) -> BidDecision {
+        BidDecision {
+            seat: self.current_player as usize,
+            features: self.bid_features(),
+        }
+    }
+}
+
+/// Bid type model - trained with `training::train_linear_policy` and
+/// loaded with `models::load_linear_policy`
+pub type BidPolicy = LinearPolicy<BidDecision>;
+
+/// Most likely bid type move - the game must be in `State::BidType`
+pub fn choose_bid(policy: &BidPolicy, game: &Game) -> i32 {
+    policy
+        .best_move(&game.bid_decision(), &game.get_moves())
+        .expect("there is always a legal bid type")
+}
+
+impl PolicyModel<Game> for BidPolicy {
+    fn priors(&self, game: &Game, moves: &[i32]) -> Vec<f32> {
+        if game.state == State::BidType {
+            PolicyModel::<BidDecision>::priors(self, &game.bid_decision(), moves)
+        } else {
+            vec![1.0; moves.len()]
+        }
+    }
+}
+
+/// Play `games` games with the ISMCTS bot and record every bid type decision.
+/// Each sample's outcome is the score the bid made in its hand scaled to -1..1.
+pub fn bid_training_samples(games: usize, iterations: i32) -> Vec<Sample> {
+    let mut samples = vec![];
+    for _ in 0..games {
+        let mut game = Game::new();
+        // bids waiting for the end of their hand
+        let mut pending: Vec<Sample> = vec![];
+        while game.winner.is_none() {
+            let moves = game.get_moves();
+            let action = if moves.len() == 1 {
+                moves[0]
+            } else {
+                get_mcts_move(&game, iterations)
+            };
+            if game.state == State::BidType {
+                pending.push(Sample {
+                    game: "dealers_dilemma_bids".to_string(),
+                    seat: game.current_player as usize,
+                    features: game.bid_features(),
+                    legal_actions: moves.iter().map(|mov| bid_index(*mov)).collect(),
+                    action: bid_index(action),
+                    outcome: 0.0,
+                });
+            }
+            game = game
+                .clone_and_apply_move(action)
+                .expect("the bot should pick a legal move");
+            // every bid scores a non-zero amount so this is only set at the end of a hand
+            if game.scores_this_hand.iter().any(|score| *score != 0) {
+                for mut sample in pending.drain(..) {
+                    let score = game.scores_this_hand[sample.seat] as f32;
+                    sample.outcome = (score / 8.0).clamp(-1.0, 1.0);
+                    samples.push(sample);
+                }
+            }
+        }
+    }
+    samples
+}
+
+/// Uses the bid policy (when given) for bid type decisions, the dealer
+/// evaluation for the dealer's card and trump and ISMCTS for everything else
+pub fn get_bot_move(game: &Game, iterations: i32, bid_policy: Option<&BidPolicy>) -> i32 {
+    match bid_policy {
+        Some(bid_policy) if game.state == State::BidType && game.player_count() == 3 => {
+            choose_bid(bid_policy, game)
+        }
+        _ if is_dealer_decision(game.state) => get_dealer_select_move(game),
+        _ => get_mcts_move(game, iterations),
+    }
+}
+
+fn is_dealer_decision(state: State) -> bool {
+    matches!(state, State::DealerSelect | State::TrumpSelect)
+}
+
+/// The dealer's card (or trump) choice - from the wide open position before
+/// anybody has bid the search spreads its iterations too thin, so instead
+/// every option is scored by how well the dealer could bid the hand it leaves
+pub fn get_dealer_select_move(game: &Game) -> i32 {
+    let mut game = game.clone();
+    game.with_no_changes();
+    let value = |action: i32| {
+        let mut option = game.clone();
+        option.apply_move(action);
+        if option.state == State::TrumpSelect {
+            [TRUMP, NO_TRUMP]
+                .into_iter()
+                .map(|trump| {
+                    let mut option = option.clone();
+                    option.apply_move(trump);
+                    dealer_bid_value(&option)
+                })
+                .fold(f64::NEG_INFINITY, f64::max)
+        } else {
+            dealer_bid_value(&option)
+        }
+    };
+    game.get_moves()
+        .into_iter()
+        .map(|action| (action, value(action)))
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(action, _)| action)
+        .expect("the dealer always has a choice")
+}
+
+/// Expected points of the dealer's best bid once trump is settled
+fn dealer_bid_value(game: &Game) -> f64 {
+    let dealer = game.dealer as usize;
+    let hand = &game.hands[dealer];
+    let bid_types = [
+        BidType::Easy,
+        BidType::Top,
+        BidType::Difference,
+        BidType::Zero,
+    ];
+    let mut best = f64::NEG_INFINITY;
+    for first in 0..hand.len() {
+        for second in 0..hand.len() {
+            if first == second {
+                continue;
+            }
+            let bid_cards = [Some(hand[first]), Some(hand[second])];
+            // the dealer's card on the table still takes part in a trick
+            let kept = hand
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| *index != first && *index != second)
+                .map(|(_, card)| card)
+                .chain(game.current_trick[dealer].iter());
+            let tricks = estimated_tricks(kept, game.trump_suit).round() as i32;
+            for bid in bid_types {
+                if bid == BidType::Difference && hand[first].value == hand[second].value {
+                    continue;
+                }
+                // allow for being a trick off either way
+                let expected = 0.5 * bid.score_for_tricks(bid_cards, tricks) as f64
+                    + 0.25 * bid.score_for_tricks(bid_cards, (tricks - 1).max(0)) as f64
+                    + 0.25 * bid.score_for_tricks(bid_cards, tricks + 1) as f64;
+                best = best.max(expected);
+            }
+        }
+    }
+    best
+}
+
+/// Rough number of tricks a set of cards takes - trumps and high cards win
+fn estimated_tricks<'a>(cards: impl Iterator<Item = &'a Card>, trump_suit: Option<Suit>) -> f64 {
+    cards
+        .map(|card| match card.value {
+            _ if Some(card.suit) == trump_suit => 0.25 + (card.value - 1) as f64 / 12.0,
+            10 => 0.8,
+            9 => 0.5,
+            8 => 0.25,
+            _ => 0.0,
+        })
+        .sum()
+}
+
 pub fn get_mcts_move(game: &Game, iterations: i32) -> i32 {
+    get_mcts_move_with_policy(game, iterations, None)
+}
+
+pub fn get_mcts_move_with_policy(
+    game: &Game,
+    iterations: i32,
+    policy: Option<PolicyHandle<Game>>,
+) -> i32 {
+    get_mcts_move_with_leaf_evaluation(game, iterations, policy, None)
+}
+
+pub fn get_mcts_move_with_leaf_evaluation(
+    game: &Game,
+    iterations: i32,
+    policy: Option<PolicyHandle<Game>>,
+    leaf_evaluation: Option<LeafEvaluation<Game>>,
+) -> i32 {
     let mut new_game = game.clone();
-    new_game.round = 6;
-    new_game.no_changes = true;
-    let mut ismcts = IsmctsHandler::new(new_game);
+    new_game.with_final_hand();
+    new_game.with_no_changes();
     let parallel_threads: usize = 8;
-    ismcts.run_iterations(
-        parallel_threads,
-        (iterations as f64 / parallel_threads as f64) as usize,
-    );
-    ismcts.best_move().expect("should have a move to make")
+    search_with_leaf_evaluation(new_game, iterations, parallel_threads, policy, leaf_evaluation)
+}
+
+/// Arena bot for measuring bid inference - plays ISMCTS with or without the
+/// bid-informed determinizations regardless of the game's setting
+/// (`test_bid_inference_strength` runs the comparison)
+pub struct BidInferenceMove {
+    pub name: String,
+    pub iterations: i32,
+    pub bid_inference: bool,
+}
+
+impl MoveMaker<Game> for BidInferenceMove {
+    fn get_move(&mut self, game: &Game, _rng: &mut StdRng) -> i32 {
+        let mut game = game.clone();
+        game.bid_inference = self.bid_inference;
+        get_mcts_move(&game, self.iterations)
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Dart enums for the change protocol - see `cargo run --bin dart`
+pub fn dart_enums() -> String {
+    use crate::dart::dart_enum;
+    [
+        dart_enum::<ChangeType>("ChangeType"),
+        dart_enum::<Location>("Location"),
+        dart_enum::<State>("State"),
+    ]
+    .join("\n")
+}
+
+/// TypeScript declarations of every serialized type in this game
+#[cfg(feature = "typescript")]
+pub fn typescript_declarations() -> String {
+    use ts_rs::TS;
+    [
+        State::decl(),
+        BidType::decl(),
+        BidOption::decl(),
+        BidResult::decl(),
+        ScoresheetRow::decl(),
+        Suit::decl(),
+        Card::decl(),
+        ChangeType::decl(),
+        Location::decl(),
+        Change::decl(),
+        Game::decl(),
+    ]
+    .iter()
+    .map(|decl| format!("export {}\n", decl))
+    .collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_features() {
+        crate::features::assert_features_consistent(Game::new(), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "features cover the 3 player game")]
+    fn test_features_four_players() {
+        Game::new_with_players(4).features(0);
+    }
+
+    #[test]
+    fn test_seats_serialize_per_player() {
+        for player_count in 3..=MAX_PLAYERS {
+            let game = Game::new_with_players(player_count);
+            assert_eq!(GameEngine::player_count(&game), player_count);
+            let json = serde_json::to_value(&game).unwrap();
+            for field in ["hands", "humanPlayer", "scores", "tricksTaken", "voids"] {
+                assert_eq!(json[field].as_array().unwrap().len(), player_count);
+            }
+            let restored: Game = serde_json::from_value(json).unwrap();
+            assert_eq!(restored, game);
+        }
+    }
+
+    #[test]
+    fn test_bid_policy() {
+        let mut game = Game::new();
+        while game.state != State::BidType {
+            let moves = game.get_moves();
+            let action = *moves.choose(&mut thread_rng()).expect("should have a move");
+            game = game.clone_and_apply_move(action).unwrap();
+        }
+        let features = game.bid_features();
+        assert_eq!(features.len(), BID_FEATURE_COUNT);
+        let legal_actions: Vec<usize> = game.get_moves().iter().map(|m| bid_index(*m)).collect();
+        let sample = |action: i32, outcome: f32| Sample {
+            features: features.clone(),
+            legal_actions: legal_actions.clone(),
+            action: bid_index(action),
+            outcome,
+            ..Default::default()
+        };
+        let mut policy = BidPolicy::zeros();
+        let samples = [sample(BID_TYPE_TOP, 1.0), sample(BID_TYPE_EASY, -0.5)];
+        crate::training::train_linear_policy(&mut policy, &samples, 20, 0.1);
+        assert_eq!(choose_bid(&policy, &game), BID_TYPE_TOP);
+        assert_eq!(get_bot_move(&game, 10, Some(&policy)), BID_TYPE_TOP);
+        let priors = policy.priors(&game, &game.get_moves());
+        assert!((priors.iter().sum::<f32>() - 1.0).abs() < 1e-4);
+    }
 
     #[test]
     fn test_deck() {
@@ -1630,19 +2839,25 @@ mod tests {
             },
         ];
 
-        let new_game = game.clone().clone_and_apply_move(DEALER_SELECT_CARD);
+        let new_game = game
+            .clone()
+            .clone_and_apply_move(DEALER_SELECT_CARD)
+            .unwrap();
         assert_eq!(new_game.trump_suit, None);
         assert_eq!(new_game.state, State::TrumpSelect);
 
-        let new_game = new_game.clone().clone_and_apply_move(TRUMP);
+        let new_game = new_game.clone().clone_and_apply_move(TRUMP).unwrap();
         assert_eq!(new_game.trump_suit, Some(Suit::Red));
         assert_eq!(new_game.state, State::BidCard);
 
-        let new_game = game.clone().clone_and_apply_move(DEALER_SELECT_CARD + 1);
+        let new_game = game
+            .clone()
+            .clone_and_apply_move(DEALER_SELECT_CARD + 1)
+            .unwrap();
         assert_eq!(new_game.trump_suit, None);
         assert_eq!(new_game.state, State::TrumpSelect);
 
-        let new_game = new_game.clone().clone_and_apply_move(NO_TRUMP);
+        let new_game = new_game.clone().clone_and_apply_move(NO_TRUMP).unwrap();
         assert_eq!(new_game.trump_suit, None);
         assert_eq!(new_game.state, State::BidCard);
 
@@ -1662,8 +2877,7 @@ mod tests {
 
     #[test]
     fn test_random_playthrough() {
-        let mut game = Game::new();
-        game.round = 6;
+        let mut game = Game::new_with_options(3, 1);
         while game.winner.is_none() {
             if game.state == State::Play {
                 // all players should have same number of cards
@@ -1680,8 +2894,380 @@ mod tests {
             let mut moves = game.get_moves();
             moves.shuffle(&mut thread_rng());
             let action = *moves.first().unwrap();
-            game = game.clone_and_apply_move(action);
+            game = game.clone_and_apply_move(action).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_rounds() {
+        let play_out = |mut game: Game| {
+            while game.winner.is_none() {
+                let mut moves = game.get_moves();
+                moves.shuffle(&mut thread_rng());
+                game = game.clone_and_apply_move(*moves.first().unwrap()).unwrap();
+            }
+            game.round
+        };
+        assert_eq!(Game::new().final_round(), 6);
+        assert_eq!(play_out(Game::new_with_options(3, 2)), 2);
+        // saved games without a round count play the full game
+        let mut game = Game::new();
+        game.rounds = 0;
+        assert_eq!(game.final_round(), 6);
+        // the final hand flag ends the game after the current hand
+        let mut game = Game::new();
+        game.with_final_hand();
+        assert_eq!(play_out(game), 1);
+    }
+
+    #[test]
+    fn test_four_player_playthrough() {
+        let mut game = Game::new_with_players(4);
+        assert_eq!(game.dealer, 3);
+        assert_eq!(game.hands[3].len(), 7);
+        assert_eq!(game.dealer_select.len(), 2);
+        for player in 0..3 {
+            assert_eq!(game.hands[player].len(), 9);
         }
+        let mut rounds = 1;
+        while game.winner.is_none() {
+            if game.state == State::Play {
+                let cards: Vec<usize> = (0..4)
+                    .map(|player| {
+                        game.hands[player].len() + game.current_trick[player].iter().count()
+                    })
+                    .collect();
+                assert!(cards.iter().all(|count| *count == cards[0]));
+            }
+            let mut moves = game.get_moves();
+            moves.shuffle(&mut thread_rng());
+            let action = *moves.first().unwrap();
+            game = game.clone_and_apply_move(action).unwrap();
+            rounds = rounds.max(game.round);
+        }
+        // everyone deals twice
+        assert_eq!(rounds, 8);
+        assert_eq!(game.scores.len(), 4);
+    }
+
+    #[test]
+    fn test_undo() {
+        let hand_ids = |game: &Game| {
+            let mut ids: Vec<i32> = game.hands[0].iter().map(|c| c.id).collect();
+            ids.sort();
+            ids
+        };
+        let mut game = Game::new();
+        game.human_player = smallvec![true, false, false];
+        game.confirm_plays = true;
+        assert_eq!(game.dealer, 0);
+        assert_eq!(game.state, State::DealerSelect);
+
+        // the dealer's card (and the trump it names) can be picked again
+        let before = game.clone();
+        game.apply_move(DEALER_SELECT_CARD);
+        assert_ne!(game.state, State::DealerSelect);
+        game.apply_move(-1);
+        assert_eq!(game.state, State::DealerSelect);
+        assert_eq!(hand_ids(&game), hand_ids(&before));
+        assert_eq!(game.current_trick, before.current_trick);
+        assert_eq!(game.trump_suit, None);
+        assert_eq!(game.lead_suit, None);
+
+        game.apply_move(DEALER_SELECT_CARD + 1);
+        if game.state == State::TrumpSelect {
+            game.apply_move(TRUMP);
+        }
+        assert_eq!(game.state, State::BidCard);
+
+        // the primary bid card goes back to the hand
+        let before = game.clone();
+        let bid_card = game.get_moves()[0];
+        game.apply_move(bid_card);
+        assert!(game.bid_cards[0][0].is_some());
+        game.apply_move(-1);
+        assert_eq!(game.state, State::BidCard);
+        assert_eq!(game.bid_cards[0], [None, None]);
+        assert_eq!(hand_ids(&game), hand_ids(&before));
+
+        while game.state != State::Play || game.current_player != 0 {
+            let action = game.get_moves()[0];
+            game.apply_move(action);
+        }
+
+        // plays wait for confirmation
+        let before = game.clone();
+        let card_id = game.get_moves()[0];
+        game.apply_move(card_id);
+        assert_eq!(game.pending_play.map(|c| c.id), Some(card_id));
+        assert_eq!(game.get_moves(), vec![CONFIRM_PLAY]);
+        assert_eq!(game.current_player, 0);
+        game.apply_move(-1);
+        assert_eq!(game.pending_play, None);
+        assert_eq!(hand_ids(&game), hand_ids(&before));
+
+        game.apply_move(card_id);
+        game.apply_move(CONFIRM_PLAY);
+        assert_eq!(game.pending_play, None);
+        assert!(!hand_ids(&game).contains(&card_id));
+    }
+
+    #[test]
+    fn test_dealer_select_move() {
+        let card = |id: i32, value: i32, suit: Suit| Card { id, value, suit };
+        let mut trumps = [
+            card(0, 10, Suit::Blue),
+            card(1, 9, Suit::Blue),
+            card(2, 2, Suit::Red),
+        ];
+        let plain = estimated_tricks(trumps.iter(), None);
+        assert!(estimated_tricks(trumps.iter(), Some(Suit::Red)) > plain);
+        trumps[2].value = 10;
+        assert!(estimated_tricks(trumps.iter(), None) > plain);
+
+        for _ in 0..10 {
+            let mut game = Game::new();
+            let action = get_dealer_select_move(&game);
+            assert!(game.get_moves().contains(&action));
+            assert_eq!(get_bot_move(&game, 10, None), action);
+            game.apply_move(action);
+            if game.state == State::TrumpSelect {
+                assert!([TRUMP, NO_TRUMP].contains(&get_dealer_select_move(&game)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_hotseat() {
+        let mut game = Game::new_hotseat(vec![true, false, true]);
+        let mut passes = 0;
+        while game.winner.is_none() {
+            let mut moves = game.get_moves();
+            moves.shuffle(&mut thread_rng());
+            game.apply_move(moves[0]);
+            for change in game.changes.iter().flatten() {
+                match change.change_type {
+                    ChangeType::PassDevice => {
+                        assert_eq!(change.player, game.current_player);
+                        passes += 1;
+                    }
+                    // only the human seats are offered cards
+                    ChangeType::ShowPlayable => assert_ne!(change.dest_offset, 1),
+                    _ => {}
+                }
+            }
+            if game.human_player[game.current_player as usize] {
+                assert_eq!(game.device_holder, Some(game.current_player));
+            }
+        }
+        assert!(passes > 0);
+    }
+
+    #[test]
+    fn test_illegal_moves() {
+        let mut game = Game::new();
+        let before = game.clone();
+        let error = game.try_apply_move(BID_TYPE_ZERO).unwrap_err();
+        assert_eq!(error.action, BID_TYPE_ZERO);
+        assert_eq!(error.state, State::DealerSelect);
+        assert_eq!(game, before);
+        assert!(game.clone().clone_and_apply_move(BID_TYPE_ZERO).is_err());
+        // undo needs something to take back
+        assert!(game.try_apply_move(-1).is_err());
+        assert!(game.try_apply_move(DEALER_SELECT_CARD).is_ok());
+        assert!(game.try_apply_move(-1).is_ok());
+        assert_eq!(game.state, State::DealerSelect);
+    }
+
+    #[test]
+    fn test_round_results() {
+        let mut game = Game::new_with_options(3, 2);
+        let mut bid_result_changes = 0;
+        while game.winner.is_none() {
+            let mut moves = game.get_moves();
+            moves.shuffle(&mut thread_rng());
+            game = game.clone_and_apply_move(*moves.first().unwrap()).unwrap();
+            bid_result_changes += game
+                .changes
+                .iter()
+                .flatten()
+                .filter(|change| change.change_type == ChangeType::BidResult)
+                .count();
+        }
+        assert_eq!(game.round_results.len(), 6);
+        assert_eq!(bid_result_changes, 6);
+        for player in 0..3 {
+            let results: Vec<&BidResult> = game
+                .round_results
+                .iter()
+                .filter(|result| result.player == player as i32)
+                .collect();
+            assert_eq!(results.len(), 2);
+            let points: i32 = results.iter().map(|result| result.points).sum();
+            assert_eq!(points, game.scores[player]);
+        }
+        let scoresheet = game.scoresheet();
+        assert_eq!(scoresheet.len(), 2);
+        assert_eq!(scoresheet[0].dealer, 0);
+        assert_eq!(scoresheet[1].dealer, 1);
+        assert!(scoresheet.iter().all(|row| row.results.len() == 3));
+        assert_eq!(scoresheet[1].scores, game.scores.to_vec());
+        // the score sheet survives saving the game
+        let restored: Game = serde_json::from_str(&serde_json::to_string(&game).unwrap()).unwrap();
+        assert_eq!(restored.scoresheet(), scoresheet);
+        let json = serde_json::to_value(&game.round_results[0]).unwrap();
+        assert_eq!(json["round"], 1);
+        assert!(json["bidCards"].is_array());
+    }
+
+    #[test]
+    fn test_hand_masks() {
+        let masks_match = |game: &Game| {
+            let hands = game.hands.clone();
+            let mut masks = [CardMask::EMPTY; MAX_PLAYERS];
+            for (mask, hand) in masks.iter_mut().zip(hands.iter()) {
+                *mask = CardMask::from_ids(hand.iter().map(|c| c.id));
+            }
+            game.hand_masks == Some(masks)
+        };
+        let mut game = Game::new();
+        game.with_no_changes();
+        while game.winner.is_none() {
+            assert!(masks_match(&game));
+            let mut unmasked = game.clone();
+            unmasked.hand_masks = None;
+            let mut moves = game.get_moves();
+            let mut expected = unmasked.get_moves();
+            moves.sort();
+            expected.sort();
+            assert_eq!(moves, expected);
+
+            let mut determinization = game.clone();
+            ismcts::Game::randomize_determination(&mut determinization, game.current_player);
+            assert!(masks_match(&determinization));
+
+            game.apply_move(*moves.choose(&mut thread_rng()).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_change_details() {
+        assert!(std::mem::size_of::<Change>() <= 64);
+        let play = serde_json::to_value(Change::default()).unwrap();
+        assert_eq!(play["message"], serde_json::Value::Null);
+        assert_eq!(play["bidDisplay"], "");
+        assert_eq!(play["bidOptions"], serde_json::Value::Null);
+        let restored: Change = serde_json::from_value(play).unwrap();
+        assert_eq!(restored, Change::default());
+
+        let message = Change {
+            change_type: ChangeType::Message,
+            details: ChangeDetails::message("Select your primary bid card".to_string()),
+            ..Default::default()
+        };
+        let json = serde_json::to_value(&message).unwrap();
+        assert_eq!(json["message"], "Select your primary bid card");
+        assert_eq!(json["changeType"], "message");
+        assert_eq!(serde_json::from_value::<Change>(json).unwrap(), message);
+        let empty = ChangeDetails::bid_display(String::new());
+        assert_eq!(empty, ChangeDetails::default());
+    }
+
+    #[test]
+    fn test_simulated_moves_emit_no_changes() {
+        let mut game = Game::new();
+        // the human seat is what adds the bid option changes
+        game.human_player = smallvec![true, false, false];
+        game.with_no_changes();
+        while game.winner.is_none() {
+            let action = *game.get_moves().choose(&mut thread_rng()).unwrap();
+            let mut with_changes = game.clone();
+            with_changes.no_changes = false;
+            with_changes.hand_masks = None;
+            let round = game.round;
+            game.apply_move(action);
+            with_changes.apply_move(action);
+            assert_eq!(game.changes, vec![vec![]]);
+            assert_eq!(game.scores, with_changes.scores);
+            // the next hand is dealt with thread_rng
+            if game.round == round {
+                assert_eq!(game.hands, with_changes.hands);
+                assert_eq!(game.current_player, with_changes.current_player);
+                assert_eq!(game.state, with_changes.state);
+            }
+        }
+    }
+
+    #[test]
+    fn test_bid_likelihood() {
+        let mut game = Game::new();
+        while game.state != State::Play {
+            let action = game.get_moves()[0];
+            game.apply_move(action);
+        }
+        let observer = game.current_player;
+        let likelihood = game.bid_likelihood(observer);
+        assert!(likelihood > 0.0 && likelihood <= 1.0);
+        game.bid_inference = true;
+        let mut determinization = game.clone();
+        ismcts::Game::randomize_determination(&mut determinization, observer);
+        // the observer's cards and every card count stay the same
+        let seat = observer as usize;
+        assert_eq!(determinization.hands[seat], game.hands[seat]);
+        for (determinized, hand) in determinization.hands.iter().zip(&game.hands) {
+            assert_eq!(determinized.len(), hand.len());
+        }
+    }
+
+    #[test]
+    fn test_bid_inference_arena() {
+        use crate::arena::{play_match, ArenaConfig};
+        // more deals and iterations for comparisons
+        let config = ArenaConfig {
+            deals: 1,
+            ..Default::default()
+        };
+        let mut informed = BidInferenceMove {
+            name: "bid inference".to_string(),
+            iterations: 10,
+            bid_inference: true,
+        };
+        let mut uniform = BidInferenceMove {
+            name: "uniform".to_string(),
+            iterations: 10,
+            bid_inference: false,
+        };
+        let report = play_match::<Game>(&mut informed, &mut uniform, &config);
+        assert_eq!(report.wins + report.draws + report.losses, report.games);
+        println!("{:?}", report);
+    }
+
+    /// Bid-informed against plain determinization at playing strength. Too
+    /// slow for every run:
+    /// `cargo test --release test_bid_inference_strength -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn test_bid_inference_strength() {
+        use crate::arena::{play_match, ArenaConfig};
+        let config = ArenaConfig::default();
+        let mut informed = BidInferenceMove {
+            name: "bid inference".to_string(),
+            iterations: 1000,
+            bid_inference: true,
+        };
+        let mut uniform = BidInferenceMove {
+            name: "uniform".to_string(),
+            iterations: 1000,
+            bid_inference: false,
+        };
+        let report = play_match::<Game>(&mut informed, &mut uniform, &config);
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+        // bid inference must at least not be measurably weaker
+        assert!(
+            report.confidence_interval.1 >= 0.5,
+            "bid inference lost: {:?}",
+            report
+        );
     }
 
     #[test]
@@ -1696,10 +3282,10 @@ mod tests {
             game.dealer = i % 3;
             game.current_player = i % 3;
             game = game.deal();
-            game.round = 6;
+            game.with_final_hand();
             while game.winner.is_none() {
                 let action = get_mcts_move(&game, iterations[game.current_player as usize]);
-                game = game.clone_and_apply_move(action);
+                game = game.clone_and_apply_move(action).unwrap();
             }
             let max_score: i32 = *game.scores.iter().max().unwrap();
             for player in 0..3 {
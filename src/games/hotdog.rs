@@ -7,21 +7,32 @@ BoardGameGeek: https://boardgamegeek.com/boardgame/365349/hotdog
 use rand::Rng;
 use std::{
     cmp::Ordering,
-    collections::{BTreeMap, HashMap, HashSet},
+    collections::{BTreeMap, HashSet},
 };
 
+use colored::Color;
 use enum_iterator::{all, Sequence};
-use ismcts::IsmctsHandler;
 use once_cell::sync::Lazy;
 use rand::seq::SliceRandom;
 use rand::thread_rng;
 use serde::{Deserialize, Serialize};
 
+use crate::engine::{projected_margin, GameEngine, GameEvent};
+use crate::features::{relative_seat, seat_offset, FeatureBuilder, FeatureExtractor};
+use crate::policy::{search_with_leaf_evaluation, LeafEvaluation, PolicyHandle};
+use crate::profiling;
+use crate::utils::render::{self, SuitStyle};
+use crate::utils::{reset_changes, Hand};
+
 const CARD_NONE: std::option::Option<Card> = None;
 const NO_RELISH: i32 = 0;
+// 7 cards in hand plus 10 straw cards for each player
+const TRICKS_PER_HAND: i32 = 17;
 
 /// All the possible bids in the game
 #[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq, Sequence, Copy)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub enum Bid {
     #[default]
@@ -36,6 +47,8 @@ pub enum Bid {
 
 /// Reader-friendly ranking of cards (Mustard -> LowStrong, Ketchup -> HighStrong, Works -> Alternating)
 #[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 enum Ranking {
     #[default]
@@ -164,31 +177,21 @@ impl Bid {
     }
 }
 
-static ID_TO_BID: Lazy<HashMap<i32, Bid>> = Lazy::new(|| {
-    let mut m = HashMap::new();
-    for bid in all::<Bid>() {
-        m.insert(bid as i32, bid);
-    }
-    m
-});
+// Lookups indexed by id - bids and suits are numbered in declaration order
+// and card ids are 0 to the deck size
+static ID_TO_BID: Lazy<Vec<Bid>> = Lazy::new(|| all::<Bid>().collect());
 
-static ID_TO_CARD: Lazy<HashMap<i32, Card>> = Lazy::new(|| {
-    let mut m = HashMap::new();
-    for card in HotdogGame::deck() {
-        m.insert(card.id as i32, card);
-    }
-    m
+static ID_TO_CARD: Lazy<Vec<Card>> = Lazy::new(|| {
+    let mut deck = HotdogGame::deck();
+    deck.sort_by_key(|card| card.id);
+    deck
 });
 
-static ID_TO_SUIT: Lazy<HashMap<i32, Suit>> = Lazy::new(|| {
-    let mut m = HashMap::new();
-    for suit in all::<Suit>() {
-        m.insert(suit as i32, suit);
-    }
-    m
-});
+static ID_TO_SUIT: Lazy<Vec<Suit>> = Lazy::new(|| all::<Suit>().collect());
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Sequence, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub enum State {
     #[default]
@@ -218,6 +221,8 @@ pub enum State {
     PartialOrd,
     Ord,
 )]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub enum Suit {
     #[default]
@@ -227,7 +232,33 @@ pub enum Suit {
     Yellow = 3,
 }
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+impl SuitStyle for Suit {
+    fn symbol(&self) -> &'static str {
+        match self {
+            Suit::Red => "R",
+            Suit::Green => "G",
+            Suit::Blue => "B",
+            Suit::Yellow => "Y",
+        }
+    }
+
+    fn color(&self) -> Option<Color> {
+        Some(match self {
+            Suit::Red => Color::Red,
+            Suit::Green => Color::Green,
+            Suit::Blue => Color::Blue,
+            Suit::Yellow => Color::Yellow,
+        })
+    }
+}
+
+fn print_card(card: &Card) -> String {
+    render::card(card.value, &card.suit)
+}
+
+#[derive(Debug, Clone, Sequence, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 enum Location {
     #[default]
@@ -246,6 +277,8 @@ enum Location {
 }
 
 #[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub struct Card {
     id: i32,
@@ -253,7 +286,9 @@ pub struct Card {
     value: i32,
 }
 
-#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Sequence, Default, Serialize, Deserialize, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub enum ChangeType {
     #[default]
@@ -278,6 +313,8 @@ pub enum ChangeType {
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub struct Change {
     #[serde(rename(serialize = "type", deserialize = "type"))]
@@ -298,6 +335,8 @@ pub struct Change {
 }
 
 #[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 struct BidOption {
     id: i32,
@@ -305,6 +344,8 @@ struct BidOption {
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub struct HotdogGame {
     // What current actions are allowed - see State
@@ -316,7 +357,8 @@ pub struct HotdogGame {
     // Cards each player has played in the current trick
     current_trick: [Option<Card>; 2],
     // Cards in each player's hand
-    pub hands: [Vec<Card>; 2],
+    #[cfg_attr(feature = "typescript", ts(as = "[Vec<Card>; 2]"))]
+    pub hands: [Hand<Card, 7>; 2],
     // 5 cards that are face up covering the straw bottom at the start of a hand
     pub straw_top: [[Option<Card>; 5]; 2],
     // 5 cards that are face down covered by the straw top at the start of a hand
@@ -366,10 +408,11 @@ impl HotdogGame {
 
     // Called at the start of a game and when a new hand is dealt
     pub fn deal(&mut self) {
+        let _timer = profiling::start("deal");
         self.picker = None;
         self.tricks_taken = [0, 0];
         self.high_wins = None;
-        self.hands = [vec![], vec![]];
+        self.hands = Default::default();
         self.state = State::Bid;
         self.current_player = self.dealer;
         self.current_trick = [None; 2];
@@ -386,8 +429,8 @@ impl HotdogGame {
         self.relish = 0;
         self.trump = None;
         // Clear bid summaries
-        self.add_bid_summary(0, "".to_string());
-        self.add_bid_summary(1, "".to_string());
+        self.add_bid_summary(0, String::new);
+        self.add_bid_summary(1, String::new);
         self.add_change(
             shuffle_index,
             Change {
@@ -553,13 +596,12 @@ impl HotdogGame {
             }
             State::NameTrump => {
                 for i in 0..=3 {
-                    moves_strings
-                        .insert(i, format!("Name Trump {:?}", ID_TO_SUIT.get(&i).unwrap()));
+                    moves_strings.insert(i, format!("Name Trump {:?}", ID_TO_SUIT[i as usize]));
                 }
             }
             State::Bid => {
                 for move_id in self.get_moves() {
-                    let bid: Bid = ID_TO_BID[&move_id];
+                    let bid: Bid = ID_TO_BID[move_id as usize];
                     moves_strings.insert(move_id, bid.description());
                 }
             }
@@ -570,7 +612,7 @@ impl HotdogGame {
             State::Play => {
                 let moves = self.get_moves();
                 for action in moves {
-                    moves_strings.insert(action, format!("{:?}", ID_TO_CARD[&action]));
+                    moves_strings.insert(action, format!("{:?}", ID_TO_CARD[action as usize]));
                 }
             }
         }
@@ -578,9 +620,17 @@ impl HotdogGame {
     }
 
     pub fn get_moves(self: &HotdogGame) -> Vec<i32> {
+        let mut moves = vec![];
+        self.get_moves_into(&mut moves);
+        moves
+    }
+
+    /// Replace the contents of `moves` with the legal moves
+    pub fn get_moves_into(self: &HotdogGame, moves: &mut Vec<i32>) {
+        moves.clear();
         match self.state {
-            State::NameRelish => (0..=9).collect(), // NO_RELISH is 0, 1-9 are card values
-            State::NameTrump => (0..=3).collect(),  // 0-3 correspond to ID_TO_SUIT
+            State::NameRelish => moves.extend(0..=9), // NO_RELISH is 0, 1-9 are card values
+            State::NameTrump => moves.extend(0..=3),  // 0-3 correspond to ID_TO_SUIT
             State::Bid => {
                 let other_player_bid = match self.bids[(self.current_player + 1) % 2] {
                     None => Bid::Pass, // Pass in next_bids maps to opening bids
@@ -601,10 +651,10 @@ impl HotdogGame {
                     }
                 }
 
-                bids
+                moves.extend(bids)
             }
-            State::WorksSelectFirstTrickType => (0..2).collect(), // 0 - Ketchup, 1 - Mustard
-            State::Play => self.playable_card_ids(),
+            State::WorksSelectFirstTrickType => moves.extend(0..2), // 0 - Ketchup, 1 - Mustard
+            State::Play => self.playable_card_ids_into(moves),
         }
     }
 
@@ -638,20 +688,27 @@ impl HotdogGame {
     }
 
     pub fn playable_card_ids(&self) -> Vec<i32> {
+        let mut moves = vec![];
+        self.playable_card_ids_into(&mut moves);
+        moves
+    }
+
+    fn playable_card_ids_into(&self, moves: &mut Vec<i32>) {
         // Must follow
         let playable_cards = &self.playable_cards()[self.current_player];
         if self.current_trick[self.lead_player].is_some() {
             let lead_suit = self.current_trick[self.lead_player].clone().unwrap().suit;
-            let moves: Vec<i32> = playable_cards
-                .iter()
-                .filter(|c| c.suit == lead_suit)
-                .map(|c| c.id)
-                .collect();
+            moves.extend(
+                playable_cards
+                    .iter()
+                    .filter(|c| c.suit == lead_suit)
+                    .map(|c| c.id),
+            );
             if !moves.is_empty() {
-                return moves;
+                return;
             }
         }
-        return playable_cards.iter().map(|c| c.id).collect();
+        moves.extend(playable_cards.iter().map(|c| c.id));
     }
 
     pub fn bid_phase_changes(&mut self) {
@@ -691,7 +748,7 @@ impl HotdogGame {
     fn apply_move_internal(&mut self, action: i32) {
         match self.state {
             State::NameTrump => {
-                let suit = ID_TO_SUIT[&action];
+                let suit = ID_TO_SUIT[action as usize];
                 let index = self.new_change();
                 self.add_change(
                     index,
@@ -710,9 +767,9 @@ impl HotdogGame {
             }
             State::Bid => {
                 let other_player_bid = self.bids[(self.current_player + 1) % 2];
-                let bid = ID_TO_BID[&action];
+                let bid = ID_TO_BID[action as usize];
                 self.bids[self.current_player] = Some(bid);
-                self.add_bid_summary(self.current_player, bid.description());
+                self.add_bid_summary(self.current_player, || bid.description());
                 if bid == Bid::TheWorksFootlong {
                     if !self.no_changes {
                         // println!(
@@ -731,8 +788,8 @@ impl HotdogGame {
                     // If both players pass, there is no Picker.
                     // The round is still played with The Works.
                     self.winning_bid = Bid::TheWorks;
-                    self.add_bid_summary(0, "The Works (no picker)".to_string());
-                    self.add_bid_summary(1, "The Works (no picker)".to_string());
+                    self.add_bid_summary(0, || "The Works (no picker)".to_string());
+                    self.add_bid_summary(1, || "The Works (no picker)".to_string());
                     // The dealer may select some Relish
                     self.current_player = self.dealer;
                     self.state = State::NameRelish;
@@ -786,12 +843,11 @@ impl HotdogGame {
                 }
                 self.relish = action;
                 if self.relish == 0 {
-                    self.add_bid_summary((self.current_player + 1) % 2, "No relish".to_string());
+                    self.add_bid_summary((self.current_player + 1) % 2, || "No relish".to_string());
                 } else {
-                    self.add_bid_summary(
-                        (self.current_player + 1) % 2,
-                        format!("Named {} as relish", &action),
-                    );
+                    self.add_bid_summary((self.current_player + 1) % 2, || {
+                        format!("Named {} as relish", &action)
+                    });
                 }
                 if self.winning_bid.ranking() == Ranking::Alternating {
                     self.state = State::WorksSelectFirstTrickType;
@@ -815,7 +871,7 @@ impl HotdogGame {
                 self.state = State::Play;
             }
             State::Play => {
-                let card = ID_TO_CARD[&action];
+                let card = ID_TO_CARD[action as usize];
                 let lead_suit = match self.current_trick[self.lead_player] {
                     Some(lead_card) => Some(lead_card.suit),
                     None => None,
@@ -1013,8 +1069,40 @@ impl HotdogGame {
         }
     }
 
+    // Tricks still to be won this hand (including the current trick)
+    fn tricks_left(&self) -> i32 {
+        TRICKS_PER_HAND - self.tricks_taken[0] - self.tricks_taken[1]
+    }
+
+    // Scores if the hand was scored now - while the bid can still go either
+    // way each player gets the points for making or setting it scaled by how
+    // many of the tricks needed they have taken
+    fn projected_scores(&self) -> [f64; 2] {
+        let mut projected = self.scores.map(|score| score as f64);
+        let picker = match self.picker {
+            Some(picker) if self.state == State::Play => picker,
+            _ => return projected,
+        };
+        let setter = (picker + 1) % 2;
+        let needed = self.winning_bid.required_tricks();
+        let tricks = self.tricks_taken[picker];
+        let setter_tricks = self.tricks_taken[setter];
+        let to_set = TRICKS_PER_HAND - needed + 1;
+        let success = self.winning_bid.points_for_picker_success(tricks) as f64;
+        let set = self.winning_bid.points_for_setter(setter_tricks) as f64;
+        if tricks >= needed {
+            projected[picker] += success;
+        } else if setter_tricks >= to_set {
+            projected[setter] += set;
+        } else {
+            projected[picker] += success * tricks as f64 / needed as f64;
+            projected[setter] += set * setter_tricks as f64 / to_set as f64;
+        }
+        projected
+    }
+
     pub fn apply_move(&mut self, action: i32) {
-        self.changes = vec![vec![]]; // card from player to table
+        reset_changes(&mut self.changes); // card from player to table
         if !self.get_moves().contains(&action) {
             // return the same game with no animations when an invalid move is made
             return;
@@ -1026,20 +1114,23 @@ impl HotdogGame {
 
     #[inline]
     fn new_change(&mut self) -> usize {
+        let _timer = profiling::start("changes");
         self.changes.push(vec![]);
         self.changes.len() - 1
     }
 
     #[inline]
     fn add_change(&mut self, index: usize, change: Change) {
+        let _timer = profiling::start("changes");
         if self.no_changes {
             return;
         }
         self.changes[index].push(change);
     }
 
+    /// `bid_summary` is only called when changes are kept
     #[inline]
-    fn add_bid_summary(&mut self, player: usize, bid_summary: String) {
+    fn add_bid_summary(&mut self, player: usize, bid_summary: impl FnOnce() -> String) {
         if self.no_changes {
             return;
         }
@@ -1048,7 +1139,7 @@ impl HotdogGame {
             Change {
                 change_type: ChangeType::BidSummary,
                 player,
-                bid_summary: Some(bid_summary),
+                bid_summary: Some(bid_summary()),
                 ..Default::default()
             },
         );
@@ -1090,6 +1181,7 @@ impl HotdogGame {
     }
 
     fn show_playable(&mut self) {
+        let _timer = profiling::start("changes");
         if self.changes.is_empty() {
             self.changes = vec![vec![]];
         }
@@ -1114,11 +1206,12 @@ impl HotdogGame {
     }
 
     fn hide_playable(&mut self) {
+        let _timer = profiling::start("changes");
         if self.changes.is_empty() {
             self.changes = vec![vec![]];
         }
         let change_index = self.changes.len() - 1;
-        let mut cards = self.hands[0].clone();
+        let mut cards = self.hands[0].to_vec();
         cards.extend(self.exposed_straw_bottoms(0));
         cards.extend(self.straw_top[0].iter().flatten());
         for card in cards {
@@ -1168,6 +1261,7 @@ impl ismcts::Game for HotdogGame {
     type MoveList = Vec<i32>;
 
     fn randomize_determination(&mut self, _observer: Self::PlayerTag) {
+        let _timer = profiling::start("determinization");
         let rng = &mut thread_rng();
         let mut remaining_cards: Vec<Card> = self.cards.clone();
         let mut hidden_straw_bottoms: [HashSet<Card>; 2] = [HashSet::new(), HashSet::new()];
@@ -1192,7 +1286,7 @@ impl ismcts::Game for HotdogGame {
             let original_hand_length: usize = self.hands[player].len();
             if player != self.current_player {
                 let mut pc = extract_short_suited_cards(&remaining_cards, &self.voids[player]);
-                self.hands[player] = vec![];
+                self.hands[player].clear();
                 pc.cards.shuffle(rng);
                 for _ in 0..original_hand_length {
                     let card = pc.cards.pop().unwrap();
@@ -1225,10 +1319,12 @@ impl ismcts::Game for HotdogGame {
     }
 
     fn available_moves(&self) -> Self::MoveList {
+        let _timer = profiling::start("get_moves");
         self.get_moves()
     }
 
     fn make_move(&mut self, mov: &Self::Move) {
+        let _timer = profiling::start_in("apply_move", &self.state);
         self.apply_move(*mov);
     }
 
@@ -1281,23 +1377,219 @@ pub fn extract_short_suited_cards(remaining_cards: &Vec<Card>, voids: &Vec<Suit>
     };
 }
 
+impl FeatureExtractor for HotdogGame {
+    // hand, visible straw, trick, trump, relish, high wins, winning bid,
+    // bids, picker, tricks taken, scores, state, voids
+    const FEATURE_COUNT: usize =
+        36 + 2 * 36 + 2 * 36 + 4 + 10 + 2 + 7 + 2 * 8 + 2 + 2 + 2 + 5 + 2 * 4;
+    // bids, trump, relish, first trick type and cards
+    const ACTION_COUNT: usize = 7 + 4 + 10 + 2 + 36;
+
+    fn features(&self, observer: usize) -> Vec<f32> {
+        let mut features = FeatureBuilder::with_capacity(Self::FEATURE_COUNT);
+        features.cards(self.hands[observer].iter().map(|c| c.id), 36);
+        for offset in 0..2 {
+            let player = relative_seat(observer, offset, 2);
+            features.cards(self.visible_straw(player).iter().map(|c| c.id), 36);
+        }
+        for offset in 0..2 {
+            let player = relative_seat(observer, offset, 2);
+            features.cards(self.current_trick[player].iter().map(|c| c.id), 36);
+        }
+        features
+            .one_hot(self.trump.map(|s| s as usize), 4)
+            .one_hot(Some(self.relish as usize), 10)
+            .one_hot(self.high_wins.map(|high| high as usize), 2)
+            .one_hot(Some(self.winning_bid as usize), 7);
+        for offset in 0..2 {
+            let player = relative_seat(observer, offset, 2);
+            features.one_hot(Some(self.bids[player].map_or(0, |b| b as usize + 1)), 8);
+        }
+        features.one_hot(self.picker.map(|p| seat_offset(observer, p, 2)), 2);
+        for offset in 0..2 {
+            let player = relative_seat(observer, offset, 2);
+            features
+                .scalar(self.tricks_taken[player] as f32 / 9.0)
+                .scalar(self.scores[player] as f32 / 5.0);
+        }
+        features.one_hot(Some(self.state.clone() as usize), 5);
+        for offset in 0..2 {
+            let player = relative_seat(observer, offset, 2);
+            for suit in all::<Suit>() {
+                features.scalar(if self.voids[player].contains(&suit) {
+                    1.0
+                } else {
+                    0.0
+                });
+            }
+        }
+        features.finish(Self::FEATURE_COUNT)
+    }
+
+    fn action_index(&self, mov: i32) -> usize {
+        let mov = mov as usize;
+        match self.state {
+            State::Bid => mov,
+            State::NameTrump => 7 + mov,
+            State::NameRelish => 11 + mov,
+            State::WorksSelectFirstTrickType => 21 + mov,
+            State::Play => 23 + mov,
+        }
+    }
+
+    fn current_seat(&self) -> usize {
+        self.current_player
+    }
+}
+
+impl GameEngine for HotdogGame {
+    const NAME: &'static str = "hotdog";
+    const PLAYER_COUNT: usize = 2;
+
+    fn new_game() -> Self {
+        HotdogGame::new()
+    }
+
+    fn scores(&self) -> Vec<i32> {
+        self.scores.to_vec()
+    }
+
+    fn winners(&self) -> Vec<usize> {
+        self.winner.into_iter().collect()
+    }
+
+    fn legal_moves_into(&self, moves: &mut Vec<i32>) {
+        self.get_moves_into(moves);
+    }
+
+    fn bot_move_with_leaf_evaluation(
+        &self,
+        iterations: i32,
+        policy: Option<PolicyHandle<Self>>,
+        leaf_evaluation: Option<LeafEvaluation<Self>>,
+    ) -> i32 {
+        get_mcts_move_with_leaf_evaluation(self, iterations, false, policy, leaf_evaluation)
+    }
+
+    fn heuristic_value(&self, seat: usize) -> f64 {
+        let projected = self.projected_scores();
+        let tricks_left = self.tricks_left() as f64;
+        projected_margin(projected[seat], projected[1 - seat], tricks_left)
+    }
+
+    fn describe_move(&self, action: i32) -> String {
+        let described = match self.state {
+            State::Play => usize::try_from(action)
+                .ok()
+                .and_then(|id| ID_TO_CARD.get(id))
+                .map(print_card),
+            _ => self.moves_to_string().remove(&action),
+        };
+        described.unwrap_or_else(|| action.to_string())
+    }
+
+    fn render(&self, seat: usize) -> String {
+        let rows: Vec<Vec<String>> = (0..2)
+            .map(|player| {
+                // face down straw cards are only counted
+                let face_down = (0..5)
+                    .filter(|i| self.straw_bottom[player][*i].is_some())
+                    .filter(|i| self.straw_top[player][*i].is_some())
+                    .count();
+                let straw = render::cards(self.visible_straw(player).iter().map(print_card));
+                vec![
+                    render::seat_label(player, seat),
+                    self.scores[player].to_string(),
+                    self.tricks_taken[player].to_string(),
+                    self.bids[player].map_or("-".to_string(), |bid| bid.description()),
+                    format!("{} (+{} face down)", straw, face_down),
+                ]
+            })
+            .collect();
+        let header = ["player", "score", "tricks", "bid", "straw"];
+        let mut lines = vec![render::table(&header, &rows)];
+        if let Some(picker) = self.picker {
+            lines.push(format!("picker: {} {}", picker, self.winning_bid.description()));
+        }
+        let relish = match self.relish {
+            NO_RELISH => "none".to_string(),
+            relish => relish.to_string(),
+        };
+        lines.push(format!(
+            "trump: {} relish: {}",
+            render::suit(self.trump.as_ref()),
+            relish
+        ));
+        let played: Vec<Option<String>> =
+            self.current_trick.iter().map(|card| card.as_ref().map(print_card)).collect();
+        lines.push(format!("trick: {}", render::trick(&played, Some(self.lead_player))));
+        lines.push(format!("hand: {}", render::cards(self.hands[seat].iter().map(print_card))));
+        lines.join("\n")
+    }
+
+    fn events(&self, after: &Self) -> Vec<GameEvent> {
+        let mut events: Vec<GameEvent> = after
+            .changes
+            .iter()
+            .flatten()
+            .find(|change| change.change_type == ChangeType::TricksToWinner)
+            .map(|change| GameEvent::TrickWon {
+                leader: self.lead_player,
+                winner: change.player,
+            })
+            .into_iter()
+            .collect();
+        let score = after
+            .changes
+            .iter()
+            .flatten()
+            .find(|change| change.change_type == ChangeType::Score);
+        if let Some(score) = score {
+            events.push(GameEvent::HandOver);
+            if let Some(picker) = self.picker {
+                // the setter scores when the picker misses
+                events.push(GameEvent::Bid {
+                    seat: picker,
+                    bid: format!("{:?}", self.winning_bid).to_lowercase(),
+                    made: score.player == picker,
+                });
+            }
+        }
+        events
+    }
+}
+
 pub fn get_mcts_move(game: &HotdogGame, iterations: i32, debug: bool) -> i32 {
+    get_mcts_move_with_policy(game, iterations, debug, None)
+}
+
+pub fn get_mcts_move_with_policy(
+    game: &HotdogGame,
+    iterations: i32,
+    debug: bool,
+    policy: Option<PolicyHandle<HotdogGame>>,
+) -> i32 {
+    get_mcts_move_with_leaf_evaluation(game, iterations, debug, policy, None)
+}
+
+pub fn get_mcts_move_with_leaf_evaluation(
+    game: &HotdogGame,
+    iterations: i32,
+    debug: bool,
+    policy: Option<PolicyHandle<HotdogGame>>,
+    leaf_evaluation: Option<LeafEvaluation<HotdogGame>>,
+) -> i32 {
     let mut new_game = game.clone();
     new_game.no_changes = true;
     // reset scores for the simulation
     new_game.scores = [0; 2];
-    let mut ismcts = IsmctsHandler::new(new_game);
-    let parallel_threads: usize = 8;
-    ismcts.run_iterations(
-        parallel_threads,
-        (iterations as f64 / parallel_threads as f64) as usize,
-    );
     if debug {
         // println!("-------");
         // ismcts.debug_children();
         // println!("-------");
     }
-    ismcts.best_move().expect("should have a move to make")
+    let parallel_threads: usize = 8;
+    search_with_leaf_evaluation(new_game, iterations, parallel_threads, policy, leaf_evaluation)
 }
 
 fn card_sorter(a: &Card, b: &Card) -> Ordering {
@@ -1308,10 +1600,80 @@ fn card_sorter(a: &Card, b: &Card) -> Ordering {
     }
 }
 
+/// Dart enums for the change protocol - see `cargo run --bin dart`
+pub fn dart_enums() -> String {
+    use crate::dart::dart_enum;
+    [
+        dart_enum::<ChangeType>("ChangeType"),
+        dart_enum::<Location>("Location"),
+        dart_enum::<State>("State"),
+    ]
+    .join("\n")
+}
+
+/// TypeScript declarations of every serialized type in this game
+#[cfg(feature = "typescript")]
+pub fn typescript_declarations() -> String {
+    use ts_rs::TS;
+    [
+        Bid::decl(),
+        Ranking::decl(),
+        State::decl(),
+        Suit::decl(),
+        Location::decl(),
+        Card::decl(),
+        ChangeType::decl(),
+        Change::decl(),
+        BidOption::decl(),
+        HotdogGame::decl(),
+    ]
+    .iter()
+    .map(|decl| format!("export {}\n", decl))
+    .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_features() {
+        crate::features::assert_features_consistent(HotdogGame::new(), 2);
+    }
+
+    #[test]
+    fn test_no_bid_summaries_while_simulating() {
+        let mut game = HotdogGame::new();
+        game.no_changes = true;
+        while !game.is_over() {
+            let action = *game.legal_moves().choose(&mut thread_rng()).unwrap();
+            game.play(action);
+            assert!(!game
+                .changes
+                .iter()
+                .flatten()
+                .any(|change| change.change_type == ChangeType::BidSummary));
+        }
+    }
+
+    #[test]
+    fn test_heuristic_value() {
+        let mut game = HotdogGame::new();
+        game.picker = Some(0);
+        game.winning_bid = Bid::Ketchup;
+        game.state = State::Play;
+        let value = |tricks_taken: [i32; 2]| {
+            let mut game = game.clone();
+            game.tricks_taken = tricks_taken;
+            game.heuristic_value(0)
+        };
+        // scores are zeroed during search so only the hand can tell these apart
+        assert!(value([5, 0]) > value([4, 0]));
+        assert!(value([4, 0]) > value([4, 1]));
+        assert!(value([9, 0]) > value([8, 0]));
+        assert_eq!(game.heuristic_value(1), -game.heuristic_value(0));
+    }
+
     #[test]
     fn test_deck() {
         let d = HotdogGame::deck();
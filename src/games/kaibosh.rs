@@ -4,23 +4,33 @@ A Euchre variant where players bid to name trump
 See rules/kaibosh.md for game rules
 */
 
-use ismcts::IsmctsHandler;
+use colored::Color;
+use enum_iterator::Sequence;
 use rand::{seq::SliceRandom, thread_rng};
 use serde::{Deserialize, Serialize};
-use std::{
-    cmp::min,
-    collections::{HashMap, HashSet},
-};
+use std::{cmp::min, collections::HashSet};
 
-use crate::utils::shuffle_and_divide_matching_cards;
+use crate::engine::{projected_margin, GameEngine};
+use crate::features::{relative_seat, seat_offset, FeatureBuilder, FeatureExtractor};
+use crate::policy::{search_with_leaf_evaluation, LeafEvaluation, PolicyHandle};
+use crate::profiling;
+use crate::utils::render::{self, SuitStyle};
+use crate::utils::{pair_mut, reset_changes, shuffle_matching_cards_between, Hand};
 
 const KAIBOSH: i32 = 12;
 const JACK: i32 = 11;
 const MISDEAL: i32 = 100; // high so it can be "bid" anytime
+const ALONE: i32 = 4; // added to a trump suit to name it and go alone
+const PASS: i32 = -1;
+
+// Suits in the order they are named as trump (see name_trump)
+const TRUMP_SUITS: [Suit; 4] = [Suit::Clubs, Suit::Diamonds, Suit::Hearts, Suit::Spades];
 
 // Define the card, player, and game state structures based on Kaibosh rules
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 pub enum Suit {
     Hearts,
     Diamonds,
@@ -28,16 +38,120 @@ pub enum Suit {
     Spades,
 }
 
+impl SuitStyle for Suit {
+    fn symbol(&self) -> &'static str {
+        match self {
+            Suit::Hearts => "♥",
+            Suit::Diamonds => "♦",
+            Suit::Clubs => "♣",
+            Suit::Spades => "♠",
+        }
+    }
+
+    fn color(&self) -> Option<Color> {
+        match self {
+            Suit::Hearts | Suit::Diamonds => Some(Color::Red),
+            Suit::Clubs | Suit::Spades => None,
+        }
+    }
+}
+
+fn print_card(card: &Card) -> String {
+    let value = match card.value {
+        JACK => "J".to_string(),
+        12 => "Q".to_string(),
+        13 => "K".to_string(),
+        14 => "A".to_string(),
+        value => value.to_string(),
+    };
+    render::card(value, &card.suit)
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 pub struct Card {
     pub value: i32,
     pub suit: Suit,
     pub id: i32,
 }
 
+#[derive(Debug, Clone, Copy, Sequence, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+enum Location {
+    #[default]
+    Deck,
+    Hand,
+    Play,
+    TricksTaken,
+    Score,
+    ReorderHand,
+    // Bid display next to each player
+    Bid,
+    // Trump display
+    Trump,
+}
+
+#[derive(Debug, Clone, Copy, Sequence, Default, Serialize, Deserialize, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub enum ChangeType {
+    #[default]
+    Deal,
+    Play,
+    TricksToWinner,
+    Trump, // display the named trump suit (none clears it)
+    Shuffle,
+    Score,
+    ShowPlayable,
+    HidePlayable,
+    OptionalPause,
+    ShowWinningCard,
+    GameOver,
+    Reorder,
+    BidOptions, // system sends bid options to be displayed in a dialog
+    BidSummary, // display the bid each player made
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct Change {
+    #[serde(rename(serialize = "type", deserialize = "type"))]
+    pub change_type: ChangeType,
+    object_id: i32,
+    dest: Location,
+    tricks_taken: i32,
+    start_score: i32,
+    end_score: i32,
+    offset: usize,
+    player: usize,
+    length: usize,
+    bid_options: Option<Vec<BidOption>>,
+    bid_title: Option<String>,
+    bid_summary: Option<String>,
+    trump: Option<Suit>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+struct BidOption {
+    id: i32,
+    description: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 pub struct KaiboshGame {
-    pub hands: [Vec<Card>; 4],
+    #[cfg_attr(feature = "typescript", ts(as = "[Vec<Card>; 4]"))]
+    pub hands: [Hand<Card, 6>; 4],
     pub bidder: Option<usize>, // player that bid
     pub high_bid: Option<i32>, // bid made by the bidder
     pub dealer: usize,
@@ -48,13 +162,33 @@ pub struct KaiboshGame {
     pub lead_card: Option<Card>,
     pub state: GameState,
     pub bids: [Option<i32>; 4],
+    // bidder's partner sits out (kaibosh bids always go alone)
+    #[serde(default)]
+    pub alone: bool,
     pub voids: [HashSet<Suit>; 4], // voids revealed during play (used for hidden information state determization)
     pub scores: [i32; 2],          // team scores
     pub scores_this_hand: [i32; 2], // team scores for current hand (used during search)
     pub score_threshold: i32,
+    // When everyone else passes the dealer has to bid instead of the hand
+    // being thrown in
+    #[serde(default)]
+    pub stick_the_dealer: bool,
+    // List of list of animations to run after a move is made to get from the
+    // current state to the next state
+    #[serde(default)]
+    pub changes: Vec<Vec<Change>>,
+    // When running simulations we save time by not creating the changes
+    #[serde(default)]
+    pub no_changes: bool,
+    // Which player is the human player - they get the bid options, playable
+    // cards and a sorted hand
+    #[serde(default)]
+    pub human_player: Option<usize>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Copy, Sequence, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 pub enum GameState {
     #[default]
     Bidding,
@@ -62,17 +196,41 @@ pub enum GameState {
     Play,
 }
 
+/// How to set up a new game - see `KaiboshGame::new_with_options`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GameOptions {
+    pub human_player: Option<usize>,
+    pub score_threshold: i32,
+    pub stick_the_dealer: bool,
+}
+
+impl Default for GameOptions {
+    fn default() -> Self {
+        GameOptions {
+            human_player: Some(0),
+            score_threshold: 25,
+            stick_the_dealer: false,
+        }
+    }
+}
+
 impl KaiboshGame {
     pub fn new() -> Self {
+        Self::new_with_options(GameOptions::default())
+    }
+
+    pub fn new_with_options(options: GameOptions) -> Self {
         let mut game = Self {
+            human_player: options.human_player,
+            stick_the_dealer: options.stick_the_dealer,
             ..Default::default()
         };
         game.new_hand();
         // always let human bid first
         game.current_player = 0;
         game.dealer = 3;
-        // TODO: make this configurable for humans playing the game
-        game.score_threshold = 25;
+        game.score_threshold = options.score_threshold;
         return game;
     }
 
@@ -85,6 +243,9 @@ impl KaiboshGame {
         self.dealer = (self.dealer + 1) % 4;
         // deal out cards
         self.hands = Self::deal();
+        if let Some(human) = self.human_player {
+            self.hands[human].sort_by_key(|card| (card.suit as i32, card.value));
+        }
         // player to the left of the dealer leads
         self.current_player = (self.dealer + 1) % 4;
         // reset trump
@@ -95,6 +256,7 @@ impl KaiboshGame {
         self.state = GameState::Bidding;
         // reset bids
         self.bids = [None; 4];
+        self.alone = false;
         // clean up trick
         self.current_trick = [None; 4];
         // no longer know which voids a player has revealed
@@ -104,14 +266,73 @@ impl KaiboshGame {
             HashSet::new(),
             HashSet::new(),
         ];
-        self.scores = [0, 0];
+        self.tricks_taken = [0, 0];
         self.scores_this_hand = [0, 0];
+        self.deal_changes();
     }
 
-    fn deal() -> [Vec<Card>; 4] {
+    fn deal_changes(&mut self) {
+        if self.no_changes {
+            return;
+        }
+        let shuffle_index = self.new_change();
+        self.add_change(
+            shuffle_index,
+            Change {
+                change_type: ChangeType::Shuffle,
+                object_id: 0,
+                dest: Location::Deck,
+                ..Default::default()
+            },
+        );
+        // clear the previous hand's bids and trump
+        self.add_change(
+            shuffle_index,
+            Change {
+                change_type: ChangeType::Trump,
+                object_id: -1,
+                dest: Location::Trump,
+                ..Default::default()
+            },
+        );
+        for player in 0..4 {
+            self.add_change(
+                shuffle_index,
+                Change {
+                    change_type: ChangeType::BidSummary,
+                    object_id: -1,
+                    dest: Location::Bid,
+                    player,
+                    bid_summary: Some("".to_string()),
+                    ..Default::default()
+                },
+            );
+        }
+        let deal_index = self.new_change();
+        for offset in 0..6 {
+            for player in 0..4 {
+                let card = self.hands[player][offset];
+                self.add_change(
+                    deal_index,
+                    Change {
+                        change_type: ChangeType::Deal,
+                        object_id: card.id,
+                        dest: Location::Hand,
+                        player,
+                        offset,
+                        length: 6,
+                        ..Default::default()
+                    },
+                );
+            }
+        }
+    }
+
+    fn deal() -> [Hand<Card, 6>; 4] {
+        let _timer = profiling::start("deal");
         let mut deck = Self::create_deck();
         let mut rng = rand::thread_rng();
-        let mut hands: [Vec<Card>; 4] = [vec![], vec![], vec![], vec![]];
+        let mut hands: [Hand<Card, 6>; 4] = Default::default();
         deck.shuffle(&mut rng);
 
         for _ in 0..6 {
@@ -126,21 +347,47 @@ impl KaiboshGame {
     }
 
     pub fn name_trump(&mut self, trump: i32) {
-        let suit = match trump {
-            0 => Suit::Clubs,
-            1 => Suit::Diamonds,
-            2 => Suit::Hearts,
-            3 => Suit::Spades,
-            _ => panic!("Invalid trump suit"),
-        };
+        let alone = trump >= ALONE;
+        let suit_index = if alone { trump - ALONE } else { trump };
+        let suit = *TRUMP_SUITS
+            .get(suit_index as usize)
+            .expect("Invalid trump suit");
         self.trump = Some(suit);
+        self.alone = alone;
+        let index = self.new_change();
+        self.add_change(
+            index,
+            Change {
+                change_type: ChangeType::Trump,
+                object_id: -1,
+                dest: Location::Trump,
+                player: self.current_player,
+                trump: Some(suit),
+                ..Default::default()
+            },
+        );
+        if alone {
+            let bid = self.high_bid.unwrap();
+            self.add_bid_summary(self.current_player, Some(bid));
+        }
         if self.high_bid != Some(KAIBOSH) {
             // current player stays when kaibosh is bid
             self.current_player = (self.dealer + 1) % 4;
+            if self.sitting_out() == Some(self.current_player) {
+                self.current_player = (self.current_player + 1) % 4;
+            }
         }
         self.state = GameState::Play;
     }
 
+    /// Partner of a bidder who is playing alone
+    pub fn sitting_out(&self) -> Option<usize> {
+        if !(self.alone || self.high_bid == Some(KAIBOSH)) {
+            return None;
+        }
+        self.bidder.map(|bidder| (bidder + 2) % 4)
+    }
+
     pub fn play_card(&mut self, id: i32) {
         let card = *self.hands[self.current_player]
             .iter()
@@ -150,7 +397,7 @@ impl KaiboshGame {
         if self.lead_card.is_none() {
             self.lead_card = Some(card);
         }
-        self.hands[self.current_player].retain(|c: &Card| *c != card);
+        self.hands[self.current_player].retain(|c| *c != card);
         if self.lead_card.is_some() && card.suit != self.lead_card.unwrap().suit {
             // if the player didn't follow suit then they have revealed a void
             // which is used when determining which cards a player might have
@@ -158,21 +405,28 @@ impl KaiboshGame {
             self.voids[self.current_player].insert(self.lead_card.unwrap().suit);
         }
         self.current_trick[self.current_player] = Some(card);
+        let index = self.change_index();
+        self.add_change(
+            index,
+            Change {
+                change_type: ChangeType::Play,
+                object_id: card.id,
+                dest: Location::Play,
+                player: self.current_player,
+                ..Default::default()
+            },
+        );
+        self.reorder_hand(self.current_player);
         self.current_player = (self.current_player + 1) % 4;
-        if self.high_bid == Some(KAIBOSH) && self.current_player == (self.bidder.unwrap() + 2) % 4 {
+        if self.sitting_out() == Some(self.current_player) {
             // skip partner during loners
             self.current_player = (self.current_player + 1) % 4;
         }
-        // TODO - animate trick to table
         self.check_trick_and_hand_end()
     }
 
     fn check_trick_and_hand_end(&mut self) {
-        let card_count = if self.bids.contains(&Some(KAIBOSH)) {
-            3
-        } else {
-            4
-        };
+        let card_count = if self.sitting_out().is_some() { 3 } else { 4 };
         if self
             .current_trick
             .iter()
@@ -189,22 +443,79 @@ impl KaiboshGame {
             );
             let winning_card =
                 self.current_trick[trick_winner].expect("there has to be a trick_winner card");
+            let trick = self.current_trick;
             self.current_trick = [None; 4];
             self.lead_card = None;
             self.tricks_taken[trick_winner % 2] += 1;
-            // TODO: animate trick to winner
+            let index = self.new_change();
+            self.add_change(
+                index,
+                Change {
+                    change_type: ChangeType::ShowWinningCard,
+                    object_id: winning_card.id,
+                    dest: Location::Play,
+                    ..Default::default()
+                },
+            );
+            self.add_change(
+                index,
+                Change {
+                    change_type: ChangeType::OptionalPause,
+                    object_id: 0,
+                    dest: Location::Play,
+                    ..Default::default()
+                },
+            );
+            let index = self.new_change();
+            for card in trick.iter().flatten() {
+                self.add_change(
+                    index,
+                    Change {
+                        change_type: ChangeType::TricksToWinner,
+                        object_id: card.id,
+                        dest: Location::TricksTaken,
+                        player: trick_winner,
+                        tricks_taken: self.tricks_taken[trick_winner % 2],
+                        ..Default::default()
+                    },
+                );
+            }
             // winner of the trick leads
             self.current_player = trick_winner;
             // check if hand is over
             if self.hands.iter().filter(|hand| hand.is_empty()).count() >= 3 {
+                let start_scores = self.scores;
                 self.calculate_scores();
+                let index = self.new_change();
+                for player in 0..4 {
+                    self.add_change(
+                        index,
+                        Change {
+                            change_type: ChangeType::Score,
+                            object_id: player as i32,
+                            dest: Location::Score,
+                            player,
+                            start_score: start_scores[player % 2],
+                            end_score: self.scores[player % 2],
+                            ..Default::default()
+                        },
+                    );
+                }
                 // check for end of game
                 if self.game_over() {
-                    // Animate game end - declare winner
+                    let index = self.new_change();
+                    self.add_change(
+                        index,
+                        Change {
+                            change_type: ChangeType::GameOver,
+                            object_id: 0,
+                            dest: Location::Deck,
+                            ..Default::default()
+                        },
+                    );
                     return;
                 }
                 // Prepare for a new hand if the game continues
-                // TODO: animate shuffle
                 self.new_hand();
             }
         }
@@ -234,6 +545,7 @@ impl KaiboshGame {
         }
 
         self.bids[self.current_player] = bid;
+        self.add_bid_summary(self.current_player, bid);
         if bid == Some(KAIBOSH) {
             self.high_bid = Some(KAIBOSH);
             self.bidder = Some(self.current_player);
@@ -244,15 +556,20 @@ impl KaiboshGame {
 
         if bid == Some(MISDEAL) {
             // redeal - dealer moves to the next player - no score
-            // TODO: animation or dialog informing of the misdeal
             self.dealer = (self.dealer + 1) % 4;
             self.new_hand();
             return;
         }
 
+        if self.current_player == self.dealer && self.bids.iter().all(|bid| bid.is_none()) {
+            // everyone passed - the hand is thrown in and the deal moves on
+            self.new_hand();
+            return;
+        }
+
         self.current_player = (self.current_player + 1) % 4;
-        if self.bids[self.current_player].is_some() {
-            // everyone bid
+        if self.current_player == (self.dealer + 1) % 4 {
+            // everyone bid (the dealer bids last)
             let bidder = self
                 .bids
                 .iter()
@@ -275,46 +592,234 @@ impl KaiboshGame {
         }
         bids.push(KAIBOSH);
         bids.retain(|bid| *bid > max_bid);
+        let stuck = self.stick_the_dealer && self.current_player == self.dealer && max_bid == 0;
+        if !stuck {
+            bids.insert(0, PASS);
+        }
         bids
     }
 
     fn play_options(&self) -> Vec<i32> {
-        let actions: Vec<i32>;
+        let mut actions = vec![];
+        self.play_options_into(&mut actions);
+        actions
+    }
+
+    fn play_options_into(&self, actions: &mut Vec<i32>) {
+        let hand = &self.hands[self.current_player as usize];
         if let Some(lead_card) = self.lead_card {
-            actions = self.hands[self.current_player as usize]
-                .iter()
-                .filter(|c| c.suit == lead_card.suit)
-                .map(|c| c.id)
-                .collect();
+            actions.extend(
+                hand.iter()
+                    .filter(|c| c.suit == lead_card.suit)
+                    .map(|c| c.id),
+            );
             if !actions.is_empty() {
-                return actions;
+                return;
             }
         }
-
-        let actions: Vec<i32> = self.hands[self.current_player as usize]
-            .iter()
-            .map(|c| c.id)
-            .collect();
-        actions
+        actions.extend(hand.iter().map(|c| c.id));
     }
 
     pub fn get_moves(&self) -> Vec<i32> {
+        let mut moves = vec![];
+        self.get_moves_into(&mut moves);
+        moves
+    }
+
+    /// Replace the contents of `moves` with the legal moves
+    pub fn get_moves_into(&self, moves: &mut Vec<i32>) {
+        moves.clear();
         match self.state {
-            GameState::Bidding => self.bidding_options(),
-            GameState::NameTrump => (0..=3).collect::<Vec<i32>>(),
-            GameState::Play => self.play_options(),
+            GameState::Bidding => moves.extend(self.bidding_options()),
+            GameState::NameTrump if self.high_bid == Some(KAIBOSH) => moves.extend(0..=3),
+            GameState::NameTrump => moves.extend((0..=3).chain(ALONE..ALONE + 4)),
+            GameState::Play => self.play_options_into(moves),
         }
     }
 
     pub fn apply_move(&mut self, mov: Option<i32>) {
+        reset_changes(&mut self.changes);
         // reset only after a move is made in the next round
         // so the tree search can see the result
         self.scores_this_hand = [0, 0];
         match self.state {
+            GameState::Bidding if mov == Some(PASS) => self.bid(None),
             GameState::Bidding => self.bid(mov),
             GameState::NameTrump => self.name_trump(mov.unwrap()),
             GameState::Play => self.play_card(mov.unwrap()),
         }
+        if self.game_over() {
+            self.hide_playable();
+        } else {
+            self.show_playable();
+            self.bid_phase_changes();
+        }
+    }
+
+    #[inline]
+    fn new_change(&mut self) -> usize {
+        let _timer = profiling::start("changes");
+        self.changes.push(vec![]);
+        self.changes.len() - 1
+    }
+
+    // The change list the current move appends to
+    #[inline]
+    fn change_index(&mut self) -> usize {
+        if self.changes.is_empty() {
+            self.new_change()
+        } else {
+            self.changes.len() - 1
+        }
+    }
+
+    #[inline]
+    fn add_change(&mut self, index: usize, change: Change) {
+        let _timer = profiling::start("changes");
+        if self.no_changes {
+            return;
+        }
+        self.changes[index].push(change);
+    }
+
+    fn add_bid_summary(&mut self, player: usize, bid: Option<i32>) {
+        if self.no_changes {
+            return;
+        }
+        let bid_summary = match bid {
+            None => "pass".to_string(),
+            Some(MISDEAL) => "misdeal".to_string(),
+            Some(bid) if self.alone => format!("{} alone", bid_to_string(bid)),
+            Some(bid) => bid_to_string(bid),
+        };
+        let index = self.change_index();
+        self.add_change(
+            index,
+            Change {
+                change_type: ChangeType::BidSummary,
+                object_id: -1,
+                dest: Location::Bid,
+                player,
+                bid_summary: Some(bid_summary),
+                ..Default::default()
+            },
+        );
+    }
+
+    fn reorder_hand(&mut self, player: usize) {
+        if self.no_changes {
+            return;
+        }
+        let length = self.hands[player].len();
+        let index = self.change_index();
+        self.changes[index].extend(self.hands[player].iter().enumerate().map(|(offset, card)| {
+            Change {
+                change_type: ChangeType::Reorder,
+                dest: Location::ReorderHand,
+                object_id: card.id,
+                player,
+                offset,
+                length,
+                ..Default::default()
+            }
+        }));
+    }
+
+    fn is_human_turn(&self) -> bool {
+        self.human_player == Some(self.current_player)
+    }
+
+    fn show_playable(&mut self) {
+        let _timer = profiling::start("changes");
+        if self.no_changes {
+            return;
+        }
+        if !(self.is_human_turn() && self.state == GameState::Play) {
+            self.hide_playable();
+            return;
+        }
+        let index = self.new_change();
+        for id in self.get_moves() {
+            self.add_change(
+                index,
+                Change {
+                    object_id: id,
+                    change_type: ChangeType::ShowPlayable,
+                    dest: Location::Hand,
+                    player: self.current_player,
+                    ..Default::default()
+                },
+            );
+        }
+    }
+
+    fn hide_playable(&mut self) {
+        let _timer = profiling::start("changes");
+        let human = match self.human_player {
+            Some(human) => human,
+            None => return,
+        };
+        let index = self.change_index();
+        // -1 so there is a hide change once the hand is empty
+        let ids: Vec<i32> = self.hands[human].iter().map(|card| card.id).collect();
+        for id in ids.into_iter().chain([-1]) {
+            self.add_change(
+                index,
+                Change {
+                    object_id: id,
+                    change_type: ChangeType::HidePlayable,
+                    dest: Location::Hand,
+                    player: human,
+                    ..Default::default()
+                },
+            );
+        }
+    }
+
+    /// Let the human player pick a bid or trump suit from a dialog
+    fn bid_phase_changes(&mut self) {
+        if self.no_changes || !self.is_human_turn() {
+            return;
+        }
+        let bid_title = match self.state {
+            GameState::Bidding => "Select a bid",
+            GameState::NameTrump => "Select trump",
+            GameState::Play => return,
+        };
+        let bid_options = self
+            .get_moves()
+            .into_iter()
+            .map(|id| BidOption {
+                id,
+                description: self.move_description(id),
+            })
+            .collect();
+        let index = self.new_change();
+        self.add_change(
+            index,
+            Change {
+                change_type: ChangeType::BidOptions,
+                object_id: -1,
+                player: self.current_player,
+                bid_title: Some(bid_title.to_string()),
+                bid_options: Some(bid_options),
+                ..Default::default()
+            },
+        );
+    }
+
+    // Plain text description of a bidding move for the UI
+    fn move_description(&self, action: i32) -> String {
+        match self.state {
+            GameState::Bidding if action == MISDEAL => "misdeal".to_string(),
+            GameState::Bidding if action == PASS => "pass".to_string(),
+            GameState::Bidding => bid_to_string(action),
+            GameState::NameTrump if action >= ALONE => {
+                format!("{:?} alone", TRUMP_SUITS[(action - ALONE) as usize])
+            }
+            GameState::NameTrump => format!("{:?}", TRUMP_SUITS[action as usize]),
+            GameState::Play => action.to_string(),
+        }
     }
 
     pub fn made_it(&self, trick_count: i32, bid: i32) -> bool {
@@ -334,7 +839,10 @@ impl KaiboshGame {
                 -12
             }
         } else {
-            if made_it {
+            if made_it && self.alone {
+                // going alone doubles the reward for making the bid
+                trick_count * 2
+            } else if made_it {
                 trick_count
             } else {
                 -bid
@@ -366,6 +874,35 @@ impl KaiboshGame {
         }
     }
 
+    // Team scores if the hand was scored now - while the bid can still go
+    // either way each team gets the points for making or setting it scaled
+    // by how many of the tricks needed it has taken
+    fn projected_scores(&self) -> [f64; 2] {
+        let mut projected = self.scores.map(|score| score as f64);
+        let (bidder, bid) = match (self.bidder, self.high_bid) {
+            (Some(bidder), Some(bid)) if self.state == GameState::Play => (bidder, bid),
+            _ => return projected,
+        };
+        let bidding_team = bidder % 2;
+        let defending_team = 1 - bidding_team;
+        let tricks = self.tricks_taken[bidding_team];
+        let defender_tricks = self.tricks_taken[defending_team];
+        let target = if bid == KAIBOSH { 6 } else { bid };
+        let to_set = 7 - target;
+        if self.made_it(tricks, bid) {
+            projected[bidding_team] += self.points_for_bid(tricks, bid) as f64;
+        } else if defender_tricks >= to_set {
+            projected[bidding_team] += self.points_for_bid(tricks, bid) as f64;
+            projected[defending_team] += defender_tricks as f64;
+        } else {
+            let success = self.points_for_bid(target, bid) as f64;
+            projected[bidding_team] += success * tricks as f64 / target as f64;
+            let set = (defender_tricks * defender_tricks) as f64 / to_set as f64;
+            projected[defending_team] += set;
+        }
+        projected
+    }
+
     fn create_deck() -> Vec<Card> {
         let mut id: i32 = 0;
         let mut deck = Vec::new();
@@ -391,17 +928,13 @@ fn bid_to_string(bid: i32) -> String {
 }
 
 pub fn get_winner(lead_suit: Suit, trump_suit: Suit, trick: &[Option<Card>; 4]) -> usize {
-    let mut card_id_to_player: HashMap<i32, usize> = HashMap::new();
-    for (index, card_option) in trick.iter().enumerate() {
-        if let Some(card) = card_option {
-            card_id_to_player.insert(card.id, index);
-        }
-    }
-    let mut cards: Vec<Card> = trick.iter().filter_map(|&c| c).collect();
-    cards.sort_by_key(|c| std::cmp::Reverse(value_for_card(lead_suit, trump_suit, c)));
-    *card_id_to_player
-        .get(&cards.first().expect("there should be a winning card").id)
-        .expect("cards_to_player missing card")
+    let (player, _) = trick
+        .iter()
+        .enumerate()
+        .filter_map(|(player, card)| card.map(|card| (player, card)))
+        .min_by_key(|(_, c)| std::cmp::Reverse(value_for_card(lead_suit, trump_suit, c)))
+        .expect("there should be a winning card");
+    player
 }
 
 pub fn same_color(suita: Suit, suitb: Suit) -> bool {
@@ -435,32 +968,27 @@ impl ismcts::Game for KaiboshGame {
     type MoveList = Vec<i32>;
 
     fn randomize_determination(&mut self, _observer: Self::PlayerTag) {
+        let _timer = profiling::start("determinization");
         let rng = &mut thread_rng();
 
+        // the hand of a partner sitting out is never played but is still
+        // unknown so its cards are swapped like any other hidden hand
         for p1 in 0..4 {
             for p2 in 0..4 {
                 if p1 == self.current_player() || p2 == self.current_player() || p1 == p2 {
                     continue;
                 }
 
-                let mut combined_voids: HashSet<Suit> =
-                    HashSet::from_iter(self.voids[p1 as usize].iter().cloned());
-                combined_voids.extend(self.voids[p2 as usize].iter());
-
-                let mut new_hands = vec![
-                    self.hands[p1 as usize].clone(),
-                    self.hands[p2 as usize].clone(),
-                ];
+                let voids = [&self.voids[p1 as usize], &self.voids[p2 as usize]];
+                let (hand1, hand2) = pair_mut(&mut self.hands, p1 as usize, p2 as usize);
 
                 // allow swapping of any cards that are not in the combined void set
-                shuffle_and_divide_matching_cards(
-                    |c: &Card| !combined_voids.contains(&c.suit),
-                    &mut new_hands,
+                shuffle_matching_cards_between(
+                    |c: &Card| !voids.iter().any(|void| void.contains(&c.suit)),
+                    hand1,
+                    hand2,
                     rng,
                 );
-
-                self.hands[p1 as usize] = new_hands[0].clone();
-                self.hands[p2 as usize] = new_hands[1].clone();
             }
         }
     }
@@ -474,10 +1002,12 @@ impl ismcts::Game for KaiboshGame {
     }
 
     fn available_moves(&self) -> Self::MoveList {
+        let _timer = profiling::start("get_moves");
         self.get_moves()
     }
 
     fn make_move(&mut self, mov: &Self::Move) {
+        let _timer = profiling::start_in("apply_move", &self.state);
         self.apply_move(Some(*mov));
     }
 
@@ -501,16 +1031,239 @@ impl ismcts::Game for KaiboshGame {
     }
 }
 
+impl FeatureExtractor for KaiboshGame {
+    // hand, trick, bids, trump, state, dealer, bidder, tricks taken,
+    // scores, high bid, alone
+    const FEATURE_COUNT: usize = 24 + 4 * 24 + 4 * 2 + 4 + 3 + 4 + 4 + 2 + 2 + 1 + 1;
+    // bids 0-12 (bid 0 is never legal so its slot is used for passing) then
+    // misdeal, trump suits (with and without going alone) and cards
+    const ACTION_COUNT: usize = 14 + 8 + 24;
+
+    fn features(&self, observer: usize) -> Vec<f32> {
+        let mut features = FeatureBuilder::with_capacity(Self::FEATURE_COUNT);
+        features.cards(self.hands[observer].iter().map(|c| c.id), 24);
+        for offset in 0..4 {
+            let player = relative_seat(observer, offset, 4);
+            features.cards(self.current_trick[player].iter().map(|c| c.id), 24);
+        }
+        for offset in 0..4 {
+            let player = relative_seat(observer, offset, 4);
+            match self.bids[player] {
+                Some(bid) => features.scalar(1.0).scalar(bid.min(KAIBOSH) as f32 / 12.0),
+                None => features.scalar(0.0).scalar(0.0),
+            };
+        }
+        features
+            .one_hot(self.trump.map(|s| s as usize), 4)
+            .one_hot(Some(self.state as usize), 3)
+            .one_hot(Some(seat_offset(observer, self.dealer, 4)), 4)
+            .one_hot(self.bidder.map(|p| seat_offset(observer, p, 4)), 4)
+            .scalar(self.tricks_taken[observer % 2] as f32 / 6.0)
+            .scalar(self.tricks_taken[(observer + 1) % 2] as f32 / 6.0)
+            .scalar(self.scores[observer % 2] as f32 / 25.0)
+            .scalar(self.scores[(observer + 1) % 2] as f32 / 25.0)
+            .scalar(self.high_bid.unwrap_or(0).min(KAIBOSH) as f32 / 12.0)
+            .scalar(if self.alone { 1.0 } else { 0.0 });
+        features.finish(Self::FEATURE_COUNT)
+    }
+
+    fn action_index(&self, mov: i32) -> usize {
+        match self.state {
+            GameState::Bidding if mov == MISDEAL => 13,
+            GameState::Bidding if mov == PASS => 0,
+            GameState::Bidding => mov as usize,
+            GameState::NameTrump => 14 + mov as usize,
+            GameState::Play => 22 + mov as usize,
+        }
+    }
+
+    fn current_seat(&self) -> usize {
+        self.current_player
+    }
+}
+
+impl GameEngine for KaiboshGame {
+    const NAME: &'static str = "kaibosh";
+    const PLAYER_COUNT: usize = 4;
+
+    fn new_game() -> Self {
+        KaiboshGame::new()
+    }
+
+    fn scores(&self) -> Vec<i32> {
+        (0..4).map(|player| self.scores[player % 2]).collect()
+    }
+
+    fn winners(&self) -> Vec<usize> {
+        if !self.game_over() {
+            return vec![];
+        }
+        let team = if self.scores[0] >= self.scores[1] { 0 } else { 1 };
+        vec![team, team + 2]
+    }
+
+    fn legal_moves_into(&self, moves: &mut Vec<i32>) {
+        self.get_moves_into(moves);
+    }
+
+    fn bot_move_with_leaf_evaluation(
+        &self,
+        iterations: i32,
+        policy: Option<PolicyHandle<Self>>,
+        leaf_evaluation: Option<LeafEvaluation<Self>>,
+    ) -> i32 {
+        get_mcts_move_with_leaf_evaluation(self, iterations, policy, leaf_evaluation)
+    }
+
+    fn heuristic_value(&self, seat: usize) -> f64 {
+        let team = seat % 2;
+        let projected = self.projected_scores();
+        let remaining = 6 - self.tricks_taken[0] - self.tricks_taken[1];
+        projected_margin(projected[team], projected[1 - team], remaining as f64)
+    }
+
+    fn describe_move(&self, action: i32) -> String {
+        match self.state {
+            GameState::Bidding if action == KAIBOSH => "kaibosh".to_string(),
+            GameState::Bidding if action == MISDEAL => "misdeal".to_string(),
+            GameState::Bidding if action == PASS => "pass".to_string(),
+            GameState::Bidding => format!("bid {}", action),
+            GameState::NameTrump => {
+                let suit = TRUMP_SUITS
+                    .get((action % ALONE) as usize)
+                    .map(|suit| render::suit(Some(suit)));
+                let suit = suit.unwrap_or_else(|| action.to_string());
+                let alone = if action >= ALONE { " alone" } else { "" };
+                format!("trump {}{}", suit, alone)
+            }
+            GameState::Play => self.hands[self.current_player]
+                .iter()
+                .find(|card| card.id == action)
+                .map(print_card)
+                .unwrap_or_else(|| action.to_string()),
+        }
+    }
+
+    fn render(&self, seat: usize) -> String {
+        let rows: Vec<Vec<String>> = (0..2)
+            .map(|team| {
+                let mut label = format!("{} & {}", team, team + 2);
+                if seat % 2 == team {
+                    label.push_str(" (you)");
+                }
+                vec![
+                    label,
+                    self.scores[team].to_string(),
+                    self.tricks_taken[team].to_string(),
+                ]
+            })
+            .collect();
+        let mut lines = vec![render::table(&["team", "score", "tricks"], &rows)];
+        let bids: Vec<String> = self
+            .bids
+            .iter()
+            .enumerate()
+            .map(|(player, bid)| match bid {
+                Some(KAIBOSH) => format!("{}: kaibosh", player),
+                Some(bid) => format!("{}: {}", player, bid),
+                None => format!("{}: -", player),
+            })
+            .collect();
+        lines.push(format!("bids: {}", bids.join(" ")));
+        if let (Some(bidder), Some(high_bid)) = (self.bidder, self.high_bid) {
+            lines.push(format!(
+                "bidder: {} bid: {}{} trump: {}",
+                bidder,
+                high_bid,
+                if self.alone { " alone" } else { "" },
+                render::suit(self.trump.as_ref())
+            ));
+        }
+        let played: Vec<Option<String>> =
+            self.current_trick.iter().map(|card| card.as_ref().map(print_card)).collect();
+        let lead_player = self
+            .lead_card
+            .and_then(|lead| self.current_trick.iter().position(|card| *card == Some(lead)));
+        lines.push(format!("trick: {}", render::trick(&played, lead_player)));
+        lines.push(format!("hand: {}", render::cards(self.hands[seat].iter().map(print_card))));
+        lines.join("\n")
+    }
+}
+
 pub fn get_mcts_move(game: &KaiboshGame, iterations: i32) -> i32 {
+    get_mcts_move_with_policy(game, iterations, None)
+}
+
+pub fn get_mcts_move_with_policy(
+    game: &KaiboshGame,
+    iterations: i32,
+    policy: Option<PolicyHandle<KaiboshGame>>,
+) -> i32 {
+    get_mcts_move_with_leaf_evaluation(game, iterations, policy, None)
+}
+
+pub fn get_mcts_move_with_leaf_evaluation(
+    game: &KaiboshGame,
+    iterations: i32,
+    policy: Option<PolicyHandle<KaiboshGame>>,
+    leaf_evaluation: Option<LeafEvaluation<KaiboshGame>>,
+) -> i32 {
     let mut new_game = game.clone();
     new_game.score_threshold = -10000;
-    let mut ismcts = IsmctsHandler::new(new_game);
+    new_game.no_changes = true;
     let parallel_threads: usize = 1;
-    ismcts.run_iterations(
-        parallel_threads,
-        (iterations as f64 / parallel_threads as f64) as usize,
-    );
-    ismcts.best_move().expect("should have a move to make")
+    search_with_leaf_evaluation(new_game, iterations, parallel_threads, policy, leaf_evaluation)
+}
+
+/// The kaibosh linear policy and the diagnostics used to see why it bids
+/// the way it does - `cargo run -- inspect-policy <policy.json>` dumps them
+pub mod policy_model {
+    use std::io;
+    use std::path::Path;
+
+    use super::KaiboshGame;
+    use crate::quantized::{LinearPolicy, PolicyReport};
+
+    pub type KaiboshPolicy = LinearPolicy<KaiboshGame>;
+
+    pub fn load(path: &Path) -> io::Result<KaiboshPolicy> {
+        KaiboshPolicy::load(path)
+    }
+
+    /// Feature importances and the logit of every legal move in `game`
+    pub fn inspect(policy: &KaiboshPolicy, game: &KaiboshGame, top: usize) -> PolicyReport {
+        policy.report(game, &game.get_moves(), top)
+    }
+}
+
+/// Dart enums for the change protocol - see `cargo run --bin dart`
+pub fn dart_enums() -> String {
+    use crate::dart::dart_enum;
+    [
+        dart_enum::<ChangeType>("ChangeType"),
+        dart_enum::<Location>("Location"),
+        dart_enum::<GameState>("GameState"),
+    ]
+    .join("\n")
+}
+
+/// TypeScript declarations of every serialized type in this game
+#[cfg(feature = "typescript")]
+pub fn typescript_declarations() -> String {
+    use ts_rs::TS;
+    [
+        Suit::decl(),
+        Card::decl(),
+        ChangeType::decl(),
+        Location::decl(),
+        BidOption::decl(),
+        Change::decl(),
+        KaiboshGame::decl(),
+        GameState::decl(),
+    ]
+    .iter()
+    .map(|decl| format!("export {}\n", decl))
+    .collect()
 }
 
 // Tests for game logic
@@ -518,6 +1271,79 @@ pub fn get_mcts_move(game: &KaiboshGame, iterations: i32) -> i32 {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_features() {
+        crate::features::assert_features_consistent(KaiboshGame::new(), 4);
+    }
+
+    #[test]
+    fn test_inspect_policy() {
+        let game = KaiboshGame::new();
+        let report = policy_model::inspect(&policy_model::KaiboshPolicy::zeros(), &game, 5);
+        assert_eq!(report.feature_importances.len(), 5);
+        let moves: Vec<i32> = report.moves.iter().map(|i| i.mov).collect();
+        assert_eq!(moves, game.get_moves());
+    }
+
+    #[test]
+    fn test_heuristic_value() {
+        let mut game = KaiboshGame::new();
+        game.bids[0] = Some(3);
+        game.bidder = Some(0);
+        game.high_bid = Some(3);
+        game.trump = Some(Suit::Hearts);
+        game.state = GameState::Play;
+        let value = |tricks_taken: [i32; 2]| {
+            let mut game = game.clone();
+            game.tricks_taken = tricks_taken;
+            game.heuristic_value(0)
+        };
+        // scores are zeroed during search so only the hand can tell these apart
+        assert!(value([2, 0]) > value([1, 0]));
+        assert!(value([1, 0]) > value([1, 1]));
+        // making the bid beats being one trick short of it
+        assert!(value([3, 1]) > value([2, 1]));
+        // and being set is the worst
+        assert!(value([1, 4]) < value([1, 3]));
+        assert!(value([1, 4]) < 0.0);
+        assert_eq!(game.heuristic_value(1), -game.heuristic_value(0));
+    }
+
+    #[test]
+    fn test_changes() {
+        let count = |game: &KaiboshGame, change_type: ChangeType| {
+            let changes = game.changes.iter().flatten();
+            changes.filter(|c| c.change_type == change_type).count()
+        };
+        let mut game = KaiboshGame::new();
+        assert_eq!(count(&game, ChangeType::Deal), 24);
+        while !game.game_over() {
+            let player = game.current_player;
+            let state = game.state;
+            let action = *game.get_moves().choose(&mut thread_rng()).unwrap();
+            game.apply_move(Some(action));
+            match state {
+                GameState::Bidding => assert!(count(&game, ChangeType::BidSummary) > 0),
+                GameState::NameTrump => assert!(count(&game, ChangeType::Trump) > 0),
+                GameState::Play => assert!(game.changes.iter().flatten().any(|c| {
+                    c.change_type == ChangeType::Play && c.object_id == action && c.player == player
+                })),
+            }
+            // the human player is only shown options on their own turn
+            let human_turn = game.current_player == 0 && !game.game_over();
+            let bidding = game.state != GameState::Play;
+            let playable = count(&game, ChangeType::ShowPlayable) > 0;
+            assert!(!playable || (human_turn && !bidding));
+            let bid_options = count(&game, ChangeType::BidOptions);
+            assert_eq!(bid_options, if human_turn && bidding { 1 } else { 0 });
+        }
+        assert_eq!(count(&game, ChangeType::GameOver), 1);
+        assert_eq!(count(&game, ChangeType::Score), 4);
+
+        let json = serde_json::to_value(&game.changes).unwrap();
+        assert_eq!(json[0][0]["type"], "play");
+    }
+
     #[test]
     fn test_bid_to_string_kaibosh() {
         assert_eq!(bid_to_string(KAIBOSH), "kaibosh");
@@ -575,7 +1401,7 @@ mod tests {
             value: 9,
             suit: Suit::Hearts,
         };
-        game.hands[0] = vec![test_card]; // Simplify the hand for the test
+        game.hands[0] = smallvec::smallvec![test_card]; // Simplify the hand for the test
         game.play_card(0);
         assert!(game.hands[0].is_empty());
         assert_eq!(game.current_trick[0], Some(test_card));
@@ -627,6 +1453,60 @@ mod tests {
         assert_eq!(game.current_player, 1); // Should move to the next player
     }
 
+    #[test]
+    fn test_everyone_passes() {
+        let mut game = KaiboshGame::new();
+        assert_eq!(game.bidding_options()[0], PASS);
+        for _ in 0..3 {
+            game.apply_move(Some(PASS));
+        }
+        // the dealer can pass too so the hand is thrown in
+        assert_eq!(game.current_player, game.dealer);
+        assert!(game.bidding_options().contains(&PASS));
+        game.apply_move(Some(PASS));
+        assert_eq!(game.dealer, 0);
+        assert_eq!(game.current_player, 1);
+        assert_eq!(game.state, GameState::Bidding);
+        assert!(game.bids.iter().all(|&bid| bid.is_none()));
+        assert_eq!(game.scores, [0, 0]);
+    }
+
+    #[test]
+    fn test_stick_the_dealer() {
+        let mut game = KaiboshGame::new_with_options(GameOptions {
+            stick_the_dealer: true,
+            ..Default::default()
+        });
+        for _ in 0..3 {
+            game.apply_move(Some(PASS));
+        }
+        // the dealer has to bid
+        assert_eq!(game.current_player, 3);
+        let options = game.bidding_options();
+        assert!(!options.contains(&PASS));
+        game.apply_move(Some(options[0]));
+        assert_eq!(game.state, GameState::NameTrump);
+        assert_eq!(game.bidder, Some(3));
+        assert_eq!(game.high_bid, Some(1));
+
+        // the dealer can still pass once someone else has bid
+        let mut game = KaiboshGame::new_with_options(GameOptions {
+            stick_the_dealer: true,
+            ..Default::default()
+        });
+        game.apply_move(Some(2));
+        game.apply_move(Some(PASS));
+        game.apply_move(Some(PASS));
+        assert!(game.bidding_options().contains(&PASS));
+        game.apply_move(Some(PASS));
+        assert_eq!(game.bidder, Some(0));
+        assert_eq!(game.current_player, 0);
+
+        let options: GameOptions = serde_json::from_str(r#"{"stickTheDealer": true}"#).unwrap();
+        assert_eq!(options.score_threshold, 25);
+        assert!(KaiboshGame::new_with_options(options).stick_the_dealer);
+    }
+
     #[test]
     fn test_bid_function_ends_with_kaibosh_bid() {
         let mut game = KaiboshGame::new();
@@ -692,7 +1572,7 @@ mod tests {
             value: 9,
             suit: Suit::Hearts,
         });
-        game.hands[0] = vec![
+        game.hands[0] = smallvec::smallvec![
             Card {
                 id: 1,
                 value: 10,
@@ -718,7 +1598,7 @@ mod tests {
             value: 9,
             suit: Suit::Hearts,
         });
-        game.hands[0] = vec![
+        game.hands[0] = smallvec::smallvec![
             Card {
                 id: 1,
                 value: 10,
@@ -740,7 +1620,7 @@ mod tests {
     fn test_play_options_all_cards() {
         let mut game = KaiboshGame::new();
         game.lead_card = None; // No lead card yet
-        game.hands[0] = vec![
+        game.hands[0] = smallvec::smallvec![
             Card {
                 id: 1,
                 value: 10,
@@ -804,12 +1684,102 @@ mod tests {
         game.calculate_scores();
         assert_eq!(game.scores[1], 4); // Player 2's team should score 4 points
     }
+
+    #[test]
+    fn test_calculate_scores_alone() {
+        let mut game = KaiboshGame::new();
+        game.bids[0] = Some(3); // Player 0 bids 3 and goes alone
+        game.alone = true;
+        game.tricks_taken[0] = 4;
+        game.calculate_scores();
+        assert_eq!(game.scores[0], 8); // Making the bid alone doubles the points
+
+        let mut game = KaiboshGame::new();
+        game.bids[0] = Some(3);
+        game.alone = true;
+        game.tricks_taken[0] = 2;
+        game.tricks_taken[1] = 4;
+        game.calculate_scores();
+        assert_eq!(game.scores, [-3, 4]); // Missing the bid is not boosted
+    }
+
+    #[test]
+    fn test_go_alone() {
+        let mut game = KaiboshGame::new();
+        for bid in 1..=3 {
+            game.apply_move(Some(bid));
+        }
+        game.apply_move(None);
+        // player 2 won the bid so player 0 sits out
+        assert_eq!(game.state, GameState::NameTrump);
+        assert_eq!(game.current_player, 2);
+        let moves = game.get_moves();
+        assert_eq!(moves, vec![0, 1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(game.describe_move(ALONE + 2), "trump ♥ alone");
+        game.apply_move(Some(ALONE + 2));
+        assert!(game.alone);
+        assert_eq!(game.trump, Some(Suit::Hearts));
+        assert_eq!(game.sitting_out(), Some(0));
+        // the partner sitting out would lead so the next player does
+        assert_eq!(game.current_player, 1);
+
+        let partner_hand = game.hands[0].clone();
+        let mut tricks = 0;
+        while game.state == GameState::Play {
+            assert_ne!(game.current_player, 0);
+            let mov = game.get_moves()[0];
+            game.apply_move(Some(mov));
+            let trick_over = game.current_trick.iter().all(|card| card.is_none());
+            tricks += trick_over as i32;
+            if tricks < 6 {
+                assert_eq!(game.hands[0], partner_hand);
+            }
+        }
+        assert_eq!(tricks, 6);
+        // a new hand was dealt and nobody is alone anymore
+        assert!(!game.alone);
+        assert_eq!(game.sitting_out(), None);
+    }
+
+    #[test]
+    fn test_determinization_with_partner_sitting_out() {
+        let mut game = KaiboshGame::new();
+        for bid in 1..=4 {
+            game.apply_move(Some(bid));
+        }
+        game.apply_move(Some(ALONE));
+        for _ in 0..4 {
+            let mov = game.get_moves()[0];
+            game.apply_move(Some(mov));
+        }
+        let observer = game.current_player;
+        let sizes: Vec<usize> = game.hands.iter().map(|hand| hand.len()).collect();
+        let mut hidden: Vec<i32> = (0..4)
+            .filter(|&player| player != observer)
+            .flat_map(|player| game.hands[player].iter().map(|card| card.id))
+            .collect();
+        hidden.sort();
+        for _ in 0..20 {
+            let mut determinization = game.clone();
+            ismcts::Game::randomize_determination(&mut determinization, observer as i32);
+            assert_eq!(determinization.hands[observer], game.hands[observer]);
+            let hands = determinization.hands.iter();
+            let new_sizes: Vec<usize> = hands.map(|hand| hand.len()).collect();
+            assert_eq!(new_sizes, sizes);
+            let mut new_hidden: Vec<i32> = (0..4)
+                .filter(|&player| player != observer)
+                .flat_map(|player| determinization.hands[player].iter().map(|card| card.id))
+                .collect();
+            new_hidden.sort();
+            assert_eq!(new_hidden, hidden);
+        }
+    }
 }
 #[test]
 fn test_misdeal_with_four_nines() {
     let mut game = KaiboshGame::new();
     // Set up a hand with four nines for the current player
-    game.hands[game.current_player] = vec![
+    game.hands[game.current_player] = smallvec::smallvec![
         Card {
             value: 9,
             suit: Suit::Hearts,
@@ -848,7 +1818,7 @@ fn test_misdeal_with_four_nines() {
 fn test_misdeal_with_three_nines_two_tens() {
     let mut game = KaiboshGame::new();
     // Set up a hand with three nines and two tens for the current player
-    game.hands[game.current_player] = vec![
+    game.hands[game.current_player] = smallvec::smallvec![
         Card {
             value: 9,
             suit: Suit::Hearts,
@@ -887,7 +1857,7 @@ fn test_misdeal_with_three_nines_two_tens() {
 fn test_get_moves_includes_misdeal() {
     let mut game = KaiboshGame::new();
     // Set up a hand with four nines for the current player
-    game.hands[game.current_player] = vec![
+    game.hands[game.current_player] = smallvec::smallvec![
         Card {
             value: 9,
             suit: Suit::Hearts,
@@ -930,7 +1900,7 @@ fn test_get_moves_includes_misdeal() {
 fn test_bid_with_misdeal_advances_game_state() {
     let mut game = KaiboshGame::new();
     // Set up a hand with four nines for the current player
-    game.hands[game.current_player] = vec![
+    game.hands[game.current_player] = smallvec::smallvec![
         Card {
             value: 9,
             suit: Suit::Hearts,
@@ -978,7 +1948,7 @@ fn test_bid_with_misdeal_advances_game_state() {
 fn test_no_misdeal_with_insufficient_nines_or_tens() {
     let mut game = KaiboshGame::new();
     // Set up a hand without the necessary nines or tens for misdeal
-    game.hands[game.current_player] = vec![
+    game.hands[game.current_player] = smallvec::smallvec![
         Card {
             value: 9,
             suit: Suit::Hearts,
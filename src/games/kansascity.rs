@@ -6,20 +6,27 @@ BoardGameGeek: https://boardgamegeek.com/boardgame/424451/kansas-city-the-trick-
 
 use std::{
     cmp::{max, min, Ordering},
-    collections::{HashMap, HashSet},
+    collections::HashSet,
 };
 
+use colored::Color;
 use enum_iterator::{all, Sequence};
-use ismcts::IsmctsHandler;
 use rand::thread_rng;
 use rand::{seq::SliceRandom, Rng};
 use serde::{Deserialize, Serialize};
 
-use crate::utils::shuffle_and_divide_matching_cards;
+use crate::engine::{projected_margin, GameEngine, GameEvent};
+use crate::features::{relative_seat, FeatureBuilder, FeatureExtractor};
+use crate::policy::{search_with_leaf_evaluation, LeafEvaluation, PolicyHandle};
+use crate::profiling;
+use crate::utils::render::{self, SuitStyle};
+use crate::utils::{pair_mut, reset_changes, shuffle_matching_cards_between, Hand};
 
 const SKIP_TRUMP_PROMOTION: i32 = -1;
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Sequence, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub enum State {
     #[default]
@@ -45,6 +52,8 @@ pub enum State {
     PartialOrd,
     Ord,
 )]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub enum Suit {
     #[default]
@@ -58,7 +67,41 @@ pub enum Suit {
     Trump = 7,
 }
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+impl SuitStyle for Suit {
+    fn symbol(&self) -> &'static str {
+        match self {
+            Suit::Stars => "★",
+            Suit::Spades => "♠",
+            Suit::Moons => "☾",
+            Suit::Hearts => "♥",
+            Suit::Diamonds => "♦",
+            Suit::Clubs => "♣",
+            Suit::Triangles => "▲",
+            Suit::Trump => "T",
+        }
+    }
+
+    fn color(&self) -> Option<Color> {
+        match self {
+            Suit::Stars => Some(Color::Yellow),
+            Suit::Spades => None,
+            Suit::Moons => Some(Color::Blue),
+            Suit::Hearts => Some(Color::Red),
+            Suit::Diamonds => Some(Color::Magenta),
+            Suit::Clubs => Some(Color::Green),
+            Suit::Triangles => Some(Color::Cyan),
+            Suit::Trump => Some(Color::BrightWhite),
+        }
+    }
+}
+
+fn print_card(card: &Card) -> String {
+    render::card(card.value, &card.suit)
+}
+
+#[derive(Debug, Clone, Sequence, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 enum Location {
     #[default]
@@ -74,6 +117,8 @@ enum Location {
 }
 
 #[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub struct Card {
     id: i32,
@@ -81,7 +126,9 @@ pub struct Card {
     value: i32,
 }
 
-#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Sequence, Default, Serialize, Deserialize, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub enum ChangeType {
     #[default]
@@ -106,6 +153,8 @@ pub enum ChangeType {
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub struct Change {
     #[serde(rename(serialize = "type", deserialize = "type"))]
@@ -125,6 +174,8 @@ pub struct Change {
 }
 
 #[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 struct BidOption {
     id: i32,
@@ -132,6 +183,8 @@ struct BidOption {
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub struct KansasCityGame {
     // Current game state
@@ -143,7 +196,8 @@ pub struct KansasCityGame {
     // Cards each player has played in the current trick
     pub current_trick: [Option<Card>; 4],
     // Cards in each player's hand
-    pub hands: [Vec<Card>; 4],
+    #[cfg_attr(feature = "typescript", ts(as = "[Vec<Card>; 4]"))]
+    pub hands: [Hand<Card, 14>; 4],
     // Voids revealed when a player couldn't follow a lead card - only applies
     // to hand - not to straw piles - used to determine possible hands
     pub voids: [Vec<Suit>; 4],
@@ -191,10 +245,11 @@ impl KansasCityGame {
 
     // Called at the start of a game and when a new hand is dealt
     pub fn deal(&mut self) {
+        let _timer = profiling::start("deal");
         self.state = State::PassCard;
         self.tricks_taken = [0, 0, 0, 0];
         self.round += 1;
-        self.hands = [vec![], vec![], vec![], vec![]];
+        self.hands = Default::default();
         self.converted_to_trump = [vec![], vec![], vec![], vec![]];
         self.passed_cards = [vec![], vec![], vec![], vec![]];
         self.current_player = self.dealer;
@@ -264,30 +319,34 @@ impl KansasCityGame {
     }
 
     pub fn get_moves(self: &KansasCityGame) -> Vec<i32> {
+        let mut moves = vec![];
+        self.get_moves_into(&mut moves);
+        moves
+    }
+
+    /// Replace the contents of `moves` with the legal moves
+    pub fn get_moves_into(self: &KansasCityGame, moves: &mut Vec<i32>) {
+        moves.clear();
         match self.state {
             State::PassCard => {
+                moves.extend(self.hands[self.current_player].iter().map(|c| c.id));
                 if self.human_player == Some(self.current_player) {
-                    let mut moves = self.current_player_card_ids();
                     moves.extend(self.passed_cards[self.current_player].iter().map(|c| c.id));
-                    moves
-                } else {
-                    self.current_player_card_ids()
                 }
             }
             State::OptionallyPromoteTrump => {
-                let mut promote_ids = self.promotable_card_ids();
-                promote_ids.insert(0, SKIP_TRUMP_PROMOTION);
-                promote_ids
+                moves.push(SKIP_TRUMP_PROMOTION);
+                moves.extend(self.promotable_card_ids());
             }
-            State::Play => self.playable_card_ids(),
+            State::Play => self.playable_card_ids_into(moves),
         }
     }
 
     pub fn promotable_card_ids(&self) -> Vec<i32> {
         let active_trump_values: HashSet<i32> = self
             .hands
-            .concat()
             .iter()
+            .flatten()
             .filter(|c| c.suit == Suit::Trump)
             .map(|c| c.value)
             .collect();
@@ -306,19 +365,22 @@ impl KansasCityGame {
     }
 
     pub fn playable_card_ids(&self) -> Vec<i32> {
+        let mut moves = vec![];
+        self.playable_card_ids_into(&mut moves);
+        moves
+    }
+
+    fn playable_card_ids_into(&self, moves: &mut Vec<i32>) {
+        let hand = &self.hands[self.current_player];
         // Must follow
         if self.current_trick[self.lead_player].is_some() {
             let lead_suit = self.current_trick[self.lead_player].clone().unwrap().suit;
-            let moves: Vec<i32> = self.hands[self.current_player]
-                .iter()
-                .filter(|c| c.suit == lead_suit)
-                .map(|c| c.id)
-                .collect();
+            moves.extend(hand.iter().filter(|c| c.suit == lead_suit).map(|c| c.id));
             if !moves.is_empty() {
-                return moves;
+                return;
             }
         }
-        self.current_player_card_ids()
+        moves.extend(hand.iter().map(|c| c.id));
     }
 
     fn apply_move_internal(&mut self, action: i32) {
@@ -410,7 +472,8 @@ impl KansasCityGame {
                                     },
                                 );
                             }
-                            self.hands[receiving_player].extend(self.passed_cards[player].iter());
+                            self.hands[receiving_player]
+                                .extend(self.passed_cards[player].iter().copied());
                             self.sort_hand(receiving_player);
                             self.reorder_hand(receiving_player, false);
                         }
@@ -639,7 +702,7 @@ impl KansasCityGame {
     }
 
     pub fn apply_move(&mut self, action: i32) {
-        self.changes = vec![vec![]]; // card from player to table
+        reset_changes(&mut self.changes); // card from player to table
         if !self.get_moves().contains(&action) {
             // return the same game with no animations when an invalid move is made
             return;
@@ -651,12 +714,14 @@ impl KansasCityGame {
 
     #[inline]
     fn new_change(&mut self) -> usize {
+        let _timer = profiling::start("changes");
         self.changes.push(vec![]);
         self.changes.len() - 1
     }
 
     #[inline]
     fn add_change(&mut self, index: usize, change: Change) {
+        let _timer = profiling::start("changes");
         if self.no_changes {
             return;
         }
@@ -696,6 +761,7 @@ impl KansasCityGame {
     }
 
     fn show_playable(&mut self) {
+        let _timer = profiling::start("changes");
         if self.changes.is_empty() {
             self.changes = vec![vec![]];
         }
@@ -725,6 +791,9 @@ impl KansasCityGame {
     }
 
     fn show_message(&mut self) {
+        if self.no_changes {
+            return;
+        }
         let player_name = self.player_name_string();
         let message = match self.state {
             State::PassCard => Some(format!(
@@ -764,6 +833,7 @@ impl KansasCityGame {
     }
 
     fn hide_playable(&mut self) {
+        let _timer = profiling::start("changes");
         if self.changes.is_empty() {
             self.changes = vec![vec![]];
         }
@@ -794,20 +864,13 @@ impl KansasCityGame {
     }
 
     pub fn get_winner(&self, lead_suit: Suit, trick: &[Option<Card>; 4]) -> usize {
-        let mut card_id_to_player: HashMap<i32, usize> = HashMap::new();
-        for (player, card) in trick.iter().enumerate() {
-            if let Some(card) = card {
-                card_id_to_player.insert(card.id, player);
-            }
-        }
-        let mut cards: Vec<Card> = trick
-            .iter() // Convert the Vec into an Iterator
-            .filter_map(|&x| x) // filter_map will only pass through the Some values
-            .collect();
-        cards.sort_by_key(|c| std::cmp::Reverse(self.value_for_card(lead_suit, c)));
-        *card_id_to_player
-            .get(&cards.first().expect("there should be a winning card").id)
-            .expect("cards_to_player missing card")
+        let (player, _) = trick
+            .iter()
+            .enumerate()
+            .filter_map(|(player, card)| card.map(|card| (player, card)))
+            .min_by_key(|(_, c)| std::cmp::Reverse(self.value_for_card(lead_suit, c)))
+            .expect("there should be a winning card");
+        player
     }
 
     pub fn value_for_card(&self, lead_suit: Suit, card: &Card) -> i32 {
@@ -839,6 +902,7 @@ impl ismcts::Game for KansasCityGame {
     type MoveList = Vec<i32>;
 
     fn randomize_determination(&mut self, _observer: Self::PlayerTag) {
+        let _timer = profiling::start("determinization");
         let rng = &mut thread_rng();
 
         let known_pass_cards = self.passed_cards[self.current_player].clone();
@@ -856,16 +920,10 @@ impl ismcts::Game for KansasCityGame {
                 // which cards have been converted to trump just like a human
                 // player in their position
 
-                let voids: HashSet<Suit> =
-                    HashSet::from_iter(self.voids[p1 as usize].iter().cloned());
-
-                let mut new_hands = vec![
-                    self.hands[p1 as usize].clone(),
-                    self.converted_to_trump[p2 as usize].clone(),
-                ];
+                let voids = &self.voids[p1 as usize];
 
                 for value in 1..=8 {
-                    shuffle_and_divide_matching_cards(
+                    shuffle_matching_cards_between(
                         |c: &Card| {
                             // Trump cards are visible - do not swap
                             c.suit != Suit::Trump
@@ -875,45 +933,35 @@ impl ismcts::Game for KansasCityGame {
                                 // redistribute cards played as trump into players' hands
                                 && c.value == value
                         },
-                        &mut new_hands,
+                        &mut self.hands[p1 as usize],
+                        &mut self.converted_to_trump[p2 as usize],
                         rng,
                     );
                 }
 
-                self.hands[p1 as usize] = new_hands[0].clone();
-                self.converted_to_trump[p2 as usize] = new_hands[1].clone();
-
                 if p1 == p2 {
                     continue;
                 }
 
-                let mut combined_voids: HashSet<Suit> =
-                    HashSet::from_iter(self.voids[p1 as usize].iter().cloned());
-                combined_voids.extend(self.voids[p2 as usize].iter());
-
-                let mut new_hands = vec![
-                    self.hands[p1 as usize].clone(),
-                    self.hands[p2 as usize].clone(),
-                ];
+                let voids = [&self.voids[p1 as usize], &self.voids[p2 as usize]];
+                let (hand1, hand2) = pair_mut(&mut self.hands, p1 as usize, p2 as usize);
 
                 for value in 1..=8 {
-                    shuffle_and_divide_matching_cards(
+                    shuffle_matching_cards_between(
                         |c: &Card| {
                             // Trump cards are visible - do not swap
                             c.suit != Suit::Trump
                                  // Do not swap cards where one player has a known void in that suit
-                                && !combined_voids.contains(&c.suit)
+                                && !voids.iter().any(|void| void.contains(&c.suit))
                                 // Values are visible on the backs of cards, only exchange
                                 // cards with the same value
                                 && c.value == value
                         },
-                        &mut new_hands,
+                        hand1.as_mut_slice(),
+                        hand2.as_mut_slice(),
                         rng,
                     );
                 }
-
-                self.hands[p1 as usize] = new_hands[0].clone();
-                self.hands[p2 as usize] = new_hands[1].clone();
             }
         }
     }
@@ -927,10 +975,12 @@ impl ismcts::Game for KansasCityGame {
     }
 
     fn available_moves(&self) -> Self::MoveList {
+        let _timer = profiling::start("get_moves");
         self.get_moves()
     }
 
     fn make_move(&mut self, mov: &Self::Move) {
+        let _timer = profiling::start_in("apply_move", &self.state);
         self.apply_move(*mov);
     }
 
@@ -969,24 +1019,202 @@ impl ismcts::Game for KansasCityGame {
     }
 }
 
+impl FeatureExtractor for KansasCityGame {
+    // hand, hand cards promoted to trump, trick, tricks taken, scores,
+    // state, voids, passed cards, round
+    const FEATURE_COUNT: usize = 56 + 56 + 4 * 56 + 4 + 4 + 3 + 4 * 8 + 56 + 1;
+    // SKIP_TRUMP_PROMOTION (-1) and card ids
+    const ACTION_COUNT: usize = 57;
+
+    fn features(&self, observer: usize) -> Vec<f32> {
+        let mut features = FeatureBuilder::with_capacity(Self::FEATURE_COUNT);
+        features
+            .cards(self.hands[observer].iter().map(|c| c.id), 56)
+            .cards(
+                self.hands[observer]
+                    .iter()
+                    .filter(|c| c.suit == Suit::Trump)
+                    .map(|c| c.id),
+                56,
+            );
+        for offset in 0..4 {
+            let player = relative_seat(observer, offset, 4);
+            features.cards(self.current_trick[player].iter().map(|c| c.id), 56);
+        }
+        for offset in 0..4 {
+            let player = relative_seat(observer, offset, 4);
+            features.scalar(self.tricks_taken[player] as f32 / 14.0);
+        }
+        for offset in 0..4 {
+            let player = relative_seat(observer, offset, 4);
+            features.scalar(self.scores[player] as f32 / 29.0);
+        }
+        features.one_hot(Some(self.state.clone() as usize), 3);
+        for offset in 0..4 {
+            let player = relative_seat(observer, offset, 4);
+            for suit in all::<Suit>() {
+                features.scalar(if self.voids[player].contains(&suit) {
+                    1.0
+                } else {
+                    0.0
+                });
+            }
+        }
+        features
+            .cards(self.passed_cards[observer].iter().map(|c| c.id), 56)
+            .scalar(self.round as f32 / 3.0);
+        features.finish(Self::FEATURE_COUNT)
+    }
+
+    fn action_index(&self, mov: i32) -> usize {
+        (mov + 1) as usize
+    }
+
+    fn current_seat(&self) -> usize {
+        self.current_player
+    }
+}
+
+impl GameEngine for KansasCityGame {
+    const NAME: &'static str = "kansascity";
+    const PLAYER_COUNT: usize = 4;
+    const RESULT_RANGE: (f64, f64) = (-1.0, 1.0);
+
+    fn new_game() -> Self {
+        KansasCityGame::new()
+    }
+
+    fn scores(&self) -> Vec<i32> {
+        self.scores.to_vec()
+    }
+
+    fn winners(&self) -> Vec<usize> {
+        self.winner.into_iter().collect()
+    }
+
+    fn legal_moves_into(&self, moves: &mut Vec<i32>) {
+        self.get_moves_into(moves);
+    }
+
+    fn bot_move_with_leaf_evaluation(
+        &self,
+        iterations: i32,
+        policy: Option<PolicyHandle<Self>>,
+        leaf_evaluation: Option<LeafEvaluation<Self>>,
+    ) -> i32 {
+        get_mcts_move_with_leaf_evaluation(self, iterations, false, policy, leaf_evaluation)
+    }
+
+    fn heuristic_value(&self, seat: usize) -> f64 {
+        // score the tricks taken as if the hand ended now
+        let mut projected = self.scores;
+        for player in 0..4 {
+            projected[player] += self.points_for_tricks_taken(self.tricks_taken[player]);
+        }
+        let best_opponent = (0..projected.len())
+            .filter(|player| *player != seat)
+            .map(|player| projected[player])
+            .max()
+            .unwrap_or(0);
+        // a trick can be worth up to 5 points
+        let reachable = self.hands[seat].len() * 5;
+        let own = projected[seat] as f64;
+        projected_margin(own, best_opponent as f64, reachable as f64)
+    }
+
+    fn describe_move(&self, action: i32) -> String {
+        let player = self.current_player;
+        let card = self.hands[player]
+            .iter()
+            .chain(self.passed_cards[player].iter())
+            .find(|card| card.id == action)
+            .map(print_card)
+            .unwrap_or_else(|| action.to_string());
+        match self.state {
+            State::PassCard => format!("pass {}", card),
+            State::OptionallyPromoteTrump if action == SKIP_TRUMP_PROMOTION => {
+                "don't promote".to_string()
+            }
+            State::OptionallyPromoteTrump => format!("promote {} to trump", card),
+            State::Play => card,
+        }
+    }
+
+    fn render(&self, seat: usize) -> String {
+        let rows: Vec<Vec<String>> = (0..4)
+            .map(|player| {
+                vec![
+                    render::seat_label(player, seat),
+                    self.scores[player].to_string(),
+                    self.tricks_taken[player].to_string(),
+                    render::cards(self.converted_to_trump[player].iter().map(print_card)),
+                ]
+            })
+            .collect();
+        let header = ["player", "score", "tricks", "promoted to trump"];
+        let mut lines = vec![render::table(&header, &rows)];
+        if !self.passed_cards[seat].is_empty() {
+            let passed = render::cards(self.passed_cards[seat].iter().map(print_card));
+            lines.push(format!("passed: {}", passed));
+        }
+        let played: Vec<Option<String>> =
+            self.current_trick.iter().map(|card| card.as_ref().map(print_card)).collect();
+        lines.push(format!("trick: {}", render::trick(&played, Some(self.lead_player))));
+        lines.push(format!("hand: {}", render::cards(self.hands[seat].iter().map(print_card))));
+        lines.join("\n")
+    }
+
+    fn events(&self, after: &Self) -> Vec<GameEvent> {
+        let mut events: Vec<GameEvent> = after
+            .changes
+            .iter()
+            .flatten()
+            .find(|change| change.change_type == ChangeType::TricksToWinner)
+            .map(|change| GameEvent::TrickWon {
+                leader: self.lead_player,
+                winner: change.player,
+            })
+            .into_iter()
+            .collect();
+        if after.round != self.round || after.winner.is_some() {
+            events.push(GameEvent::HandOver);
+        }
+        events
+    }
+}
+
 pub fn get_mcts_move(game: &KansasCityGame, iterations: i32, debug: bool) -> i32 {
+    get_mcts_move_with_policy(game, iterations, debug, None)
+}
+
+pub fn get_mcts_move_with_policy(
+    game: &KansasCityGame,
+    iterations: i32,
+    debug: bool,
+    policy: Option<PolicyHandle<KansasCityGame>>,
+) -> i32 {
+    get_mcts_move_with_leaf_evaluation(game, iterations, debug, policy, None)
+}
+
+pub fn get_mcts_move_with_leaf_evaluation(
+    game: &KansasCityGame,
+    iterations: i32,
+    debug: bool,
+    policy: Option<PolicyHandle<KansasCityGame>>,
+    leaf_evaluation: Option<LeafEvaluation<KansasCityGame>>,
+) -> i32 {
     let mut new_game = game.clone();
     new_game.no_changes = true;
     // reset scores for the simulation
     new_game.scores = [0; 4];
     new_game.round = 6; // force evaluation of a single hand
-    let mut ismcts = IsmctsHandler::new(new_game);
-    let parallel_threads: usize = 8;
-    ismcts.run_iterations(
-        parallel_threads,
-        (iterations as f64 / parallel_threads as f64) as usize,
-    );
     if debug {
         // println!("-------");
         // ismcts.debug_children();
         // println!("-------");
     }
-    ismcts.best_move().expect("should have a move to make")
+    let parallel_threads: usize = 8;
+    search_with_leaf_evaluation(new_game, iterations, parallel_threads, policy, leaf_evaluation)
 }
 
 fn human_card_sorter(a: &Card, b: &Card) -> Ordering {
@@ -1007,10 +1235,77 @@ fn opponent_card_sorter(a: &Card, b: &Card) -> Ordering {
     }
 }
 
+/// Dart enums for the change protocol - see `cargo run --bin dart`
+pub fn dart_enums() -> String {
+    use crate::dart::dart_enum;
+    [
+        dart_enum::<ChangeType>("ChangeType"),
+        dart_enum::<Location>("Location"),
+        dart_enum::<State>("State"),
+    ]
+    .join("\n")
+}
+
+/// TypeScript declarations of every serialized type in this game
+#[cfg(feature = "typescript")]
+pub fn typescript_declarations() -> String {
+    use ts_rs::TS;
+    [
+        State::decl(),
+        Suit::decl(),
+        Location::decl(),
+        Card::decl(),
+        ChangeType::decl(),
+        Change::decl(),
+        BidOption::decl(),
+        KansasCityGame::decl(),
+    ]
+    .iter()
+    .map(|decl| format!("export {}\n", decl))
+    .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_features() {
+        crate::features::assert_features_consistent(KansasCityGame::new(), 4);
+    }
+
+    #[test]
+    fn test_no_messages_while_simulating() {
+        let mut game = KansasCityGame::new();
+        game.no_changes = true;
+        while !game.is_over() {
+            let action = *game.legal_moves().choose(&mut thread_rng()).unwrap();
+            game.play(action);
+            assert!(!game
+                .changes
+                .iter()
+                .flatten()
+                .any(|change| change.change_type == ChangeType::Message));
+        }
+    }
+
+    #[test]
+    fn test_heuristic_value() {
+        let mut game = KansasCityGame::new();
+        let value = |tricks_taken: [i32; 4]| {
+            let mut game = game.clone();
+            game.tricks_taken = tricks_taken;
+            game.heuristic_value(0)
+        };
+        // scores are zeroed during search so only the hand can tell these apart
+        assert!(value([1, 0, 0, 0]) > value([0, 0, 0, 0]));
+        assert!(value([0, 2, 0, 0]) < value([0, 1, 0, 0]));
+        // taking a fifth trick scores nothing
+        assert!(value([5, 0, 0, 0]) < value([4, 0, 0, 0]));
+        game.tricks_taken = [0, 3, 0, 0];
+        assert!(game.heuristic_value(1) > 0.0);
+    }
+
     #[test]
     fn test_deck() {
         let d = KansasCityGame::deck();
@@ -5,19 +5,20 @@ BoardGameGeek: https://boardgamegeek.com/boardgame/394691/the-six-of-viii
 */
 
 use core::panic;
-use std::{
-    cmp::Ordering,
-    collections::{HashMap, HashSet},
-    ops::RangeInclusive,
-};
+use std::{cmp::Ordering, collections::HashSet, ops::RangeInclusive};
 
+use colored::Color;
 use enum_iterator::{all, Sequence};
-use ismcts::IsmctsHandler;
 use rand::thread_rng;
 use rand::{seq::SliceRandom, Rng};
 use serde::{Deserialize, Serialize};
 
-use crate::utils::shuffle_and_divide_matching_cards;
+use crate::engine::{projected_margin, GameEngine, GameEvent};
+use crate::features::{relative_seat, FeatureBuilder, FeatureExtractor};
+use crate::policy::{search_with_leaf_evaluation, LeafEvaluation, PolicyHandle};
+use crate::profiling;
+use crate::utils::render::{self, SuitStyle};
+use crate::utils::{pair_mut, reset_changes, shuffle_matching_cards_between, Hand};
 
 const KING: i32 = 13;
 const KING_ID: i32 = 62;
@@ -25,7 +26,9 @@ const PASS: i32 = -100;
 const ANNUL_TRICK: i32 = -101;
 const MAX_POINTS_PER_HAND: f64 = 50.0;
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Sequence, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub enum State {
     #[default]
@@ -51,6 +54,8 @@ pub enum State {
     PartialOrd,
     Ord,
 )]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub enum Suit {
     #[default]
@@ -64,7 +69,7 @@ pub enum Suit {
 }
 
 impl Suit {
-    pub fn text_display(&self) -> &str {
+    pub fn text_display(&self) -> &'static str {
         match self {
             Suit::Black => "♠",
             Suit::Red => "♥",
@@ -77,7 +82,35 @@ impl Suit {
     }
 }
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+impl SuitStyle for Suit {
+    fn symbol(&self) -> &'static str {
+        self.text_display()
+    }
+
+    fn color(&self) -> Option<Color> {
+        match self {
+            Suit::Black => None,
+            Suit::Red => Some(Color::Red),
+            Suit::Orange => Some(Color::TrueColor {
+                r: 255,
+                g: 165,
+                b: 0,
+            }),
+            Suit::Yellow => Some(Color::Yellow),
+            Suit::Green => Some(Color::Green),
+            Suit::Blue => Some(Color::Blue),
+            Suit::Purple => Some(Color::Magenta),
+        }
+    }
+}
+
+fn print_card(card: &Card) -> String {
+    render::color(card.text_display(false).trim_end(), card.suit.color())
+}
+
+#[derive(Debug, Clone, Sequence, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 enum Location {
     #[default]
@@ -99,6 +132,8 @@ enum Location {
 }
 
 #[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub struct Card {
     id: i32,
@@ -130,7 +165,9 @@ impl Card {
     }
 }
 
-#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Sequence, Default, Serialize, Deserialize, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub enum ChangeType {
     #[default]
@@ -157,6 +194,8 @@ pub enum ChangeType {
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub struct Change {
     #[serde(rename(serialize = "type", deserialize = "type"))]
@@ -175,6 +214,8 @@ pub struct Change {
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub struct SixOfVIIIGame {
     // Current game state
@@ -186,7 +227,8 @@ pub struct SixOfVIIIGame {
     // Cards each player has played in the current trick
     pub current_trick: [Option<Card>; 4],
     // Cards in each player's hand
-    pub hands: [Vec<Card>; 4],
+    #[cfg_attr(feature = "typescript", ts(as = "[Vec<Card>; 4]"))]
+    pub hands: [Hand<Card, 15>; 4],
     // Voids revealed when a player couldn't follow a lead card (used during determination)
     pub voids: [Vec<Suit>; 4],
     // Total number of tricks taken for the current hand (per team)
@@ -245,10 +287,11 @@ impl SixOfVIIIGame {
 
     // Called at the start of a game and when a new hand is dealt
     pub fn deal(&mut self) {
+        let _timer = profiling::start("deal");
         self.state = State::PassCard;
         self.cards_taken = [vec![], vec![]];
         self.round += 1;
-        self.hands = [vec![], vec![], vec![], vec![]];
+        self.hands = Default::default();
         self.passed_cards = [vec![], vec![], vec![], vec![]];
         self.current_player = self.dealer;
         self.lead_player = self.current_player;
@@ -396,20 +439,23 @@ impl SixOfVIIIGame {
     }
 
     pub fn get_moves(self: &SixOfVIIIGame) -> Vec<i32> {
+        let mut moves = vec![];
+        self.get_moves_into(&mut moves);
+        moves
+    }
+
+    /// Replace the contents of `moves` with the legal moves
+    pub fn get_moves_into(self: &SixOfVIIIGame, moves: &mut Vec<i32>) {
+        moves.clear();
         match self.state {
             State::PassCard => {
+                moves.extend(self.hands[self.current_player].iter().map(|c| c.id));
                 if self.human_player == Some(self.current_player) {
-                    let mut moves = self.current_player_card_ids();
                     moves.extend(self.passed_cards[self.current_player].iter().map(|c| c.id));
-                    moves
-                } else {
-                    self.current_player_card_ids()
                 }
             }
-            State::Play => self.playable_card_ids(),
-            State::OptionallyPlayChurchOfEngland => {
-                vec![PASS, ANNUL_TRICK]
-            }
+            State::Play => self.playable_card_ids_into(moves),
+            State::OptionallyPlayChurchOfEngland => moves.extend([PASS, ANNUL_TRICK]),
         }
     }
 
@@ -433,11 +479,18 @@ impl SixOfVIIIGame {
     }
 
     pub fn playable_card_ids(&self) -> Vec<i32> {
+        let mut moves = vec![];
+        self.playable_card_ids_into(&mut moves);
+        moves
+    }
+
+    fn playable_card_ids_into(&self, moves: &mut Vec<i32>) {
+        let hand = &self.hands[self.current_player];
         // Must follow
         if let Some(lead_suit) = self.get_lead_suit() {
-            let may_play_cant_be_pulled: Vec<i32> = self.hands[self.current_player]
-                .iter()
-                .filter(|c| {
+            moves.extend(hand.iter().filter(|c| c.suit == lead_suit).map(|c| c.id));
+            if !moves.is_empty() {
+                let may_play_cant_be_pulled = |c: &&Card| {
                     // The King card can be played whenever trump is led but it cannot be pulled
                     (lead_suit == self.current_trump && c.id == KING_ID)
                         // Black zeroes may be played as a red 13 to follow a red lead,
@@ -446,26 +499,18 @@ impl SixOfVIIIGame {
                         // Red zeroes may be played as a black 13 to follow a black lead,
                         // but cannot be pulled from a hand to follow black
                         || (lead_suit == Suit::Black && c.value == 0 && c.suit == Suit::Red)
-                })
-                .map(|c| c.id)
-                .collect();
-            let mut moves: Vec<i32> = self.hands[self.current_player]
-                .iter()
-                .filter(|c| c.suit == lead_suit)
-                .map(|c| c.id)
-                .collect();
-            if !moves.is_empty() {
-                moves.extend(may_play_cant_be_pulled);
-                return moves;
+                };
+                moves.extend(hand.iter().filter(may_play_cant_be_pulled).map(|c| c.id));
+                return;
             }
         }
-        self.current_player_card_ids()
+        moves.extend(hand.iter().map(|c| c.id));
     }
 
     fn apply_move_internal(&mut self, action: i32) {
         match self.state {
             State::OptionallyPlayChurchOfEngland => {
-                if action == ANNUL_TRICK {
+                if action == ANNUL_TRICK && !self.no_changes {
                     let index = self.new_change();
                     let message = if self.current_player == 0 {
                         Some("You chose to annul the trick".to_string())
@@ -583,7 +628,8 @@ impl SixOfVIIIGame {
                                     },
                                 );
                             }
-                            self.hands[receiving_player].extend(self.passed_cards[player].iter());
+                            self.hands[receiving_player]
+                                .extend(self.passed_cards[player].iter().copied());
                             self.sort_hand(receiving_player);
                             self.reorder_hand(receiving_player, false);
                         }
@@ -821,8 +867,31 @@ impl SixOfVIIIGame {
         }
     }
 
+    // Points each team would score for the cards taken so far this hand
+    fn points_taken(&self) -> [i32; 2] {
+        let mut points = [0; 2];
+        for (team, cards) in self.cards_taken.iter().enumerate() {
+            let card_points: i32 = cards.iter().map(|card| card.points).sum();
+            points[team] = cards.len() as i32 / 4 + card_points;
+        }
+        points
+    }
+
+    // Points still to be won this hand - card points and a point per trick
+    fn points_in_play(&self) -> i32 {
+        let cards: Vec<&Card> = self
+            .hands
+            .iter()
+            .flatten()
+            .chain(self.passed_cards.iter().flatten())
+            .chain(self.current_trick.iter().flatten())
+            .collect();
+        let card_points: i32 = cards.iter().map(|card| card.points.abs()).sum();
+        card_points + cards.len() as i32 / 4
+    }
+
     pub fn apply_move(&mut self, action: i32) {
-        self.changes = vec![vec![]]; // card from player to table
+        reset_changes(&mut self.changes); // card from player to table
         if !self.get_moves().contains(&action) {
             println!("Invalid move: {}", action);
             println!("Moves: {:?}", self.get_moves());
@@ -835,12 +904,14 @@ impl SixOfVIIIGame {
 
     #[inline]
     fn new_change(&mut self) -> usize {
+        let _timer = profiling::start("changes");
         self.changes.push(vec![]);
         self.changes.len() - 1
     }
 
     #[inline]
     fn add_change(&mut self, index: usize, change: Change) {
+        let _timer = profiling::start("changes");
         if self.no_changes {
             return;
         }
@@ -879,6 +950,7 @@ impl SixOfVIIIGame {
     }
 
     fn show_playable(&mut self) {
+        let _timer = profiling::start("changes");
         if self.changes.is_empty() {
             self.changes = vec![vec![]];
         }
@@ -908,6 +980,9 @@ impl SixOfVIIIGame {
     }
 
     fn show_message(&mut self) {
+        if self.no_changes {
+            return;
+        }
         let player_name = self.player_name_string();
         let message = match self.state {
             State::PassCard => Some(format!(
@@ -947,6 +1022,7 @@ impl SixOfVIIIGame {
     }
 
     fn hide_playable(&mut self) {
+        let _timer = profiling::start("changes");
         if self.changes.is_empty() {
             self.changes = vec![vec![]];
         }
@@ -980,24 +1056,17 @@ impl SixOfVIIIGame {
     }
 
     pub fn get_trick_winner(&self) -> usize {
-        let mut card_id_to_player: HashMap<i32, usize> = HashMap::new();
-        for (player, card) in self.current_trick.iter().enumerate() {
-            if let Some(card) = card {
-                card_id_to_player.insert(card.id, player);
-            }
-        }
-        let mut cards: Vec<Card> = self
-            .current_trick
-            .iter() // Convert the Vec into an Iterator
-            .filter_map(|&x| x) // filter_map will only pass through the Some values
-            .collect();
-        for card in cards.iter() {
+        for card in self.current_trick.iter().flatten() {
             println!("{:?} = {}", card, self.value_for_card(&card));
         }
-        cards.sort_by_key(|c| std::cmp::Reverse(self.value_for_card(c)));
-        *card_id_to_player
-            .get(&cards.first().expect("there should be a winning card").id)
-            .expect("cards_to_player missing card")
+        let (player, _) = self
+            .current_trick
+            .iter()
+            .enumerate()
+            .filter_map(|(player, card)| card.map(|card| (player, card)))
+            .min_by_key(|(_, c)| std::cmp::Reverse(self.value_for_card(c)))
+            .expect("there should be a winning card");
+        player
     }
 
     pub fn value_for_card(&self, card: &Card) -> i32 {
@@ -1045,23 +1114,20 @@ impl ismcts::Game for SixOfVIIIGame {
     type MoveList = Vec<i32>;
 
     fn randomize_determination(&mut self, _observer: Self::PlayerTag) {
+        let _timer = profiling::start("determinization");
         let rng = &mut thread_rng();
 
         for p1 in 0..4 {
             if p1 != self.current_player() {
                 // randomly swap each player's hand with the burned cards
-                let mut new_hands =
-                    vec![self.hands[p1 as usize].clone(), self.burned_cards.clone()];
-
+                let voids = &self.voids[p1];
                 // only swap cards that aren't in the current players void set
-                shuffle_and_divide_matching_cards(
-                    |c: &Card| !self.voids[p1].contains(&c.suit),
-                    &mut new_hands,
+                shuffle_matching_cards_between(
+                    |c: &Card| !voids.contains(&c.suit),
+                    &mut self.hands[p1],
+                    &mut self.burned_cards,
                     rng,
                 );
-
-                self.hands[p1] = new_hands[0].clone();
-                self.burned_cards = new_hands[1].clone();
             }
 
             for p2 in 0..4 {
@@ -1069,24 +1135,16 @@ impl ismcts::Game for SixOfVIIIGame {
                     continue;
                 }
 
-                let mut combined_voids: HashSet<Suit> =
-                    HashSet::from_iter(self.voids[p1 as usize].iter().cloned());
-                combined_voids.extend(self.voids[p2 as usize].iter());
-
-                let mut new_hands = vec![
-                    self.hands[p1 as usize].clone(),
-                    self.hands[p2 as usize].clone(),
-                ];
+                let voids = [&self.voids[p1 as usize], &self.voids[p2 as usize]];
+                let (hand1, hand2) = pair_mut(&mut self.hands, p1 as usize, p2 as usize);
 
                 // allow swapping of any cards that are not in the combined void set
-                shuffle_and_divide_matching_cards(
-                    |c: &Card| !combined_voids.contains(&c.suit),
-                    &mut new_hands,
+                shuffle_matching_cards_between(
+                    |c: &Card| !voids.iter().any(|void| void.contains(&c.suit)),
+                    hand1,
+                    hand2,
                     rng,
                 );
-
-                self.hands[p1 as usize] = new_hands[0].clone();
-                self.hands[p2 as usize] = new_hands[1].clone();
             }
         }
     }
@@ -1100,10 +1158,12 @@ impl ismcts::Game for SixOfVIIIGame {
     }
 
     fn available_moves(&self) -> Self::MoveList {
+        let _timer = profiling::start("get_moves");
         self.get_moves()
     }
 
     fn make_move(&mut self, mov: &Self::Move) {
+        let _timer = profiling::start_in("apply_move", &self.state);
         self.apply_move(*mov);
     }
 
@@ -1126,19 +1186,193 @@ impl ismcts::Game for SixOfVIIIGame {
     }
 }
 
+impl FeatureExtractor for SixOfVIIIGame {
+    // hand, trick, trump, cards taken per team, scores, state, voids,
+    // passed cards, church of england played
+    const FEATURE_COUNT: usize = 63 + 4 * 63 + 7 + 2 * 63 + 2 + 3 + 4 * 7 + 63 + 1;
+    // card ids then PASS and ANNUL_TRICK
+    const ACTION_COUNT: usize = 65;
+
+    fn features(&self, observer: usize) -> Vec<f32> {
+        let mut features = FeatureBuilder::with_capacity(Self::FEATURE_COUNT);
+        features.cards(self.hands[observer].iter().map(|c| c.id), 63);
+        for offset in 0..4 {
+            let player = relative_seat(observer, offset, 4);
+            features.cards(self.current_trick[player].iter().map(|c| c.id), 63);
+        }
+        features
+            .one_hot(Some(self.current_trump as usize), 7)
+            .cards(self.cards_taken[observer % 2].iter().map(|c| c.id), 63)
+            .cards(self.cards_taken[(observer + 1) % 2].iter().map(|c| c.id), 63)
+            .scalar(self.scores[observer % 2] as f32 / MAX_POINTS_PER_HAND as f32)
+            .scalar(self.scores[(observer + 1) % 2] as f32 / MAX_POINTS_PER_HAND as f32)
+            .one_hot(Some(self.state.clone() as usize), 3);
+        for offset in 0..4 {
+            let player = relative_seat(observer, offset, 4);
+            for suit in all::<Suit>() {
+                features.scalar(if self.voids[player].contains(&suit) {
+                    1.0
+                } else {
+                    0.0
+                });
+            }
+        }
+        features
+            .cards(self.passed_cards[observer].iter().map(|c| c.id), 63)
+            .scalar(if self.church_of_england_played {
+                1.0
+            } else {
+                0.0
+            });
+        features.finish(Self::FEATURE_COUNT)
+    }
+
+    fn action_index(&self, mov: i32) -> usize {
+        match mov {
+            PASS => 63,
+            ANNUL_TRICK => 64,
+            _ => mov as usize,
+        }
+    }
+
+    fn current_seat(&self) -> usize {
+        self.current_player
+    }
+}
+
+impl GameEngine for SixOfVIIIGame {
+    const NAME: &'static str = "so8";
+    const PLAYER_COUNT: usize = 4;
+    const RESULT_RANGE: (f64, f64) = (-1.0, 1.0);
+
+    fn new_game() -> Self {
+        SixOfVIIIGame::new()
+    }
+
+    fn scores(&self) -> Vec<i32> {
+        (0..4).map(|player| self.scores[player % 2]).collect()
+    }
+
+    fn winners(&self) -> Vec<usize> {
+        // winner is the winning team
+        match self.winner {
+            Some(team) => vec![team % 2, team % 2 + 2],
+            None => vec![],
+        }
+    }
+
+    fn legal_moves_into(&self, moves: &mut Vec<i32>) {
+        self.get_moves_into(moves);
+    }
+
+    fn bot_move_with_leaf_evaluation(
+        &self,
+        iterations: i32,
+        policy: Option<PolicyHandle<Self>>,
+        leaf_evaluation: Option<LeafEvaluation<Self>>,
+    ) -> i32 {
+        get_mcts_move_with_leaf_evaluation(self, iterations, false, policy, leaf_evaluation)
+    }
+
+    fn heuristic_value(&self, seat: usize) -> f64 {
+        let team = seat % 2;
+        let taken = self.points_taken();
+        let own = self.scores[team] + taken[team];
+        let opponent = self.scores[1 - team] + taken[1 - team];
+        projected_margin(own as f64, opponent as f64, self.points_in_play() as f64)
+    }
+
+    fn describe_move(&self, action: i32) -> String {
+        let player = self.current_player;
+        let card = self.hands[player]
+            .iter()
+            .chain(self.passed_cards[player].iter())
+            .find(|card| card.id == action)
+            .map(print_card)
+            .unwrap_or_else(|| action.to_string());
+        match self.state {
+            State::PassCard => format!("pass {} to partner", card),
+            State::Play => card,
+            State::OptionallyPlayChurchOfEngland if action == ANNUL_TRICK => {
+                "annul the trick (Church of England)".to_string()
+            }
+            State::OptionallyPlayChurchOfEngland => "pass".to_string(),
+        }
+    }
+
+    fn render(&self, seat: usize) -> String {
+        let rows: Vec<Vec<String>> = (0..2)
+            .map(|team| {
+                let mut label = format!("{} & {}", team, team + 2);
+                if seat % 2 == team {
+                    label.push_str(" (you)");
+                }
+                vec![
+                    label,
+                    self.scores[team].to_string(),
+                    self.cards_taken[team].len().to_string(),
+                ]
+            })
+            .collect();
+        let mut lines = vec![render::table(&["team", "score", "cards taken"], &rows)];
+        if !self.passed_cards[seat].is_empty() {
+            let passed = render::cards(self.passed_cards[seat].iter().map(print_card));
+            lines.push(format!("passed: {}", passed));
+        }
+        lines.push(format!("trump: {}", render::suit(Some(&self.current_trump))));
+        let played: Vec<Option<String>> =
+            self.current_trick.iter().map(|card| card.as_ref().map(print_card)).collect();
+        lines.push(format!("trick: {}", render::trick(&played, Some(self.lead_player))));
+        lines.push(format!("hand: {}", render::cards(self.hands[seat].iter().map(print_card))));
+        lines.join("\n")
+    }
+
+    fn events(&self, after: &Self) -> Vec<GameEvent> {
+        let mut events: Vec<GameEvent> = after
+            .changes
+            .iter()
+            .flatten()
+            .find(|change| change.change_type == ChangeType::TricksToWinner)
+            .map(|change| GameEvent::TrickWon {
+                leader: self.lead_player,
+                winner: change.player,
+            })
+            .into_iter()
+            .collect();
+        if after.round != self.round || after.winner.is_some() {
+            events.push(GameEvent::HandOver);
+        }
+        events
+    }
+}
+
 pub fn get_mcts_move(game: &SixOfVIIIGame, iterations: i32, debug: bool) -> i32 {
+    get_mcts_move_with_policy(game, iterations, debug, None)
+}
+
+pub fn get_mcts_move_with_policy(
+    game: &SixOfVIIIGame,
+    iterations: i32,
+    debug: bool,
+    policy: Option<PolicyHandle<SixOfVIIIGame>>,
+) -> i32 {
+    get_mcts_move_with_leaf_evaluation(game, iterations, debug, policy, None)
+}
+
+pub fn get_mcts_move_with_leaf_evaluation(
+    game: &SixOfVIIIGame,
+    iterations: i32,
+    debug: bool,
+    policy: Option<PolicyHandle<SixOfVIIIGame>>,
+    leaf_evaluation: Option<LeafEvaluation<SixOfVIIIGame>>,
+) -> i32 {
     let mut new_game = game.clone();
     new_game.no_changes = true;
     // reset scores for the simulation
     new_game.scores = [0; 2];
     new_game.round = 4; // force evaluation of a single hand
-    let mut ismcts = IsmctsHandler::new(new_game);
     let parallel_threads: usize = 8;
-    ismcts.run_iterations(
-        parallel_threads,
-        (iterations as f64 / parallel_threads as f64) as usize,
-    );
-    ismcts.best_move().expect("should have a move to make")
+    search_with_leaf_evaluation(new_game, iterations, parallel_threads, policy, leaf_evaluation)
 }
 
 fn human_card_sorter(a: &Card, b: &Card) -> Ordering {
@@ -1161,10 +1395,83 @@ fn trick_number_to_trump(trick_number: i32) -> Suit {
     }
 }
 
+/// Dart enums for the change protocol - see `cargo run --bin dart`
+pub fn dart_enums() -> String {
+    use crate::dart::dart_enum;
+    [
+        dart_enum::<ChangeType>("ChangeType"),
+        dart_enum::<Location>("Location"),
+        dart_enum::<State>("State"),
+    ]
+    .join("\n")
+}
+
+/// TypeScript declarations of every serialized type in this game
+#[cfg(feature = "typescript")]
+pub fn typescript_declarations() -> String {
+    use ts_rs::TS;
+    [
+        State::decl(),
+        Suit::decl(),
+        Location::decl(),
+        Card::decl(),
+        ChangeType::decl(),
+        Change::decl(),
+        SixOfVIIIGame::decl(),
+    ]
+    .iter()
+    .map(|decl| format!("export {}\n", decl))
+    .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_features() {
+        crate::features::assert_features_consistent(SixOfVIIIGame::new(), 4);
+    }
+
+    #[test]
+    fn test_no_messages_while_simulating() {
+        let mut game = SixOfVIIIGame::new();
+        game.no_changes = true;
+        while !game.is_over() {
+            let action = *game.legal_moves().choose(&mut thread_rng()).unwrap();
+            game.play(action);
+            assert!(!game
+                .changes
+                .iter()
+                .flatten()
+                .any(|change| change.change_type == ChangeType::Message));
+        }
+    }
+
+    #[test]
+    fn test_heuristic_value() {
+        let mut game = SixOfVIIIGame::new();
+        let card = |id: i32, points: i32| Card {
+            id,
+            suit: Suit::Red,
+            points,
+            value: id,
+        };
+        let one_trick: Vec<Card> = (0..4).map(|id| card(id, 0)).collect();
+        let two_tricks: Vec<Card> = (0..8).map(|id| card(id, 0)).collect();
+        game.cards_taken = [one_trick.clone(), vec![]];
+        let fewer_tricks = game.heuristic_value(0);
+        game.cards_taken = [two_tricks, vec![]];
+        let more_tricks = game.heuristic_value(0);
+        // scores are zeroed during search so only the hand can tell these apart
+        assert!(more_tricks > fewer_tricks);
+        assert!(fewer_tricks > 0.0);
+        let points = vec![card(0, 3), card(1, 0), card(2, 0), card(3, 0)];
+        game.cards_taken = [one_trick, points];
+        assert!(game.heuristic_value(0) < 0.0);
+        assert_eq!(game.heuristic_value(1), -game.heuristic_value(0));
+    }
+
     #[test]
     fn test_deck() {
         let d = SixOfVIIIGame::deck();
@@ -4,27 +4,39 @@ Designer: Taylor Reiner
 BoardGameGeek: https://boardgamegeek.com/boardgame/366458/short-zoot-suit
 */
 
-use crate::utils::shuffle_and_divide_matching_cards;
+use colored::Color;
+use crate::engine::{projected_margin, GameEngine, GameEvent};
+use crate::features::{relative_seat, FeatureBuilder, FeatureExtractor};
+use crate::policy::{search_with_leaf_evaluation, LeafEvaluation, PolicyHandle};
+use crate::profiling;
+use crate::utils::cardmask::CardMask;
+use crate::utils::render::{self, SuitStyle};
+use crate::utils::{pair_mut, reset_changes, shuffle_matching_cards_between, Hand};
 use enum_iterator::{all, Sequence};
-use ismcts::IsmctsHandler;
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
-use rand::thread_rng;
-use serde::{Deserialize, Serialize};
+use rand::{thread_rng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize, Serializer};
 use std::cmp::{min, Ordering};
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 
 const DRAW: i32 = 0;
 const PASS: i32 = 1;
+const DEAL: i32 = 0; // the only move while the shuffled deck waits to be dealt
 const DISCARD_OFFSET: i32 = 2; // 2-50 discards
 const PLAY_OFFSET: i32 = 51; // 51-99 plays
 
 #[derive(Debug, Clone, Copy, Default, PartialEq, Sequence, Serialize, Deserialize, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 enum State {
     #[default]
     Play,
     Discard,
     OptionalDraw,
+    // Between rounds with the deck shuffled but not dealt (see shuffle_step)
+    Shuffled,
 }
 
 #[derive(
@@ -41,6 +53,8 @@ enum State {
     PartialEq,
     Eq,
 )]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub enum Suit {
     #[default]
@@ -50,7 +64,33 @@ pub enum Suit {
     Green,
 }
 
+impl SuitStyle for Suit {
+    fn symbol(&self) -> &'static str {
+        match self {
+            Suit::Red => "♥",
+            Suit::Blue => "♣",
+            Suit::Yellow => "♦",
+            Suit::Green => "♠",
+        }
+    }
+
+    fn color(&self) -> Option<Color> {
+        Some(match self {
+            Suit::Red => Color::Red,
+            Suit::Blue => Color::Blue,
+            Suit::Yellow => Color::Yellow,
+            Suit::Green => Color::Green,
+        })
+    }
+}
+
+fn print_card(card: &Card) -> String {
+    render::card(card.value, &card.suit)
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub struct Card {
     pub id: i32,
@@ -60,7 +100,7 @@ pub struct Card {
 
 fn move_offset(state: State, card: &Card) -> i32 {
     match state {
-        State::OptionalDraw => 0,
+        State::OptionalDraw | State::Shuffled => 0,
         State::Discard => card.id + DISCARD_OFFSET,
         State::Play => card.id + PLAY_OFFSET,
     }
@@ -68,13 +108,26 @@ fn move_offset(state: State, card: &Card) -> i32 {
 
 fn card_offset(state: State, offset: i32) -> i32 {
     match state {
-        State::OptionalDraw => panic!("impossible move"),
+        State::OptionalDraw | State::Shuffled => panic!("impossible move"),
         State::Discard => offset - DISCARD_OFFSET,
         State::Play => offset - PLAY_OFFSET,
     }
 }
 
 pub fn deck() -> Vec<Card> {
+    deck_with_rng(&mut thread_rng())
+}
+
+/// A deck shuffled with `rng`
+// https://prng.di.unimi.it/splitmix64.c
+fn splitmix64(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+pub fn deck_with_rng(rng: &mut impl Rng) -> Vec<Card> {
     let mut deck: Vec<Card> = vec![];
     let mut id = 0;
     for suit in all::<Suit>() {
@@ -87,11 +140,18 @@ pub fn deck() -> Vec<Card> {
             id += 1;
         }
     }
-    deck.shuffle(&mut thread_rng());
+    deck.shuffle(rng);
     deck
 }
 
+/// Ids of the cards of a suit - ids are numbered by suit (see deck)
+fn suit_mask(suit: Suit) -> CardMask {
+    CardMask(0xfff << (suit as u32 * 12))
+}
+
 #[derive(Debug, Clone, Copy, Sequence, Default, Serialize, Deserialize, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub enum ChangeType {
     #[default]
@@ -111,6 +171,8 @@ pub enum ChangeType {
 }
 
 #[derive(Debug, Clone, Copy, Sequence, Default, Serialize, Deserialize, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 enum Location {
     #[default]
@@ -126,6 +188,8 @@ enum Location {
 }
 
 #[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub struct Change {
     #[serde(rename(serialize = "type", deserialize = "type"))]
@@ -143,12 +207,42 @@ pub struct Change {
     cards_remaining: i32,
 }
 
+/// How one player's hand went - see `Game::round_results`
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct RoundResult {
+    pub round: i32,
+    pub dealer: i32,
+    pub player: i32,
+    pub tricks_taken: i32,
+    pub shorts: i32,
+    pub points: i32,
+}
+
+/// The final result of a game - set when the GameOver change is emitted
 #[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct GameSummary {
+    pub winner: i32,
+    pub scores: Vec<i32>,
+    // players from the highest score to the lowest (ties in seat order)
+    pub standings: Vec<i32>,
+    pub round_results: Vec<RoundResult>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub struct Game {
     undo_players: HashSet<i32>,
     action_size: i32,
-    hands: [Vec<Card>; 3],
+    #[cfg_attr(feature = "typescript", ts(as = "[Vec<Card>; 3]"))]
+    hands: [Hand<Card, 16>; 3],
     pub draw_decks: [Vec<Card>; 3],
     shorts_piles: [Vec<Card>; 3],
     pub changes: Vec<Vec<Change>>,
@@ -157,6 +251,12 @@ pub struct Game {
     lead_suit: Option<Suit>,
     pub round: i32,
     pub scores: Vec<i32>,
+    // every player's result for each hand played so far
+    #[serde(default)]
+    pub round_results: Vec<RoundResult>,
+    #[serde(default)]
+    pub summary: Option<GameSummary>,
+    #[serde(serialize_with = "serialize_voids")]
     pub voids: Vec<HashSet<Suit>>,
     pub current_player: i32,
     pub winner: Option<i32>,
@@ -166,17 +266,150 @@ pub struct Game {
     lead_player: i32,
     #[serde(default)]
     pub no_changes: bool,
+    // Which player is the human player - their hand is the one kept sorted
+    // and shown playable (games saved before this was configurable used 0)
+    #[serde(default = "default_human_player")]
+    pub human_player: Option<i32>,
+    // Seed the deals and draw deck shuffles are made from - games with the
+    // same seed get the same cards (None shuffles with thread_rng)
+    #[serde(default)]
+    #[cfg_attr(feature = "typescript", ts(type = "number | null"))]
+    pub seed: Option<u64>,
+    // Stop after shuffling between rounds until DEAL is applied so the
+    // shuffled deck is part of a recorded state (for golden file checks)
+    #[serde(default)]
+    pub shuffle_step: bool,
+    // Number of hands in the game (0 - saved before this was configurable -
+    // plays STANDARD_ROUNDS)
+    #[serde(default)]
+    pub rounds: i32,
+    // Shuffled cards waiting to be dealt (see shuffle_step) - the last card
+    // is dealt first
+    #[serde(default)]
+    pub deck: Vec<Card>,
+    // Search only: the game ends with the current hand so result() scores
+    // it without touching the round (see with_final_hand)
+    #[serde(skip)]
+    #[cfg_attr(feature = "typescript", ts(skip))]
+    final_hand: bool,
+    // Bitmask of each hand - only kept while simulating (see with_no_changes)
+    #[serde(skip)]
+    #[cfg_attr(feature = "typescript", ts(skip))]
+    hand_masks: Option<[CardMask; 3]>,
+}
+
+// Sorted since set order changes from run to run and seeded games should
+// serialize the same way every time
+fn serialize_voids<S: Serializer>(
+    voids: &[HashSet<Suit>],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let sorted: Vec<Vec<Suit>> = voids
+        .iter()
+        .map(|void| {
+            let mut suits: Vec<Suit> = void.iter().copied().collect();
+            suits.sort();
+            suits
+        })
+        .collect();
+    sorted.serialize(serializer)
+}
+
+fn default_human_player() -> Option<i32> {
+    Some(0)
+}
+
+/// Hands in a standard game
+pub const STANDARD_ROUNDS: i32 = 4;
+
+/// How to set up a new game - see `Game::new_with_options`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GameOptions {
+    pub human_player: Option<i32>,
+    pub seed: Option<u64>,
+    /// Hands to play - 0 plays the standard STANDARD_ROUNDS
+    pub rounds: i32,
+    pub shuffle_step: bool,
+}
+
+impl Default for GameOptions {
+    fn default() -> Self {
+        GameOptions {
+            human_player: Some(0),
+            seed: None,
+            rounds: 0,
+            shuffle_step: false,
+        }
+    }
 }
 
 impl Game {
-    /// Factory to create a default game
+    /// Factory to create a default game (the human player is player 0)
     pub fn new() -> Game {
-        let mut game = Game::default();
+        Game::new_with_human_player(0)
+    }
+
+    pub fn new_with_human_player(human_player: i32) -> Game {
+        Game::new_with_options(GameOptions {
+            human_player: Some(human_player),
+            ..Default::default()
+        })
+    }
+
+    /// Factory to create a game whose deals are reproducible from `seed`
+    pub fn new_with_seed(seed: u64) -> Game {
+        Game::new_with_options(GameOptions {
+            seed: Some(seed),
+            ..Default::default()
+        })
+    }
+
+    pub fn new_with_options(options: GameOptions) -> Game {
+        assert!(options.rounds >= 0, "rounds can't be negative");
+        let mut game = Game {
+            human_player: options.human_player,
+            seed: options.seed,
+            rounds: options.rounds,
+            shuffle_step: options.shuffle_step,
+            ..Default::default()
+        };
         game.deal();
         game.scores = vec![0, 0, 0];
         game
     }
 
+    /// The (zero based) round after which the game is over
+    pub fn final_round(self: &Game) -> i32 {
+        if self.rounds > 0 {
+            self.rounds - 1
+        } else {
+            STANDARD_ROUNDS - 1
+        }
+    }
+
+    // Treat the current hand as the last one of the game
+    pub fn with_final_hand(self: &mut Game) {
+        self.final_hand = true;
+    }
+
+    // Shuffling rng for one step of the round - seeded games use a rng
+    // derived from the seed, round and step so replays shuffle the same way
+    fn shuffle_rng(self: &Game, step: u64) -> StdRng {
+        match self.seed {
+            Some(seed) => {
+                // mixed so neighbouring seeds don't share a round's shuffle
+                let round = splitmix64(seed) ^ self.round as u64;
+                StdRng::seed_from_u64(splitmix64(splitmix64(round) ^ step))
+            }
+            None => StdRng::from_rng(thread_rng()).expect("thread_rng should not fail"),
+        }
+    }
+
+    fn is_human(self: &Game, player: i32) -> bool {
+        self.human_player == Some(player)
+    }
+
     /// Set which players can undo their moves when discarding
     /// (The human player (0) is set as an undo player on
     /// Trickster's Table)
@@ -188,25 +421,41 @@ impl Game {
     // This is used to increase the speed of simulations
     pub fn with_no_changes(self: &mut Game) {
         self.no_changes = true;
+        self.hand_masks = Some(Default::default());
+        self.update_hand_masks();
+    }
+
+    // Rebuild the hand masks from the hands
+    fn update_hand_masks(&mut self) {
+        if let Some(masks) = self.hand_masks.as_mut() {
+            for (mask, hand) in masks.iter_mut().zip(self.hands.iter()) {
+                *mask = CardMask::from_ids(hand.iter().map(|card| card.id));
+            }
+        }
     }
 
     fn deal(self: &mut Game) {
+        let cards = deck_with_rng(&mut self.shuffle_rng(0));
+        self.deal_from(cards);
+    }
+
+    fn deal_from(self: &mut Game, mut cards: Vec<Card>) {
+        let _timer = profiling::start("deal");
         self.state = State::Discard;
         self.current_trick = [None, None, None];
         self.draw_players_remaining = (0..3).collect();
         self.tricks_taken = [0, 0, 0];
-        self.hands = [vec![], vec![], vec![]];
+        self.hands = Default::default();
         self.draw_decks = [vec![], vec![], vec![]];
         self.shorts_piles = [vec![], vec![], vec![]];
         self.dealer = (self.dealer + 1) % 3;
         self.current_player = self.dealer;
         self.voids = vec![HashSet::new(), HashSet::new(), HashSet::new()];
-        let mut cards = deck();
         let deal_index: usize = self.changes.len();
         let reorder_index = deal_index + 1;
         self.changes.push(vec![]); // deal_index
         self.changes.push(vec![]); // reorder_index
-        self.hands = [vec![], vec![], vec![]];
+        self.hands = Default::default();
         for y in 0..16 {
             for player in 0..3 {
                 let card = cards.pop().expect("cards should be available here");
@@ -223,30 +472,43 @@ impl Game {
                 self.hands[player as usize].push(card);
             }
         }
-        self.hands[0].sort_by(card_sorter);
-        self.changes[reorder_index].append(&mut reorder_hand(0, &self.hands[0]));
+        if let Some(human) = self.human_player {
+            self.hands[human as usize].sort_by(card_sorter);
+            reorder_hand_into(
+                &mut self.changes[reorder_index],
+                human,
+                &self.hands[human as usize],
+            );
+        }
+        self.update_hand_masks();
         let playable_changes = self.show_playable();
         self.changes.push(playable_changes);
     }
 
     pub fn apply_move(self: &mut Game, action: i32) {
-        self.changes = vec![vec![]]; // card from player to table or discard to draw deck
+        reset_changes(&mut self.changes); // card from player to table or discard to draw deck
         if !self.get_moves().contains(&action) {
             panic!("illegal move");
         }
+        if self.state == State::Shuffled {
+            let cards = std::mem::take(&mut self.deck);
+            self.deal_from(cards);
+            return;
+        }
         if self.state == State::OptionalDraw {
             if action == DRAW {
                 // Once a player draws a card we don't know what their voids are
                 self.voids[self.current_player as usize] = HashSet::new();
                 let new_card: Card = self.draw_decks[self.current_player as usize].remove(0);
                 self.hands[self.current_player as usize].push(new_card);
+                if let Some(masks) = self.hand_masks.as_mut() {
+                    masks[self.current_player as usize].insert(new_card.id);
+                }
                 self.hands[self.current_player as usize].sort_by(card_sorter);
-                self.changes[0].append(
-                    reorder_hand(
-                        self.current_player,
-                        &self.hands[self.current_player as usize].to_vec(),
-                    )
-                    .as_mut(),
+                reorder_hand_into(
+                    &mut self.changes[0],
+                    self.current_player,
+                    &self.hands[self.current_player as usize],
                 );
                 for card in &self.draw_decks[self.current_player as usize] {
                     self.changes[0].push(Change {
@@ -281,7 +543,7 @@ impl Game {
             return;
         }
         if self.state == State::Discard {
-            let mut all_cards = self.hands[self.current_player as usize].clone();
+            let mut all_cards = self.hands[self.current_player as usize].to_vec();
             all_cards.append(&mut self.draw_decks[self.current_player as usize].clone());
             let card_id = action - DISCARD_OFFSET;
             let card = all_cards
@@ -292,12 +554,18 @@ impl Game {
                 // Allows undo
                 self.draw_decks[self.current_player as usize].retain(|c| c != card);
                 self.hands[self.current_player as usize].push(*card);
+                if let Some(masks) = self.hand_masks.as_mut() {
+                    masks[self.current_player as usize].insert(card_id);
+                }
             } else {
                 self.hands[self.current_player as usize].retain(|c| c != card);
                 self.draw_decks[self.current_player as usize].push(*card);
+                if let Some(masks) = self.hand_masks.as_mut() {
+                    masks[self.current_player as usize].remove(card_id);
+                }
             }
             let mut offset: i32 = 0;
-            if self.current_player == 0 {
+            if self.is_human(self.current_player) {
                 for card in &self.draw_decks[self.current_player as usize] {
                     self.changes[0].push(Change {
                         change_type: ChangeType::Discard,
@@ -324,18 +592,16 @@ impl Game {
                 });
             }
             self.hands[self.current_player as usize].sort_by(card_sorter);
-            self.changes[0].append(
-                reorder_hand(
-                    self.current_player,
-                    &self.hands[self.current_player as usize],
-                )
-                .as_mut(),
+            reorder_hand_into(
+                &mut self.changes[0],
+                self.current_player,
+                &self.hands[self.current_player as usize],
             );
             if self.draw_decks[self.current_player as usize].len() == 5 {
                 if !self.no_changes {
-                    if self.current_player == 0 {
+                    if self.is_human(self.current_player) {
                         let mut cards_remaining_changes: Vec<Change> = vec![];
-                        for card in &self.draw_decks[0] {
+                        for card in &self.draw_decks[self.current_player as usize] {
                             cards_remaining_changes.push(Change {
                                 object_id: card.id,
                                 change_type: ChangeType::Discard,
@@ -350,8 +616,9 @@ impl Game {
                 self.current_player = (self.current_player + 1) % 3;
             }
             if self.draw_decks[self.current_player as usize].len() == 5 {
+                let mut rng = self.shuffle_rng(1);
                 for player in 0..3 {
-                    self.draw_decks[player].shuffle(&mut thread_rng());
+                    self.draw_decks[player].shuffle(&mut rng);
                 }
                 self.state = State::OptionalDraw;
             }
@@ -367,6 +634,9 @@ impl Game {
             .expect("this card has to be in the player's hand")
             .clone();
         self.hands[self.current_player as usize].retain(|c| c.id != card_id);
+        if let Some(masks) = self.hand_masks.as_mut() {
+            masks[self.current_player as usize].remove(card_id);
+        }
         if !self.no_changes {
             self.changes[0].push(Change {
                 change_type: ChangeType::Play,
@@ -377,12 +647,10 @@ impl Game {
                 player: self.current_player,
                 ..Default::default()
             });
-            self.changes[0].append(
-                reorder_hand(
-                    self.current_player,
-                    &self.hands[self.current_player as usize],
-                )
-                .as_mut(),
+            reorder_hand_into(
+                &mut self.changes[0],
+                self.current_player,
+                &self.hands[self.current_player as usize],
             );
         }
         let last_change = self.changes.len() - 1;
@@ -489,43 +757,62 @@ impl Game {
     }
 
     pub fn get_moves(self: &Game) -> Vec<i32> {
+        let mut actions = vec![];
+        self.get_moves_into(&mut actions);
+        actions
+    }
+
+    /// Replace the contents of `actions` with the legal moves
+    pub fn get_moves_into(self: &Game, actions: &mut Vec<i32>) {
+        actions.clear();
+        if self.state == State::Shuffled {
+            actions.push(DEAL);
+            return;
+        }
         if self.state == State::OptionalDraw {
             if !self.draw_decks[self.current_player as usize].is_empty() {
-                return vec![DRAW, PASS];
+                actions.push(DRAW);
             }
-            return vec![PASS];
+            actions.push(PASS);
+            return;
         }
-        let allow_undoes = self.undo_players.contains(&self.current_player);
-        let mut actions: Vec<i32>;
+        let hand = &self.hands[self.current_player as usize];
         if self.state == State::Discard {
-            actions = self.hands[self.current_player as usize]
-                .iter()
-                .map(|c| move_offset(self.state, c))
-                .collect();
-            if allow_undoes {
-                actions.append(
-                    &mut self.draw_decks[self.current_player as usize]
+            actions.extend(hand.iter().map(|c| move_offset(self.state, c)));
+            if self.undo_players.contains(&self.current_player) {
+                actions.extend(
+                    self.draw_decks[self.current_player as usize]
                         .iter()
-                        .map(|c| move_offset(self.state, c))
-                        .collect(),
+                        .map(|c| move_offset(self.state, c)),
                 );
             }
-            return actions;
+            return;
+        }
+        if let Some(masks) = self.hand_masks {
+            let hand = masks[self.current_player as usize];
+            let following = match self.lead_suit {
+                Some(suit) => hand & suit_mask(suit),
+                None => CardMask::EMPTY,
+            };
+            let playable = if following.is_empty() {
+                hand
+            } else {
+                following
+            };
+            actions.extend(playable.ids().map(|id| id + PLAY_OFFSET));
+            return;
         }
         if self.lead_suit.is_some() {
-            actions = self.hands[self.current_player as usize]
-                .iter()
-                .filter(|c| Some(c.suit) == self.lead_suit)
-                .map(|c| move_offset(self.state, c))
-                .collect();
+            actions.extend(
+                hand.iter()
+                    .filter(|c| Some(c.suit) == self.lead_suit)
+                    .map(|c| move_offset(self.state, c)),
+            );
             if !actions.is_empty() {
-                return actions;
+                return;
             }
         }
-        return self.hands[self.current_player as usize]
-            .iter()
-            .map(|c| move_offset(self.state, c))
-            .collect();
+        actions.extend(hand.iter().map(|c| move_offset(self.state, c)));
     }
 
     fn check_hand_end(self: &mut Game) -> bool {
@@ -542,6 +829,16 @@ impl Game {
             &self.tricks_taken,
             self.shorts_piles.iter().map(|sp| sp.len() as i32).collect(),
         );
+        for player in 0..3 {
+            self.round_results.push(RoundResult {
+                round: self.round,
+                dealer: self.dealer,
+                player,
+                tricks_taken: self.tricks_taken[player as usize],
+                shorts: self.shorts_piles[player as usize].len() as i32,
+                points: self.scores[player as usize] - original_scores[player as usize],
+            });
+        }
         let mut max_score = 0;
         for player in 0..3 {
             if self.scores[player] > max_score {
@@ -575,8 +872,16 @@ impl Game {
                 winners.push(player as i32);
             }
         }
-        if self.round >= 3 {
+        if self.final_hand || self.round >= self.final_round() {
             self.winner = Some(winners[0]);
+            let mut standings: Vec<i32> = (0..3).collect();
+            standings.sort_by_key(|player| -self.scores[*player as usize]);
+            self.summary = Some(GameSummary {
+                winner: winners[0],
+                scores: self.scores.clone(),
+                standings,
+                round_results: self.round_results.clone(),
+            });
             self.changes.push(vec![Change {
                 change_type: ChangeType::GameOver,
                 dest: Location::Deck,
@@ -593,18 +898,26 @@ impl Game {
                 dest_offset: 0,
                 ..Default::default()
             }]);
-            self.deal();
+            if self.shuffle_step {
+                self.deck = deck_with_rng(&mut self.shuffle_rng(0));
+                self.state = State::Shuffled;
+                // the next dealer deals (see deal)
+                self.current_player = (self.dealer + 1) % 3;
+            } else {
+                self.deal();
+            }
         }
         return true;
     }
 
     fn show_playable(self: &mut Game) -> Vec<Change> {
+        let _timer = profiling::start("changes");
         if self.no_changes {
             return vec![];
         }
         let mut changes: Vec<Change> = vec![];
 
-        if self.current_player == 0 {
+        if self.is_human(self.current_player) {
             if self.state == State::OptionalDraw {
                 changes.push(Change {
                     object_id: -1,
@@ -654,18 +967,21 @@ impl Game {
     }
 
     fn hide_playable(self: &Game) -> Vec<Change> {
+        let _timer = profiling::start("changes");
         if self.no_changes {
             return vec![];
         }
         let mut changes: Vec<Change> = vec![];
-        for card in &self.hands[0] {
-            changes.push(Change {
-                object_id: card.id,
-                change_type: ChangeType::HidePlayable,
-                dest: Location::Hand,
-                dest_offset: self.current_player,
-                ..Default::default()
-            });
+        if let Some(human) = self.human_player {
+            for card in &self.hands[human as usize] {
+                changes.push(Change {
+                    object_id: card.id,
+                    change_type: ChangeType::HidePlayable,
+                    dest: Location::Hand,
+                    dest_offset: human,
+                    ..Default::default()
+                });
+            }
         }
         changes.push(Change {
             object_id: -1,
@@ -694,20 +1010,13 @@ fn card_sorter(a: &Card, b: &Card) -> Ordering {
 }
 
 pub fn get_winner(lead_suit: Option<Suit>, trick: [Option<Card>; 3]) -> i32 {
-    let mut card_id_to_player: HashMap<i32, i32> = HashMap::new();
-    for (player, card) in trick.iter().enumerate() {
-        if let Some(card) = card {
-            card_id_to_player.insert(card.id, player as i32);
-        }
-    }
-    let mut cards: Vec<Card> = trick
-        .iter() // Convert the Vec into an Iterator
-        .filter_map(|&x| x) // filter_map will only pass through the Some values
-        .collect();
-    cards.sort_by_key(|c| std::cmp::Reverse(value_for_card(lead_suit, c)));
-    return *card_id_to_player
-        .get(&cards.first().expect("there should be a winning card").id)
-        .expect("cards_to_player missing card");
+    let (player, _) = trick
+        .iter()
+        .enumerate()
+        .filter_map(|(player, card)| card.map(|card| (player, card)))
+        .min_by_key(|(_, c)| std::cmp::Reverse(value_for_card(lead_suit, c)))
+        .expect("there should be a winning card");
+    player as i32
 }
 
 pub fn value_for_card(lead_suit: Option<Suit>, card: &Card) -> i32 {
@@ -736,8 +1045,15 @@ pub fn score_game(
     scores
 }
 
-pub fn reorder_hand(player: i32, hand: &Vec<Card>) -> Vec<Change> {
-    let mut changes: Vec<Change> = vec![];
+pub fn reorder_hand(player: i32, hand: &[Card]) -> Vec<Change> {
+    let mut changes: Vec<Change> = Vec::with_capacity(hand.len());
+    reorder_hand_into(&mut changes, player, hand);
+    changes
+}
+
+/// `reorder_hand` appended to an existing change list
+pub fn reorder_hand_into(changes: &mut Vec<Change>, player: i32, hand: &[Card]) {
+    changes.reserve(hand.len());
     for (offset_in_hand, card) in hand.iter().enumerate() {
         changes.push(Change {
             object_id: card.id,
@@ -749,7 +1065,6 @@ pub fn reorder_hand(player: i32, hand: &Vec<Card>) -> Vec<Change> {
             ..Default::default()
         });
     }
-    changes
 }
 
 impl ismcts::Game for Game {
@@ -758,51 +1073,42 @@ impl ismcts::Game for Game {
     type MoveList = Vec<i32>;
 
     fn randomize_determination(&mut self, _observer: Self::PlayerTag) {
+        let _timer = profiling::start("determinization");
+        // the seed would reveal the upcoming shuffles to the search
+        self.seed = None;
+        self.deck.shuffle(&mut thread_rng());
         for p1 in 0..3 {
             for p2 in 0..3 {
                 if p1 == self.current_player() || p2 == self.current_player() || p1 == p2 {
                     continue;
                 }
 
-                let mut combined_voids: HashSet<Suit> =
-                    HashSet::from_iter(self.voids[p1 as usize].iter().cloned());
-                combined_voids.extend(self.voids[p2 as usize].iter());
-
-                let mut new_hands = vec![
-                    self.hands[p1 as usize].clone(),
-                    self.hands[p2 as usize].clone(),
-                ];
-                //println!("original hands: {:?}", new_hands);
+                let voids = [&self.voids[p1 as usize], &self.voids[p2 as usize]];
+                let (hand1, hand2) = pair_mut(&mut self.hands, p1 as usize, p2 as usize);
 
                 // allow swapping of any cards that are not in the combined void set
-                shuffle_and_divide_matching_cards(
-                    |c: &Card| !combined_voids.contains(&c.suit),
-                    &mut new_hands,
+                shuffle_matching_cards_between(
+                    |c: &Card| !voids.iter().any(|void| void.contains(&c.suit)),
+                    hand1,
+                    hand2,
                     &mut thread_rng(),
                 );
 
-                self.hands[p1 as usize] = new_hands[0].clone();
-                self.hands[p2 as usize] = new_hands[1].clone();
-                //println!("new hands: {:?} {:?}", self.hands[p1 as usize], self.hands[p2 as usize]);
-
                 // Draw deck shuffling
 
-                let mut new_draw_decks = vec![
-                    self.draw_decks[p1 as usize].clone(),
-                    self.draw_decks[p2 as usize].clone(),
-                ];
+                let (draw_deck1, draw_deck2) =
+                    pair_mut(&mut self.draw_decks, p1 as usize, p2 as usize);
 
                 // allow swapping of any cards
-                shuffle_and_divide_matching_cards(
+                shuffle_matching_cards_between(
                     |_c: &Card| true,
-                    &mut new_draw_decks,
+                    draw_deck1,
+                    draw_deck2,
                     &mut thread_rng(),
                 );
-
-                self.draw_decks[p1 as usize] = new_draw_decks[0].clone();
-                self.draw_decks[p2 as usize] = new_draw_decks[1].clone();
             }
         }
+        self.update_hand_masks();
     }
 
     fn current_player(&self) -> Self::PlayerTag {
@@ -814,10 +1120,12 @@ impl ismcts::Game for Game {
     }
 
     fn available_moves(&self) -> Self::MoveList {
+        let _timer = profiling::start("get_moves");
         self.get_moves()
     }
 
     fn make_move(&mut self, mov: &Self::Move) {
+        let _timer = profiling::start_in("apply_move", &self.state);
         self.apply_move(*mov);
     }
 
@@ -850,23 +1158,293 @@ impl ismcts::Game for Game {
     }
 }
 
+impl FeatureExtractor for Game {
+    // hand, trick, voids, tricks taken, scores, state, lead suit
+    const FEATURE_COUNT: usize = 48 + 3 * 48 + 3 * 4 + 3 + 3 + 3 + 4;
+    // draw, pass, discards and plays
+    const ACTION_COUNT: usize = PLAY_OFFSET as usize + 48;
+
+    fn features(&self, observer: usize) -> Vec<f32> {
+        let mut features = FeatureBuilder::with_capacity(Self::FEATURE_COUNT);
+        features.cards(self.hands[observer].iter().map(|c| c.id), 48);
+        for offset in 0..3 {
+            let player = relative_seat(observer, offset, 3);
+            features.cards(self.current_trick[player].iter().map(|c| c.id), 48);
+        }
+        for offset in 0..3 {
+            let player = relative_seat(observer, offset, 3);
+            for suit in all::<Suit>() {
+                features.scalar(if self.voids[player].contains(&suit) {
+                    1.0
+                } else {
+                    0.0
+                });
+            }
+        }
+        for offset in 0..3 {
+            let player = relative_seat(observer, offset, 3);
+            features.scalar(self.tricks_taken[player] as f32 / 16.0);
+        }
+        for offset in 0..3 {
+            let player = relative_seat(observer, offset, 3);
+            features.scalar(self.scores[player] as f32 / 25.0);
+        }
+        features
+            // waiting for the deal has a single move so it isn't encoded
+            .one_hot(Some(self.state as usize).filter(|state| *state < 3), 3)
+            .one_hot(self.lead_suit.map(|s| s as usize), 4);
+        features.finish(Self::FEATURE_COUNT)
+    }
+
+    fn action_index(&self, mov: i32) -> usize {
+        mov as usize
+    }
+
+    fn current_seat(&self) -> usize {
+        self.current_player as usize
+    }
+}
+
+impl GameEngine for Game {
+    const NAME: &'static str = "szs";
+    const PLAYER_COUNT: usize = 3;
+
+    fn new_game() -> Self {
+        Game::new()
+    }
+
+    fn scores(&self) -> Vec<i32> {
+        self.scores.clone()
+    }
+
+    fn winners(&self) -> Vec<usize> {
+        self.winner.iter().map(|w| *w as usize).collect()
+    }
+
+    fn legal_moves_into(&self, moves: &mut Vec<i32>) {
+        self.get_moves_into(moves);
+    }
+
+    fn bot_move_with_leaf_evaluation(
+        &self,
+        iterations: i32,
+        policy: Option<PolicyHandle<Self>>,
+        leaf_evaluation: Option<LeafEvaluation<Self>>,
+    ) -> i32 {
+        get_mcts_move_with_leaf_evaluation(self, iterations, policy, leaf_evaluation)
+    }
+
+    fn heuristic_value(&self, seat: usize) -> f64 {
+        // score the hand as if it ended now
+        let shorts = self.shorts_piles.iter().map(|pile| pile.len() as i32);
+        let projected = score_game(self.scores.clone(), &self.tricks_taken, shorts.collect());
+        let best_opponent = (0..projected.len())
+            .filter(|player| *player != seat)
+            .map(|player| projected[player])
+            .max()
+            .unwrap_or(0);
+        let tricks_left = self.hands.iter().map(|hand| hand.len()).max().unwrap_or(0);
+        let own = projected[seat] as f64;
+        projected_margin(own, best_opponent as f64, tricks_left as f64)
+    }
+
+    fn describe_move(&self, action: i32) -> String {
+        let player = self.current_player as usize;
+        match self.state {
+            State::OptionalDraw if action == DRAW => "draw".to_string(),
+            State::OptionalDraw => "pass".to_string(),
+            State::Shuffled => "deal".to_string(),
+            State::Discard | State::Play => {
+                let id = card_offset(self.state, action);
+                let card = self.hands[player]
+                    .iter()
+                    .chain(self.draw_decks[player].iter())
+                    .find(|card| card.id == id)
+                    .map(print_card)
+                    .unwrap_or_else(|| action.to_string());
+                if self.state == State::Discard {
+                    format!("discard {}", card)
+                } else {
+                    card
+                }
+            }
+        }
+    }
+
+    fn render(&self, seat: usize) -> String {
+        let rows: Vec<Vec<String>> = (0..3)
+            .map(|player| {
+                vec![
+                    render::seat_label(player, seat),
+                    self.scores[player].to_string(),
+                    self.tricks_taken[player].to_string(),
+                    self.draw_decks[player].len().to_string(),
+                    self.shorts_piles[player].len().to_string(),
+                ]
+            })
+            .collect();
+        let header = ["player", "score", "tricks", "draw deck", "shorts"];
+        let mut lines = vec![render::table(&header, &rows)];
+        lines.push(format!("lead: {}", render::suit(self.lead_suit.as_ref())));
+        let played: Vec<Option<String>> =
+            self.current_trick.iter().map(|card| card.as_ref().map(print_card)).collect();
+        let lead_player = Some(self.lead_player as usize);
+        lines.push(format!("trick: {}", render::trick(&played, lead_player)));
+        lines.push(format!("hand: {}", render::cards(self.hands[seat].iter().map(print_card))));
+        lines.join("\n")
+    }
+
+    fn events(&self, after: &Self) -> Vec<GameEvent> {
+        let mut events: Vec<GameEvent> = after
+            .changes
+            .iter()
+            .flatten()
+            .find(|change| change.change_type == ChangeType::TricksToWinner)
+            .map(|change| GameEvent::TrickWon {
+                leader: self.lead_player as usize,
+                winner: change.player as usize,
+            })
+            .into_iter()
+            .collect();
+        if after.round != self.round || after.winner.is_some() {
+            events.push(GameEvent::HandOver);
+        }
+        events
+    }
+}
+
 pub fn get_mcts_move(game: &Game, iterations: i32) -> i32 {
+    get_mcts_move_with_policy(game, iterations, None)
+}
+
+pub fn get_mcts_move_with_policy(
+    game: &Game,
+    iterations: i32,
+    policy: Option<PolicyHandle<Game>>,
+) -> i32 {
+    get_mcts_move_with_leaf_evaluation(game, iterations, policy, None)
+}
+
+pub fn get_mcts_move_with_leaf_evaluation(
+    game: &Game,
+    iterations: i32,
+    policy: Option<PolicyHandle<Game>>,
+    leaf_evaluation: Option<LeafEvaluation<Game>>,
+) -> i32 {
     let mut new_game = game.clone();
-    new_game.no_changes = true;
+    new_game.with_no_changes();
     new_game.scores = vec![0, 0, 0];
-    let mut ismcts = IsmctsHandler::new(new_game);
     let parallel_threads: usize = 8;
-    ismcts.run_iterations(
-        parallel_threads,
-        (iterations as f64 / parallel_threads as f64) as usize,
-    );
-    ismcts.best_move().expect("should have a move to make")
+    search_with_leaf_evaluation(new_game, iterations, parallel_threads, policy, leaf_evaluation)
+}
+
+/// Bot strength presets for the apps
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Sequence, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Medium,
+    Hard,
+    Expert,
+}
+
+/// How a difficulty searches - see `Difficulty::settings`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BotSettings {
+    pub iterations: i32,
+    pub parallel_threads: usize,
+    /// Search as if the current hand were the last one so rollouts stop at
+    /// the end of the hand instead of dealing (and guessing) later hands -
+    /// much cheaper but blind to the running totals
+    pub simulate_final_round: bool,
+}
+
+impl Difficulty {
+    pub fn settings(self) -> BotSettings {
+        let (iterations, parallel_threads, simulate_final_round) = match self {
+            Difficulty::Easy => (100, 1, true),
+            Difficulty::Medium => (500, 4, true),
+            Difficulty::Hard => (1000, 8, true),
+            Difficulty::Expert => (4000, 8, false),
+        };
+        BotSettings {
+            iterations,
+            parallel_threads,
+            simulate_final_round,
+        }
+    }
+}
+
+/// The move the bot picks at `difficulty`
+pub fn get_bot_move(game: &Game, difficulty: Difficulty) -> i32 {
+    let settings = difficulty.settings();
+    let mut new_game = game.clone();
+    new_game.with_no_changes();
+    new_game.scores = vec![0, 0, 0];
+    if settings.simulate_final_round {
+        new_game.with_final_hand();
+    }
+    search_with_leaf_evaluation(
+        new_game,
+        settings.iterations,
+        settings.parallel_threads,
+        None,
+        None,
+    )
+}
+
+/// Dart enums for the change protocol - see `cargo run --bin dart`
+pub fn dart_enums() -> String {
+    use crate::dart::dart_enum;
+    [
+        dart_enum::<ChangeType>("ChangeType"),
+        dart_enum::<Location>("Location"),
+        dart_enum::<State>("State"),
+    ]
+    .join("\n")
+}
+
+/// TypeScript declarations of every serialized type in this game
+#[cfg(feature = "typescript")]
+pub fn typescript_declarations() -> String {
+    use ts_rs::TS;
+    [
+        State::decl(),
+        Suit::decl(),
+        Card::decl(),
+        ChangeType::decl(),
+        Location::decl(),
+        Change::decl(),
+        RoundResult::decl(),
+        GameSummary::decl(),
+        Game::decl(),
+    ]
+    .iter()
+    .map(|decl| format!("export {}\n", decl))
+    .collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_features() {
+        crate::features::assert_features_consistent(Game::new(), 3);
+    }
+
+    #[test]
+    fn test_heuristic_value() {
+        let mut game = Game::new();
+        let fewer_tricks = game.heuristic_value(0);
+        game.tricks_taken = [1, 0, 0];
+        let more_tricks = game.heuristic_value(0);
+        // scores are zeroed during search so only the hand can tell these apart
+        assert!(more_tricks > fewer_tricks);
+        assert!(game.heuristic_value(1) < 0.0);
+    }
+
     #[test]
     fn test_deck() {
         let d = deck();
@@ -962,13 +1540,276 @@ mod tests {
     #[test]
     fn test_random_playthrough() {
         let mut game = Game::new();
-        game.round = 4;
+        game.with_final_hand();
         while game.winner.is_none() {
             let action = *game.get_moves().first().unwrap();
             game.apply_move(action);
         }
     }
 
+    #[test]
+    fn test_seeded_deal() {
+        let play = |seed: u64| {
+            let mut game = Game::new_with_seed(seed);
+            let mut states = vec![game.clone()];
+            while game.winner.is_none() {
+                let action = *game.get_moves().first().unwrap();
+                game.apply_move(action);
+                states.push(game.clone());
+            }
+            states
+        };
+        let states = play(7);
+        assert_eq!(states, play(7));
+        assert_ne!(states[0].hands, play(8)[0].hands);
+
+        // restoring a seeded game shuffles the draw decks the same way
+        let discarding = states
+            .iter()
+            .rposition(|s| s.state == State::Discard)
+            .unwrap();
+        let mut restored: Game =
+            serde_json::from_str(&serde_json::to_string(&states[discarding]).unwrap()).unwrap();
+        let action = *restored.get_moves().first().unwrap();
+        restored.apply_move(action);
+        assert_eq!(restored.draw_decks, states[discarding + 1].draw_decks);
+    }
+
+    #[test]
+    fn test_adjacent_seeds_shuffle_differently() {
+        let deck = |seed: u64, round: i32| {
+            let mut game = Game::new_with_seed(seed);
+            game.round = round;
+            deck_with_rng(&mut game.shuffle_rng(0))
+        };
+        assert_eq!(deck(7, 2), deck(7, 2));
+        assert_ne!(deck(7, 2), deck(8, 2));
+        // a seed 2 higher used to replay the previous round's deck
+        assert_ne!(deck(7, 2), deck(9, 1));
+        assert_ne!(deck(7, 2), deck(8, 1));
+    }
+
+    #[test]
+    fn test_shuffle_step() {
+        let mut game = Game::new();
+        game.shuffle_step = true;
+        game.round = 2;
+        while game.state != State::Shuffled {
+            let action = *game.get_moves().first().unwrap();
+            game.apply_move(action);
+        }
+        assert_eq!(game.get_moves(), vec![DEAL]);
+        assert_eq!(game.deck.len(), 48);
+        assert!(game.hands.iter().all(|hand| hand.is_empty()));
+        // the last card of the deck is dealt first
+        let mut deck: Vec<i32> = game.deck.iter().map(|c| c.id).collect();
+        deck.reverse();
+        game.apply_move(DEAL);
+        assert!(game.deck.is_empty());
+        assert_eq!(game.state, State::Discard);
+        assert_eq!(game.round, 3);
+        for (player, hand) in game.hands.iter().enumerate() {
+            let mut hand_ids: Vec<i32> = hand.iter().map(|c| c.id).collect();
+            let mut dealt: Vec<i32> = deck.iter().skip(player).step_by(3).copied().collect();
+            hand_ids.sort();
+            dealt.sort();
+            assert_eq!(hand_ids, dealt);
+        }
+    }
+
+    #[test]
+    fn test_round_results() {
+        let mut game = Game::new();
+        while game.winner.is_none() {
+            assert!(game.summary.is_none());
+            let action = *game.get_moves().first().unwrap();
+            game.apply_move(action);
+        }
+        assert_eq!(game.round_results.len(), 12);
+        for player in 0..3 {
+            let points: i32 = game
+                .round_results
+                .iter()
+                .filter(|result| result.player == player)
+                .map(|result| result.points)
+                .sum();
+            assert_eq!(points, game.scores[player as usize]);
+        }
+        let rounds: Vec<i32> = game.round_results.iter().map(|r| r.round).collect();
+        assert_eq!(rounds, vec![0, 0, 0, 1, 1, 1, 2, 2, 2, 3, 3, 3]);
+
+        let summary = game.summary.clone().unwrap();
+        assert_eq!(Some(summary.winner), game.winner);
+        assert_eq!(summary.standings[0], summary.winner);
+        let standing_scores: Vec<i32> = summary
+            .standings
+            .iter()
+            .map(|player| game.scores[*player as usize])
+            .collect();
+        assert!(standing_scores.windows(2).all(|pair| pair[0] >= pair[1]));
+        assert_eq!(summary.round_results, game.round_results);
+    }
+
+    #[test]
+    fn test_difficulty() {
+        let iterations: Vec<i32> = all::<Difficulty>()
+            .map(|d| d.settings().iterations)
+            .collect();
+        assert!(iterations.windows(2).all(|pair| pair[0] < pair[1]));
+
+        // the easy bot plays a legal hand against the first legal move
+        let mut game = Game::new();
+        while game.round == 0 {
+            let action = if game.current_player == 0 {
+                get_bot_move(&game, Difficulty::Easy)
+            } else {
+                *game.get_moves().first().unwrap()
+            };
+            assert!(game.get_moves().contains(&action));
+            game.apply_move(action);
+        }
+    }
+
+    #[test]
+    fn test_rounds() {
+        let play_out = |mut game: Game| {
+            while game.winner.is_none() {
+                let action = *game.get_moves().choose(&mut thread_rng()).unwrap();
+                game.apply_move(action);
+            }
+            game
+        };
+        assert_eq!(Game::new().final_round(), 3);
+        let game = play_out(Game::new_with_options(GameOptions {
+            rounds: 2,
+            ..Default::default()
+        }));
+        assert_eq!(game.round, 1);
+        assert_eq!(game.round_results.len(), 6);
+        // games saved without a round count play the standard game
+        let mut game = Game::new();
+        game.rounds = 0;
+        assert_eq!(game.final_round(), STANDARD_ROUNDS - 1);
+
+        let options: GameOptions = serde_json::from_str(r#"{"rounds": 1, "seed": 3}"#).unwrap();
+        assert_eq!(options.human_player, Some(0));
+        let game = Game::new_with_options(options);
+        assert_eq!(game.seed, Some(3));
+        assert_eq!(play_out(game).round, 0);
+    }
+
+    #[test]
+    fn test_final_hand() {
+        let mut game = Game::new();
+        game.with_final_hand();
+        while game.winner.is_none() {
+            assert_eq!(ismcts::Game::result(&game, 0), None);
+            let action = *game.get_moves().choose(&mut thread_rng()).unwrap();
+            game.apply_move(action);
+        }
+        assert_eq!(game.round, 0);
+        assert_eq!(game.round_results.len(), 3);
+        assert!(ismcts::Game::result(&game, 0).is_some());
+    }
+
+    #[test]
+    fn test_human_player() {
+        let mut game = Game::new_with_human_player(1);
+        let mut sorted = game.hands[1].clone();
+        sorted.sort_by(card_sorter);
+        assert_eq!(game.hands[1], sorted);
+        while game.winner.is_none() {
+            let current_player = game.current_player;
+            let action = *game.get_moves().choose(&mut thread_rng()).unwrap();
+            game.changes = vec![];
+            game.apply_move(action);
+            for change in game.changes.iter().flatten() {
+                if change.change_type == ChangeType::ShowPlayable {
+                    assert_eq!(change.dest_offset, 1);
+                    assert!(current_player == 1 || game.current_player == 1);
+                }
+            }
+        }
+
+        let mut value = serde_json::to_value(Game::new()).unwrap();
+        value.as_object_mut().unwrap().remove("humanPlayer");
+        let game: Game = serde_json::from_value(value).unwrap();
+        assert_eq!(game.human_player, Some(0));
+    }
+
+    #[test]
+    fn test_hand_masks() {
+        let masks_match = |game: &Game| {
+            let hands = game.hands.clone();
+            game.hand_masks == Some(hands.map(|hand| CardMask::from_ids(hand.iter().map(|c| c.id))))
+        };
+        let mut game = Game::new();
+        game.with_no_changes();
+        while game.winner.is_none() {
+            assert!(masks_match(&game));
+            let mut unmasked = game.clone();
+            unmasked.hand_masks = None;
+            let mut moves = game.get_moves();
+            let mut expected = unmasked.get_moves();
+            moves.sort();
+            expected.sort();
+            assert_eq!(moves, expected);
+
+            let mut determinization = game.clone();
+            ismcts::Game::randomize_determination(&mut determinization, game.current_player);
+            assert!(masks_match(&determinization));
+
+            game.apply_move(*moves.choose(&mut thread_rng()).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_determinization() {
+        let hidden_cards = |game: &Game| {
+            let mut ids: Vec<i32> = game
+                .hands
+                .iter()
+                .flatten()
+                .chain(game.draw_decks.iter().flatten())
+                .map(|c| c.id)
+                .collect();
+            ids.sort();
+            ids
+        };
+        let mut rng = thread_rng();
+        for _ in 0..20 {
+            let mut game = Game::new();
+            game.with_no_changes();
+            for _ in 0..rng.gen_range(0..60) {
+                if game.winner.is_some() {
+                    break;
+                }
+                game.apply_move(*game.get_moves().choose(&mut rng).unwrap());
+            }
+            let observer = game.current_player as usize;
+            for _ in 0..50 {
+                let mut world = game.clone();
+                ismcts::Game::randomize_determination(&mut world, game.current_player);
+                // the observer's own cards and everything on the table stay put
+                assert_eq!(world.hands[observer], game.hands[observer]);
+                assert_eq!(world.draw_decks[observer], game.draw_decks[observer]);
+                assert_eq!(world.current_trick, game.current_trick);
+                assert_eq!(world.shorts_piles, game.shorts_piles);
+                assert_eq!(world.voids, game.voids);
+                // no card is lost, duplicated or moved between hand sizes
+                assert_eq!(hidden_cards(&world), hidden_cards(&game));
+                for player in 0..3 {
+                    let hand = &world.hands[player];
+                    assert_eq!(hand.len(), game.hands[player].len());
+                    let draw_deck = &world.draw_decks[player];
+                    assert_eq!(draw_deck.len(), game.draw_decks[player].len());
+                    let voids = &world.voids[player];
+                    assert!(hand.iter().all(|c| !voids.contains(&c.suit)));
+                }
+            }
+        }
+    }
+
     struct ScoreCase {
         tricks_taken: [i32; 3],
         shorts: Vec<i32>,
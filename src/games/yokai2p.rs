@@ -5,8 +5,8 @@ Yokai Septet Designers: yio, Muneyuki Yokouchi (横内宗幸)
 BoardGameGeek: https://boardgamegeek.com/boardgame/251433/yokai-septet
 */
 
+use colored::Color;
 use enum_iterator::{all, Sequence};
-use ismcts::IsmctsHandler;
 use once_cell::sync::Lazy;
 use rand::seq::SliceRandom;
 use rand::thread_rng;
@@ -16,9 +16,18 @@ use std::{
     collections::{HashMap, HashSet},
 };
 
+use crate::engine::{projected_margin, GameEngine, GameEvent};
+use crate::features::{relative_seat, FeatureBuilder, FeatureExtractor};
+use crate::policy::{search_with_leaf_evaluation, LeafEvaluation, PolicyHandle};
+use crate::profiling;
+use crate::utils::render::{self, SuitStyle};
+use crate::utils::{reset_changes, Hand};
+
 #[derive(
     Debug, PartialOrd, Ord, Clone, Copy, Sequence, Serialize, Deserialize, Hash, PartialEq, Eq,
 )]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub enum Suit {
     Green,
@@ -30,6 +39,36 @@ pub enum Suit {
     Blue,
 }
 
+impl SuitStyle for Suit {
+    fn symbol(&self) -> &'static str {
+        match self {
+            Suit::Green => "G",
+            Suit::Purple => "P",
+            Suit::Pink => "Pk",
+            Suit::Yellow => "Y",
+            Suit::Black => "Bk",
+            Suit::Red => "R",
+            Suit::Blue => "B",
+        }
+    }
+
+    fn color(&self) -> Option<Color> {
+        match self {
+            Suit::Green => Some(Color::Green),
+            Suit::Purple => Some(Color::Magenta),
+            Suit::Pink => Some(Color::BrightMagenta),
+            Suit::Yellow => Some(Color::Yellow),
+            Suit::Black => None,
+            Suit::Red => Some(Color::Red),
+            Suit::Blue => Some(Color::Blue),
+        }
+    }
+}
+
+fn print_card(card: &Card) -> String {
+    render::card(card.value, &card.suit)
+}
+
 pub fn suit_offset(suit: Suit) -> i32 {
     match suit {
         Suit::Green => 0,
@@ -43,6 +82,8 @@ pub fn suit_offset(suit: Suit) -> i32 {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 pub struct Card {
     pub id: i32,
     pub value: i32,
@@ -66,15 +107,16 @@ pub fn deck() -> Vec<Card> {
     deck
 }
 
-static ID_TO_CARD: Lazy<HashMap<i32, Card>> = Lazy::new(|| {
-    let mut m = HashMap::new();
-    for card in deck().iter() {
-        m.insert(card.id, *card);
-    }
-    m
+// Indexed by card id
+static ID_TO_CARD: Lazy<Vec<Card>> = Lazy::new(|| {
+    let mut deck = deck();
+    deck.sort_by_key(|card| card.id);
+    deck
 });
 
 #[derive(Debug, Clone, Copy, Sequence, Default, Serialize, Deserialize, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub enum ChangeType {
     #[default]
@@ -97,6 +139,8 @@ pub enum ChangeType {
 }
 
 #[derive(Debug, Clone, Copy, Sequence, Default, Serialize, Deserialize, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub enum Location {
     #[default]
@@ -115,6 +159,8 @@ pub enum Location {
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub struct Change {
     #[serde(rename(serialize = "type", deserialize = "type"))]
@@ -130,7 +176,9 @@ pub struct Change {
     length: usize,
 }
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Sequence, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub enum State {
     #[default]
@@ -139,11 +187,14 @@ pub enum State {
 }
 
 #[derive(Debug, Clone, Serialize, PartialEq, Eq, Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub struct Yokai2pGame {
     pub state: State,
     pub trump_card: Option<Card>,
-    pub hands: [Vec<Card>; 2],
+    #[cfg_attr(feature = "typescript", ts(as = "[Vec<Card>; 2]"))]
+    pub hands: [Hand<Card, 11>; 2],
     pub changes: Vec<Vec<Change>>,
     pub current_trick: [Option<Card>; 2],
     pub tricks_taken: [i32; 2],
@@ -173,10 +224,11 @@ impl Yokai2pGame {
     }
 
     pub fn deal(&mut self) {
+        let _timer = profiling::start("deal");
         self.lead_suit = None;
         self.round += 1;
         self.tricks_taken = [0, 0];
-        self.hands = [vec![], vec![]];
+        self.hands = Default::default();
         self.state = State::Discard;
         self.current_player = self.lead_player;
         self.lead_player = (self.lead_player + 1) % 2;
@@ -241,7 +293,7 @@ impl Yokai2pGame {
         cards.extend(removed_sevens);
         cards.shuffle(&mut thread_rng());
         // End dealing with sevens
-        self.hands = [vec![], vec![]];
+        self.hands = Default::default();
         for _ in 0..11 {
             for player in 0..2 {
                 let card = cards.pop().unwrap();
@@ -271,18 +323,21 @@ impl Yokai2pGame {
 
     #[inline]
     fn new_change(&mut self) -> usize {
+        let _timer = profiling::start("changes");
         self.changes.push(vec![]);
         self.changes.len() - 1
     }
 
     #[inline]
     fn add_change(&mut self, index: usize, change: Change) {
+        let _timer = profiling::start("changes");
         if !self.no_changes {
             self.changes[index].push(change);
         }
     }
 
     fn show_playable(&mut self) {
+        let _timer = profiling::start("changes");
         if self.changes.is_empty() {
             self.changes = vec![vec![]];
         }
@@ -308,11 +363,12 @@ impl Yokai2pGame {
     }
 
     fn hide_playable(&mut self) {
+        let _timer = profiling::start("changes");
         if self.changes.is_empty() {
             self.changes = vec![vec![]];
         }
         let change_index = self.changes.len() - 1;
-        let mut cards = self.hands[0].clone();
+        let mut cards = self.hands[0].to_vec();
         cards.extend(self.exposed_straw_bottoms(0));
         cards.extend(self.straw_top[0].iter().flatten());
         //cards.sort_by_key(|c| c.id); - needed for verification against Dart engine
@@ -356,28 +412,35 @@ impl Yokai2pGame {
     }
 
     fn get_moves(&self) -> Vec<i32> {
+        let mut moves = vec![];
+        self.get_moves_into(&mut moves);
+        moves
+    }
+
+    /// Replace the contents of `moves` with the legal moves
+    fn get_moves_into(&self, moves: &mut Vec<i32>) {
+        moves.clear();
+        let hand = &self.hands[self.current_player];
         if self.state == State::Discard {
-            return self.hands[self.current_player]
-                .iter()
-                .filter(|c| c.value != 7) // can't discard 7s
-                .map(|c| c.id)
-                .collect();
+            // can't discard 7s
+            moves.extend(hand.iter().filter(|c| c.value != 7).map(|c| c.id));
+            return;
         }
         // must follow
-        let mut playable_cards = self.visible_straw(self.current_player);
-        playable_cards.extend(self.hands[self.current_player].clone());
-
+        let visible = self.visible_straw(self.current_player);
         if let Some(lead_suit) = self.lead_suit {
-            let moves: Vec<i32> = playable_cards
-                .iter()
-                .filter(|c| c.suit == lead_suit)
-                .map(|c| c.id)
-                .collect();
+            moves.extend(
+                visible
+                    .iter()
+                    .chain(hand.iter())
+                    .filter(|c| c.suit == lead_suit)
+                    .map(|c| c.id),
+            );
             if !moves.is_empty() {
-                return moves;
+                return;
             }
         }
-        return playable_cards.iter().map(|c| c.id).collect();
+        moves.extend(visible.iter().chain(hand.iter()).map(|c| c.id));
     }
 
     fn visible_straw(&self, player: usize) -> Vec<Card> {
@@ -440,8 +503,8 @@ impl Yokai2pGame {
             println!("move: {:?}", action);
             panic!("illegal move");
         }
-        self.changes = vec![vec![]]; // card from player to table
-        let card: &Card = ID_TO_CARD.get(&action).unwrap();
+        reset_changes(&mut self.changes); // card from player to table
+        let card: &Card = &ID_TO_CARD[action as usize];
         match self.state {
             State::Discard => {
                 self.hands[self.current_player].retain(|c| c.id != card.id);
@@ -739,6 +802,7 @@ impl ismcts::Game for Yokai2pGame {
     type MoveList = Vec<i32>;
 
     fn randomize_determination(&mut self, _observer: Self::PlayerTag) {
+        let _timer = profiling::start("determinization");
         let rng = &mut thread_rng();
         let mut remaining_cards: Vec<Card> = vec![];
         let mut hidden_straw_bottoms: [HashSet<Card>; 2] = [HashSet::new(), HashSet::new()];
@@ -763,7 +827,7 @@ impl ismcts::Game for Yokai2pGame {
             let original_hand_length: usize = self.hands[player].len();
             if player != self.current_player {
                 let mut pc = extract_short_suited_cards(&remaining_cards, &self.voids[player]);
-                self.hands[player] = vec![];
+                self.hands[player].clear();
                 pc.cards.shuffle(rng);
                 for _ in 0..original_hand_length {
                     let card = pc.cards.pop().unwrap();
@@ -796,10 +860,12 @@ impl ismcts::Game for Yokai2pGame {
     }
 
     fn available_moves(&self) -> Self::MoveList {
+        let _timer = profiling::start("get_moves");
         self.get_moves()
     }
 
     fn make_move(&mut self, mov: &Self::Move) {
+        let _timer = profiling::start_in("apply_move", &self.state);
         self.apply_move(mov);
     }
 
@@ -904,19 +970,195 @@ pub fn extract_short_suited_cards(remaining_cards: &Vec<Card>, voids: &Vec<Suit>
     };
 }
 
+impl FeatureExtractor for Yokai2pGame {
+    // hand, visible straw, trick, trump card, captured sevens, tricks taken,
+    // hand scores, scores, state, lead suit, voids
+    const FEATURE_COUNT: usize = 49 + 2 * 49 + 2 * 49 + 49 + 2 * 49 + 2 + 2 + 2 + 2 + 7 + 2 * 7;
+    const ACTION_COUNT: usize = 49;
+
+    fn features(&self, observer: usize) -> Vec<f32> {
+        let mut features = FeatureBuilder::with_capacity(Self::FEATURE_COUNT);
+        features.cards(self.hands[observer].iter().map(|c| c.id), 49);
+        for offset in 0..2 {
+            let player = relative_seat(observer, offset, 2);
+            features.cards(self.visible_straw(player).iter().map(|c| c.id), 49);
+        }
+        for offset in 0..2 {
+            let player = relative_seat(observer, offset, 2);
+            features.cards(self.current_trick[player].iter().map(|c| c.id), 49);
+        }
+        features.cards(self.trump_card.iter().map(|c| c.id), 49);
+        for offset in 0..2 {
+            let player = relative_seat(observer, offset, 2);
+            features.cards(self.captured_sevens[player].iter().map(|c| c.id), 49);
+        }
+        for offset in 0..2 {
+            let player = relative_seat(observer, offset, 2);
+            features
+                .scalar(self.tricks_taken[player] as f32 / 8.0)
+                .scalar(self.hand_scores[player] as f32 / 7.0)
+                .scalar(self.scores[player] as f32 / 7.0);
+        }
+        features
+            .one_hot(Some(self.state.clone() as usize), 2)
+            .one_hot(self.lead_suit.map(|s| s as usize), 7);
+        for offset in 0..2 {
+            let player = relative_seat(observer, offset, 2);
+            for suit in all::<Suit>() {
+                features.scalar(if self.voids[player].contains(&suit) {
+                    1.0
+                } else {
+                    0.0
+                });
+            }
+        }
+        features.finish(Self::FEATURE_COUNT)
+    }
+
+    fn action_index(&self, mov: i32) -> usize {
+        mov as usize
+    }
+
+    fn current_seat(&self) -> usize {
+        self.current_player
+    }
+}
+
+impl GameEngine for Yokai2pGame {
+    const NAME: &'static str = "yokai2p";
+    const PLAYER_COUNT: usize = 2;
+
+    fn new_game() -> Self {
+        Yokai2pGame::new()
+    }
+
+    fn scores(&self) -> Vec<i32> {
+        self.scores.to_vec()
+    }
+
+    fn winners(&self) -> Vec<usize> {
+        self.winner.into_iter().collect()
+    }
+
+    fn legal_moves_into(&self, moves: &mut Vec<i32>) {
+        self.get_moves_into(moves);
+    }
+
+    fn bot_move_with_leaf_evaluation(
+        &self,
+        iterations: i32,
+        policy: Option<PolicyHandle<Self>>,
+        leaf_evaluation: Option<LeafEvaluation<Self>>,
+    ) -> i32 {
+        get_mcts_move_with_leaf_evaluation(self, iterations, policy, leaf_evaluation)
+    }
+
+    fn heuristic_value(&self, seat: usize) -> f64 {
+        // points each player would score for their sevens by winning the hand
+        let mut projected = self.scores;
+        if let Some(trump_card) = self.trump_card {
+            for player in 0..2 {
+                projected[player] += score_sevens(&self.captured_sevens[player], &trump_card);
+            }
+        }
+        let straw = self.straw_top[seat].iter().chain(&self.straw_bottom[seat]);
+        let tricks_left = self.hands[seat].len() + straw.flatten().count();
+        let opponent = projected[1 - seat] as f64;
+        projected_margin(projected[seat] as f64, opponent, tricks_left as f64)
+    }
+
+    fn describe_move(&self, action: i32) -> String {
+        let player = self.current_player;
+        let card = self.hands[player]
+            .iter()
+            .copied()
+            .chain(self.visible_straw(player))
+            .find(|card| card.id == action)
+            .map(|card| print_card(&card))
+            .unwrap_or_else(|| action.to_string());
+        match self.state {
+            State::Discard => format!("discard {}", card),
+            State::PlayCard => card,
+        }
+    }
+
+    fn render(&self, seat: usize) -> String {
+        let rows: Vec<Vec<String>> = (0..2)
+            .map(|player| {
+                // face down straw cards are only counted
+                let face_down = self.straw_bottom[player]
+                    .iter()
+                    .zip(self.straw_top[player].iter())
+                    .filter(|(bottom, top)| bottom.is_some() && top.is_some())
+                    .count();
+                let straw = render::cards(self.visible_straw(player).iter().map(print_card));
+                vec![
+                    render::seat_label(player, seat),
+                    self.scores[player].to_string(),
+                    self.hand_scores[player].to_string(),
+                    self.tricks_taken[player].to_string(),
+                    render::cards(self.captured_sevens[player].iter().map(print_card)),
+                    format!("{} (+{} face down)", straw, face_down),
+                ]
+            })
+            .collect();
+        let header = ["player", "score", "hand score", "tricks", "sevens", "straw"];
+        let mut lines = vec![render::table(&header, &rows)];
+        let trump = self.trump_card.as_ref().map(print_card);
+        lines.push(format!("trump: {}", trump.unwrap_or_else(|| "-".to_string())));
+        let played: Vec<Option<String>> =
+            self.current_trick.iter().map(|card| card.as_ref().map(print_card)).collect();
+        lines.push(format!("trick: {}", render::trick(&played, Some(self.lead_player))));
+        lines.push(format!("hand: {}", render::cards(self.hands[seat].iter().map(print_card))));
+        lines.join("\n")
+    }
+
+    fn events(&self, after: &Self) -> Vec<GameEvent> {
+        let mut events: Vec<GameEvent> = after
+            .changes
+            .iter()
+            .flatten()
+            .find(|change| change.change_type == ChangeType::TricksToWinner)
+            .map(|change| GameEvent::TrickWon {
+                leader: self.lead_player,
+                winner: change.player,
+            })
+            .into_iter()
+            .collect();
+        if after.round != self.round || after.winner.is_some() {
+            events.push(GameEvent::HandOver);
+        }
+        events
+    }
+}
+
 pub fn get_mcts_move(game: &Yokai2pGame, iterations: i32) -> i32 {
+    get_mcts_move_with_policy(game, iterations, None)
+}
+
+pub fn get_mcts_move_with_policy(
+    game: &Yokai2pGame,
+    iterations: i32,
+    policy: Option<PolicyHandle<Yokai2pGame>>,
+) -> i32 {
+    get_mcts_move_with_leaf_evaluation(game, iterations, policy, None)
+}
+
+pub fn get_mcts_move_with_leaf_evaluation(
+    game: &Yokai2pGame,
+    iterations: i32,
+    policy: Option<PolicyHandle<Yokai2pGame>>,
+    leaf_evaluation: Option<LeafEvaluation<Yokai2pGame>>,
+) -> i32 {
     let mut new_game = game.clone();
     new_game.no_changes = true;
-    let mut ismcts = IsmctsHandler::new(new_game);
     let parallel_threads: usize = 8;
-    ismcts.run_iterations(
-        parallel_threads,
-        (iterations as f64 / parallel_threads as f64) as usize,
-    );
-    ismcts.best_move().expect("should have a move to make")
+    search_with_leaf_evaluation(new_game, iterations, parallel_threads, policy, leaf_evaluation)
 }
 
 #[derive(Debug, Clone, Serialize, PartialEq, Eq, Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub struct Yokai2pDartFormat {
     pub state: State,
@@ -947,7 +1189,7 @@ impl Yokai2pDartFormat {
         Yokai2pGame {
             state: self.state.clone(),
             trump_card: self.trump_card.clone(),
-            hands: self.hands.clone(),
+            hands: [self.hands[0].to_vec(), self.hands[1].to_vec()],
             changes,
             current_trick: [trick1, trick2],
             tricks_taken: [
@@ -973,3 +1215,33 @@ impl Yokai2pDartFormat {
         }
     }
 }
+
+/// Dart enums for the change protocol - see `cargo run --bin dart`
+pub fn dart_enums() -> String {
+    use crate::dart::dart_enum;
+    [
+        dart_enum::<ChangeType>("ChangeType"),
+        dart_enum::<Location>("Location"),
+        dart_enum::<State>("State"),
+    ]
+    .join("\n")
+}
+
+/// TypeScript declarations of every serialized type in this game
+#[cfg(feature = "typescript")]
+pub fn typescript_declarations() -> String {
+    use ts_rs::TS;
+    [
+        Suit::decl(),
+        Card::decl(),
+        ChangeType::decl(),
+        Location::decl(),
+        Change::decl(),
+        State::decl(),
+        Yokai2pGame::decl(),
+        Yokai2pDartFormat::decl(),
+    ]
+    .iter()
+    .map(|decl| format!("export {}\n", decl))
+    .collect()
+}
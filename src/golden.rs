@@ -0,0 +1,389 @@
+/*
+Golden tests - replaying games recorded by another implementation
+
+A golden file is JSONL with one `{"move": <move or null>, "gameState": {...}}`
+object per line. A line without a move starts (or restarts) the replay from
+its state; every other line is the state expected after playing its move on
+the previous state. States must be in the engine's own serialization.
+
+Some differences are expected rather than bugs:
+
+- `ignored` fields are left out of the comparison (tracking the other
+  implementation doesn't keep)
+- `synced` fields are copied from the expected state after every move so
+  the replay follows the recorded deal (decks the other implementation
+  shuffled, the dealer it picked)
+- states that include a shuffle change are adopted as is since the
+  intermediate state before the shuffle isn't recorded (szs games played
+  with `shuffleStep` stop after the shuffle instead so only the shuffled
+  deck has to be synced - see GoldenConfig::szs_shuffle_step)
+
+A mismatch is reported with its differing fields and the replay continues
+from the expected state.
+
+`generate_golden` writes files in the same format from seeded random games
+so the Dart implementations can be checked against the Rust engines. The
+seed picks the moves and, for games with seeded deals (szs), the cards so
+the same seed writes the same file - the other engines deal with thread_rng.
+*/
+
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::registry::{self, DynGame, RegistryError};
+use crate::utils::diff::{diff_values, Difference};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GoldenConfig {
+    pub game: String,
+    /// Field names (anywhere in the state) or full field paths to skip
+    pub ignored: Vec<String>,
+    /// Top level fields copied from the expected state after each move
+    pub synced: Vec<String>,
+    /// Change types after which the state is adopted instead of checked
+    pub resync_changes: Vec<String>,
+    /// Drop empty change groups before comparing
+    pub drop_empty_changes: bool,
+}
+
+impl GoldenConfig {
+    pub fn new(game: &str) -> Self {
+        GoldenConfig {
+            game: game.to_string(),
+            ignored: vec![],
+            synced: vec![],
+            resync_changes: vec!["shuffle".to_string()],
+            drop_empty_changes: false,
+        }
+    }
+
+    /// The settings data/szs.multiplegames.json was verified with
+    pub fn szs() -> Self {
+        GoldenConfig {
+            // the hand history isn't kept by the Dart implementation
+            ignored: vec![
+                "voids".to_string(),
+                "roundResults".to_string(),
+                "summary".to_string(),
+            ],
+            synced: vec!["dealer".to_string(), "drawDecks".to_string()],
+            ..GoldenConfig::new("szs")
+        }
+    }
+
+    /// Settings for szs games recorded with `shuffleStep` - every move
+    /// including the reshuffle between rounds is checked
+    pub fn szs_shuffle_step() -> Self {
+        GoldenConfig {
+            synced: vec!["drawDecks".to_string(), "deck".to_string()],
+            resync_changes: vec![],
+            ..GoldenConfig::szs()
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum GoldenError {
+    Io(io::Error),
+    /// Line (1 based) that isn't a test case
+    InvalidCase(usize, serde_json::Error),
+    Registry(usize, RegistryError),
+}
+
+impl fmt::Display for GoldenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GoldenError::Io(error) => write!(f, "{}", error),
+            GoldenError::InvalidCase(line, error) => write!(f, "line {}: {}", line, error),
+            GoldenError::Registry(line, error) => write!(f, "line {}: {}", line, error),
+        }
+    }
+}
+
+impl Error for GoldenError {}
+
+impl From<io::Error> for GoldenError {
+    fn from(error: io::Error) -> Self {
+        GoldenError::Io(error)
+    }
+}
+
+/// Safety net for engines that never reach a terminal state
+const MAX_MOVES_PER_GAME: usize = 10_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TestCase {
+    #[serde(rename = "move")]
+    action: Option<i32>,
+    game_state: Value,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    pub line: usize,
+    pub action: i32,
+    pub differences: Vec<Difference>,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {} move {}:", self.line, self.action)?;
+        for difference in self.differences.iter() {
+            write!(f, "\n  {}", difference)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GoldenReport {
+    pub cases: usize,
+    /// Moves whose resulting state matched
+    pub verified: usize,
+    /// Starting states and states adopted after a resync change
+    pub adopted: usize,
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl GoldenReport {
+    pub fn passed(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+fn has_change(state: &Value, change_types: &[String]) -> bool {
+    let groups = state.get("changes").and_then(Value::as_array);
+    for group in groups.into_iter().flatten() {
+        for change in group.as_array().into_iter().flatten() {
+            let change_type = change.get("type").and_then(Value::as_str).unwrap_or_default();
+            if change_types.iter().any(|resync| resync == change_type) {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+fn drop_empty_changes(state: &mut Value) {
+    if let Some(Value::Array(groups)) = state.get_mut("changes") {
+        groups.retain(|group| !matches!(group.as_array(), Some(group) if group.is_empty()));
+    }
+}
+
+fn to_value(game: &dyn DynGame) -> Value {
+    serde_json::from_str(&game.state_json()).expect("game state should serialize")
+}
+
+/// Replay every test case read from `reader`
+pub fn verify_golden(
+    config: &GoldenConfig,
+    reader: impl BufRead,
+) -> Result<GoldenReport, GoldenError> {
+    let ignored: Vec<&str> = config.ignored.iter().map(String::as_str).collect();
+    let mut game =
+        registry::new_game(&config.game).map_err(|error| GoldenError::Registry(0, error))?;
+    let mut expected_game = game.clone_game();
+    let mut report = GoldenReport::default();
+    for (index, line) in reader.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let case: TestCase = serde_json::from_str(&line)
+            .map_err(|error| GoldenError::InvalidCase(line_number, error))?;
+        let registry_error = |error| GoldenError::Registry(line_number, error);
+        report.cases += 1;
+        // normalize through the engine so defaulted fields compare equal
+        expected_game
+            .set_state_json(&case.game_state.to_string())
+            .map_err(registry_error)?;
+        let action = match case.action {
+            Some(action) if !has_change(&case.game_state, &config.resync_changes) => action,
+            _ => {
+                game.set_state_json(&expected_game.state_json()).map_err(registry_error)?;
+                report.adopted += 1;
+                continue;
+            }
+        };
+        game.apply_move(action).map_err(registry_error)?;
+        let mut expected = to_value(expected_game.as_ref());
+        let mut actual = to_value(game.as_ref());
+        if let (Value::Object(actual), Value::Object(expected)) = (&mut actual, &expected) {
+            for field in config.synced.iter() {
+                if let Some(value) = expected.get(field) {
+                    actual.insert(field.clone(), value.clone());
+                }
+            }
+        }
+        game.set_state_json(&actual.to_string()).map_err(registry_error)?;
+        if config.drop_empty_changes {
+            drop_empty_changes(&mut actual);
+            drop_empty_changes(&mut expected);
+        }
+        let differences = diff_values(&actual, &expected, &ignored);
+        if differences.is_empty() {
+            report.verified += 1;
+        } else {
+            report.mismatches.push(Mismatch {
+                line: line_number,
+                action,
+                differences,
+            });
+            game.set_state_json(&expected_game.state_json()).map_err(registry_error)?;
+        }
+    }
+    Ok(report)
+}
+
+/// Write `games` random games of the named game as test cases - returns the
+/// number of lines written
+pub fn generate_golden(
+    name: &str,
+    games: usize,
+    seed: u64,
+    mut writer: impl Write,
+) -> Result<usize, GoldenError> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut lines = 0;
+    let mut write_case = |action: Option<i32>, game: &dyn DynGame| -> Result<(), GoldenError> {
+        let case = TestCase {
+            action,
+            game_state: to_value(game),
+        };
+        let line = serde_json::to_string(&case).expect("test case should serialize");
+        writeln!(writer, "{}", line)?;
+        lines += 1;
+        Ok(())
+    };
+    for _ in 0..games {
+        let mut game = registry::new_seeded_game(name, rng.gen())
+            .map_err(|error| GoldenError::Registry(0, error))?;
+        write_case(None, game.as_ref())?;
+        let mut moves = 0;
+        while game.winners().is_empty() && moves < MAX_MOVES_PER_GAME {
+            let action = *game.moves().choose(&mut rng).expect("should have a move to make");
+            game.apply_move(action).map_err(|error| GoldenError::Registry(0, error))?;
+            write_case(Some(action), game.as_ref())?;
+            moves += 1;
+        }
+    }
+    Ok(lines)
+}
+
+pub fn verify_golden_file(config: &GoldenConfig, path: &str) -> Result<GoldenReport, GoldenError> {
+    verify_golden(config, BufReader::new(File::open(path)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn golden_lines(game: &str, moves: usize) -> Vec<String> {
+        let mut game = registry::new_game(game).unwrap();
+        let line = |action: Option<i32>, game: &dyn DynGame| {
+            let state: Value = serde_json::from_str(&game.state_json()).unwrap();
+            serde_json::json!({"move": action, "gameState": state}).to_string()
+        };
+        let mut lines = vec![line(None, game.as_ref())];
+        for _ in 0..moves {
+            let action = game.moves()[0];
+            game.apply_move(action).unwrap();
+            lines.push(line(Some(action), game.as_ref()));
+        }
+        lines
+    }
+
+    #[test]
+    fn test_replay() {
+        let lines = golden_lines("hotdog", 5).join("\n");
+        let report = verify_golden(&GoldenConfig::new("hotdog"), lines.as_bytes()).unwrap();
+        assert!(report.passed());
+        assert_eq!((report.cases, report.adopted, report.verified), (6, 1, 5));
+    }
+
+    #[test]
+    fn test_generate() {
+        let mut output = vec![];
+        let lines = generate_golden("hotdog", 2, 7, &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.lines().count(), lines);
+        assert!(output.starts_with("{\"move\":null,\"gameState\":{"));
+        let report = verify_golden(&GoldenConfig::new("hotdog"), output.as_bytes()).unwrap();
+        assert!(report.passed());
+        assert_eq!(report.cases, lines);
+        // each game starts from its recorded state
+        assert!(report.adopted >= 2);
+        assert!(generate_golden("chess", 1, 0, io::sink()).is_err());
+    }
+
+    #[test]
+    fn test_generate_reproducible() {
+        let generate = |seed: u64| {
+            let mut output = vec![];
+            generate_golden("szs", 2, seed, &mut output).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+        let output = generate(7);
+        assert_eq!(output, generate(7));
+        assert_ne!(output, generate(8));
+    }
+
+    #[test]
+    fn test_szs_shuffle_step() {
+        let mut game = registry::new_game("szs").unwrap();
+        let mut state: Value = serde_json::from_str(&game.state_json()).unwrap();
+        state["shuffleStep"] = Value::from(true);
+        game.set_state_json(&state.to_string()).unwrap();
+        let mut lines = vec![];
+        let mut action = None;
+        loop {
+            let state: Value = serde_json::from_str(&game.state_json()).unwrap();
+            lines.push(serde_json::json!({"move": action, "gameState": state}).to_string());
+            if !game.winners().is_empty() {
+                break;
+            }
+            action = Some(game.moves()[0]);
+            game.apply_move(action.unwrap()).unwrap();
+        }
+        let output = lines.join("\n");
+        let report = verify_golden(&GoldenConfig::szs_shuffle_step(), output.as_bytes()).unwrap();
+        assert!(report.passed());
+        assert!(output.contains("\"type\":\"shuffle\""));
+        // nothing but the starting state is adopted
+        assert_eq!(report.adopted, 1);
+    }
+
+    #[test]
+    fn test_mismatch() {
+        let mut lines = golden_lines("hotdog", 2);
+        let mut state: Value = serde_json::from_str(&lines[2]).unwrap();
+        state["gameState"]["scores"][0] = Value::from(99);
+        lines[2] = state.to_string();
+        let config = GoldenConfig::new("hotdog");
+        let report = verify_golden(&config, lines.join("\n").as_bytes()).unwrap();
+        assert_eq!(report.verified, 1);
+        assert_eq!(report.mismatches.len(), 1);
+        assert_eq!(report.mismatches[0].differences[0].path, "scores[0]");
+
+        let config = GoldenConfig {
+            ignored: vec!["scores".to_string()],
+            ..GoldenConfig::new("hotdog")
+        };
+        assert!(verify_golden(&config, lines.join("\n").as_bytes()).unwrap().passed());
+        assert!(matches!(
+            verify_golden(&config, "{".as_bytes()),
+            Err(GoldenError::InvalidCase(1, _))
+        ));
+    }
+}
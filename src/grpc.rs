@@ -0,0 +1,194 @@
+/*
+gRPC service over the registry (enabled with the `grpc` feature)
+
+For deployments where all rules run on the server and the client only
+renders changes. Games are kept in memory keyed by an id handed out by
+CreateGame - see proto/engine.proto for the messages.
+*/
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use tonic::{Request, Response, Status};
+
+use crate::registry::{self, DynGame, RegistryError};
+
+pub mod proto {
+    tonic::include_proto!("tricksterstable");
+}
+
+use proto::engine_server::Engine;
+use proto::{
+    ApplyMoveRequest, CreateGameRequest, GameReply, GetBotMoveReply, GetBotMoveRequest,
+    GetStateRequest,
+};
+
+pub use proto::engine_server::EngineServer;
+
+#[derive(Default)]
+pub struct EngineService {
+    games: Mutex<HashMap<String, Box<dyn DynGame>>>,
+    next_id: AtomicU64,
+}
+
+fn status(error: RegistryError) -> Status {
+    match error {
+        RegistryError::UnknownGame(_) => Status::not_found(error.to_string()),
+        _ => Status::invalid_argument(error.to_string()),
+    }
+}
+
+fn game_reply(id: &str, game: &dyn DynGame) -> GameReply {
+    GameReply {
+        id: id.to_string(),
+        game: game.name().to_string(),
+        current_seat: game.current_seat() as u32,
+        moves: game.moves(),
+        winners: game.winners().iter().map(|seat| *seat as u32).collect(),
+        scores: game.scores(),
+        state_json: game.state_json(),
+        changes_json: game.changes_json(),
+    }
+}
+
+impl EngineService {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `f` on the game with `id`
+    fn with_game<T>(
+        &self,
+        id: &str,
+        f: impl FnOnce(&mut dyn DynGame) -> Result<T, Status>,
+    ) -> Result<T, Status> {
+        let mut games = self.games.lock().expect("game lock poisoned");
+        match games.get_mut(id) {
+            Some(game) => f(game.as_mut()),
+            None => Err(Status::not_found(format!("no game with id {}", id))),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Engine for EngineService {
+    async fn create_game(
+        &self,
+        request: Request<CreateGameRequest>,
+    ) -> Result<Response<GameReply>, Status> {
+        let request = request.into_inner();
+        let game = match request.state_json {
+            Some(state) => registry::load_game(&request.game, &state),
+            None => registry::new_game(&request.game),
+        }
+        .map_err(status)?;
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed).to_string();
+        let reply = game_reply(&id, game.as_ref());
+        self.games
+            .lock()
+            .expect("game lock poisoned")
+            .insert(id, game);
+        Ok(Response::new(reply))
+    }
+
+    async fn get_state(
+        &self,
+        request: Request<GetStateRequest>,
+    ) -> Result<Response<GameReply>, Status> {
+        let id = request.into_inner().id;
+        self.with_game(&id, |game| Ok(game_reply(&id, game)))
+            .map(Response::new)
+    }
+
+    async fn apply_move(
+        &self,
+        request: Request<ApplyMoveRequest>,
+    ) -> Result<Response<GameReply>, Status> {
+        let request = request.into_inner();
+        self.with_game(&request.id, |game| {
+            game.apply_move(request.action).map_err(status)?;
+            Ok(game_reply(&request.id, game))
+        })
+        .map(Response::new)
+    }
+
+    /// Searches on a copy of the game off the async runtime's threads
+    async fn get_bot_move(
+        &self,
+        request: Request<GetBotMoveRequest>,
+    ) -> Result<Response<GetBotMoveReply>, Status> {
+        let request = request.into_inner();
+        let game = self.with_game(&request.id, |game| {
+            if game.moves().is_empty() {
+                return Err(Status::failed_precondition("game is over"));
+            }
+            Ok(game.clone_game())
+        })?;
+        let action = tokio::task::spawn_blocking(move || game.bot_move(request.iterations))
+            .await
+            .map_err(|error| Status::internal(error.to_string()))?;
+        Ok(Response::new(GetBotMoveReply { action }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_engine_service() {
+        let service = EngineService::new();
+        let created = service
+            .create_game(Request::new(CreateGameRequest {
+                game: "kansascity".to_string(),
+                state_json: None,
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(created.game, "kansascity");
+        assert!(!created.moves.is_empty());
+
+        let bot_move = service
+            .get_bot_move(Request::new(GetBotMoveRequest {
+                id: created.id.clone(),
+                iterations: 10,
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(created.moves.contains(&bot_move.action));
+
+        let applied = service
+            .apply_move(Request::new(ApplyMoveRequest {
+                id: created.id.clone(),
+                action: bot_move.action,
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        let state = service
+            .get_state(Request::new(GetStateRequest {
+                id: created.id.clone(),
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(state, applied);
+
+        let illegal = service
+            .apply_move(Request::new(ApplyMoveRequest {
+                id: created.id.clone(),
+                action: i32::MIN,
+            }))
+            .await;
+        assert_eq!(illegal.unwrap_err().code(), tonic::Code::InvalidArgument);
+        let missing = service
+            .get_state(Request::new(GetStateRequest {
+                id: "missing".to_string(),
+            }))
+            .await;
+        assert_eq!(missing.unwrap_err().code(), tonic::Code::NotFound);
+    }
+}
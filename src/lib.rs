@@ -1,2 +1,38 @@
+pub mod analysis;
+pub mod arena;
+pub mod dart;
+pub mod engine;
+pub mod env;
+pub mod features;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 pub mod games;
+pub mod golden;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "uniffi")]
+pub mod mobile;
+pub mod models;
+#[cfg(feature = "onnx")]
+pub mod onnx;
+pub mod play;
+pub mod policy;
+pub mod profiling;
+pub mod protocol;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod quantized;
+pub mod registry;
+pub mod repro;
+pub mod simulate;
+pub mod tournament;
+pub mod training;
 pub mod utils;
+pub mod validate;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "protobuf")]
+pub mod wire;
+
+#[cfg(feature = "uniffi")]
+uniffi::setup_scaffolding!();
@@ -1,184 +1,429 @@
-use games::szs::{ChangeType, Game};
-use ismcts::{Game as MctsGame, IsmctsHandler};
-use rand::{seq::SliceRandom, thread_rng};
-use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
-use std::fs::File;
-use std::io::{self, prelude::*, BufReader};
+use clap::{Args, Parser, Subcommand};
+use serde::Serialize;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
 use std::time::Instant;
+use tricksterstable_rs::analysis::{analyze, AnalysisConfig};
+use tricksterstable_rs::games::kaibosh::{get_mcts_move, policy_model, KaiboshGame};
+use tricksterstable_rs::golden::{generate_golden, verify_golden_file, GoldenConfig, GoldenError};
+use tricksterstable_rs::play::{play, PlayOptions};
+use tricksterstable_rs::registry;
+use tricksterstable_rs::repro::ReproBundle;
+use tricksterstable_rs::simulate::{
+    progress_line, scaling, simulate, thread_counts, SimulationConfig,
+};
+use tricksterstable_rs::tournament::{run_tournament, BotConfig, TournamentConfig};
+use tricksterstable_rs::utils::diff::{diff_values, DART_IGNORED};
 
-pub mod games;
-pub mod utils;
+const SZS_GOLDEN_FILE: &str = "data/szs.multiplegames.json";
+
+#[derive(Parser)]
+#[command(about = "Trickster's Table engines - play, benchmark and verify the games")]
+struct Cli {
+    /// Verifies data/szs.multiplegames.json when no command is given
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Play a game against the bots in the terminal
+    Play(PlayArgs),
+    /// Rotate bot configurations around the table - see src/tournament.rs
+    Tournament(TournamentArgs),
+    /// Score, trick and bid statistics of random or bot games
+    Analyze(AnalyzeArgs),
+    /// Print every field that differs between two states of a game
+    Diff(DiffArgs),
+    /// Write seeded random games as JSONL test cases - see src/golden.rs
+    GenTestcases(GenTestcasesArgs),
+    /// Play a batch of games on several threads - see src/simulate.rs
+    Simulate(SimulateArgs),
+    /// Time the same random games at 1, 2, 4... threads - see src/simulate.rs
+    Bench(BenchArgs),
+    /// Replay a szs golden file recorded by the Dart implementation
+    Verify(VerifyArgs),
+    /// Replay a failure written by simulate - see src/repro.rs
+    Repro(ReproArgs),
+    /// Dump a kaibosh policy's feature importances and move logits as JSON
+    InspectPolicy(InspectPolicyArgs),
+}
+
+#[derive(Args)]
+struct PlayArgs {
+    game: String,
+    /// Human seat (repeat for several) - seat 0 when none are given
+    #[arg(long = "seat")]
+    seats: Vec<usize>,
+    /// ISMCTS iterations per bot move
+    #[arg(long, default_value_t = 1000)]
+    iterations: i32,
+}
+
+#[derive(Args)]
+struct TournamentArgs {
+    game: String,
+    /// mcts:<iterations> or random (repeat for each bot)
+    #[arg(long = "bot", value_parser = BotConfig::parse)]
+    bots: Vec<BotConfig>,
+    #[arg(long, default_value_t = 10)]
+    deals: usize,
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Worker threads - every core when not given
+    #[arg(long)]
+    threads: Option<usize>,
+    /// Play each deal once instead of once per lineup
+    #[arg(long)]
+    no_rotate: bool,
+    /// Per game results (CSV for .csv paths, JSON otherwise)
+    #[arg(long)]
+    results: Option<String>,
+    /// Per bot summary (CSV for .csv paths, JSON otherwise)
+    #[arg(long)]
+    summary: Option<String>,
+}
+
+#[derive(Args)]
+struct AnalyzeArgs {
+    game: String,
+    #[arg(long, default_value_t = 100)]
+    games: usize,
+    /// ISMCTS iterations per move - random moves when not given
+    #[arg(long)]
+    iterations: Option<i32>,
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Write the report as JSON
+    #[arg(long)]
+    json: Option<String>,
+}
+
+#[derive(Args)]
+struct DiffArgs {
+    game: String,
+    rust_state: String,
+    expected_state: String,
+    /// Field name or path to skip (voids and drawDecks always are)
+    #[arg(long = "ignore")]
+    ignored: Vec<String>,
+}
+
+#[derive(Args)]
+struct GenTestcasesArgs {
+    game: String,
+    #[arg(long, default_value_t = 1)]
+    games: usize,
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Write to a file instead of stdout
+    #[arg(long)]
+    output: Option<String>,
+}
+
+#[derive(Args)]
+struct SimulateArgs {
+    game: String,
+    #[arg(long, default_value_t = 1000)]
+    games: usize,
+    /// Worker threads - every core when not given
+    #[arg(long)]
+    threads: Option<usize>,
+    /// Seed of the first game - the run covers seed..seed + games
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// mcts:<iterations> or random (repeat for each seat) - random when none
+    #[arg(long = "bot", value_parser = BotConfig::parse)]
+    bots: Vec<BotConfig>,
+    /// Report the time spent in each phase of the engine
+    #[arg(long)]
+    profile: bool,
+    /// Check the change list emitted by every move
+    #[arg(long)]
+    check: bool,
+    /// Where repro bundles of failed games are written
+    #[arg(long, default_value = "repro")]
+    repro_dir: PathBuf,
+    /// Write the report as JSON
+    #[arg(long)]
+    json: Option<String>,
+}
+
+#[derive(Args)]
+struct BenchArgs {
+    game: String,
+    #[arg(long, default_value_t = 10_000)]
+    games: usize,
+    /// Most worker threads to time - every core when not given
+    #[arg(long)]
+    threads: Option<usize>,
+    /// Seed of the first game - every thread count plays seed..seed + games
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Write the report as JSON
+    #[arg(long)]
+    json: Option<String>,
+}
+
+#[derive(Args)]
+struct VerifyArgs {
+    #[arg(default_value = SZS_GOLDEN_FILE)]
+    path: String,
+}
+
+#[derive(Args)]
+struct ReproArgs {
+    bundle: PathBuf,
+    /// Replay every move from the starting state instead of only the failing one
+    #[arg(long)]
+    from_start: bool,
+}
+
+#[derive(Args)]
+struct InspectPolicyArgs {
+    policy: PathBuf,
+    /// Bot moves played from a new game before the position is inspected
+    #[arg(long, default_value_t = 0)]
+    moves: usize,
+    /// ISMCTS iterations per bot move
+    #[arg(long, default_value_t = 500)]
+    iterations: i32,
+    /// Features listed overall and for each move
+    #[arg(long, default_value_t = 10)]
+    top: usize,
+}
 
 fn main() {
-    let _ = verify_against_dart();
-    //let _ = random_play();
-    //let _ = ismcts_play();
-}
-
-#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct TestCase {
-    #[serde(rename(serialize = "move", deserialize = "move"))]
-    action: Option<i32>,
-    game_state: games::szs::Game,
-}
-
-fn verify_against_dart() -> io::Result<()> {
-    let mut game: Game = games::szs::Game::new();
-
-    let file = File::open("data/szs.multiplegames.json")?;
-    let reader = BufReader::new(file);
-    let mut test_count: i32 = 0;
-
-    for line in reader.lines() {
-        test_count = test_count + 1;
-        let test_case: TestCase = serde_json::from_str(&line.unwrap()).unwrap();
-        if test_case
-            .game_state
-            .changes
-            .iter()
-            .filter(|cs| cs.iter().any(|c| c.change_type == ChangeType::Shuffle))
-            .count()
-            > 0
-        {
-            // Can't easily test this case since we don't have the intermediate step where
-            // the shuffle occurred
-            game = test_case.game_state.clone();
-            continue;
-        }
-        if test_case.action.is_none() {
-            game = test_case.game_state.clone();
-        } else {
-            game.apply_move(test_case.action.unwrap());
-            game.dealer = test_case.game_state.dealer.clone();
-            game.voids = vec![HashSet::new(), HashSet::new(), HashSet::new()];
-            game.draw_decks = test_case.game_state.draw_decks.clone();
-            println!("rust: {}", serde_json::to_string(&game).unwrap());
-            if game != test_case.game_state {
-                println!("test_count: {}", &test_count);
-                println!("move: {}", &test_case.action.unwrap());
-                println!("rust: {}", serde_json::to_string(&game).unwrap());
-                println!(
-                    "dart: {}",
-                    serde_json::to_string(&test_case.game_state).unwrap()
-                );
-                panic!("mismatch");
-            }
-        }
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Some(Command::Play(args)) => play_command(args),
+        Some(Command::Tournament(args)) => tournament_command(args),
+        Some(Command::Analyze(args)) => analyze_command(args),
+        Some(Command::Diff(args)) => diff_command(args),
+        Some(Command::GenTestcases(args)) => gen_testcases_command(args),
+        Some(Command::Simulate(args)) => simulate_command(args),
+        Some(Command::Bench(args)) => bench_command(args),
+        Some(Command::Verify(args)) => verify_against_dart(&args.path),
+        Some(Command::Repro(args)) => repro_command(args),
+        Some(Command::InspectPolicy(args)) => inspect_policy_command(args),
+        None => verify_against_dart(SZS_GOLDEN_FILE),
+    };
+    // scripts and CI go by the exit status
+    if let Err(error) = result {
+        eprintln!("{}", error);
+        std::process::exit(1);
     }
-    println!("Verified {} game states", test_count);
-    Ok(())
 }
 
-#[allow(dead_code)]
-fn random_play() {
-    // let mut scores = vec![0, 0, 0];
+/// Prefix an error with the file it came from
+fn in_file<E: std::fmt::Display>(path: &str) -> impl Fn(E) -> String + '_ {
+    move |error| format!("{}: {}", path, error)
+}
 
-    let start = Instant::now();
-    for _ in 0..10000 {
-        let mut game = games::szs::Game::new();
-        while game.winner.is_none() {
-            let mut actions = game.get_moves();
-            actions.shuffle(&mut thread_rng());
-            game.apply_move(*actions.first().expect("should have a move to make"));
-        }
-        // print!(".");
-        // io::stdout().flush().unwrap();
-        // scores = (0..3).map(|x| scores[x] + game.scores[x]).collect();
-    }
-    // println!("\n{:?}", scores);
+/// Write `csv` to paths ending in .csv and `json` to anything else
+fn write_output(path: &str, csv: String, json: impl Serialize) -> io::Result<()> {
+    let contents = if path.ends_with(".csv") {
+        csv
+    } else {
+        serde_json::to_string_pretty(&json)?
+    };
+    std::fs::write(path, contents)
+}
+
+fn write_json(path: &str, json: impl Serialize) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(&json).expect("report should serialize");
+    std::fs::write(path, json).map_err(in_file(path))
+}
 
-    let duration = start.elapsed();
+fn play_command(args: PlayArgs) -> Result<(), String> {
+    let mut options = PlayOptions {
+        game: args.game,
+        iterations: args.iterations,
+        ..Default::default()
+    };
+    if !args.seats.is_empty() {
+        options.human_seats = args.seats;
+    }
+    let stdin = io::stdin();
+    play(&options, stdin.lock(), io::stdout()).map_err(|error| error.to_string())
+}
 
-    println!("Time elapsed for 10,000 games in Rust: {:?}", duration);
+fn tournament_command(args: TournamentArgs) -> Result<(), String> {
+    let mut config = TournamentConfig {
+        game: args.game,
+        deals: args.deals,
+        rotate: !args.no_rotate,
+        seed: args.seed,
+        ..Default::default()
+    };
+    if let Some(threads) = args.threads {
+        config.threads = threads;
+    }
+    if !args.bots.is_empty() {
+        config.bots = args.bots;
+    }
+    let report = run_tournament(&config).map_err(|error| error.to_string())?;
+    println!("{}", report);
+    if let Some(path) = args.results {
+        write_output(&path, report.results_csv(), &report.results).map_err(in_file(&path))?;
+    }
+    if let Some(path) = args.summary {
+        write_output(&path, report.summary_csv(), &report.bots).map_err(in_file(&path))?;
+    }
+    Ok(())
 }
 
-trait MoveMaker {
-    fn get_move(&self, game: &Game) -> i32;
-    fn get_name(&self) -> &str;
+fn analyze_command(args: AnalyzeArgs) -> Result<(), String> {
+    let config = AnalysisConfig {
+        game: args.game,
+        games: args.games,
+        iterations: args.iterations,
+        seed: args.seed,
+    };
+    let report = analyze(&config).map_err(|error| error.to_string())?;
+    print!("{}", report);
+    match args.json {
+        Some(path) => write_json(&path, &report),
+        None => Ok(()),
+    }
 }
 
-struct MCTSMove {}
-struct RandomMove {
-    id: String,
+/// Read a state file through the game so defaulted fields compare equal
+fn read_state(game: &str, path: &str) -> Result<serde_json::Value, String> {
+    let contents = std::fs::read_to_string(path).map_err(|error| format!("{}: {}", path, error))?;
+    let mut game = registry::new_game(game).map_err(|error| error.to_string())?;
+    game.set_state_json(&contents).map_err(|error| format!("{}: {}", path, error))?;
+    serde_json::from_str(&game.state_json()).map_err(|error| error.to_string())
 }
 
-impl MoveMaker for MCTSMove {
-    fn get_move(&self, game: &Game) -> i32 {
-        let mut new_game = game.clone();
-        new_game.round = 4;
-        let mut ismcts = IsmctsHandler::new(new_game);
-        let parallel_threads: usize = 8;
-        ismcts.run_iterations(parallel_threads, 1000 / parallel_threads);
-        // ismcts.debug_select();
-        ismcts.best_move().expect("should have a move to make")
+fn diff_command(args: DiffArgs) -> Result<(), String> {
+    let mut ignored: Vec<&str> = DART_IGNORED.to_vec();
+    ignored.extend(args.ignored.iter().map(String::as_str));
+    let rust = read_state(&args.game, &args.rust_state)?;
+    let expected = read_state(&args.game, &args.expected_state)?;
+    let differences = diff_values(&rust, &expected, &ignored);
+    for difference in differences.iter() {
+        println!("{}", difference);
     }
+    println!("{} differences", differences.len());
+    Ok(())
+}
 
-    fn get_name(&self) -> &str {
-        "MCTS"
+fn gen_testcases_command(args: GenTestcasesArgs) -> Result<(), String> {
+    let result = match &args.output {
+        Some(path) => std::fs::File::create(path).map_err(GoldenError::from).and_then(|file| {
+            generate_golden(&args.game, args.games, args.seed, io::BufWriter::new(file))
+        }),
+        None => generate_golden(&args.game, args.games, args.seed, io::stdout().lock()),
+    };
+    let lines = result.map_err(|error| error.to_string())?;
+    eprintln!("wrote {} test cases", lines);
+    Ok(())
+}
+
+fn simulate_command(args: SimulateArgs) -> Result<(), String> {
+    let mut config = SimulationConfig {
+        game: args.game,
+        games: args.games,
+        seed: args.seed,
+        profile: args.profile,
+        check: args.check,
+        repro_dir: Some(args.repro_dir),
+        ..Default::default()
+    };
+    if let Some(threads) = args.threads {
+        config.threads = threads;
+    }
+    if !args.bots.is_empty() {
+        config.bots = args.bots;
+    }
+    let start = Instant::now();
+    // keeps the progress updates of the worker threads from interleaving
+    let stderr = Mutex::new(io::stderr());
+    let progress = |done: usize| {
+        let mut stderr = stderr.lock().unwrap();
+        let _ = write!(stderr, "\r{}", progress_line(done, config.games, start.elapsed()));
+        let _ = stderr.flush();
+    };
+    let report = simulate(&config, progress);
+    eprintln!();
+    let report = report.map_err(|error| error.to_string())?;
+    println!("{}", report);
+    match args.json {
+        Some(path) => write_json(&path, &report),
+        None => Ok(()),
     }
 }
 
-impl MoveMaker for RandomMove {
-    fn get_move(&self, game: &Game) -> i32 {
-        let mut actions = game.get_moves();
-        actions.shuffle(&mut thread_rng());
-        *actions.first().expect("should have a move to make")
+fn bench_command(args: BenchArgs) -> Result<(), String> {
+    let config = SimulationConfig {
+        game: args.game,
+        games: args.games,
+        seed: args.seed,
+        repro_dir: None,
+        ..Default::default()
+    };
+    let max_threads = args.threads.unwrap_or(config.threads);
+    let counts = thread_counts(max_threads);
+    let report = scaling(&config, &counts).map_err(|error| error.to_string())?;
+    println!("{}", report);
+    match args.json {
+        Some(path) => write_json(&path, &report),
+        None => Ok(()),
     }
+}
 
-    fn get_name(&self) -> &str {
-        &self.id
+fn repro_command(args: ReproArgs) -> Result<(), String> {
+    let bundle = ReproBundle::read(&args.bundle)
+        .map_err(|error| format!("{}: {}", args.bundle.display(), error))?;
+    println!(
+        "{} seed {} failed after {} moves: {}",
+        bundle.game,
+        bundle.seed,
+        bundle.moves.len(),
+        bundle.failure
+    );
+    let replayed = if args.from_start {
+        bundle.replay_from_start()
+    } else {
+        bundle.replay()
+    };
+    let violations = replayed.map_err(|error| error.to_string())?;
+    if violations.is_empty() {
+        println!("the move replayed cleanly");
+    }
+    for violation in violations {
+        println!("{}", violation);
     }
+    Ok(())
 }
 
-pub fn ismcts_play() {
-    let mut players: Vec<Box<dyn MoveMaker>> = vec![
-        Box::new(MCTSMove {}),
-        Box::new(RandomMove {
-            id: String::from("random1"),
-        }),
-        Box::new(RandomMove {
-            id: String::from("random2"),
-        }),
-    ];
-    let mut wins: HashMap<String, usize> = HashMap::new();
-    for _i in 0..33 {
-        let mut start_game = games::szs::Game::new();
-        start_game.with_no_changes();
-        start_game.round = 4;
-        for _ in 0..3 {
-            let mut total_move_time: HashMap<String, u128> = HashMap::new();
-            let mut game = start_game.clone();
-            let player = players.pop().unwrap();
-            players.insert(0, player);
-            let mut total_moves = 0;
-            while game.winner.is_none() {
-                total_moves += 1;
-                let start = Instant::now();
-                let mov = players[game.current_player() as usize].get_move(&game);
-                let duration = start.elapsed();
-                *total_move_time
-                    .entry(
-                        players[game.current_player() as usize]
-                            .get_name()
-                            .to_owned(),
-                    )
-                    .or_insert(0) += duration.as_millis();
-                game.apply_move(mov);
-            }
-
-            let high_score = game.scores.iter().reduce(|x, y| if x > y { x } else { y });
-            for player in 0..3 {
-                if game.scores[player] == *high_score.unwrap() {
-                    *wins
-                        .entry(players[player].get_name().to_owned())
-                        .or_insert(0) += 1;
-                }
-            }
-            println!("total moves: {:?}", total_moves);
-            println!("wins: {:?}", wins);
-            println!("total_move_time: {:?}", total_move_time);
+fn inspect_policy_command(args: InspectPolicyArgs) -> Result<(), String> {
+    let policy = policy_model::load(&args.policy)
+        .map_err(|error| format!("{}: {}", args.policy.display(), error))?;
+    let mut game = KaiboshGame::new();
+    for _ in 0..args.moves {
+        if game.get_moves().is_empty() {
+            break;
         }
+        let action = get_mcts_move(&game, args.iterations);
+        game.apply_move(Some(action));
     }
+    let report = policy_model::inspect(&policy, &game, args.top);
+    let json = serde_json::to_string_pretty(&report).expect("report should serialize");
+    println!("{}", json);
+    Ok(())
+}
+
+fn verify_against_dart(path: &str) -> Result<(), String> {
+    let report = verify_golden_file(&GoldenConfig::szs(), path).map_err(in_file(path))?;
+    for mismatch in report.mismatches.iter() {
+        println!("{}", mismatch);
+    }
+    if !report.passed() {
+        return Err(format!("{} mismatches", report.mismatches.len()));
+    }
+    println!("Verified {} game states", report.cases);
+    Ok(())
 }
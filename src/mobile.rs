@@ -0,0 +1,110 @@
+/*
+UniFFI bindings for native iOS and Android apps (enabled with the `uniffi` feature)
+
+Generate the Kotlin and Swift sources from the built library with:
+
+  cargo run --features uniffi --bin uniffi-bindgen generate \
+    --library target/release/libtricksterstable_rs.so --language kotlin --out-dir out
+*/
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use crate::registry::{self, DynGame, RegistryError, GAME_NAMES};
+
+#[derive(Debug, uniffi::Error)]
+pub enum MobileError {
+    UnknownGame { name: String },
+    InvalidState { message: String },
+    IllegalMove { action: i32 },
+}
+
+impl fmt::Display for MobileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MobileError::UnknownGame { name } => write!(f, "unknown game {}", name),
+            MobileError::InvalidState { message } => write!(f, "invalid game state: {}", message),
+            MobileError::IllegalMove { action } => write!(f, "illegal move {}", action),
+        }
+    }
+}
+
+impl std::error::Error for MobileError {}
+
+impl From<RegistryError> for MobileError {
+    fn from(error: RegistryError) -> Self {
+        match error {
+            RegistryError::UnknownGame(name) => MobileError::UnknownGame { name },
+            RegistryError::InvalidState(error) => MobileError::InvalidState {
+                message: error.to_string(),
+            },
+            RegistryError::IllegalMove(action) => MobileError::IllegalMove { action },
+        }
+    }
+}
+
+#[uniffi::export]
+pub fn game_names() -> Vec<String> {
+    GAME_NAMES.iter().map(|name| name.to_string()).collect()
+}
+
+#[derive(uniffi::Object)]
+pub struct MobileGame {
+    game: Mutex<Box<dyn DynGame>>,
+}
+
+impl MobileGame {
+    fn with_game<T>(&self, f: impl FnOnce(&mut Box<dyn DynGame>) -> T) -> T {
+        f(&mut self.game.lock().expect("game lock poisoned"))
+    }
+}
+
+#[uniffi::export]
+impl MobileGame {
+    #[uniffi::constructor]
+    pub fn new(name: String) -> Result<Arc<Self>, MobileError> {
+        let game = registry::new_game(&name)?;
+        Ok(Arc::new(MobileGame {
+            game: Mutex::new(game),
+        }))
+    }
+
+    #[uniffi::constructor]
+    pub fn from_state(name: String, state: String) -> Result<Arc<Self>, MobileError> {
+        let game = registry::load_game(&name, &state)?;
+        Ok(Arc::new(MobileGame {
+            game: Mutex::new(game),
+        }))
+    }
+
+    pub fn moves(&self) -> Vec<i32> {
+        self.with_game(|game| game.moves())
+    }
+
+    pub fn apply_move(&self, action: i32) -> Result<(), MobileError> {
+        self.with_game(|game| game.apply_move(action))?;
+        Ok(())
+    }
+
+    pub fn bot_move(&self, iterations: i32) -> i32 {
+        // search a copy so the UI thread can keep reading the game meanwhile
+        let game = self.with_game(|game| game.clone_game());
+        game.bot_move(iterations)
+    }
+
+    pub fn current_player(&self) -> u32 {
+        self.with_game(|game| game.current_seat() as u32)
+    }
+
+    pub fn winners(&self) -> Vec<u32> {
+        self.with_game(|game| game.winners().iter().map(|seat| *seat as u32).collect())
+    }
+
+    pub fn state(&self) -> String {
+        self.with_game(|game| game.state_json())
+    }
+
+    pub fn changes(&self) -> String {
+        self.with_game(|game| game.changes_json())
+    }
+}
@@ -0,0 +1,290 @@
+/*
+Runtime model management
+
+Models are loaded from disk instead of being embedded in the binary so they
+can be replaced without recompiling. Every model file is accompanied by a
+JSON header (same path with a `.json` extension) describing which game it was
+trained for and the shape of its inputs and outputs:
+
+  {"format": 1, "game": "kaibosh", "version": 3, "featureCount": 138, "actionCount": 46}
+
+The header is checked against the game's FeatureExtractor before the model is
+swapped in so a stale or mismatched file is rejected instead of producing
+garbage priors.
+
+`ModelManager` is itself a `PolicyModel` so it can be handed to the search
+once (wrapped in an Arc) and reloaded underneath running games.
+*/
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::engine::GameEngine;
+use crate::features::FeatureExtractor;
+use crate::policy::{PolicyHandle, PolicyModel, PriorPruning};
+use crate::quantized::LinearPolicy;
+use crate::training::ModelLoader;
+
+/// Version of the header layout understood by this crate
+pub const MODEL_FORMAT: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelHeader {
+    pub format: u32,
+    pub game: String,
+    // increases every time a model is promoted
+    pub version: u64,
+    pub feature_count: usize,
+    pub action_count: usize,
+}
+
+impl ModelHeader {
+    /// Header describing a model for `G` at `version`
+    pub fn for_game<G: GameEngine>(version: u64) -> Self {
+        ModelHeader {
+            format: MODEL_FORMAT,
+            game: G::NAME.to_string(),
+            version,
+            feature_count: G::FEATURE_COUNT,
+            action_count: G::ACTION_COUNT,
+        }
+    }
+
+    /// Location of the header for the model at `model_path`
+    pub fn path_for(model_path: &Path) -> PathBuf {
+        model_path.with_extension("json")
+    }
+
+    pub fn read(model_path: &Path) -> io::Result<Self> {
+        let header = fs::read_to_string(Self::path_for(model_path))?;
+        Ok(serde_json::from_str(&header)?)
+    }
+
+    pub fn write(&self, model_path: &Path) -> io::Result<()> {
+        fs::write(Self::path_for(model_path), serde_json::to_string(self)?)
+    }
+
+    /// Check the header matches what `G` expects
+    pub fn validate<G: GameEngine>(&self) -> io::Result<()> {
+        let expected = ModelHeader::for_game::<G>(self.version);
+        let mismatch = |field: &str, found: String, wanted: String| -> io::Result<()> {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("model {} is {} but {} expects {}", field, found, G::NAME, wanted),
+            ))
+        };
+        if self.format != expected.format {
+            return mismatch("format", self.format.to_string(), expected.format.to_string());
+        }
+        if self.game != expected.game {
+            return mismatch("game", self.game.clone(), expected.game);
+        }
+        if self.feature_count != expected.feature_count {
+            return mismatch(
+                "feature count",
+                self.feature_count.to_string(),
+                expected.feature_count.to_string(),
+            );
+        }
+        if self.action_count != expected.action_count {
+            return mismatch(
+                "action count",
+                self.action_count.to_string(),
+                expected.action_count.to_string(),
+            );
+        }
+        Ok(())
+    }
+}
+
+struct LoadedModel<G> {
+    header: ModelHeader,
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    policy: PolicyHandle<G>,
+}
+
+/// Holds the active model for a game and swaps it when the file changes
+pub struct ModelManager<G: GameEngine> {
+    loader: ModelLoader<G>,
+    current: RwLock<Option<LoadedModel<G>>>,
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+impl<G: GameEngine> ModelManager<G> {
+    pub fn new(loader: ModelLoader<G>) -> Self {
+        ModelManager {
+            loader,
+            current: RwLock::new(None),
+        }
+    }
+
+    /// Validate and load the model at `path` replacing the active model.
+    /// The active model is left untouched when loading fails.
+    pub fn load(&self, path: &Path) -> io::Result<ModelHeader> {
+        let header = ModelHeader::read(path)?;
+        header.validate::<G>()?;
+        let policy = (self.loader)(path)?;
+        let loaded = LoadedModel {
+            header: header.clone(),
+            path: path.to_path_buf(),
+            modified: modified(path),
+            policy,
+        };
+        *self.current.write().expect("model lock poisoned") = Some(loaded);
+        Ok(header)
+    }
+
+    /// Reload the active model if its file has been replaced since it was
+    /// loaded - returns the new header when a reload happened
+    pub fn reload_if_changed(&self) -> io::Result<Option<ModelHeader>> {
+        let (path, loaded_at) = match &*self.current.read().expect("model lock poisoned") {
+            Some(loaded) => (loaded.path.clone(), loaded.modified),
+            None => return Ok(None),
+        };
+        if modified(&path) == loaded_at {
+            return Ok(None);
+        }
+        self.load(&path).map(Some)
+    }
+
+    /// Drop the active model - the manager then returns uniform priors
+    pub fn unload(&self) {
+        *self.current.write().expect("model lock poisoned") = None;
+    }
+
+    pub fn header(&self) -> Option<ModelHeader> {
+        self.current
+            .read()
+            .expect("model lock poisoned")
+            .as_ref()
+            .map(|loaded| loaded.header.clone())
+    }
+
+    /// The active model, if any
+    pub fn policy(&self) -> Option<PolicyHandle<G>> {
+        self.current
+            .read()
+            .expect("model lock poisoned")
+            .as_ref()
+            .map(|loaded| loaded.policy.clone())
+    }
+}
+
+/// Read a float linear policy and check it matches `G`'s feature layout
+pub fn load_linear_policy<G: FeatureExtractor>(path: &Path) -> io::Result<LinearPolicy<G>> {
+    let policy: LinearPolicy<G> = LinearPolicy::load(path)?;
+    let layer = &policy.layer;
+    if layer.rows != G::ACTION_COUNT
+        || layer.cols != G::FEATURE_COUNT
+        || layer.weights.len() != layer.rows * layer.cols
+        || layer.bias.len() != layer.rows
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} does not match the feature layout", path.display()),
+        ));
+    }
+    Ok(policy)
+}
+
+impl<G: GameEngine> PolicyModel<G> for ModelManager<G> {
+    fn priors(&self, game: &G, moves: &[i32]) -> Vec<f32> {
+        match self.policy() {
+            Some(policy) => policy.priors(game, moves),
+            None => vec![1.0; moves.len()],
+        }
+    }
+
+    fn pruning(&self) -> PriorPruning {
+        match self.policy() {
+            Some(policy) => policy.pruning(),
+            None => PriorPruning::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::dealers_dilemma::Game;
+    use std::sync::Arc;
+
+    struct Fixed(f32);
+
+    impl PolicyModel<Game> for Fixed {
+        fn priors(&self, _game: &Game, moves: &[i32]) -> Vec<f32> {
+            vec![self.0; moves.len()]
+        }
+    }
+
+    #[test]
+    fn test_load_and_validate() {
+        let dir = std::env::temp_dir().join(format!("models-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("best.onnx");
+        fs::write(&path, b"1").unwrap();
+
+        let manager: ModelManager<Game> = ModelManager::new(Box::new(|path| {
+            let weight = fs::read_to_string(path)?.trim().parse().unwrap_or(0.0);
+            Ok(Arc::new(Fixed(weight)) as PolicyHandle<Game>)
+        }));
+        let game = Game::new();
+        let moves = game.legal_moves();
+        assert_eq!(manager.priors(&game, &moves), vec![1.0; moves.len()]);
+
+        // missing header
+        assert!(manager.load(&path).is_err());
+
+        // wrong shape
+        let mut header = ModelHeader::for_game::<Game>(1);
+        header.feature_count += 1;
+        header.write(&path).unwrap();
+        assert!(manager.load(&path).is_err());
+        assert_eq!(manager.header(), None);
+
+        let header = ModelHeader::for_game::<Game>(1);
+        header.write(&path).unwrap();
+        assert_eq!(manager.load(&path).unwrap(), header);
+        assert_eq!(manager.reload_if_changed().unwrap(), None);
+
+        fs::write(&path, b"3").unwrap();
+        let header = ModelHeader::for_game::<Game>(2);
+        header.write(&path).unwrap();
+        // rewrite until the timestamp moves on filesystems with coarse mtimes
+        while modified(&path) == manager.current.read().unwrap().as_ref().unwrap().modified {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            fs::write(&path, b"3").unwrap();
+        }
+        assert_eq!(manager.reload_if_changed().unwrap(), Some(header));
+        assert_eq!(manager.priors(&game, &moves), vec![3.0; moves.len()]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_load_linear_policy() {
+        use crate::games::dealers_dilemma::{BidDecision, BidPolicy};
+
+        let dir = std::env::temp_dir().join(format!("linear-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("bids.json");
+        let mut policy = BidPolicy::zeros();
+        policy.layer.bias[1] = 0.5;
+        policy.save(&path).unwrap();
+        let loaded = load_linear_policy::<BidDecision>(&path).unwrap();
+        assert_eq!(loaded.layer, policy.layer);
+        // a policy for another feature layout is rejected
+        assert!(load_linear_policy::<Game>(&path).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
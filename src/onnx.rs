@@ -0,0 +1,133 @@
+/*
+ONNX Runtime inference backend (enabled with the `onnx` feature)
+
+Models are trained outside of this crate (e.g. in Python) and exported with a
+single float input of shape [batch, FEATURE_COUNT] built by the game's
+FeatureExtractor. The first output holds policy logits of shape
+[batch, ACTION_COUNT]. An optional second output holds a value estimate in
+the [-1, 1] range from the point of view of the player to act - it is used
+to score truncated rollouts when the model is attached as a leaf evaluator.
+*/
+
+use std::io;
+use std::marker::PhantomData;
+use std::path::Path;
+use std::sync::Arc;
+
+use ort::session::{builder::GraphOptimizationLevel, Session};
+use ort::value::Tensor;
+
+use crate::features::FeatureExtractor;
+use crate::policy::{LeafEvaluator, PolicyHandle, PolicyModel};
+use crate::training::ModelLoader;
+
+pub struct OnnxModel<G> {
+    session: Session,
+    _game: PhantomData<fn(&G)>,
+}
+
+impl<G: FeatureExtractor> OnnxModel<G> {
+    /// Load a model from disk
+    pub fn load(path: impl AsRef<Path>) -> ort::Result<Self> {
+        let session = Session::builder()?
+            .with_optimization_level(GraphOptimizationLevel::Level3)?
+            .with_intra_threads(1)?
+            .commit_from_file(path)?;
+        Ok(OnnxModel {
+            session,
+            _game: PhantomData,
+        })
+    }
+
+    /// Load a model from an in memory buffer (e.g. embedded with include_bytes!)
+    pub fn from_memory(model: &[u8]) -> ort::Result<Self> {
+        let session = Session::builder()?
+            .with_intra_threads(1)?
+            .commit_from_memory(model)?;
+        Ok(OnnxModel {
+            session,
+            _game: PhantomData,
+        })
+    }
+
+    /// Run the model for the player to act returning the raw policy logits
+    /// (length ACTION_COUNT) and the value estimate when the model has one
+    pub fn infer(&self, game: &G) -> ort::Result<(Vec<f32>, Option<f32>)> {
+        self.infer_for(game, game.current_seat())
+    }
+
+    /// Run the model with the features seen by `observer`
+    pub fn infer_for(&self, game: &G, observer: usize) -> ort::Result<(Vec<f32>, Option<f32>)> {
+        let features = game.features(observer);
+        let input = Tensor::from_array(([1usize, G::FEATURE_COUNT], features))?;
+        let outputs = self.session.run(ort::inputs![input]?)?;
+        let (_, logits) = outputs[0].try_extract_raw_tensor::<f32>()?;
+        let value = if outputs.len() > 1 {
+            let (_, value) = outputs[1].try_extract_raw_tensor::<f32>()?;
+            value.first().copied()
+        } else {
+            None
+        };
+        Ok((logits.to_vec(), value))
+    }
+}
+
+/// Loader for ModelManager and the training orchestrator
+pub fn onnx_loader<G: FeatureExtractor + 'static>() -> ModelLoader<G> {
+    Box::new(|path| {
+        let model = OnnxModel::<G>::load(path).map_err(io::Error::other)?;
+        Ok(Arc::new(model) as PolicyHandle<G>)
+    })
+}
+
+/// Softmax over the logits of the legal moves only
+pub fn legal_move_priors(logits: &[f32], indexes: impl Iterator<Item = usize>) -> Vec<f32> {
+    let selected: Vec<f32> = indexes
+        .map(|index| logits.get(index).copied().unwrap_or(f32::NEG_INFINITY))
+        .collect();
+    let max = selected.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+    if !max.is_finite() {
+        return vec![1.0; selected.len()];
+    }
+    let exp: Vec<f32> = selected.iter().map(|logit| (logit - max).exp()).collect();
+    let total: f32 = exp.iter().sum();
+    exp.iter().map(|e| e / total).collect()
+}
+
+impl<G: FeatureExtractor> PolicyModel<G> for OnnxModel<G> {
+    fn priors(&self, game: &G, moves: &[i32]) -> Vec<f32> {
+        match self.infer(game) {
+            Ok((logits, _)) => {
+                legal_move_priors(&logits, moves.iter().map(|mov| game.action_index(*mov)))
+            }
+            // Fall back to uniform priors so a bad model can't stall a game
+            Err(_) => vec![1.0; moves.len()],
+        }
+    }
+}
+
+impl<G: FeatureExtractor> LeafEvaluator<G> for OnnxModel<G> {
+    fn evaluate(&self, game: &G, seat: usize) -> f64 {
+        match self.infer_for(game, seat) {
+            Ok((_, Some(value))) => value as f64,
+            // Models without a value head (or failing ones) treat the leaf as even
+            _ => 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_legal_move_priors() {
+        let priors = legal_move_priors(&[0.0, 10.0, 0.0, 10.0], vec![0, 3].into_iter());
+        assert_eq!(priors.len(), 2);
+        assert!(priors[1] > 0.99);
+        assert_eq!(
+            legal_move_priors(&[0.0], vec![5, 6].into_iter()),
+            vec![1.0, 1.0]
+        );
+    }
+}
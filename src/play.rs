@@ -0,0 +1,134 @@
+/*
+Terminal play against the ISMCTS bots
+
+  cargo run --release -- play <game> [--seat <seat>]... [--iterations <n>]
+
+Human seats are shown the table from their seat and pick a move by its
+index in the list of legal moves. Bots announce the moves they make.
+Entering `q` (or closing stdin) abandons the game.
+*/
+
+use std::io::{self, BufRead, Write};
+
+use crate::registry::{self, DynGame};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlayOptions {
+    pub game: String,
+    pub human_seats: Vec<usize>,
+    pub iterations: i32,
+}
+
+impl Default for PlayOptions {
+    fn default() -> Self {
+        PlayOptions {
+            game: "dealers_dilemma".to_string(),
+            human_seats: vec![0],
+            iterations: 1000,
+        }
+    }
+}
+
+fn score_line(game: &dyn DynGame) -> String {
+    game.scores()
+        .iter()
+        .enumerate()
+        .map(|(seat, score)| format!("player {}: {}", seat, score))
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+/// Ask the human at the current seat for a move - None when they quit
+fn read_move(
+    game: &dyn DynGame,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> io::Result<Option<i32>> {
+    let moves = game.moves();
+    writeln!(output, "\n{}", game.render(game.current_seat()))?;
+    for (index, action) in moves.iter().enumerate() {
+        writeln!(output, "  {}: {}", index, game.describe_move(*action))?;
+    }
+    loop {
+        write!(output, "player {} move: ", game.current_seat())?;
+        output.flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim();
+        if line == "q" {
+            return Ok(None);
+        }
+        match line.parse::<usize>().ok().and_then(|index| moves.get(index)) {
+            Some(action) => return Ok(Some(*action)),
+            None => writeln!(output, "choose a move from 0 to {}", moves.len() - 1)?,
+        }
+    }
+}
+
+/// Play one game - returns the winning seats (empty if it was abandoned)
+pub fn play(
+    options: &PlayOptions,
+    mut input: impl BufRead,
+    mut output: impl Write,
+) -> io::Result<Vec<usize>> {
+    let mut game = registry::new_game(&options.game)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error.to_string()))?;
+    while game.winners().is_empty() {
+        let seat = game.current_seat();
+        let action = if options.human_seats.contains(&seat) {
+            match read_move(game.as_ref(), &mut input, &mut output)? {
+                Some(action) => action,
+                None => return Ok(vec![]),
+            }
+        } else {
+            let action = game.bot_move(options.iterations);
+            writeln!(output, "player {} plays {}", seat, game.describe_move(action))?;
+            action
+        };
+        game.apply_move(action)
+            .map_err(|error| io::Error::other(error.to_string()))?;
+    }
+    let winners = game.winners();
+    writeln!(output, "\n{}", game.render(options.human_seats.first().copied().unwrap_or(0)))?;
+    writeln!(output, "final scores: {}", score_line(game.as_ref()))?;
+    writeln!(output, "winners: {:?}", winners)?;
+    Ok(winners)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bots_only() {
+        let options = PlayOptions {
+            game: "kansascity".to_string(),
+            human_seats: vec![],
+            iterations: 5,
+        };
+        let mut output = vec![];
+        let winners = play(&options, io::empty(), &mut output).unwrap();
+        assert!(!winners.is_empty());
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("final scores: player 0: "));
+    }
+
+    #[test]
+    fn test_human_input() {
+        let options = PlayOptions {
+            game: "szs".to_string(),
+            human_seats: vec![0, 1, 2],
+            iterations: 5,
+        };
+        let mut output = vec![];
+        let input = "x\n99\n0\nq\n";
+        let winners = play(&options, input.as_bytes(), &mut output).unwrap();
+        assert!(winners.is_empty());
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.matches("choose a move from 0 to").count(), 2);
+        // three attempts by the first player and one by the next
+        assert_eq!(output.matches(" move: ").count(), 4);
+    }
+}
@@ -0,0 +1,396 @@
+/*
+Prior pruning and leaf evaluation for the ISMCTS search
+
+This is not PUCT: the ismcts crate owns node selection (plain UCT) and only
+sees the moves `available_moves` returns. Priors are applied by wrapping a
+game and trimming its move list down to the moves that hold most of the
+policy's probability mass. Moves that survive are then searched with equal
+weight. A few of the best moves are always kept however little mass they
+hold so a move the policy underrates can still be found by the search.
+
+Rollouts can also be cut short: once a simulation is `horizon` plies past
+the root the wrapper reports no further moves and scores the position with
+a leaf evaluator (a learned value function or a per-game heuristic).
+
+With no policy or evaluator attached the wrapper is a transparent pass-through.
+*/
+
+use std::fmt;
+use std::sync::Arc;
+
+use ismcts::IsmctsHandler;
+
+/// Cumulative prior mass kept when pruning the move list
+pub const DEFAULT_PRIOR_MASS: f32 = 0.95;
+/// Moves that are never pruned away (the best ones by prior)
+pub const DEFAULT_MIN_MOVES: usize = 3;
+
+/// How much of the move list survives the policy's priors
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PriorPruning {
+    /// Cumulative prior mass kept - 1.0 keeps every move
+    pub mass: f32,
+    /// Moves kept regardless of the mass they hold
+    pub min_moves: usize,
+}
+
+impl Default for PriorPruning {
+    fn default() -> Self {
+        PriorPruning {
+            mass: DEFAULT_PRIOR_MASS,
+            min_moves: DEFAULT_MIN_MOVES,
+        }
+    }
+}
+
+/// Produces a prior weight for each legal move in a position
+pub trait PolicyModel<G>: Send + Sync {
+    /// Returns one non-negative (not necessarily normalized) weight per
+    /// entry in `moves`, in the same order
+    fn priors(&self, game: &G, moves: &[i32]) -> Vec<f32>;
+
+    /// How the search trims the move list with these priors
+    fn pruning(&self) -> PriorPruning {
+        PriorPruning::default()
+    }
+}
+
+/// Shared handle to a policy model that can be cloned into every search thread
+pub type PolicyHandle<G> = Arc<dyn PolicyModel<G>>;
+
+/// Estimates the value of a non-terminal position
+pub trait LeafEvaluator<G>: Send + Sync {
+    /// Value for `seat` between -1.0 (certain loss) and 1.0 (certain win)
+    fn evaluate(&self, game: &G, seat: usize) -> f64;
+}
+
+pub type LeafHandle<G> = Arc<dyn LeafEvaluator<G>>;
+
+/// Truncate simulations after `horizon` plies and score them with `evaluator`
+#[derive(Clone)]
+pub struct LeafEvaluation<G> {
+    pub horizon: usize,
+    pub evaluator: LeafHandle<G>,
+    /// Range of the game's `result` values - estimates are mapped into it
+    /// so truncated and completed rollouts are directly comparable
+    pub result_range: (f64, f64),
+}
+
+impl<G> LeafEvaluation<G> {
+    fn scale(&self, value: f64) -> f64 {
+        let (low, high) = self.result_range;
+        low + (value.clamp(-1.0, 1.0) + 1.0) / 2.0 * (high - low)
+    }
+}
+
+/// Keep the highest prior moves until `pruning.mass` of the total weight is
+/// covered and at least `pruning.min_moves` moves are kept. The surviving
+/// moves keep their original relative order and at least one move is always
+/// returned.
+pub fn prune_by_prior(moves: Vec<i32>, priors: &[f32], pruning: PriorPruning) -> Vec<i32> {
+    let keep_all = moves.len() <= pruning.min_moves.max(1) || pruning.mass >= 1.0;
+    if keep_all || priors.len() != moves.len() {
+        return moves;
+    }
+    let total: f32 = priors.iter().map(|p| p.max(0.0)).sum();
+    if total <= 0.0 || !total.is_finite() {
+        return moves;
+    }
+    let mut ranked: Vec<usize> = (0..moves.len()).collect();
+    ranked.sort_by(|a, b| {
+        priors[*b]
+            .partial_cmp(&priors[*a])
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    let mut keep = vec![false; moves.len()];
+    let mut covered = 0.0;
+    for (rank, index) in ranked.into_iter().enumerate() {
+        keep[index] = true;
+        covered += priors[index].max(0.0) / total;
+        if covered >= pruning.mass && rank + 1 >= pruning.min_moves {
+            break;
+        }
+    }
+    moves
+        .into_iter()
+        .zip(keep)
+        .filter(|(_, keep)| *keep)
+        .map(|(mov, _)| mov)
+        .collect()
+}
+
+/// Game wrapper which prunes the moves offered to the search by policy prior
+#[derive(Clone)]
+pub struct PriorPruned<G> {
+    pub game: G,
+    policy: Option<PolicyHandle<G>>,
+    pruning: PriorPruning,
+    leaf_evaluation: Option<LeafEvaluation<G>>,
+    // plies played since the root of the search
+    plies: usize,
+}
+
+impl<G> PriorPruned<G> {
+    pub fn new(game: G, policy: Option<PolicyHandle<G>>) -> Self {
+        let pruning = match &policy {
+            Some(policy) => policy.pruning(),
+            None => PriorPruning::default(),
+        };
+        PriorPruned {
+            game,
+            policy,
+            pruning,
+            leaf_evaluation: None,
+            plies: 0,
+        }
+    }
+
+    pub fn with_leaf_evaluation(mut self, leaf_evaluation: Option<LeafEvaluation<G>>) -> Self {
+        self.leaf_evaluation = leaf_evaluation;
+        self
+    }
+
+    fn truncated(&self) -> bool {
+        match &self.leaf_evaluation {
+            Some(leaf) => self.plies >= leaf.horizon,
+            None => false,
+        }
+    }
+
+    pub fn with_pruning(mut self, pruning: PriorPruning) -> Self {
+        self.pruning = pruning;
+        self
+    }
+}
+
+impl<G: fmt::Debug> fmt::Debug for PriorPruned<G> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PriorPruned")
+            .field("game", &self.game)
+            .field("policy", &self.policy.is_some())
+            .field("pruning", &self.pruning)
+            .field("horizon", &self.leaf_evaluation.as_ref().map(|leaf| leaf.horizon))
+            .field("plies", &self.plies)
+            .finish()
+    }
+}
+
+impl<G> ismcts::Game for PriorPruned<G>
+where
+    G: ismcts::Game<Move = i32, MoveList = Vec<i32>> + Clone,
+    G::PlayerTag: Copy + TryInto<usize>,
+{
+    type Move = i32;
+    type PlayerTag = G::PlayerTag;
+    type MoveList = Vec<i32>;
+
+    fn randomize_determination(&mut self, observer: Self::PlayerTag) {
+        self.game.randomize_determination(observer)
+    }
+
+    fn current_player(&self) -> Self::PlayerTag {
+        self.game.current_player()
+    }
+
+    fn next_player(&self) -> Self::PlayerTag {
+        self.game.next_player()
+    }
+
+    fn available_moves(&self) -> Self::MoveList {
+        if self.truncated() {
+            return vec![];
+        }
+        let moves = self.game.available_moves();
+        match &self.policy {
+            Some(policy) if moves.len() > 1 => {
+                let priors = policy.priors(&self.game, &moves);
+                prune_by_prior(moves, &priors, self.pruning)
+            }
+            _ => moves,
+        }
+    }
+
+    fn make_move(&mut self, mov: &Self::Move) {
+        self.plies += 1;
+        self.game.make_move(mov)
+    }
+
+    fn result(&self, player: Self::PlayerTag) -> Option<f64> {
+        let result = self.game.result(player);
+        match &self.leaf_evaluation {
+            Some(leaf) if result.is_none() && self.truncated() => {
+                let seat = player.try_into().unwrap_or(0);
+                Some(leaf.scale(leaf.evaluator.evaluate(&self.game, seat)))
+            }
+            _ => result,
+        }
+    }
+}
+
+/// Run a (possibly prior pruned) search from an already prepared game state
+pub fn search<G>(
+    game: G,
+    iterations: i32,
+    parallel_threads: usize,
+    policy: Option<PolicyHandle<G>>,
+) -> i32
+where
+    G: ismcts::Game<Move = i32, MoveList = Vec<i32>> + Clone + Send + Sync + 'static,
+    G::PlayerTag: Copy + TryInto<usize>,
+{
+    search_with_leaf_evaluation(game, iterations, parallel_threads, policy, None)
+}
+
+/// Like `search` but simulations may be truncated and scored by a leaf evaluator
+pub fn search_with_leaf_evaluation<G>(
+    game: G,
+    iterations: i32,
+    parallel_threads: usize,
+    policy: Option<PolicyHandle<G>>,
+    leaf_evaluation: Option<LeafEvaluation<G>>,
+) -> i32
+where
+    G: ismcts::Game<Move = i32, MoveList = Vec<i32>> + Clone + Send + Sync + 'static,
+    G::PlayerTag: Copy + TryInto<usize>,
+{
+    // wasm builds have no threads to spread the search over
+    #[cfg(target_arch = "wasm32")]
+    let parallel_threads = 1;
+    let mut ismcts = IsmctsHandler::new(
+        PriorPruned::new(game, policy).with_leaf_evaluation(leaf_evaluation),
+    );
+    ismcts.run_iterations(
+        parallel_threads,
+        (iterations as f64 / parallel_threads as f64) as usize,
+    );
+    ismcts.best_move().expect("should have a move to make")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mass(mass: f32) -> PriorPruning {
+        PriorPruning { mass, min_moves: 1 }
+    }
+
+    #[test]
+    fn test_prune_by_prior() {
+        let moves = vec![1, 2, 3, 4];
+        // 3 and 1 hold 90% of the mass
+        assert_eq!(
+            prune_by_prior(moves.clone(), &[0.3, 0.05, 0.6, 0.05], mass(0.85)),
+            vec![1, 3]
+        );
+        // the single best move is always kept
+        assert_eq!(
+            prune_by_prior(moves.clone(), &[0.1, 0.7, 0.1, 0.1], mass(0.0)),
+            vec![2]
+        );
+        // degenerate priors leave the move list untouched
+        assert_eq!(prune_by_prior(moves.clone(), &[0.0; 4], mass(0.9)), moves);
+        assert_eq!(prune_by_prior(moves.clone(), &[1.0], mass(0.9)), moves);
+        // the best moves are kept even when they hold almost no mass
+        let pruning = PriorPruning {
+            mass: 0.85,
+            min_moves: 3,
+        };
+        assert_eq!(
+            prune_by_prior(moves.clone(), &[0.9, 0.01, 0.02, 0.07], pruning),
+            vec![1, 3, 4]
+        );
+        assert_eq!(
+            prune_by_prior(moves.clone(), &[0.9, 0.0, 0.0, 0.1], mass(1.0)),
+            moves
+        );
+    }
+
+    // A single decision where only move 3 wins
+    #[derive(Clone)]
+    struct Choice {
+        chosen: Option<i32>,
+    }
+
+    impl ismcts::Game for Choice {
+        type Move = i32;
+        type PlayerTag = usize;
+        type MoveList = Vec<i32>;
+
+        fn randomize_determination(&mut self, _observer: Self::PlayerTag) {}
+
+        fn current_player(&self) -> Self::PlayerTag {
+            0
+        }
+
+        fn next_player(&self) -> Self::PlayerTag {
+            0
+        }
+
+        fn available_moves(&self) -> Self::MoveList {
+            match self.chosen {
+                Some(_) => vec![],
+                None => vec![0, 1, 2, 3, 4, 5],
+            }
+        }
+
+        fn make_move(&mut self, mov: &Self::Move) {
+            self.chosen = Some(*mov);
+        }
+
+        fn result(&self, _player: Self::PlayerTag) -> Option<f64> {
+            self.chosen.map(|mov| if mov == 3 { 1.0 } else { 0.0 })
+        }
+    }
+
+    // Confident in move 0 and all but rules out the winning move
+    struct Misjudged;
+
+    impl PolicyModel<Choice> for Misjudged {
+        fn priors(&self, _game: &Choice, moves: &[i32]) -> Vec<f32> {
+            moves
+                .iter()
+                .map(|mov| match mov {
+                    0 => 0.96,
+                    3 => 0.02,
+                    _ => 0.005,
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_low_prior_move_is_searched() {
+        let game = Choice { chosen: None };
+        let policy: PolicyHandle<Choice> = Arc::new(Misjudged);
+        let pruned = PriorPruned::new(game.clone(), Some(policy.clone()));
+        let moves = ismcts::Game::available_moves(&pruned);
+        assert_eq!(moves.len(), DEFAULT_MIN_MOVES);
+        assert!(moves.contains(&3));
+        assert_eq!(search(game, 200, 1, Some(policy)), 3);
+    }
+
+    struct Constant(f64);
+
+    impl LeafEvaluator<()> for Constant {
+        fn evaluate(&self, _game: &(), _seat: usize) -> f64 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_leaf_evaluation_scale() {
+        let leaf = LeafEvaluation {
+            horizon: 4,
+            evaluator: Arc::new(Constant(0.0)),
+            result_range: (0.0, 1.0),
+        };
+        assert_eq!(leaf.scale(-1.0), 0.0);
+        assert_eq!(leaf.scale(0.0), 0.5);
+        assert_eq!(leaf.scale(3.0), 1.0);
+        let leaf = LeafEvaluation {
+            result_range: (-1.0, 1.0),
+            ..leaf
+        };
+        assert_eq!(leaf.scale(0.5), 0.5);
+    }
+}
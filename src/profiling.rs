@@ -0,0 +1,202 @@
+/*
+Time spent in each phase of the engines
+
+  cargo run --release -- simulate <game> --profile
+
+Profiling is off unless `set_enabled(true)` is called - the engines create a
+timer at the start of each phase and a disabled timer is a single atomic
+load. The phases are:
+
+- deal
+- get_moves (ismcts available_moves)
+- apply_move (ismcts make_move) by the state the move was made in
+- changes (building the UI change lists)
+- determinization
+
+Phases nest - apply_move includes the deals and changes the move makes.
+Each thread accumulates its own timings and hands them over when it exits
+(or when `take_report` is called on it).
+*/
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::utils::render;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+// phase and state (for apply_move) to the time spent and calls
+type Timings = BTreeMap<(&'static str, Option<String>), (Duration, u64)>;
+
+static FINISHED: Mutex<Timings> = Mutex::new(BTreeMap::new());
+
+struct ThreadTimings(Timings);
+
+impl Drop for ThreadTimings {
+    fn drop(&mut self) {
+        merge(std::mem::take(&mut self.0));
+    }
+}
+
+thread_local! {
+    static TIMINGS: RefCell<ThreadTimings> = RefCell::new(ThreadTimings(BTreeMap::new()));
+}
+
+fn merge(timings: Timings) {
+    let mut finished = FINISHED.lock().unwrap_or_else(|error| error.into_inner());
+    for (key, (time, calls)) in timings {
+        let total = finished.entry(key).or_default();
+        total.0 += time;
+        total.1 += calls;
+    }
+}
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Records the time until it is dropped
+pub struct Timer {
+    phase: &'static str,
+    state: Option<String>,
+    start: Instant,
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        let key = (self.phase, self.state.take());
+        let _ = TIMINGS.try_with(|timings| {
+            let mut timings = timings.borrow_mut();
+            let total = timings.0.entry(key).or_default();
+            total.0 += elapsed;
+            total.1 += 1;
+        });
+    }
+}
+
+/// Time a phase - `let _timer = profiling::start("deal");`
+pub fn start(phase: &'static str) -> Option<Timer> {
+    if !enabled() {
+        return None;
+    }
+    Some(Timer {
+        phase,
+        state: None,
+        start: Instant::now(),
+    })
+}
+
+/// Time a phase broken down by the game's state
+pub fn start_in(phase: &'static str, state: &impl fmt::Debug) -> Option<Timer> {
+    if !enabled() {
+        return None;
+    }
+    Some(Timer {
+        phase,
+        state: Some(format!("{:?}", state)),
+        start: Instant::now(),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PhaseTiming {
+    pub phase: String,
+    pub state: Option<String>,
+    pub calls: u64,
+    pub seconds: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileReport {
+    /// Games the timings were collected over
+    pub games: usize,
+    pub phases: Vec<PhaseTiming>,
+}
+
+/// Timings collected since the last report (by threads that exited and the
+/// calling thread) - the totals start again from zero
+pub fn take_report(games: usize) -> ProfileReport {
+    let _ = TIMINGS.try_with(|timings| merge(std::mem::take(&mut timings.borrow_mut().0)));
+    let timings = std::mem::take(&mut *FINISHED.lock().unwrap_or_else(|error| error.into_inner()));
+    ProfileReport {
+        games,
+        phases: timings
+            .into_iter()
+            .map(|((phase, state), (time, calls))| PhaseTiming {
+                phase: phase.to_string(),
+                state,
+                calls,
+                seconds: time.as_secs_f64(),
+            })
+            .collect(),
+    }
+}
+
+impl fmt::Display for ProfileReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let games = self.games.max(1) as f64;
+        let rows: Vec<Vec<String>> = self
+            .phases
+            .iter()
+            .map(|timing| {
+                vec![
+                    timing.phase.clone(),
+                    timing.state.clone().unwrap_or_default(),
+                    timing.calls.to_string(),
+                    format!("{:.3}", timing.seconds),
+                    format!("{:.3}", timing.seconds * 1000.0 / games),
+                    format!("{:.0}", timing.seconds * 1e9 / timing.calls.max(1) as f64),
+                ]
+            })
+            .collect();
+        let header = ["phase", "state", "calls", "total s", "ms/game", "ns/call"];
+        write!(f, "{}", render::table(&header, &rows))
+    }
+}
+
+/// Held by tests that enable profiling - the timings are process wide
+#[cfg(test)]
+pub(crate) static TEST_LOCK: Mutex<()> = Mutex::new(());
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profiling() {
+        let _lock = TEST_LOCK.lock().unwrap_or_else(|error| error.into_inner());
+        set_enabled(false);
+        assert!(start("deal").is_none());
+        set_enabled(true);
+        drop(start("deal"));
+        drop(start_in("apply_move", &Some(1)));
+        drop(start_in("apply_move", &Some(1)));
+        std::thread::spawn(|| drop(start("deal"))).join().unwrap();
+        set_enabled(false);
+        let report = take_report(2);
+        let calls = |phase: &str, state: Option<&str>| {
+            report
+                .phases
+                .iter()
+                .find(|timing| timing.phase == phase && timing.state.as_deref() == state)
+                .map_or(0, |timing| timing.calls)
+        };
+        // other tests may be playing games on their own threads
+        assert!(calls("deal", None) >= 2);
+        assert_eq!(calls("apply_move", Some("Some(1)")), 2);
+        assert!(report.to_string().starts_with("phase"));
+    }
+}
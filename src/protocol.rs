@@ -0,0 +1,217 @@
+/*
+Line based engine protocol (in the spirit of UCI)
+
+Lets GUIs, scripts and test harnesses in any language drive the engines
+over stdin/stdout without linking against the crate:
+
+  > newgame szs 42
+  < ok 42
+  > moves
+  < moves 3 17 21
+  > apply 17
+  < ok
+  > botmove 500
+  < botmove 21
+  > state
+  < state {"...": ...}
+
+Every command gets exactly one line back. Failures are reported as
+`error <message>` and leave the game untouched.
+
+Commands: games, newgame <name> [seed], load <name> <state json>, moves,
+apply <move>, botmove <ms>, state, changes, seat, winners, quit
+
+The engines deal with thread_rng so the newgame seed is only echoed back
+for now - use load to reproduce a position.
+*/
+
+use std::io::{self, BufRead, Write};
+use std::time::Instant;
+
+use crate::registry::{self, DynGame, GAME_NAMES};
+
+// Iterations used to measure a game's search speed before the first timed move
+const PROBE_ITERATIONS: i32 = 50;
+
+pub struct Session {
+    game: Option<Box<dyn DynGame>>,
+    // measured search speed of the current game
+    seconds_per_iteration: Option<f64>,
+}
+
+fn join<T: ToString>(values: &[T]) -> String {
+    values
+        .iter()
+        .map(|value| value.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Session {
+            game: None,
+            seconds_per_iteration: None,
+        }
+    }
+
+    fn game(&mut self) -> Result<&mut dyn DynGame, String> {
+        self.game
+            .as_deref_mut()
+            .ok_or_else(|| "no game - send newgame first".to_string())
+    }
+
+    fn start(&mut self, game: Box<dyn DynGame>) {
+        self.game = Some(game);
+        self.seconds_per_iteration = None;
+    }
+
+    /// Search for about `ms` milliseconds - the iteration count is derived
+    /// from the speed of previous searches in this game
+    fn bot_move(&mut self, ms: u64) -> Result<i32, String> {
+        let mut budget = ms as f64 / 1000.0;
+        let per_iteration = match self.seconds_per_iteration {
+            Some(per_iteration) => per_iteration,
+            None => {
+                let start = Instant::now();
+                let action = self.game()?.bot_move(PROBE_ITERATIONS);
+                let spent = start.elapsed().as_secs_f64();
+                let per_iteration = (spent / PROBE_ITERATIONS as f64).max(1e-9);
+                self.seconds_per_iteration = Some(per_iteration);
+                // not enough time left for a search larger than the probe
+                if budget - spent < spent {
+                    return Ok(action);
+                }
+                budget -= spent;
+                per_iteration
+            }
+        };
+        let iterations = (budget / per_iteration).clamp(1.0, i32::MAX as f64) as i32;
+        let start = Instant::now();
+        let action = self.game()?.bot_move(iterations);
+        let measured = start.elapsed().as_secs_f64() / iterations as f64;
+        self.seconds_per_iteration = Some((per_iteration + measured.max(1e-9)) / 2.0);
+        Ok(action)
+    }
+
+    /// Response line for `command` or None when the session should end
+    pub fn handle(&mut self, command: &str) -> Option<String> {
+        let command = command.trim();
+        let (name, args) = command.split_once(' ').unwrap_or((command, ""));
+        let args = args.trim();
+        let response = match name {
+            "quit" => return None,
+            "games" => Ok(format!("games {}", GAME_NAMES.join(" "))),
+            "newgame" => {
+                let mut parts = args.split_whitespace();
+                match parts.next() {
+                    Some(game) => registry::new_game(game)
+                        .map_err(|e| e.to_string())
+                        .map(|game| {
+                            self.start(game);
+                            match parts.next() {
+                                Some(seed) => format!("ok {}", seed),
+                                None => "ok".to_string(),
+                            }
+                        }),
+                    None => Err("usage: newgame <name> [seed]".to_string()),
+                }
+            }
+            "load" => match args.split_once(' ') {
+                Some((game, state)) => registry::load_game(game, state)
+                    .map_err(|e| e.to_string())
+                    .map(|game| {
+                        self.start(game);
+                        "ok".to_string()
+                    }),
+                None => Err("usage: load <name> <state json>".to_string()),
+            },
+            "moves" => self.game().map(|game| format!("moves {}", join(&game.moves()))),
+            "apply" => match args.parse::<i32>() {
+                Ok(action) => self.game().and_then(|game| {
+                    game.apply_move(action)
+                        .map(|_| "ok".to_string())
+                        .map_err(|e| e.to_string())
+                }),
+                Err(_) => Err("usage: apply <move>".to_string()),
+            },
+            "botmove" => match args.parse::<u64>() {
+                Ok(ms) => self.bot_move(ms).map(|action| format!("botmove {}", action)),
+                Err(_) => Err("usage: botmove <ms>".to_string()),
+            },
+            "state" => self.game().map(|game| format!("state {}", game.state_json())),
+            "changes" => self.game().map(|game| format!("changes {}", game.changes_json())),
+            "seat" => self.game().map(|game| format!("seat {}", game.current_seat())),
+            "winners" => self.game().map(|game| format!("winners {}", join(&game.winners()))),
+            _ => Err(format!("unknown command {}", name)),
+        };
+        Some(response.unwrap_or_else(|message| format!("error {}", message)))
+    }
+}
+
+/// Answer commands from `input` until it closes or `quit` is received
+pub fn run(input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    let mut session = Session::new();
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match session.handle(&line) {
+            Some(response) => {
+                writeln!(output, "{}", response.trim_end())?;
+                output.flush()?;
+            }
+            None => break,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session() {
+        let mut session = Session::new();
+        assert!(session.handle("moves").unwrap().starts_with("error"));
+        assert_eq!(session.handle("newgame szs 42").unwrap(), "ok 42");
+        assert!(session.handle("newgame chess").unwrap().starts_with("error"));
+
+        let moves = session.handle("moves").unwrap();
+        let first = moves.split_whitespace().nth(1).unwrap().to_string();
+        assert_eq!(session.handle(&format!("apply {}", first)).unwrap(), "ok");
+        assert!(session.handle("apply x").unwrap().starts_with("error"));
+
+        let action = session.handle("botmove 50").unwrap();
+        let action = action.strip_prefix("botmove ").unwrap();
+        let moves = session.handle("moves").unwrap();
+        assert!(moves.split_whitespace().skip(1).any(|mov| mov == action));
+
+        let state = session.handle("state").unwrap();
+        let state = state.strip_prefix("state ").unwrap();
+        assert_eq!(session.handle(&format!("load szs {}", state)).unwrap(), "ok");
+        assert_eq!(session.handle("state").unwrap(), format!("state {}", state));
+        assert_eq!(session.handle("quit"), None);
+    }
+
+    #[test]
+    fn test_run() {
+        let input = "games\n\nnewgame hotdog\nseat\nquit\nmoves\n";
+        let mut output = vec![];
+        run(input.as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("games dealers_dilemma"));
+        assert_eq!(lines[1], "ok");
+        assert!(lines[2].starts_with("seat "));
+    }
+}
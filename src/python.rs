@@ -0,0 +1,126 @@
+/*
+Python bindings (enabled with the `python` feature, built with maturin)
+
+A gym-like interface over the production rules engines:
+
+  import tricksterstable_rs as tt
+  game = tt.Game("szs")
+  observation = game.reset()
+  while True:
+      observation, rewards, done, player = game.step(game.legal_actions()[0])
+      if done:
+          break
+*/
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use crate::registry::{self, DynGame, GAME_NAMES};
+
+fn to_py(error: registry::RegistryError) -> PyErr {
+    PyValueError::new_err(error.to_string())
+}
+
+#[pyclass(name = "Game")]
+pub struct PyGame {
+    game: Box<dyn DynGame>,
+}
+
+#[pymethods]
+impl PyGame {
+    #[new]
+    fn new(name: &str) -> PyResult<Self> {
+        let game = registry::new_game(name).map_err(to_py)?;
+        Ok(PyGame { game })
+    }
+
+    /// Deal a new game and return the observation of the player to act
+    fn reset(&mut self) -> PyResult<Vec<f32>> {
+        self.game = registry::new_game(self.game.name()).map_err(to_py)?;
+        Ok(self.observation(None))
+    }
+
+    fn legal_actions(&self) -> Vec<i32> {
+        self.game.moves()
+    }
+
+    /// Apply `action` returning (observation of the next player to act,
+    /// reward for every seat, done, next player to act)
+    fn step(&mut self, action: i32) -> PyResult<(Vec<f32>, Vec<f32>, bool, usize)> {
+        self.game.apply_move(action).map_err(to_py)?;
+        let done = !self.game.winners().is_empty();
+        let rewards = (0..self.game.player_count())
+            .map(|seat| self.game.outcome(seat))
+            .collect();
+        Ok((self.observation(None), rewards, done, self.game.current_seat()))
+    }
+
+    /// Features seen by `seat` (the player to act when omitted)
+    #[pyo3(signature = (seat=None))]
+    fn observation(&self, seat: Option<usize>) -> Vec<f32> {
+        self.game.features(seat.unwrap_or_else(|| self.game.current_seat()))
+    }
+
+    /// Policy head slot of `action`
+    fn action_index(&self, action: i32) -> usize {
+        self.game.action_index(action)
+    }
+
+    /// Run the ISMCTS bot for the player to act without holding the GIL
+    fn bot_move(&self, py: Python<'_>, iterations: i32) -> i32 {
+        let game = &self.game;
+        py.allow_threads(|| game.bot_move(iterations))
+    }
+
+    fn state(&self) -> String {
+        self.game.state_json()
+    }
+
+    fn set_state(&mut self, state: &str) -> PyResult<()> {
+        self.game.set_state_json(state).map_err(to_py)
+    }
+
+    fn changes(&self) -> String {
+        self.game.changes_json()
+    }
+
+    fn winners(&self) -> Vec<usize> {
+        self.game.winners()
+    }
+
+    fn scores(&self) -> Vec<i32> {
+        self.game.scores()
+    }
+
+    #[getter]
+    fn name(&self) -> &'static str {
+        self.game.name()
+    }
+
+    #[getter]
+    fn current_player(&self) -> usize {
+        self.game.current_seat()
+    }
+
+    #[getter]
+    fn player_count(&self) -> usize {
+        self.game.player_count()
+    }
+
+    #[getter]
+    fn feature_count(&self) -> usize {
+        self.game.feature_count()
+    }
+
+    #[getter]
+    fn action_count(&self) -> usize {
+        self.game.action_count()
+    }
+}
+
+#[pymodule]
+fn tricksterstable_rs(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyGame>()?;
+    m.add("GAME_NAMES", GAME_NAMES.to_vec())?;
+    Ok(())
+}
@@ -0,0 +1,371 @@
+/*
+Linear policy models with an int8 inference path
+
+Low-end phones can't afford a float network on every move so policies can
+be exported as a single linear layer over the game's FeatureExtractor
+features. Quantized models store one i8 weight per input with a float scale
+per output row; features are quantized per position with a single scale so
+the dot products are accumulated in i32.
+*/
+
+use std::io;
+use std::marker::PhantomData;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::features::FeatureExtractor;
+use crate::policy::PolicyModel;
+
+/// A dense layer: `rows` outputs over `cols` inputs stored row-major
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Linear {
+    pub rows: usize,
+    pub cols: usize,
+    pub weights: Vec<f32>,
+    pub bias: Vec<f32>,
+}
+
+impl Linear {
+    pub fn zeros(rows: usize, cols: usize) -> Self {
+        Linear {
+            rows,
+            cols,
+            weights: vec![0.0; rows * cols],
+            bias: vec![0.0; rows],
+        }
+    }
+
+    pub fn logits(&self, input: &[f32]) -> Vec<f32> {
+        self.weights
+            .chunks(self.cols)
+            .zip(&self.bias)
+            .map(|(row, bias)| bias + row.iter().zip(input).map(|(w, x)| w * x).sum::<f32>())
+            .collect()
+    }
+
+    pub fn quantize(&self) -> QuantizedLinear {
+        let mut weights = Vec::with_capacity(self.weights.len());
+        let mut scales = Vec::with_capacity(self.rows);
+        for row in self.weights.chunks(self.cols) {
+            let scale = symmetric_scale(row);
+            scales.push(scale);
+            weights.extend(row.iter().map(|w| quantize_value(*w, scale)));
+        }
+        QuantizedLinear {
+            rows: self.rows,
+            cols: self.cols,
+            weights,
+            scales,
+            bias: self.bias.clone(),
+        }
+    }
+}
+
+/// Int8 version of `Linear`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuantizedLinear {
+    pub rows: usize,
+    pub cols: usize,
+    pub weights: Vec<i8>,
+    // one scale per row
+    pub scales: Vec<f32>,
+    pub bias: Vec<f32>,
+}
+
+impl QuantizedLinear {
+    pub fn logits(&self, input: &[f32]) -> Vec<f32> {
+        let input_scale = symmetric_scale(input);
+        let input: Vec<i8> = input
+            .iter()
+            .map(|x| quantize_value(*x, input_scale))
+            .collect();
+        self.weights
+            .chunks(self.cols)
+            .zip(self.scales.iter().zip(&self.bias))
+            .map(|(row, (scale, bias))| {
+                let dot: i32 = row
+                    .iter()
+                    .zip(&input)
+                    .map(|(w, x)| *w as i32 * *x as i32)
+                    .sum();
+                bias + dot as f32 * scale * input_scale
+            })
+            .collect()
+    }
+}
+
+fn symmetric_scale(values: &[f32]) -> f32 {
+    let max = values.iter().fold(0.0f32, |max, v| max.max(v.abs()));
+    if max == 0.0 {
+        1.0
+    } else {
+        max / 127.0
+    }
+}
+
+fn quantize_value(value: f32, scale: f32) -> i8 {
+    (value / scale).round().clamp(-127.0, 127.0) as i8
+}
+
+/// Layers that produce policy logits - implemented by both precisions
+pub trait Logits: Send + Sync {
+    fn logits(&self, input: &[f32]) -> Vec<f32>;
+}
+
+impl Logits for Linear {
+    fn logits(&self, input: &[f32]) -> Vec<f32> {
+        Linear::logits(self, input)
+    }
+}
+
+impl Logits for QuantizedLinear {
+    fn logits(&self, input: &[f32]) -> Vec<f32> {
+        QuantizedLinear::logits(self, input)
+    }
+}
+
+/// Linear policy over a game's features - `LinearPolicy<G, QuantizedLinear>`
+/// is the int8 variant
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound = "L: Serialize + serde::de::DeserializeOwned")]
+pub struct LinearPolicy<G, L = Linear> {
+    pub layer: L,
+    #[serde(skip)]
+    _game: PhantomData<fn(&G)>,
+}
+
+impl<G: FeatureExtractor, L: Logits> LinearPolicy<G, L> {
+    pub fn new(layer: L) -> Self {
+        LinearPolicy {
+            layer,
+            _game: PhantomData,
+        }
+    }
+
+    /// Legal move with the highest logit for the player to act
+    pub fn best_move(&self, game: &G, moves: &[i32]) -> Option<i32> {
+        let logits = self.layer.logits(&game.features(game.current_seat()));
+        moves.iter().copied().max_by(|a, b| {
+            let a = logits[game.action_index(*a)];
+            let b = logits[game.action_index(*b)];
+            a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+        })
+    }
+}
+
+impl<G: FeatureExtractor> LinearPolicy<G, Linear> {
+    pub fn zeros() -> Self {
+        LinearPolicy::new(Linear::zeros(G::ACTION_COUNT, G::FEATURE_COUNT))
+    }
+
+    pub fn quantize(&self) -> LinearPolicy<G, QuantizedLinear> {
+        LinearPolicy::new(self.layer.quantize())
+    }
+}
+
+/// Why a float linear policy scored a move the way it did
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MoveInspection {
+    #[serde(rename = "move")]
+    pub mov: i32,
+    pub action_index: usize,
+    pub logit: f32,
+    pub prior: f32,
+    // (feature index, weight * feature value) ordered by absolute size
+    pub contributions: Vec<(usize, f32)>,
+}
+
+/// The policy's most important features and how it scores each move
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PolicyReport {
+    // (feature index, importance) ordered by importance
+    pub feature_importances: Vec<(usize, f32)>,
+    pub moves: Vec<MoveInspection>,
+}
+
+impl<G: FeatureExtractor> LinearPolicy<G, Linear> {
+    /// Logit, prior and the `top` largest feature contributions of each move
+    pub fn inspect(&self, game: &G, moves: &[i32], top: usize) -> Vec<MoveInspection> {
+        let features = game.features(game.current_seat());
+        let logits = self.layer.logits(&features);
+        let priors = self.priors(game, moves);
+        moves
+            .iter()
+            .zip(priors)
+            .map(|(mov, prior)| {
+                let action_index = game.action_index(*mov);
+                let row = &self.layer.weights[action_index * self.layer.cols..][..self.layer.cols];
+                let mut contributions: Vec<(usize, f32)> = row
+                    .iter()
+                    .zip(&features)
+                    .enumerate()
+                    .filter(|(_, (_, x))| **x != 0.0)
+                    .map(|(index, (w, x))| (index, w * x))
+                    .collect();
+                contributions.sort_by(|a, b| b.1.abs().total_cmp(&a.1.abs()));
+                contributions.truncate(top);
+                MoveInspection {
+                    mov: *mov,
+                    action_index,
+                    logit: logits[action_index],
+                    prior,
+                    contributions,
+                }
+            })
+            .collect()
+    }
+
+    /// Mean absolute weight of every feature across all actions
+    pub fn feature_importances(&self) -> Vec<f32> {
+        let mut importances = vec![0.0; self.layer.cols];
+        for row in self.layer.weights.chunks(self.layer.cols) {
+            for (importance, weight) in importances.iter_mut().zip(row) {
+                *importance += weight.abs() / self.layer.rows as f32;
+            }
+        }
+        importances
+    }
+
+    /// `inspect` along with the `top` most important features
+    pub fn report(&self, game: &G, moves: &[i32], top: usize) -> PolicyReport {
+        let mut importances: Vec<(usize, f32)> =
+            self.feature_importances().into_iter().enumerate().collect();
+        importances.sort_by(|a, b| b.1.total_cmp(&a.1));
+        importances.truncate(top);
+        PolicyReport {
+            feature_importances: importances,
+            moves: self.inspect(game, moves, top),
+        }
+    }
+}
+
+impl<G, L: Serialize + serde::de::DeserializeOwned> LinearPolicy<G, L> {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        std::fs::write(path, serde_json::to_string(self)?)
+    }
+}
+
+impl<G: FeatureExtractor, L: Logits> PolicyModel<G> for LinearPolicy<G, L> {
+    fn priors(&self, game: &G, moves: &[i32]) -> Vec<f32> {
+        let logits = self.layer.logits(&game.features(game.current_seat()));
+        let selected: Vec<f32> = moves
+            .iter()
+            .map(|mov| logits[game.action_index(*mov)])
+            .collect();
+        let max = selected.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        let exp: Vec<f32> = selected.iter().map(|logit| (logit - max).exp()).collect();
+        let total: f32 = exp.iter().sum();
+        exp.iter().map(|e| e / total).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::szs::Game;
+    use crate::models::load_linear_policy;
+    use crate::training::Sample;
+    use serde_json::Value;
+    use std::fs::File;
+    use std::io::{prelude::*, BufReader};
+
+    #[test]
+    fn test_quantized_logits() {
+        let layer = Linear {
+            rows: 2,
+            cols: 3,
+            weights: vec![0.5, -1.0, 0.25, 0.0, 0.0, 0.0],
+            bias: vec![0.1, -0.2],
+        };
+        let input = [1.0, 0.5, 0.0];
+        let exact = layer.logits(&input);
+        let quantized = layer.quantize().logits(&input);
+        for (exact, quantized) in exact.iter().zip(quantized) {
+            assert!((exact - quantized).abs() < 0.01);
+        }
+        assert_eq!(layer.quantize().logits(&[0.0; 3]), layer.bias);
+    }
+
+    #[test]
+    fn test_inspect() {
+        let mut policy: LinearPolicy<Game> = LinearPolicy::zeros();
+        for (index, weight) in policy.layer.weights.iter_mut().enumerate() {
+            *weight = (index % 7) as f32 - 3.0;
+        }
+        let game = Game::new();
+        let moves = game.get_moves();
+        let inspections = policy.inspect(&game, &moves, 5);
+        assert_eq!(inspections.len(), moves.len());
+        let priors: f32 = inspections.iter().map(|i| i.prior).sum();
+        assert!((priors - 1.0).abs() < 1e-4);
+        assert!(inspections.iter().all(|i| i.contributions.len() <= 5));
+        assert_eq!(policy.feature_importances().len(), policy.layer.cols);
+    }
+
+    /// The legal action with the highest logit, picked the way `best_move` does
+    fn best_action(logits: &[f32], legal_actions: &[usize]) -> Option<usize> {
+        legal_actions.iter().copied().max_by(|a, b| {
+            logits[*a]
+                .partial_cmp(&logits[*b])
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+    }
+
+    /// Quantized and float versions of a trained policy must pick the same
+    /// move. data/szs.policy.json was trained with `train_linear_policy` on
+    /// the decisions in data/szs.multiplegames.json and data/szs.samples.jsonl
+    /// holds the decisions of two games it wasn't trained on.
+    #[test]
+    fn test_quantized_move_agreement() {
+        let policy: LinearPolicy<Game> =
+            load_linear_policy(Path::new("data/szs.policy.json")).unwrap();
+        let quantized = policy.quantize();
+
+        let mut positions = 0;
+        let mut agreements = 0;
+        let reader = BufReader::new(File::open("data/szs.samples.jsonl").unwrap());
+        for line in reader.lines() {
+            let sample: Sample = serde_json::from_str(&line.unwrap()).unwrap();
+            if sample.legal_actions.len() < 2 {
+                continue;
+            }
+            positions += 1;
+            let exact = policy.layer.logits(&sample.features);
+            let rounded = quantized.layer.logits(&sample.features);
+            if best_action(&exact, &sample.legal_actions)
+                == best_action(&rounded, &sample.legal_actions)
+            {
+                agreements += 1;
+            }
+        }
+        // and on positions from a recorded game
+        let reader = BufReader::new(File::open("data/szs.singlegame.json").unwrap());
+        for line in reader.lines() {
+            let test_case: Value = serde_json::from_str(&line.unwrap()).unwrap();
+            let game: Game = serde_json::from_value(test_case["gameState"].clone()).unwrap();
+            if game.winner.is_some() {
+                continue;
+            }
+            let moves = game.get_moves();
+            if moves.len() < 2 {
+                continue;
+            }
+            positions += 1;
+            if policy.best_move(&game, &moves) == quantized.best_move(&game, &moves) {
+                agreements += 1;
+            }
+        }
+        assert!(positions > 0);
+        let agreement = agreements as f64 / positions as f64;
+        assert!(agreement >= 0.95, "move agreement {:.3}", agreement);
+    }
+}
@@ -0,0 +1,142 @@
+/*
+Repro bundles for failures found by simulations
+
+  cargo run -- repro <bundle> [--from-start]
+
+When a move panics (or breaks a change list invariant with `--check`)
+during `simulate` the game is written to `<repro dir>/<game>-<seed>.json`:
+the game name, seed, bots, the state the game started from, every move up
+to the failure, the state right before the failing move and the failure
+itself.
+
+`repro` replays the failing move on a single thread so a debugger can
+break on it. With `--from-start` the whole move list is replayed from the
+starting state instead - the engines deal later hands with thread_rng so
+that only reproduces failures in the first hand.
+*/
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::registry::{self, RegistryError};
+use crate::tournament::BotConfig;
+use crate::validate::{self, Violation};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReproBundle {
+    pub game: String,
+    pub seed: u64,
+    pub bots: Vec<BotConfig>,
+    pub start_state: Value,
+    /// Every move made - the last one failed
+    pub moves: Vec<i32>,
+    /// The state the failing move was made in
+    pub state: Value,
+    /// Panic message or the change list violations
+    pub failure: String,
+}
+
+impl ReproBundle {
+    pub fn failing_move(&self) -> Option<i32> {
+        self.moves.last().copied()
+    }
+
+    /// Write the bundle into `dir` (created if needed)
+    pub fn write(&self, dir: &Path) -> io::Result<PathBuf> {
+        fs::create_dir_all(dir)?;
+        let path = dir.join(format!("{}-{}.json", self.game, self.seed));
+        let json = serde_json::to_string_pretty(self).expect("bundle should serialize");
+        fs::write(&path, json)?;
+        Ok(path)
+    }
+
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        serde_json::from_str(&contents)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+
+    /// Make the failing move on the saved state - returns the change list
+    /// violations of the move (a panic propagates to the caller)
+    pub fn replay(&self) -> Result<Vec<Violation>, RegistryError> {
+        let mut game = registry::new_game(&self.game)?;
+        game.set_state_json(&self.state.to_string())?;
+        if let Some(action) = self.failing_move() {
+            return apply_checked(game.as_mut(), action);
+        }
+        Ok(vec![])
+    }
+
+    /// Replay every move from the starting state
+    pub fn replay_from_start(&self) -> Result<Vec<Violation>, RegistryError> {
+        let mut game = registry::new_game(&self.game)?;
+        game.set_state_json(&self.start_state.to_string())?;
+        let mut violations = vec![];
+        for action in self.moves.iter() {
+            violations = apply_checked(game.as_mut(), *action)?;
+        }
+        Ok(violations)
+    }
+}
+
+pub fn state_value(game: &dyn registry::DynGame) -> Value {
+    serde_json::from_str(&game.state_json()).expect("game state should serialize")
+}
+
+/// Make a move and check the changes it emitted
+pub fn apply_checked(
+    game: &mut dyn registry::DynGame,
+    action: i32,
+) -> Result<Vec<Violation>, RegistryError> {
+    let before = state_value(game);
+    game.apply_move(action)?;
+    Ok(validate::validate_move(&before, &state_value(game), game.player_count()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundle() {
+        let mut game = registry::new_game("kansascity").unwrap();
+        let start_state = state_value(game.as_ref());
+        let mut moves = vec![];
+        for _ in 0..3 {
+            let action = game.moves()[0];
+            moves.push(action);
+            game.apply_move(action).unwrap();
+        }
+        let action = game.moves()[0];
+        moves.push(action);
+        let bundle = ReproBundle {
+            game: "kansascity".to_string(),
+            seed: 3,
+            bots: vec![BotConfig::Random],
+            start_state,
+            moves,
+            state: state_value(game.as_ref()),
+            failure: "test".to_string(),
+        };
+        let dir = std::env::temp_dir().join(format!("repro-test-{}", std::process::id()));
+        let path = bundle.write(&dir).unwrap();
+        assert!(path.ends_with("kansascity-3.json"));
+        let read = ReproBundle::read(&path).unwrap();
+        assert_eq!(read, bundle);
+        assert_eq!(read.failing_move(), Some(action));
+        read.replay().unwrap();
+        read.replay_from_start().unwrap();
+        fs::remove_dir_all(dir).unwrap();
+
+        let illegal = ReproBundle {
+            moves: vec![i32::MIN],
+            ..bundle
+        };
+        assert!(matches!(illegal.replay(), Err(RegistryError::IllegalMove(i32::MIN))));
+    }
+}
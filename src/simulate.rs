@@ -0,0 +1,441 @@
+/*
+Headless batch simulation
+
+  cargo run --release -- simulate <game> [--games <n>] [--threads <n>] [--seed <n>]
+      [--bot <mcts:<iterations>|random>]... [--profile] [--check] [--repro-dir <path>]
+      [--json <path>]
+
+Plays games on a pool of threads (see src/utils/batch.rs) and reports wins,
+mean scores and throughput. Game `i` picks its random moves with seed
+`seed + i` so a run is the seed range `seed..seed + games` whatever the
+thread count (the engines deal with thread_rng so only the moves are
+reproducible).
+
+A game that panics (or breaks a change list invariant with `check`) stops
+there and is written out as a repro bundle - see src/repro.rs.
+
+With `profile` the report includes the time spent in each phase of the
+engine - see src/profiling.rs.
+
+  cargo run --release -- bench <game> [--games <n>] [--threads <n>] [--seed <n>]
+      [--json <path>]
+
+Plays the same seed range (10,000 random games by default) at 1, 2, 4...
+threads and reports the throughput and speedup of each thread count so
+performance work has a baseline to compare against.
+*/
+
+use std::any::Any;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use serde::{Deserialize, Serialize};
+
+use crate::profiling::{self, ProfileReport};
+use crate::registry::{self, RegistryError};
+use crate::repro::{self, ReproBundle};
+use crate::tournament::BotConfig;
+use crate::utils::batch::{self, BatchGame, BatchReport};
+use crate::utils::render;
+
+/// Safety net for engines that never reach a terminal state
+const MAX_MOVES_PER_GAME: usize = 10_000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulationConfig {
+    pub game: String,
+    pub games: usize,
+    pub threads: usize,
+    /// Seed of the first game
+    pub seed: u64,
+    /// Bots by seat - repeated around the table when there are fewer bots
+    /// than seats
+    pub bots: Vec<BotConfig>,
+    /// Time the phases of the engine
+    pub profile: bool,
+    /// Check the change list of every move (see src/validate.rs)
+    pub check: bool,
+    /// Where repro bundles of failed games are written - none when None
+    pub repro_dir: Option<PathBuf>,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        SimulationConfig {
+            game: "dealers_dilemma".to_string(),
+            games: 1000,
+            threads: batch::default_threads(),
+            seed: 0,
+            bots: vec![BotConfig::Random],
+            profile: false,
+            check: false,
+            repro_dir: Some(PathBuf::from("repro")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulatedGame {
+    pub seed: u64,
+    pub scores: Vec<i32>,
+    pub winners: Vec<usize>,
+    pub moves: usize,
+    /// Panic message or change list violations that ended the game early
+    pub failure: Option<String>,
+    /// Repro bundle written for the failure
+    pub repro: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulationReport {
+    pub config: SimulationConfig,
+    /// In seed order
+    pub games: Vec<SimulatedGame>,
+    /// Games won by each seat (shared wins count for every winner)
+    pub wins: Vec<usize>,
+    pub mean_scores: Vec<f64>,
+    pub elapsed_seconds: f64,
+    pub profile: Option<ProfileReport>,
+}
+
+impl SimulationReport {
+    pub fn games_per_second(&self) -> f64 {
+        self.games.len() as f64 / self.elapsed_seconds.max(f64::EPSILON)
+    }
+}
+
+impl fmt::Display for SimulationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows: Vec<Vec<String>> = (0..self.wins.len())
+            .map(|seat| {
+                vec![
+                    seat.to_string(),
+                    self.config.bots[seat % self.config.bots.len()].to_string(),
+                    self.wins[seat].to_string(),
+                    format!("{:.2}", self.mean_scores[seat]),
+                ]
+            })
+            .collect();
+        writeln!(f, "{}", render::table(&["seat", "bot", "wins", "mean score"], &rows))?;
+        let moves: usize = self.games.iter().map(|game| game.moves).sum();
+        write!(
+            f,
+            "{} games ({} moves) in {:.2}s - {:.1} games/s",
+            self.games.len(),
+            moves,
+            self.elapsed_seconds,
+            self.games_per_second()
+        )?;
+        for game in self.games.iter() {
+            if let Some(failure) = &game.failure {
+                let repro = game.repro.as_deref().unwrap_or("no repro bundle");
+                write!(f, "\nseed {} failed ({}): {}", game.seed, repro, failure)?;
+            }
+        }
+        if let Some(profile) = &self.profile {
+            write!(f, "\n\n{}", profile)?;
+        }
+        Ok(())
+    }
+}
+
+/// Throughput of one thread count of a scaling run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScalingRun {
+    pub threads: usize,
+    pub moves: usize,
+    pub elapsed_seconds: f64,
+    pub games_per_second: f64,
+    /// Relative to the first run
+    pub speedup: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScalingReport {
+    pub game: String,
+    pub games: usize,
+    pub seed: u64,
+    pub runs: Vec<ScalingRun>,
+}
+
+impl fmt::Display for ScalingReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows: Vec<Vec<String>> = self
+            .runs
+            .iter()
+            .map(|run| {
+                vec![
+                    run.threads.to_string(),
+                    format!("{:.2}", run.elapsed_seconds),
+                    format!("{:.1}", run.games_per_second),
+                    format!("{:.2}x", run.speedup),
+                ]
+            })
+            .collect();
+        writeln!(f, "{} - {} games from seed {}", self.game, self.games, self.seed)?;
+        write!(f, "{}", render::table(&["threads", "seconds", "games/s", "speedup"], &rows))
+    }
+}
+
+/// 1, 2, 4... doubling up to `max_threads` (which is always the last count)
+pub fn thread_counts(max_threads: usize) -> Vec<usize> {
+    let max_threads = max_threads.max(1);
+    let mut counts: Vec<usize> = std::iter::successors(Some(1), |threads| Some(threads * 2))
+        .take_while(|threads| *threads < max_threads)
+        .collect();
+    counts.push(max_threads);
+    counts
+}
+
+/// Play the games of `config` once for each of `thread_counts`
+pub fn scaling(
+    config: &SimulationConfig,
+    thread_counts: &[usize],
+) -> Result<ScalingReport, RegistryError> {
+    let mut runs: Vec<ScalingRun> = vec![];
+    for threads in thread_counts {
+        let config = SimulationConfig {
+            threads: *threads,
+            ..config.clone()
+        };
+        let report = simulate(&config, |_| {})?;
+        let games_per_second = report.games_per_second();
+        let speedup = match runs.first() {
+            Some(first) => games_per_second / first.games_per_second.max(f64::EPSILON),
+            None => 1.0,
+        };
+        runs.push(ScalingRun {
+            threads: *threads,
+            moves: report.games.iter().map(|game| game.moves).sum(),
+            elapsed_seconds: report.elapsed_seconds,
+            games_per_second,
+            speedup,
+        });
+    }
+    Ok(ScalingReport {
+        game: config.game.clone(),
+        games: config.games,
+        seed: config.seed,
+        runs,
+    })
+}
+
+/// `[=====     ] 50/100 eta 12s`
+pub fn progress_line(done: usize, total: usize, elapsed: Duration) -> String {
+    const WIDTH: usize = 30;
+    let filled = if total > 0 { WIDTH * done / total } else { WIDTH };
+    let eta = if done > 0 {
+        let remaining = elapsed.as_secs_f64() * (total - done) as f64 / done as f64;
+        format!("eta {:.0}s", remaining)
+    } else {
+        "eta -".to_string()
+    };
+    format!(
+        "[{}{}] {}/{} {}",
+        "=".repeat(filled),
+        " ".repeat(WIDTH - filled),
+        done,
+        total,
+        eta
+    )
+}
+
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    match (panic.downcast_ref::<&str>(), panic.downcast_ref::<String>()) {
+        (Some(message), _) => message.to_string(),
+        (_, Some(message)) => message.clone(),
+        _ => "panic".to_string(),
+    }
+}
+
+fn play_game(
+    config: &SimulationConfig,
+    seed: u64,
+    rng: &mut StdRng,
+) -> Result<SimulatedGame, RegistryError> {
+    let mut game = registry::new_game(&config.game)?;
+    let start_state = config.repro_dir.as_ref().map(|_| repro::state_value(game.as_ref()));
+    let mut moves = vec![];
+    let mut legal_moves = vec![];
+    let mut failure = None;
+    let mut repro_path = None;
+    while game.winners().is_empty() && moves.len() < MAX_MOVES_PER_GAME {
+        let bot = config.bots[game.current_seat() % config.bots.len()];
+        let action = bot.get_move_with_buffer(game.as_ref(), rng, &mut legal_moves);
+        moves.push(action);
+        // only needed for the repro bundle
+        let before = start_state.as_ref().map(|_| game.clone_game());
+        let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+            if config.check {
+                repro::apply_checked(game.as_mut(), action)
+            } else {
+                game.apply_move(action).map(|_| vec![])
+            }
+        }));
+        let message = match outcome {
+            Ok(Ok(violations)) if violations.is_empty() => continue,
+            Ok(Ok(violations)) => {
+                let violations: Vec<String> = violations.iter().map(ToString::to_string).collect();
+                violations.join("\n")
+            }
+            Ok(Err(error)) => return Err(error),
+            Err(panic) => panic_message(panic),
+        };
+        if let (Some(start_state), Some(before), Some(dir)) =
+            (start_state, before, config.repro_dir.as_ref())
+        {
+            let bundle = ReproBundle {
+                game: config.game.clone(),
+                seed,
+                bots: config.bots.clone(),
+                start_state,
+                moves: moves.clone(),
+                state: repro::state_value(before.as_ref()),
+                failure: message.clone(),
+            };
+            match bundle.write(dir) {
+                Ok(path) => repro_path = Some(path.display().to_string()),
+                Err(error) => eprintln!("{}: {}", dir.display(), error),
+            }
+        }
+        failure = Some(message);
+        break;
+    }
+    Ok(SimulatedGame {
+        seed,
+        scores: game.scores(),
+        winners: game.winners(),
+        moves: moves.len(),
+        failure,
+        repro: repro_path,
+    })
+}
+
+/// Play `config.games` games - `progress` is called with the number of
+/// finished games after each one
+pub fn simulate(
+    config: &SimulationConfig,
+    progress: impl Fn(usize) + Sync,
+) -> Result<SimulationReport, RegistryError> {
+    assert!(!config.bots.is_empty(), "a simulation needs at least one bot");
+    let player_count = registry::new_game(&config.game)?.player_count();
+    if config.profile {
+        // drop timings left over from earlier runs
+        profiling::take_report(0);
+        profiling::set_enabled(true);
+    }
+    let start = Instant::now();
+    let done = AtomicUsize::new(0);
+    let results = batch::simulate_batch(config.games, config.threads, config.seed, |index, rng| {
+        let game = play_game(config, config.seed.wrapping_add(index as u64), rng);
+        progress(done.fetch_add(1, Ordering::Relaxed) + 1);
+        game
+    });
+    let elapsed_seconds = start.elapsed().as_secs_f64();
+    let profile = if config.profile {
+        profiling::set_enabled(false);
+        Some(profiling::take_report(config.games))
+    } else {
+        None
+    };
+    let games: Vec<SimulatedGame> = results.into_iter().collect::<Result<_, _>>()?;
+    let outcomes = games
+        .iter()
+        .map(|game| BatchGame {
+            scores: game.scores.clone(),
+            winners: game.winners.clone(),
+            moves: game.moves,
+        })
+        .collect();
+    let totals = BatchReport::new(player_count, outcomes);
+    Ok(SimulationReport {
+        config: config.clone(),
+        games,
+        wins: totals.wins,
+        mean_scores: totals.mean_scores,
+        elapsed_seconds,
+        profile,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simulate() {
+        let config = SimulationConfig {
+            game: "yokai2p".to_string(),
+            games: 5,
+            threads: 2,
+            seed: 10,
+            ..Default::default()
+        };
+        let calls = AtomicUsize::new(0);
+        let report = simulate(&config, |_| {
+            calls.fetch_add(1, Ordering::Relaxed);
+        })
+        .unwrap();
+        assert_eq!(calls.load(Ordering::Relaxed), 5);
+        let seeds: Vec<u64> = report.games.iter().map(|game| game.seed).collect();
+        assert_eq!(seeds, vec![10, 11, 12, 13, 14]);
+        assert!(report.wins.iter().sum::<usize>() >= 5);
+        assert!(report.to_string().contains("5 games"));
+        assert_eq!(report.profile, None);
+
+        let config = SimulationConfig {
+            games: 2,
+            threads: 2,
+            profile: true,
+            ..config
+        };
+        let _lock = profiling::TEST_LOCK.lock().unwrap_or_else(|error| error.into_inner());
+        let profile = simulate(&config, |_| {}).unwrap().profile.unwrap();
+        assert_eq!(profile.games, 2);
+        for phase in ["deal", "get_moves", "apply_move"] {
+            assert!(profile.phases.iter().any(|timing| timing.phase == phase));
+        }
+
+        let unknown = SimulationConfig {
+            game: "chess".to_string(),
+            ..Default::default()
+        };
+        assert!(simulate(&unknown, |_| {}).is_err());
+    }
+
+    #[test]
+    fn test_scaling() {
+        assert_eq!(thread_counts(1), vec![1]);
+        assert_eq!(thread_counts(8), vec![1, 2, 4, 8]);
+        assert_eq!(thread_counts(6), vec![1, 2, 4, 6]);
+
+        let config = SimulationConfig {
+            game: "yokai2p".to_string(),
+            games: 4,
+            repro_dir: None,
+            ..Default::default()
+        };
+        let report = scaling(&config, &[1, 2]).unwrap();
+        let threads: Vec<usize> = report.runs.iter().map(|run| run.threads).collect();
+        assert_eq!(threads, vec![1, 2]);
+        assert_eq!(report.runs[0].speedup, 1.0);
+        assert!(report.runs.iter().all(|run| run.moves > 0));
+        assert!(report.to_string().contains("yokai2p - 4 games from seed 0"));
+    }
+
+    #[test]
+    fn test_progress_line() {
+        let line = progress_line(15, 30, Duration::from_secs(10));
+        assert_eq!(line, format!("[{}{}] 15/30 eta 10s", "=".repeat(15), " ".repeat(15)));
+        assert!(progress_line(0, 30, Duration::ZERO).ends_with("0/30 eta -"));
+    }
+}
@@ -0,0 +1,417 @@
+/*
+Tournaments between bot configurations
+
+  cargo run --release -- tournament <game> [--bot <mcts:<iterations>|random>]...
+      [--deals <n>] [--seed <n>] [--threads <n>] [--no-rotate] [--results <path>]
+      [--summary <path>]
+
+Bots are assigned to seats in the order given and repeated around the table
+when there are fewer bots than seats (two bots in a four player game are
+partners with themselves). Every deal is replayed with the lineup rotated
+one seat at a time so no bot benefits from a lucky hand or a favourable
+seat.
+
+The engines deal with thread_rng so the seed only makes the random bots
+reproducible - each deal's rotations start from the same cloned position.
+Deals are played in parallel (see src/utils/batch.rs).
+
+Results are written per game and per bot as CSV (paths ending in `.csv`) or
+JSON (anything else).
+*/
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+
+use crate::arena::{wilson_interval, Z_95};
+use crate::registry::{self, DynGame, RegistryError};
+use crate::utils::batch;
+use crate::utils::render;
+
+/// Safety net for engines that never reach a terminal state
+const MAX_MOVES_PER_GAME: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum BotConfig {
+    Mcts { iterations: i32 },
+    Random,
+}
+
+impl BotConfig {
+    /// `mcts:<iterations>` or `random`
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        match spec.split_once(':') {
+            Some(("mcts", iterations)) => match iterations.parse::<i32>() {
+                Ok(iterations) if iterations > 0 => Ok(BotConfig::Mcts { iterations }),
+                _ => Err(format!("invalid iteration count in {}", spec)),
+            },
+            None if spec == "random" => Ok(BotConfig::Random),
+            _ => Err(format!("unknown bot {} - expected mcts:<iterations> or random", spec)),
+        }
+    }
+
+    /// The bot's move in `game` - `rng` picks random moves
+    pub fn get_move(&self, game: &dyn DynGame, rng: &mut StdRng) -> i32 {
+        self.get_move_with_buffer(game, rng, &mut vec![])
+    }
+
+    /// `get_move` listing the random bot's moves in `moves` so a game loop
+    /// can reuse one buffer
+    pub fn get_move_with_buffer(
+        &self,
+        game: &dyn DynGame,
+        rng: &mut StdRng,
+        moves: &mut Vec<i32>,
+    ) -> i32 {
+        match self {
+            BotConfig::Mcts { iterations } => game.bot_move(*iterations),
+            BotConfig::Random => {
+                game.moves_into(moves);
+                *moves.choose(rng).expect("should have a move to make")
+            }
+        }
+    }
+}
+
+impl fmt::Display for BotConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BotConfig::Mcts { iterations } => write!(f, "mcts:{}", iterations),
+            BotConfig::Random => write!(f, "random"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentConfig {
+    pub game: String,
+    /// Bots by seat - repeated around the table when there are fewer bots
+    /// than seats
+    pub bots: Vec<BotConfig>,
+    /// Number of deals - each one is played once per lineup
+    pub deals: usize,
+    /// Replay every deal with the lineup rotated around the table
+    pub rotate: bool,
+    /// Seed of the first deal's random bots
+    pub seed: u64,
+    /// Deals played at once
+    #[serde(default = "batch::default_threads")]
+    pub threads: usize,
+}
+
+impl Default for TournamentConfig {
+    fn default() -> Self {
+        TournamentConfig {
+            game: "dealers_dilemma".to_string(),
+            bots: vec![BotConfig::Mcts { iterations: 1000 }, BotConfig::Random],
+            deals: 10,
+            rotate: true,
+            seed: 0,
+            threads: batch::default_threads(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GameResult {
+    pub deal: usize,
+    /// Index into the deal's lineups
+    pub lineup: usize,
+    pub seed: u64,
+    /// Bot index by seat
+    pub seats: Vec<usize>,
+    pub scores: Vec<i32>,
+    pub winners: Vec<usize>,
+    pub moves: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BotSummary {
+    pub bot: usize,
+    pub name: String,
+    pub games: usize,
+    /// Games won - a win shared with other bots is split between them
+    pub points: f64,
+    pub win_rate: f64,
+    pub confidence_interval: (f64, f64),
+    /// Mean of the bot's score minus the best score of the other bots
+    pub average_margin: f64,
+    pub seconds_per_move: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentReport {
+    pub config: TournamentConfig,
+    pub results: Vec<GameResult>,
+    pub bots: Vec<BotSummary>,
+}
+
+fn list<T: ToString>(values: &[T]) -> String {
+    values
+        .iter()
+        .map(|value| value.to_string())
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+impl TournamentReport {
+    /// One row per game - the per seat columns are `;` separated
+    pub fn results_csv(&self) -> String {
+        let mut csv = "deal,lineup,seed,seats,scores,winners,moves\n".to_string();
+        for result in self.results.iter() {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{}\n",
+                result.deal,
+                result.lineup,
+                result.seed,
+                list(&result.seats),
+                list(&result.scores),
+                list(&result.winners),
+                result.moves
+            ));
+        }
+        csv
+    }
+
+    /// One row per bot
+    pub fn summary_csv(&self) -> String {
+        let mut csv = "bot,name,games,points,win_rate,win_rate_low,win_rate_high,\
+            average_margin,seconds_per_move\n"
+            .to_string();
+        for bot in self.bots.iter() {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{},{},{}\n",
+                bot.bot,
+                bot.name,
+                bot.games,
+                bot.points,
+                bot.win_rate,
+                bot.confidence_interval.0,
+                bot.confidence_interval.1,
+                bot.average_margin,
+                bot.seconds_per_move
+            ));
+        }
+        csv
+    }
+}
+
+impl fmt::Display for TournamentReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows: Vec<Vec<String>> = self
+            .bots
+            .iter()
+            .map(|bot| {
+                vec![
+                    bot.bot.to_string(),
+                    bot.name.clone(),
+                    bot.games.to_string(),
+                    format!(
+                        "{:.3} ({:.3} - {:.3})",
+                        bot.win_rate, bot.confidence_interval.0, bot.confidence_interval.1
+                    ),
+                    format!("{:.2}", bot.average_margin),
+                    format!("{:.4}", bot.seconds_per_move),
+                ]
+            })
+            .collect();
+        let header = ["bot", "name", "games", "win rate", "margin", "s/move"];
+        write!(f, "{}", render::table(&header, &rows))
+    }
+}
+
+/// Bot index for every seat of each lineup a deal is played with
+pub fn lineups(bot_count: usize, player_count: usize, rotate: bool) -> Vec<Vec<usize>> {
+    let base: Vec<usize> = (0..player_count).map(|seat| seat % bot_count).collect();
+    let mut lineups = vec![base.clone()];
+    if rotate {
+        for shift in 1..player_count {
+            let mut lineup = base.clone();
+            lineup.rotate_right(shift);
+            if !lineups.contains(&lineup) {
+                lineups.push(lineup);
+            }
+        }
+    }
+    lineups
+}
+
+fn summarize(
+    config: &TournamentConfig,
+    results: &[GameResult],
+    thinking: &[(Duration, usize)],
+) -> Vec<BotSummary> {
+    (0..config.bots.len())
+        .map(|bot| {
+            let mut games = 0;
+            let mut points = 0.0;
+            let mut margin = 0.0;
+            for result in results.iter() {
+                let seats = 0..result.seats.len();
+                let own = seats
+                    .clone()
+                    .filter(|seat| result.seats[*seat] == bot)
+                    .map(|seat| result.scores[seat])
+                    .max();
+                let own = match own {
+                    Some(own) => own,
+                    None => continue,
+                };
+                games += 1;
+                let mut winning_bots: Vec<usize> =
+                    result.winners.iter().map(|seat| result.seats[*seat]).collect();
+                winning_bots.sort();
+                winning_bots.dedup();
+                if winning_bots.contains(&bot) {
+                    points += 1.0 / winning_bots.len() as f64;
+                }
+                let best_opponent = seats
+                    .filter(|seat| result.seats[*seat] != bot)
+                    .map(|seat| result.scores[seat])
+                    .max();
+                margin += best_opponent.map_or(0, |best| own - best) as f64;
+            }
+            let (time, moves) = thinking[bot];
+            let per_game = |total: f64| if games > 0 { total / games as f64 } else { 0.0 };
+            BotSummary {
+                bot,
+                name: config.bots[bot].to_string(),
+                games,
+                points,
+                win_rate: per_game(points),
+                confidence_interval: wilson_interval(points, games, Z_95),
+                average_margin: per_game(margin),
+                seconds_per_move: if moves > 0 {
+                    time.as_secs_f64() / moves as f64
+                } else {
+                    0.0
+                },
+            }
+        })
+        .collect()
+}
+
+/// Play a deal once with each lineup - returns the games and the thinking
+/// time and moves of each bot
+fn play_deal(
+    config: &TournamentConfig,
+    lineups: &[Vec<usize>],
+    deal: usize,
+) -> Result<(Vec<GameResult>, Vec<(Duration, usize)>), RegistryError> {
+    let start_game = registry::new_game(&config.game)?;
+    let seed = config.seed.wrapping_add(deal as u64);
+    let mut results = vec![];
+    let mut thinking = vec![(Duration::ZERO, 0); config.bots.len()];
+    let mut legal_moves = vec![];
+    for (index, lineup) in lineups.iter().enumerate() {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut game = start_game.clone_game();
+        let mut moves = 0;
+        while game.winners().is_empty() && moves < MAX_MOVES_PER_GAME {
+            let bot = lineup[game.current_seat()];
+            let start = Instant::now();
+            let action =
+                config.bots[bot].get_move_with_buffer(game.as_ref(), &mut rng, &mut legal_moves);
+            thinking[bot].0 += start.elapsed();
+            thinking[bot].1 += 1;
+            game.apply_move(action)?;
+            moves += 1;
+        }
+        results.push(GameResult {
+            deal,
+            lineup: index,
+            seed,
+            seats: lineup.clone(),
+            scores: game.scores(),
+            winners: game.winners(),
+            moves,
+        });
+    }
+    Ok((results, thinking))
+}
+
+/// Play every deal of the tournament with each lineup (deals are played on
+/// `config.threads` threads)
+pub fn run_tournament(config: &TournamentConfig) -> Result<TournamentReport, RegistryError> {
+    assert!(!config.bots.is_empty(), "a tournament needs at least one bot");
+    let player_count = registry::new_game(&config.game)?.player_count();
+    let lineups = lineups(config.bots.len(), player_count, config.rotate);
+    let deals = batch::simulate_batch(config.deals, config.threads, config.seed, |deal, _| {
+        play_deal(config, &lineups, deal)
+    });
+    let mut results = vec![];
+    // total thinking time and moves of each bot
+    let mut thinking = vec![(Duration::ZERO, 0); config.bots.len()];
+    for deal in deals {
+        let (deal_results, deal_thinking) = deal?;
+        results.extend(deal_results);
+        for (total, (time, moves)) in thinking.iter_mut().zip(deal_thinking) {
+            total.0 += time;
+            total.1 += moves;
+        }
+    }
+    let bots = summarize(config, &results, &thinking);
+    Ok(TournamentReport {
+        config: config.clone(),
+        results,
+        bots,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_bot() {
+        assert_eq!(
+            BotConfig::parse("mcts:500"),
+            Ok(BotConfig::Mcts { iterations: 500 })
+        );
+        assert_eq!(BotConfig::parse("random"), Ok(BotConfig::Random));
+        assert!(BotConfig::parse("mcts:0").is_err());
+        assert!(BotConfig::parse("minimax").is_err());
+        assert_eq!(BotConfig::Mcts { iterations: 500 }.to_string(), "mcts:500");
+    }
+
+    #[test]
+    fn test_lineups() {
+        assert_eq!(lineups(2, 2, true), vec![vec![0, 1], vec![1, 0]]);
+        assert_eq!(lineups(2, 4, true), vec![vec![0, 1, 0, 1], vec![1, 0, 1, 0]]);
+        assert_eq!(lineups(3, 3, false), vec![vec![0, 1, 2]]);
+        assert_eq!(lineups(3, 3, true).len(), 3);
+    }
+
+    #[test]
+    fn test_run_tournament() {
+        let config = TournamentConfig {
+            game: "hotdog".to_string(),
+            bots: vec![BotConfig::Random, BotConfig::Random],
+            deals: 2,
+            ..Default::default()
+        };
+        let report = run_tournament(&config).unwrap();
+        assert_eq!(report.results.len(), 4);
+        assert!(report.results.iter().all(|result| !result.winners.is_empty()));
+        let points: f64 = report.bots.iter().map(|bot| bot.points).sum();
+        assert!((points - 4.0).abs() < 1e-9);
+        assert_eq!(report.results_csv().lines().count(), 5);
+        assert_eq!(report.summary_csv().lines().count(), 3);
+        assert!(report.to_string().starts_with("bot  name"));
+
+        let unknown = TournamentConfig {
+            game: "chess".to_string(),
+            ..Default::default()
+        };
+        assert!(run_tournament(&unknown).is_err());
+    }
+}
@@ -0,0 +1,461 @@
+/*
+AlphaZero style training orchestration
+
+Each generation:
+1. the current best model plays games against itself (guiding the ISMCTS search)
+2. every decision is written to a JSONL dataset
+3. an external trainer is run to produce a candidate model from the datasets
+4. the candidate plays the incumbent and is promoted when it wins often enough
+
+Every candidate gets a model header (see models.rs) so promoted models can be
+picked up by a running ModelManager.
+
+Training itself happens outside of this crate (typically a Python script) so
+the trainer is just a command line with placeholders that are filled in for
+each generation:
+  {data}      - directory holding every dataset written so far
+  {dataset}   - dataset written for this generation
+  {incumbent} - current best model (empty on the first generation)
+  {output}    - path the trainer must write the candidate model to
+*/
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, prelude::*, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+use crate::arena::{play_match, seatings, ArenaConfig, MctsMove};
+use crate::engine::GameEngine;
+use crate::features::FeatureExtractor;
+use crate::models::ModelHeader;
+use crate::policy::PolicyHandle;
+use crate::quantized::LinearPolicy;
+use crate::utils::batch::{default_threads, simulate_batch};
+
+/// Safety net for engines that never reach a terminal state
+const MAX_MOVES_PER_GAME: usize = 10_000;
+
+/// One decision taken during self-play
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Sample {
+    pub game: String,
+    pub seat: usize,
+    pub features: Vec<f32>,
+    // policy head slots of every legal move
+    pub legal_actions: Vec<usize>,
+    // policy head slot of the move that was chosen
+    pub action: usize,
+    // final outcome of the game for `seat` (1 win, -1 loss)
+    pub outcome: f32,
+}
+
+/// Play a single game with every seat searching with the same (optional)
+/// model and return one sample per decision
+pub fn self_play_game<G: GameEngine>(
+    iterations: i32,
+    policy: Option<PolicyHandle<G>>,
+) -> Vec<Sample> {
+    record_game(G::new_game(), |game| {
+        game.bot_move(iterations, policy.clone())
+    })
+}
+
+/// `self_play_game` over `games` games played on `threads` threads -
+/// `played` is called with the number of finished games after each one
+pub fn self_play_batch<G: GameEngine>(
+    games: usize,
+    threads: usize,
+    iterations: i32,
+    policy: Option<PolicyHandle<G>>,
+    played: impl Fn(usize) + Sync,
+) -> Vec<Vec<Sample>> {
+    let done = AtomicUsize::new(0);
+    simulate_batch(games, threads, 0, |_, _| {
+        let samples = self_play_game::<G>(iterations, policy.clone());
+        played(done.fetch_add(1, Ordering::Relaxed) + 1);
+        samples
+    })
+}
+
+/// Play `game` to the end choosing moves with `choose_move` and label every
+/// decision with the final outcome for the player who made it
+pub fn record_game<G: GameEngine>(
+    mut game: G,
+    mut choose_move: impl FnMut(&G) -> i32,
+) -> Vec<Sample> {
+    let mut samples: Vec<Sample> = vec![];
+    for _ in 0..MAX_MOVES_PER_GAME {
+        if game.is_over() {
+            break;
+        }
+        let moves = game.legal_moves();
+        if moves.is_empty() {
+            break;
+        }
+        let seat = game.current_seat();
+        let action = if moves.len() == 1 {
+            moves[0]
+        } else {
+            choose_move(&game)
+        };
+        samples.push(Sample {
+            game: G::NAME.to_string(),
+            seat,
+            features: game.features(seat),
+            legal_actions: moves.iter().map(|mov| game.action_index(*mov)).collect(),
+            action: game.action_index(action),
+            outcome: 0.0,
+        });
+        game.play(action);
+    }
+    for sample in samples.iter_mut() {
+        sample.outcome = game.outcome(sample.seat);
+    }
+    samples
+}
+
+/// Record the decisions of plain (unguided) ISMCTS over many games as a
+/// labelled dataset for supervised pretraining. The file uses the same
+/// format as the self-play datasets so the trainer can consume either.
+/// Returns the number of samples written.
+pub fn record_imitation_dataset<G: GameEngine>(
+    path: &Path,
+    games: usize,
+    iterations: i32,
+) -> io::Result<usize> {
+    let played = |done: usize| {
+        if done % 100 == 0 {
+            println!("{}: {} games", G::NAME, done);
+        }
+    };
+    let games = self_play_batch::<G>(games, default_threads(), iterations, None, played);
+    let mut sample_count = 0;
+    for samples in games.iter() {
+        sample_count += samples.len();
+        write_samples(path, samples)?;
+    }
+    Ok(sample_count)
+}
+
+/// Append samples to a JSONL dataset
+pub fn write_samples(path: &Path, samples: &[Sample]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let mut writer = BufWriter::new(file);
+    for sample in samples {
+        serde_json::to_writer(&mut writer, sample)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()
+}
+
+pub fn read_samples(path: &Path) -> io::Result<Vec<Sample>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut samples = vec![];
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        samples.push(serde_json::from_str(&line)?);
+    }
+    Ok(samples)
+}
+
+/// Policy gradient for small linear models trained in process (such as the
+/// Dealer's Dilemma bid model): moves that did better than the average
+/// sample become more likely and the rest less likely
+pub fn train_linear_policy<G: FeatureExtractor>(
+    policy: &mut LinearPolicy<G>,
+    samples: &[Sample],
+    epochs: usize,
+    learning_rate: f32,
+) {
+    if samples.is_empty() {
+        return;
+    }
+    let baseline = samples.iter().map(|s| s.outcome).sum::<f32>() / samples.len() as f32;
+    let layer = &mut policy.layer;
+    for _ in 0..epochs {
+        for sample in samples {
+            let advantage = sample.outcome - baseline;
+            let logits = layer.logits(&sample.features);
+            let selected: Vec<f32> = sample.legal_actions.iter().map(|a| logits[*a]).collect();
+            let max = selected.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+            let exp: Vec<f32> = selected.iter().map(|logit| (logit - max).exp()).collect();
+            let total: f32 = exp.iter().sum();
+            for (action, exp) in sample.legal_actions.iter().zip(exp) {
+                let target = if *action == sample.action { 1.0 } else { 0.0 };
+                let step = learning_rate * advantage * (target - exp / total);
+                layer.bias[*action] += step;
+                let row = &mut layer.weights[action * layer.cols..][..layer.cols];
+                for (weight, feature) in row.iter_mut().zip(&sample.features) {
+                    *weight += step * feature;
+                }
+            }
+        }
+    }
+}
+
+/// Promotion gate - the candidate must beat the incumbent by this margin
+pub const DEFAULT_PROMOTION_THRESHOLD: f64 = 0.55;
+
+#[derive(Debug, Clone)]
+pub struct TrainingConfig {
+    pub work_dir: PathBuf,
+    pub generations: usize,
+    pub games_per_generation: usize,
+    pub iterations: i32,
+    pub evaluation_games: usize,
+    pub promotion_threshold: f64,
+    pub trainer_command: Vec<String>,
+    /// Self-play games played at once
+    pub threads: usize,
+}
+
+impl Default for TrainingConfig {
+    fn default() -> Self {
+        TrainingConfig {
+            work_dir: PathBuf::from("training"),
+            generations: 10,
+            games_per_generation: 100,
+            iterations: 500,
+            evaluation_games: 100,
+            promotion_threshold: DEFAULT_PROMOTION_THRESHOLD,
+            trainer_command: vec![],
+            threads: default_threads(),
+        }
+    }
+}
+
+/// Loads a model file into a policy usable by the search
+pub type ModelLoader<G> = Box<dyn Fn(&Path) -> io::Result<PolicyHandle<G>> + Send + Sync>;
+
+/// Plays the candidate against the incumbent (None means plain ISMCTS) and
+/// returns the candidate's win rate
+pub type Evaluator<G> = Box<dyn FnMut(PolicyHandle<G>, Option<PolicyHandle<G>>, usize) -> f64>;
+
+pub struct Orchestrator<G: GameEngine> {
+    pub config: TrainingConfig,
+    loader: ModelLoader<G>,
+    evaluator: Evaluator<G>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerationReport {
+    pub generation: usize,
+    pub samples: usize,
+    pub win_rate: f64,
+    pub promoted: bool,
+}
+
+impl<G: GameEngine> Orchestrator<G> {
+    pub fn new(config: TrainingConfig, loader: ModelLoader<G>) -> Self {
+        let iterations = config.iterations;
+        Orchestrator {
+            config,
+            loader,
+            evaluator: Box::new(move |candidate, incumbent, games| {
+                head_to_head(candidate, incumbent, games, iterations)
+            }),
+        }
+    }
+
+    pub fn with_evaluator(mut self, evaluator: Evaluator<G>) -> Self {
+        self.evaluator = evaluator;
+        self
+    }
+
+    pub fn data_dir(&self) -> PathBuf {
+        self.config.work_dir.join(G::NAME).join("data")
+    }
+
+    pub fn model_dir(&self) -> PathBuf {
+        self.config.work_dir.join(G::NAME).join("models")
+    }
+
+    /// Path of the currently promoted model
+    pub fn best_model_path(&self) -> PathBuf {
+        self.model_dir().join("best.onnx")
+    }
+
+    fn incumbent(&self) -> io::Result<Option<PolicyHandle<G>>> {
+        let best = self.best_model_path();
+        if best.exists() {
+            Ok(Some((self.loader)(&best)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn run(&mut self) -> io::Result<Vec<GenerationReport>> {
+        fs::create_dir_all(self.data_dir())?;
+        fs::create_dir_all(self.model_dir())?;
+        let mut reports = vec![];
+        for generation in 0..self.config.generations {
+            let report = self.run_generation(generation)?;
+            println!(
+                "{} generation {}: {} samples, win rate {:.3}, promoted: {}",
+                G::NAME,
+                report.generation,
+                report.samples,
+                report.win_rate,
+                report.promoted
+            );
+            self.log_report(&report)?;
+            reports.push(report);
+        }
+        Ok(reports)
+    }
+
+    pub fn run_generation(&mut self, generation: usize) -> io::Result<GenerationReport> {
+        let incumbent = self.incumbent()?;
+
+        // 1. self-play
+        let dataset = self.data_dir().join(format!("gen_{:04}.jsonl", generation));
+        let games = self_play_batch::<G>(
+            self.config.games_per_generation,
+            self.config.threads,
+            self.config.iterations,
+            incumbent.clone(),
+            |_| {},
+        );
+        let mut sample_count = 0;
+        for samples in games.iter() {
+            sample_count += samples.len();
+            write_samples(&dataset, samples)?;
+        }
+
+        // 2. train
+        let candidate_path = self.model_dir().join(format!("gen_{:04}.onnx", generation));
+        self.train(&dataset, &candidate_path)?;
+        ModelHeader::for_game::<G>(generation as u64).write(&candidate_path)?;
+        let candidate = (self.loader)(&candidate_path)?;
+
+        // 3. evaluate and promote
+        let win_rate = (self.evaluator)(candidate, incumbent, self.config.evaluation_games);
+        let promoted = win_rate > self.config.promotion_threshold;
+        if promoted {
+            fs::copy(&candidate_path, self.best_model_path())?;
+            fs::copy(
+                ModelHeader::path_for(&candidate_path),
+                ModelHeader::path_for(&self.best_model_path()),
+            )?;
+        }
+
+        Ok(GenerationReport {
+            generation,
+            samples: sample_count,
+            win_rate,
+            promoted,
+        })
+    }
+
+    fn train(&self, dataset: &Path, output: &Path) -> io::Result<()> {
+        let (program, args) = self.config.trainer_command.split_first().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "no trainer command configured")
+        })?;
+        let best = self.best_model_path();
+        let incumbent = if best.exists() {
+            best.to_string_lossy().to_string()
+        } else {
+            String::new()
+        };
+        let substitute = |arg: &String| {
+            arg.replace("{data}", &self.data_dir().to_string_lossy())
+                .replace("{dataset}", &dataset.to_string_lossy())
+                .replace("{incumbent}", &incumbent)
+                .replace("{output}", &output.to_string_lossy())
+        };
+        let status = Command::new(substitute(program))
+            .args(args.iter().map(substitute))
+            .status()?;
+        if !status.success() {
+            return Err(io::Error::other(format!("trainer exited with {}", status)));
+        }
+        if !output.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("trainer did not write {}", output.display()),
+            ));
+        }
+        Ok(())
+    }
+
+    fn log_report(&self, report: &GenerationReport) -> io::Result<()> {
+        let path = self.config.work_dir.join(G::NAME).join("generations.jsonl");
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        serde_json::to_writer(&mut file, report)?;
+        file.write_all(b"\n")
+    }
+}
+
+/// Evaluation used when no other evaluator is configured: an arena match
+/// over enough deals to play roughly `games` games
+pub fn head_to_head<G: GameEngine>(
+    candidate: PolicyHandle<G>,
+    incumbent: Option<PolicyHandle<G>>,
+    games: usize,
+    iterations: i32,
+) -> f64 {
+    if games == 0 {
+        return 0.0;
+    }
+    let mut candidate = MctsMove {
+        name: String::from("candidate"),
+        iterations,
+        policy: Some(candidate),
+    };
+    let mut incumbent = MctsMove {
+        name: String::from("incumbent"),
+        iterations,
+        policy: incumbent,
+    };
+    let config = ArenaConfig {
+        deals: games.div_ceil(seatings(G::PLAYER_COUNT).len()),
+        ..Default::default()
+    };
+    let report = play_match(&mut candidate, &mut incumbent, &config);
+    println!(
+        "{} candidate vs incumbent: win rate {:.3} ({:.3} - {:.3})",
+        G::NAME,
+        report.win_rate,
+        report.confidence_interval.0,
+        report.confidence_interval.1
+    );
+    report.win_rate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::dealers_dilemma::Game;
+    use rand::seq::SliceRandom;
+
+    #[test]
+    fn test_record_and_read_samples() {
+        let samples = record_game(Game::new(), |game| {
+            *game
+                .legal_moves()
+                .choose(&mut rand::thread_rng())
+                .expect("should have a move")
+        });
+        assert!(!samples.is_empty());
+        assert!(samples
+            .iter()
+            .all(|s| s.legal_actions.contains(&s.action) && s.outcome != 0.0));
+
+        let path = std::env::temp_dir().join(format!("samples-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+        write_samples(&path, &samples).unwrap();
+        assert_eq!(read_samples(&path).unwrap(), samples);
+        fs::remove_file(&path).unwrap();
+    }
+}
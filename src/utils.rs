@@ -1,24 +1,46 @@
 use rand::{seq::SliceRandom, Rng};
+use smallvec::SmallVec;
+
+pub mod batch;
+pub mod cardmask;
+pub mod diff;
+pub mod render;
+
+/// Cards held by a player - up to `N` cards are stored inline so the clones
+/// made for every search iteration don't allocate (serialized as a list)
+pub type Hand<T, const N: usize> = SmallVec<[T; N]>;
+
+/// Empty the change lists for the next move without giving back their
+/// memory - the outer list and the first list (which most moves append to)
+/// keep their capacity
+pub fn reset_changes<T>(changes: &mut Vec<Vec<T>>) {
+    changes.truncate(1);
+    match changes.first_mut() {
+        Some(first) => first.clear(),
+        None => changes.push(vec![]),
+    }
+}
 
 /// Shuffle and exchanges items matching criteria between two lists
 /// Used when determining possible cards a player could have in their
 /// hand given the current state of a game.
-pub fn shuffle_and_divide_matching_cards<T: Copy>(
+pub fn shuffle_and_divide_matching_cards<T: Copy, H: AsRef<[T]> + AsMut<[T]>>(
     matcher: impl Fn(&T) -> bool,
-    hands: &mut Vec<Vec<T>>,
+    hands: &mut [H],
     rng: &mut impl Rng,
 ) {
     let mut hand_locations = vec![
-        Vec::with_capacity(hands[0].len()),
-        Vec::with_capacity(hands[1].len()),
+        Vec::with_capacity(hands[0].as_ref().len()),
+        Vec::with_capacity(hands[1].as_ref().len()),
     ];
     // Pre-allocate array so we don't spend time growing the array
     // (might waste a little space but should get more performance)
-    let mut matched_cards: Vec<T> = Vec::with_capacity(hands[0].len() + hands[1].len());
+    let mut matched_cards: Vec<T> =
+        Vec::with_capacity(hands[0].as_ref().len() + hands[1].as_ref().len());
 
     // Find all cards that match the criteria
     for hand_index in 0..2 {
-        for (card_index, card) in hands[hand_index].iter().enumerate() {
+        for (card_index, card) in hands[hand_index].as_ref().iter().enumerate() {
             if matcher(card) {
                 hand_locations[hand_index].push(card_index);
                 matched_cards.push(card.clone());
@@ -32,7 +54,7 @@ pub fn shuffle_and_divide_matching_cards<T: Copy>(
     // Redistribute the matching cards
     for hand_index in 0..2 {
         for card_index in hand_locations[hand_index].iter() {
-            hands[hand_index][*card_index] = matched_cards
+            hands[hand_index].as_mut()[*card_index] = matched_cards
                 .pop()
                 .expect("there should be a card left to pop")
         }
@@ -42,6 +64,50 @@ pub fn shuffle_and_divide_matching_cards<T: Copy>(
     assert!(matched_cards.len() == 0);
 }
 
+/// Shuffle the cards matching `matcher` between two hands in place - each
+/// matching card ends up in one of the places a matching card was. Deals from
+/// the same distribution as `shuffle_and_divide_matching_cards` without
+/// copying the hands in and out of a list.
+pub fn shuffle_matching_cards_between<T>(
+    matcher: impl Fn(&T) -> bool,
+    first: &mut [T],
+    second: &mut [T],
+    rng: &mut impl Rng,
+) {
+    // (in the second hand, index) of every matching card
+    let mut locations: SmallVec<[(bool, usize); 32]> = SmallVec::new();
+    for (in_second, hand) in [(false, &*first), (true, &*second)] {
+        for (index, card) in hand.iter().enumerate() {
+            if matcher(card) {
+                locations.push((in_second, index));
+            }
+        }
+    }
+    // Fisher-Yates over the matching places
+    for i in (1..locations.len()).rev() {
+        let j = rng.gen_range(0..=i);
+        match (locations[i], locations[j]) {
+            ((false, a), (false, b)) => first.swap(a, b),
+            ((true, a), (true, b)) => second.swap(a, b),
+            ((false, a), (true, b)) | ((true, b), (false, a)) => {
+                std::mem::swap(&mut first[a], &mut second[b])
+            }
+        }
+    }
+}
+
+/// Mutable references to two different items of a list
+pub fn pair_mut<T>(items: &mut [T], first: usize, second: usize) -> (&mut T, &mut T) {
+    assert!(first != second, "a pair needs two different items");
+    if first < second {
+        let (low, high) = items.split_at_mut(second);
+        (&mut low[first], &mut high[0])
+    } else {
+        let (low, high) = items.split_at_mut(first);
+        (&mut high[0], &mut low[second])
+    }
+}
+
 pub mod tests {
     use super::*;
 
@@ -166,4 +232,48 @@ pub mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_shuffle_matching_cards_between() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut first: Vec<i32> = (0..6).collect();
+        let mut second: Vec<i32> = (6..10).collect();
+        for _ in 0..20 {
+            // odd cards stay where they are
+            shuffle_matching_cards_between(|c| c % 2 == 0, &mut first, &mut second, &mut rng);
+            for (index, card) in first.iter().chain(second.iter()).enumerate() {
+                assert_eq!(card % 2, index as i32 % 2);
+            }
+        }
+        let mut cards: Vec<i32> = first.iter().chain(second.iter()).copied().collect();
+        cards.sort();
+        assert_eq!(cards, (0..10).collect::<Vec<i32>>());
+
+        let mut hands = [vec![1], vec![2], vec![3]];
+        let (high, low) = pair_mut(&mut hands, 2, 0);
+        std::mem::swap(high, low);
+        assert_eq!(hands, [vec![3], vec![2], vec![1]]);
+    }
+
+    #[test]
+    fn test_reset_changes() {
+        let mut changes = vec![Vec::with_capacity(8), vec![1, 2]];
+        changes[0].push(0);
+        reset_changes(&mut changes);
+        assert_eq!(changes, vec![Vec::<i32>::new()]);
+        assert!(changes[0].capacity() >= 8);
+        let mut changes: Vec<Vec<i32>> = vec![];
+        reset_changes(&mut changes);
+        assert_eq!(changes, vec![vec![]]);
+    }
+
+    #[test]
+    fn test_hand_serializes_as_list() {
+        let hand: Hand<i32, 4> = [3, 1, 2].into_iter().collect();
+        assert_eq!(serde_json::to_string(&hand).unwrap(), "[3,1,2]");
+        // hands larger than the inline capacity move to the heap
+        let hand: Hand<i32, 2> = serde_json::from_str("[1,2,3]").unwrap();
+        assert_eq!(hand.as_slice(), &[1, 2, 3]);
+        assert!(hand.spilled());
+    }
 }
@@ -0,0 +1,145 @@
+/*
+Parallel batches of games
+
+`simulate_batch` spreads the games of a batch over a pool of threads which
+take the next game index whenever they finish one. Game `i` gets its own rng
+seeded with `seed + i` (created on the thread that plays it) so a batch has
+the same results whatever the thread count, and the results come back in
+game order.
+
+The simulate and tournament commands and the training data exporter all play
+their games through it - `random_batch` is the plain random self-play loop.
+*/
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+use crate::engine::GameEngine;
+
+/// Safety net for engines that never reach a terminal state
+const MAX_MOVES_PER_GAME: usize = 10_000;
+
+/// One thread per core
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, |threads| threads.get())
+}
+
+/// Call `play` for every game index below `games` on `threads` threads -
+/// each call gets an rng seeded with `seed + index`
+pub fn simulate_batch<T: Send>(
+    games: usize,
+    threads: usize,
+    seed: u64,
+    play: impl Fn(usize, &mut StdRng) -> T + Sync,
+) -> Vec<T> {
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<(usize, T)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads.clamp(1, games.max(1)))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut played = vec![];
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        if index >= games {
+                            return played;
+                        }
+                        let mut rng = StdRng::seed_from_u64(seed.wrapping_add(index as u64));
+                        played.push((index, play(index, &mut rng)));
+                    }
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("batch thread panicked"))
+            .collect()
+    });
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Outcome of one game of a batch
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchGame {
+    pub scores: Vec<i32>,
+    pub winners: Vec<usize>,
+    pub moves: usize,
+}
+
+/// Games of a batch with the wins and mean score of every seat
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchReport {
+    /// In game order
+    pub games: Vec<BatchGame>,
+    /// Games won by each seat (shared wins count for every winner)
+    pub wins: Vec<usize>,
+    pub mean_scores: Vec<f64>,
+}
+
+impl BatchReport {
+    pub fn new(player_count: usize, games: Vec<BatchGame>) -> Self {
+        let mut wins = vec![0; player_count];
+        let mut totals = vec![0.0; player_count];
+        for game in games.iter() {
+            for seat in game.winners.iter() {
+                wins[*seat] += 1;
+            }
+            for (seat, score) in game.scores.iter().enumerate() {
+                totals[seat] += *score as f64;
+            }
+        }
+        let count = games.len().max(1) as f64;
+        BatchReport {
+            games,
+            wins,
+            mean_scores: totals.iter().map(|total| total / count).collect(),
+        }
+    }
+}
+
+/// Play `games` games of random moves from the deals made by `game_factory`
+pub fn random_batch<G: GameEngine>(
+    game_factory: impl Fn() -> G + Sync,
+    games: usize,
+    threads: usize,
+    seed: u64,
+) -> BatchReport {
+    let games = simulate_batch(games, threads, seed, |_, rng| {
+        let mut game = game_factory();
+        let mut moves = vec![];
+        let mut played = 0;
+        while !game.is_over() && played < MAX_MOVES_PER_GAME {
+            game.legal_moves_into(&mut moves);
+            game.play(*moves.choose(rng).expect("should have a move to make"));
+            played += 1;
+        }
+        BatchGame {
+            scores: game.scores(),
+            winners: game.winners(),
+            moves: played,
+        }
+    });
+    BatchReport::new(G::PLAYER_COUNT, games)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::yokai2p::Yokai2pGame;
+
+    #[test]
+    fn test_simulate_batch() {
+        let square = |index: usize, _: &mut StdRng| index * index;
+        assert_eq!(simulate_batch(5, 3, 0, square), vec![0, 1, 4, 9, 16]);
+        assert_eq!(simulate_batch(0, 3, 0, square), vec![]);
+
+        let report = random_batch(Yokai2pGame::new_game, 4, 2, 7);
+        assert_eq!(report.games.len(), 4);
+        assert!(report.wins.iter().sum::<usize>() >= 4);
+        assert!(report.games.iter().all(|game| game.moves > 0));
+    }
+}
@@ -0,0 +1,110 @@
+/*
+Compact card sets for simulations
+
+Every game numbers its cards with ids below 64 so a set of cards fits in a
+`u64` with one bit per id. Engines keep a mask of each hand next to the
+`Vec<Card>` hands while searching so checks like "can this player follow
+suit" are a single `&` with the mask of the suit instead of a scan of the
+hand. The hands stay the source of truth for serialization and the UI.
+*/
+
+use std::ops::{BitAnd, BitOr, Not};
+
+/// Set of card ids (0 - 63)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct CardMask(pub u64);
+
+impl CardMask {
+    pub const EMPTY: CardMask = CardMask(0);
+
+    pub fn from_ids(ids: impl IntoIterator<Item = i32>) -> Self {
+        let mut mask = CardMask::EMPTY;
+        for id in ids {
+            mask.insert(id);
+        }
+        mask
+    }
+
+    fn bit(id: i32) -> u64 {
+        debug_assert!((0..64).contains(&id), "card id {} does not fit in a mask", id);
+        1 << id
+    }
+
+    pub fn insert(&mut self, id: i32) {
+        self.0 |= Self::bit(id);
+    }
+
+    pub fn remove(&mut self, id: i32) {
+        self.0 &= !Self::bit(id);
+    }
+
+    pub fn contains(self, id: i32) -> bool {
+        self.0 & Self::bit(id) != 0
+    }
+
+    pub fn len(self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Ids in ascending order
+    pub fn ids(self) -> impl Iterator<Item = i32> {
+        let mut bits = self.0;
+        std::iter::from_fn(move || {
+            if bits == 0 {
+                return None;
+            }
+            let id = bits.trailing_zeros() as i32;
+            bits &= bits - 1;
+            Some(id)
+        })
+    }
+}
+
+impl BitAnd for CardMask {
+    type Output = CardMask;
+
+    fn bitand(self, other: CardMask) -> CardMask {
+        CardMask(self.0 & other.0)
+    }
+}
+
+impl BitOr for CardMask {
+    type Output = CardMask;
+
+    fn bitor(self, other: CardMask) -> CardMask {
+        CardMask(self.0 | other.0)
+    }
+}
+
+impl Not for CardMask {
+    type Output = CardMask;
+
+    fn not(self) -> CardMask {
+        CardMask(!self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_card_mask() {
+        let mut mask = CardMask::from_ids([3, 0, 63]);
+        assert_eq!(mask.len(), 3);
+        assert!(mask.contains(63) && !mask.contains(1));
+        assert_eq!(mask.ids().collect::<Vec<i32>>(), vec![0, 3, 63]);
+        mask.remove(3);
+        mask.remove(3);
+        assert_eq!(mask, CardMask::from_ids([0, 63]));
+        let low = CardMask::from_ids(0..32);
+        assert_eq!((mask & low).ids().collect::<Vec<i32>>(), vec![0]);
+        assert_eq!((mask & !low).ids().collect::<Vec<i32>>(), vec![63]);
+        assert_eq!((mask | low).len(), 33);
+        assert!(CardMask::EMPTY.is_empty());
+    }
+}
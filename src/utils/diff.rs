@@ -0,0 +1,148 @@
+/*
+Structured differences between two serialized game states
+
+Used to compare the Rust engines against states recorded from the Dart
+implementation - each difference is reported by its field path (for example
+`hands[1][3].value`) instead of printing both states in full.
+*/
+
+use std::fmt;
+
+use serde::Serialize;
+use serde_json::Value;
+
+/// Fields that are bookkeeping of one implementation rather than game state
+pub const DART_IGNORED: [&str; 4] = ["voids", "drawDecks", "roundResults", "summary"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Difference {
+    pub path: String,
+    /// None when the field is missing from that side
+    pub rust: Option<Value>,
+    pub expected: Option<Value>,
+}
+
+fn describe(value: &Option<Value>) -> String {
+    match value {
+        Some(value) => value.to_string(),
+        None => "missing".to_string(),
+    }
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: rust {} expected {}",
+            self.path,
+            describe(&self.rust),
+            describe(&self.expected)
+        )
+    }
+}
+
+fn field_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+fn collect(
+    path: String,
+    rust: Option<&Value>,
+    expected: Option<&Value>,
+    ignored: &[&str],
+    differences: &mut Vec<Difference>,
+) {
+    match (rust, expected) {
+        (Some(Value::Object(rust)), Some(Value::Object(expected))) => {
+            let mut keys: Vec<&String> = rust.keys().chain(expected.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let key_path = field_path(&path, key);
+                if ignored.contains(&key.as_str()) || ignored.contains(&key_path.as_str()) {
+                    continue;
+                }
+                collect(key_path, rust.get(key), expected.get(key), ignored, differences);
+            }
+        }
+        (Some(Value::Array(rust)), Some(Value::Array(expected))) => {
+            for index in 0..rust.len().max(expected.len()) {
+                let index_path = format!("{}[{}]", path, index);
+                if ignored.contains(&index_path.as_str()) {
+                    continue;
+                }
+                collect(
+                    index_path,
+                    rust.get(index),
+                    expected.get(index),
+                    ignored,
+                    differences,
+                );
+            }
+        }
+        (rust, expected) if rust != expected => differences.push(Difference {
+            path,
+            rust: rust.cloned(),
+            expected: expected.cloned(),
+        }),
+        _ => {}
+    }
+}
+
+/// Every leaf that differs between `rust` and `expected` - `ignored` are
+/// field names (anywhere in the state) or full field paths to skip
+pub fn diff_values(rust: &Value, expected: &Value, ignored: &[&str]) -> Vec<Difference> {
+    let mut differences = vec![];
+    collect(String::new(), Some(rust), Some(expected), ignored, &mut differences);
+    differences
+}
+
+pub fn diff<T: Serialize>(rust: &T, expected: &T, ignored: &[&str]) -> Vec<Difference> {
+    diff_values(
+        &serde_json::to_value(rust).expect("state should serialize"),
+        &serde_json::to_value(expected).expect("state should serialize"),
+        ignored,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_diff_values() {
+        let rust = json!({
+            "hands": [[{"id": 1, "value": 3}], [{"id": 2, "value": 5}]],
+            "voids": [["red"]],
+            "round": 1,
+            "winner": null,
+        });
+        let expected = json!({
+            "hands": [[{"id": 1, "value": 4}], [{"id": 2, "value": 5}, {"id": 3}]],
+            "voids": [],
+            "round": 1,
+            "dealer": 2,
+        });
+        let differences: Vec<String> = diff_values(&rust, &expected, &["voids"])
+            .iter()
+            .map(|difference| difference.to_string())
+            .collect();
+        assert_eq!(
+            differences,
+            vec![
+                "dealer: rust missing expected 2",
+                "hands[0][0].value: rust 3 expected 4",
+                "hands[1][1]: rust missing expected {\"id\":3}",
+                "winner: rust null expected missing",
+            ]
+        );
+        assert!(diff_values(&rust, &rust, &[]).is_empty());
+        assert_eq!(diff_values(&rust, &expected, &["voids", "hands", "dealer"]).len(), 1);
+        assert_eq!(diff_values(&rust, &expected, &["hands[0]", "voids"]).len(), 3);
+    }
+}
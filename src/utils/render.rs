@@ -0,0 +1,148 @@
+/*
+Terminal rendering shared by every game
+
+Games describe how their suits are drawn with `SuitStyle` and build their
+`GameEngine::render` output from these helpers so the play command and debug
+logging look the same across games.
+*/
+
+use std::fmt::Display;
+
+use colored::{Color, Colorize};
+use once_cell::sync::Lazy;
+
+/// How a suit is drawn in the terminal
+pub trait SuitStyle {
+    fn symbol(&self) -> &'static str;
+    fn color(&self) -> Option<Color>;
+}
+
+/// Whether the terminal can draw colors - checked once for every game
+pub fn colors_enabled() -> bool {
+    static ENABLED: Lazy<bool> = Lazy::new(enable_colors);
+    *ENABLED
+}
+
+// Windows consoles only understand the color escape codes once virtual
+// terminal processing is turned on (older consoles can't turn it on)
+#[cfg(windows)]
+fn enable_colors() -> bool {
+    colored::control::set_virtual_terminal(true).is_ok()
+}
+
+#[cfg(not(windows))]
+fn enable_colors() -> bool {
+    true
+}
+
+/// `string` drawn in `color` (plain text when the terminal has no colors)
+pub fn color(string: impl Display, color: Option<Color>) -> String {
+    match color {
+        Some(color) if colors_enabled() => string.to_string().as_str().color(color).to_string(),
+        _ => string.to_string(),
+    }
+}
+
+/// Colored suit symbol - `?` when there is no suit (e.g. nothing led yet)
+pub fn suit<S: SuitStyle>(suit: Option<&S>) -> String {
+    match suit {
+        Some(suit) => color(suit.symbol(), suit.color()),
+        None => "?".to_string(),
+    }
+}
+
+/// A card's value followed by its suit symbol in the suit's color
+pub fn card<S: SuitStyle>(value: impl Display, suit: &S) -> String {
+    color(format!("{}{}", value, suit.symbol()), suit.color())
+}
+
+/// Space separated cards - `-` when there are none
+pub fn cards(cards: impl IntoIterator<Item = String>) -> String {
+    let cards: Vec<String> = cards.into_iter().collect();
+    if cards.is_empty() {
+        "-".to_string()
+    } else {
+        cards.join(" ")
+    }
+}
+
+/// The card played by every seat in seat order - the leader is marked with
+/// `*` and seats that haven't played yet with `.`
+pub fn trick(played: &[Option<String>], lead_player: Option<usize>) -> String {
+    played
+        .iter()
+        .enumerate()
+        .map(|(seat, card)| {
+            let lead = if lead_player == Some(seat) { "*" } else { "" };
+            let card = card.as_deref().unwrap_or(".");
+            format!("{}{}: {}", lead, seat, card)
+        })
+        .collect::<Vec<_>>()
+        .join("  ")
+}
+
+/// Left aligned columns under `header` (cells are plain text)
+pub fn table(header: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = header.iter().map(|title| title.chars().count()).collect();
+    for row in rows {
+        for (column, cell) in row.iter().enumerate() {
+            if column < widths.len() {
+                widths[column] = widths[column].max(cell.chars().count());
+            }
+        }
+    }
+    let line = |cells: Vec<String>| {
+        cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+    let mut lines = vec![line(header.iter().map(|title| title.to_string()).collect())];
+    lines.extend(rows.iter().map(|row| line(row.clone())));
+    lines.join("\n")
+}
+
+/// Row label for `seat` - the viewer's own seat is marked
+pub fn seat_label(seat: usize, viewer: usize) -> String {
+    if seat == viewer {
+        format!("{} (you)", seat)
+    } else {
+        seat.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trick() {
+        let played = [Some("5".to_string()), None, Some("9".to_string())];
+        assert_eq!(trick(&played, Some(2)), "0: 5  1: .  *2: 9");
+        assert_eq!(cards(vec![]), "-");
+    }
+
+    #[test]
+    fn test_color() {
+        assert_eq!(color("7", None), "7");
+        if !cfg!(windows) {
+            assert!(colors_enabled());
+        }
+    }
+
+    #[test]
+    fn test_table() {
+        let rows = vec![
+            vec!["0 (you)".to_string(), "12".to_string()],
+            vec!["1".to_string(), "-3".to_string()],
+        ];
+        assert_eq!(
+            table(&["player", "score"], &rows),
+            "player   score\n0 (you)  12\n1        -3"
+        );
+    }
+}
@@ -0,0 +1,277 @@
+/*
+Invariants of the change lists sent to the UI
+
+Every engine emits its animations as `changes: [[change, ...], ...]` in the
+same JSON shape so the checks work on the serialized state rather than each
+game's Change type:
+
+- card changes (deal, play, tricksToWinner, ...) refer to a card that is in
+  the state before or after the move, or to the -1 sentinel
+- within a move a card isn't played again after it went to a trick pile and
+  isn't played twice
+- cards highlighted with showPlayable are hidden (or highlighted again)
+  by the next move
+- seats, offsets and lengths are in range
+
+Debug builds check every move made through the registry and print the
+violations - the engines keep running so a UI bug doesn't hide the next one.
+*/
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use serde_json::Value;
+
+// Change types whose objectId is a card id
+const CARD_CHANGES: [&str; 14] = [
+    "deal",
+    "play",
+    "tricksToWinner",
+    "tricksAnnulled",
+    "faceup",
+    "discard",
+    "revealCard",
+    "captureSeven",
+    "passCard",
+    "trickToShortsPile",
+    "promoteToTrump",
+    "showWinningCard",
+    "bid",
+    "cardsBurned",
+];
+
+// Change types that put a card on a pile it can't be played from this hand
+const TRICK_PILES: [&str; 3] = ["tricksToWinner", "tricksAnnulled", "trickToShortsPile"];
+
+const OFFSETS: [&str; 4] = ["handOffset", "sourceOffset", "destOffset", "offset"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// Position of the change in the move's change list
+    pub group: usize,
+    pub index: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "changes[{}][{}]: {}", self.group, self.index, self.message)
+    }
+}
+
+/// Ids of every card (an object with an id and a suit) in a serialized state
+pub fn card_ids(state: &Value) -> HashSet<i64> {
+    let mut ids = HashSet::new();
+    let mut pending = vec![state];
+    while let Some(value) = pending.pop() {
+        match value {
+            Value::Array(values) => pending.extend(values),
+            Value::Object(map) => {
+                if let (Some(id), true) = (map.get("id"), map.contains_key("suit")) {
+                    ids.extend(id.as_i64());
+                }
+                pending.extend(map.values());
+            }
+            _ => {}
+        }
+    }
+    ids
+}
+
+fn playable_shown(changes: &Value) -> bool {
+    let mut shown = false;
+    for group in changes.as_array().into_iter().flatten() {
+        for change in group.as_array().into_iter().flatten() {
+            match change.get("type").and_then(Value::as_str) {
+                Some("showPlayable") => shown = true,
+                Some("hidePlayable") => shown = false,
+                _ => {}
+            }
+        }
+    }
+    shown
+}
+
+// Problems with a single change - `moved_to` is where each card went earlier
+// in the move
+fn check_change<'a>(
+    change: &'a Value,
+    cards: &HashSet<i64>,
+    player_count: usize,
+    moved_to: &mut HashMap<i64, &'a str>,
+) -> Vec<String> {
+    let change_type = match change.get("type").and_then(Value::as_str) {
+        Some(change_type) => change_type,
+        None => return vec!["change without a type".to_string()],
+    };
+    let number = |key: &str| change.get(key).and_then(Value::as_i64);
+    let mut problems = vec![];
+    if let Some(player) = number("player") {
+        if player < 0 || player as usize >= player_count {
+            problems.push(format!("{} has seat {}", change_type, player));
+        }
+    }
+    for key in OFFSETS {
+        if let Some(offset) = number(key).filter(|offset| *offset < 0) {
+            problems.push(format!("{} has {} {}", change_type, key, offset));
+        }
+    }
+    match (number("handOffset"), number("length")) {
+        (_, Some(length)) if length < 0 => {
+            problems.push(format!("{} has length {}", change_type, length))
+        }
+        (Some(offset), Some(length)) if length > 0 && offset >= length => problems.push(format!(
+            "{} has handOffset {} past length {}",
+            change_type, offset, length
+        )),
+        _ => {}
+    }
+    if !CARD_CHANGES.contains(&change_type) {
+        return problems;
+    }
+    let id = match number("objectId") {
+        Some(-1) => return problems,
+        Some(id) => id,
+        None => {
+            problems.push(format!("{} without an objectId", change_type));
+            return problems;
+        }
+    };
+    if !cards.contains(&id) {
+        problems.push(format!("{} of unknown card {}", change_type, id));
+    }
+    match (change_type, moved_to.get(&id)) {
+        ("play", Some(pile)) if TRICK_PILES.contains(pile) => {
+            problems.push(format!("card {} played after {}", id, pile))
+        }
+        ("play", Some(&"play")) => problems.push(format!("card {} played twice", id)),
+        _ => {}
+    }
+    if change_type == "deal" {
+        moved_to.remove(&id);
+    } else {
+        moved_to.insert(id, change_type);
+    }
+    problems
+}
+
+/// Check the `changes` emitted by one move - `previous` are the changes of
+/// the move before and `cards` the ids of the cards in the game
+pub fn validate_changes(
+    previous: &Value,
+    changes: &Value,
+    cards: &HashSet<i64>,
+    player_count: usize,
+) -> Vec<Violation> {
+    let violation = |group: usize, index: usize, message: &str| Violation {
+        group,
+        index,
+        message: message.to_string(),
+    };
+    let groups = match changes.as_array() {
+        Some(groups) => groups,
+        None => return vec![violation(0, 0, "changes must be a list of lists")],
+    };
+    let mut violations = vec![];
+    let mut moved_to = HashMap::new();
+    let mut playable_updated = false;
+    for (group_index, group) in groups.iter().enumerate() {
+        let group = match group.as_array() {
+            Some(group) => group,
+            None => {
+                violations.push(violation(group_index, 0, "change groups must be lists"));
+                continue;
+            }
+        };
+        for (index, change) in group.iter().enumerate() {
+            let change_type = change.get("type").and_then(Value::as_str);
+            if change_type == Some("showPlayable") || change_type == Some("hidePlayable") {
+                playable_updated = true;
+            }
+            for problem in check_change(change, cards, player_count, &mut moved_to) {
+                violations.push(violation(group_index, index, &problem));
+            }
+        }
+    }
+    if playable_shown(previous) && !playable_updated {
+        let message = "cards highlighted by the previous move were never hidden";
+        violations.push(violation(0, 0, message));
+    }
+    violations
+}
+
+/// Check the move that turned the serialized state `before` into `after`
+pub fn validate_move(before: &Value, after: &Value, player_count: usize) -> Vec<Violation> {
+    let empty = Value::Array(vec![]);
+    let changes = after.get("changes").unwrap_or(&empty);
+    let mut cards = card_ids(before);
+    cards.extend(card_ids(after));
+    validate_changes(before.get("changes").unwrap_or(&empty), changes, &cards, player_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn messages(previous: Value, changes: Value) -> Vec<String> {
+        let cards: HashSet<i64> = [1, 2, 3].into_iter().collect();
+        validate_changes(&previous, &changes, &cards, 2)
+            .into_iter()
+            .map(|violation| violation.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_card_ids() {
+        let state = json!({
+            "hands": [[{"id": 4, "suit": "red", "value": 1}], []],
+            "trumpCard": {"id": 9, "suit": "blue"},
+            "changes": [[{"objectId": 17, "type": "play"}]],
+        });
+        assert_eq!(card_ids(&state), [4, 9].into_iter().collect());
+    }
+
+    #[test]
+    fn test_valid_changes() {
+        let changes = json!([
+            [{"type": "hidePlayable", "objectId": 1, "player": 0}],
+            [{"type": "play", "objectId": 1, "player": 0}],
+            [{"type": "tricksToWinner", "objectId": 1, "player": 1}],
+            [{"type": "deal", "objectId": 1, "handOffset": 0, "length": 5}],
+            [{"type": "play", "objectId": 1, "player": 1}],
+            [{"type": "score", "objectId": 1, "player": 1}],
+        ]);
+        let previous = json!([[{"type": "showPlayable", "objectId": 1}]]);
+        assert!(messages(previous, changes).is_empty());
+    }
+
+    #[test]
+    fn test_violations() {
+        let changes = json!([
+            [
+                {"type": "play", "objectId": 7, "player": 2},
+                {"type": "tricksToWinner", "objectId": 2},
+                {"type": "play", "objectId": 2},
+                {"type": "play", "objectId": 3},
+                {"type": "play", "objectId": 3, "handOffset": 5, "length": 5},
+                {"objectId": 3},
+            ],
+            {"type": "deal"},
+        ]);
+        let previous = json!([[{"type": "showPlayable", "objectId": 1}]]);
+        assert_eq!(
+            messages(previous, changes),
+            vec![
+                "changes[0][0]: play has seat 2",
+                "changes[0][0]: play of unknown card 7",
+                "changes[0][2]: card 2 played after tricksToWinner",
+                "changes[0][4]: play has handOffset 5 past length 5",
+                "changes[0][4]: card 3 played twice",
+                "changes[0][5]: change without a type",
+                "changes[1][0]: change groups must be lists",
+                "changes[0][0]: cards highlighted by the previous move were never hidden",
+            ]
+        );
+    }
+}
@@ -0,0 +1,74 @@
+/*
+wasm-bindgen facade (enabled with the `wasm` feature)
+
+Everything crosses the boundary as numbers or JSON strings so the web build
+can reuse the same parsing code as the Flutter apps.
+*/
+
+use wasm_bindgen::prelude::*;
+
+use crate::registry::{self, DynGame, GAME_NAMES};
+
+/// Browsers run the search on the main thread so keep it short by default
+pub const WASM_DEFAULT_ITERATIONS: i32 = 200;
+
+fn to_js(error: registry::RegistryError) -> JsValue {
+    JsValue::from_str(&error.to_string())
+}
+
+#[wasm_bindgen(js_name = gameNames)]
+pub fn game_names() -> String {
+    serde_json::to_string(&GAME_NAMES).expect("names should serialize")
+}
+
+#[wasm_bindgen]
+pub struct WasmGame {
+    game: Box<dyn DynGame>,
+}
+
+#[wasm_bindgen]
+impl WasmGame {
+    #[wasm_bindgen(constructor)]
+    pub fn new(name: &str) -> Result<WasmGame, JsValue> {
+        let game = registry::new_game(name).map_err(to_js)?;
+        Ok(WasmGame { game })
+    }
+
+    #[wasm_bindgen(js_name = fromState)]
+    pub fn from_state(name: &str, state: &str) -> Result<WasmGame, JsValue> {
+        let game = registry::load_game(name, state).map_err(to_js)?;
+        Ok(WasmGame { game })
+    }
+
+    #[wasm_bindgen(js_name = getMoves)]
+    pub fn get_moves(&self) -> Vec<i32> {
+        self.game.moves()
+    }
+
+    #[wasm_bindgen(js_name = applyMove)]
+    pub fn apply_move(&mut self, action: i32) -> Result<(), JsValue> {
+        self.game.apply_move(action).map_err(to_js)
+    }
+
+    #[wasm_bindgen(js_name = botMove)]
+    pub fn bot_move(&self, iterations: Option<i32>) -> i32 {
+        self.game.bot_move(iterations.unwrap_or(WASM_DEFAULT_ITERATIONS))
+    }
+
+    #[wasm_bindgen(js_name = currentPlayer)]
+    pub fn current_player(&self) -> usize {
+        self.game.current_seat()
+    }
+
+    pub fn state(&self) -> String {
+        self.game.state_json()
+    }
+
+    pub fn changes(&self) -> String {
+        self.game.changes_json()
+    }
+
+    pub fn winners(&self) -> Vec<u32> {
+        self.game.winners().iter().map(|seat| *seat as u32).collect()
+    }
+}
@@ -0,0 +1,244 @@
+/*
+Compact protobuf encoding of game states and changes (enabled with the
+`protobuf` feature)
+
+Rather than maintaining a .proto schema per game the encoding mirrors the
+JSON data model so every engine gets it for free and decoding always
+reproduces exactly the same value as the JSON path. Object keys are
+interned into a table once per document and referenced by index which is
+where most of the savings come from: a change list repeats the same handful
+of keys hundreds of times.
+
+  message Document { repeated string keys = 1; Value root = 2; }
+  message Value {
+    oneof kind {
+      bool null = 1; bool bool = 2; sint64 int = 3; uint64 uint = 4;
+      double float = 5; string string = 6; List list = 7; Object object = 8;
+    }
+  }
+  message List { repeated Value values = 1; }
+  message Object { repeated uint32 keys = 1; repeated Value values = 2; }
+*/
+
+use std::collections::HashMap;
+use std::fmt;
+
+use prost::Message;
+use serde_json::{Map, Number};
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Document {
+    #[prost(string, repeated, tag = "1")]
+    pub keys: Vec<String>,
+    #[prost(message, optional, tag = "2")]
+    pub root: Option<Value>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Value {
+    #[prost(oneof = "Kind", tags = "1, 2, 3, 4, 5, 6, 7, 8")]
+    pub kind: Option<Kind>,
+}
+
+#[derive(Clone, PartialEq, prost::Oneof)]
+pub enum Kind {
+    #[prost(bool, tag = "1")]
+    Null(bool),
+    #[prost(bool, tag = "2")]
+    Bool(bool),
+    #[prost(sint64, tag = "3")]
+    Int(i64),
+    #[prost(uint64, tag = "4")]
+    Uint(u64),
+    #[prost(double, tag = "5")]
+    Float(f64),
+    #[prost(string, tag = "6")]
+    String(String),
+    #[prost(message, tag = "7")]
+    List(List),
+    #[prost(message, tag = "8")]
+    Object(Object),
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct List {
+    #[prost(message, repeated, tag = "1")]
+    pub values: Vec<Value>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Object {
+    #[prost(uint32, repeated, tag = "1")]
+    pub keys: Vec<u32>,
+    #[prost(message, repeated, tag = "2")]
+    pub values: Vec<Value>,
+}
+
+#[derive(Debug)]
+pub enum WireError {
+    Decode(prost::DecodeError),
+    // a key index outside the key table or keys without values
+    Malformed(String),
+}
+
+impl fmt::Display for WireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WireError::Decode(error) => write!(f, "invalid protobuf: {}", error),
+            WireError::Malformed(message) => write!(f, "malformed document: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for WireError {}
+
+impl From<prost::DecodeError> for WireError {
+    fn from(error: prost::DecodeError) -> Self {
+        WireError::Decode(error)
+    }
+}
+
+#[derive(Default)]
+struct Encoder {
+    keys: Vec<String>,
+    indexes: HashMap<String, u32>,
+}
+
+impl Encoder {
+    fn key(&mut self, key: &str) -> u32 {
+        if let Some(index) = self.indexes.get(key) {
+            return *index;
+        }
+        let index = self.keys.len() as u32;
+        self.keys.push(key.to_string());
+        self.indexes.insert(key.to_string(), index);
+        index
+    }
+
+    fn value(&mut self, value: &serde_json::Value) -> Value {
+        let kind = match value {
+            serde_json::Value::Null => Kind::Null(true),
+            serde_json::Value::Bool(value) => Kind::Bool(*value),
+            serde_json::Value::Number(number) => {
+                if let Some(value) = number.as_i64() {
+                    Kind::Int(value)
+                } else if let Some(value) = number.as_u64() {
+                    Kind::Uint(value)
+                } else {
+                    Kind::Float(number.as_f64().unwrap_or_default())
+                }
+            }
+            serde_json::Value::String(value) => Kind::String(value.clone()),
+            serde_json::Value::Array(values) => Kind::List(List {
+                values: values.iter().map(|value| self.value(value)).collect(),
+            }),
+            serde_json::Value::Object(map) => {
+                let mut object = Object::default();
+                for (key, value) in map {
+                    object.keys.push(self.key(key));
+                    object.values.push(self.value(value));
+                }
+                Kind::Object(object)
+            }
+        };
+        Value { kind: Some(kind) }
+    }
+}
+
+fn decode_value(keys: &[String], value: Value) -> Result<serde_json::Value, WireError> {
+    Ok(match value.kind {
+        None | Some(Kind::Null(_)) => serde_json::Value::Null,
+        Some(Kind::Bool(value)) => serde_json::Value::Bool(value),
+        Some(Kind::Int(value)) => serde_json::Value::Number(value.into()),
+        Some(Kind::Uint(value)) => serde_json::Value::Number(value.into()),
+        Some(Kind::Float(value)) => Number::from_f64(value)
+            .map(serde_json::Value::Number)
+            .ok_or_else(|| WireError::Malformed(format!("{} is not a JSON number", value)))?,
+        Some(Kind::String(value)) => serde_json::Value::String(value),
+        Some(Kind::List(list)) => serde_json::Value::Array(
+            list.values
+                .into_iter()
+                .map(|value| decode_value(keys, value))
+                .collect::<Result<_, _>>()?,
+        ),
+        Some(Kind::Object(object)) => {
+            if object.keys.len() != object.values.len() {
+                return Err(WireError::Malformed(
+                    "object keys and values differ in length".to_string(),
+                ));
+            }
+            let mut map = Map::new();
+            for (key, value) in object.keys.into_iter().zip(object.values) {
+                let key = keys
+                    .get(key as usize)
+                    .ok_or_else(|| WireError::Malformed(format!("unknown key {}", key)))?;
+                map.insert(key.clone(), decode_value(keys, value)?);
+            }
+            serde_json::Value::Object(map)
+        }
+    })
+}
+
+/// Protobuf bytes for a JSON value
+pub fn encode(value: &serde_json::Value) -> Vec<u8> {
+    let mut encoder = Encoder::default();
+    let root = encoder.value(value);
+    Document {
+        keys: encoder.keys,
+        root: Some(root),
+    }
+    .encode_to_vec()
+}
+
+/// The JSON value encoded in `bytes`
+pub fn decode(bytes: &[u8]) -> Result<serde_json::Value, WireError> {
+    let document = Document::decode(bytes)?;
+    match document.root {
+        Some(root) => decode_value(&document.keys, root),
+        None => Ok(serde_json::Value::Null),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::{new_game, GAME_NAMES};
+    use serde_json::json;
+
+    #[test]
+    fn test_round_trip_values() {
+        let value = json!({
+            "null": null,
+            "flags": [true, false],
+            "numbers": [0, -3, u64::MAX, 0.5],
+            "nested": {"name": "szs", "list": [{"name": "deal"}]},
+        });
+        assert_eq!(decode(&encode(&value)).unwrap(), value);
+        assert!(decode(&[0xff, 0xff]).is_err());
+    }
+
+    /// The protobuf path must produce exactly what the JSON path does for
+    /// every game's state and changes
+    #[test]
+    fn test_round_trip_games() {
+        for name in GAME_NAMES {
+            let mut game = new_game(name).unwrap();
+            for _ in 0..10 {
+                let state_json = game.state_json();
+                let state: serde_json::Value = serde_json::from_str(&state_json).unwrap();
+                let bytes = encode(&state);
+                assert_eq!(decode(&bytes).unwrap(), state);
+                assert!(bytes.len() < state_json.len());
+                let changes: serde_json::Value =
+                    serde_json::from_str(&game.changes_json()).unwrap();
+                assert_eq!(decode(&encode(&changes)).unwrap(), changes);
+
+                let moves = game.moves();
+                if moves.is_empty() {
+                    break;
+                }
+                game.apply_move(moves[0]).unwrap();
+            }
+        }
+    }
+}